@@ -14,6 +14,7 @@ use dupe::Dupe;
 use once_cell::sync::OnceCell;
 use starlark::environment::GlobalsBuilder;
 use starlark::typing::Ty;
+use starlark::typing::TyFunction;
 use starlark::typing::TyStarlarkValue;
 use starlark::values::function::NativeFunction;
 use starlark::values::typing::TypeInstanceId;
@@ -54,9 +55,11 @@ impl<'v, P: StarlarkValue<'v> + ProviderLike<'v>, C: StarlarkValue<'v> + Provide
         }
     }
 
-    pub(crate) fn callable(&self, creator_func: for<'a> fn(&'a mut GlobalsBuilder)) -> Ty {
+    /// Type of the provider callable. If there are several creator functions (e.g. aliases),
+    /// the type is the union of all of them.
+    pub(crate) fn callable(&self, creator_funcs: &[for<'a> fn(&'a mut GlobalsBuilder)]) -> Ty {
         self.callable
-            .get_or_init(|| builtin_provider_typechecker_ty::<C>(creator_func))
+            .get_or_init(|| builtin_provider_typechecker_ty::<C>(creator_funcs))
             .dupe()
     }
 
@@ -77,23 +80,90 @@ impl<'v, P: StarlarkValue<'v> + ProviderLike<'v>, C: StarlarkValue<'v> + Provide
 }
 
 fn builtin_provider_typechecker_ty<'v, C: StarlarkValue<'v> + ProviderCallableLike>(
-    creator_func: for<'a> fn(&'a mut GlobalsBuilder),
+    creator_funcs: &[for<'a> fn(&'a mut GlobalsBuilder)],
 ) -> Ty {
-    let globals = GlobalsBuilder::new().with(creator_func).build();
-    let mut iter = globals.iter();
-    let Some(first) = iter.next() else {
+    let tys: Vec<Ty> = creator_func_tys(creator_funcs)
+        .into_iter()
+        .map(|ty_function| ty_provider_callable::<C>(ty_function).unwrap())
+        .collect();
+    if tys.is_empty() {
         panic!("empty globals");
-    };
-    if iter.next().is_some() {
-        panic!("more then one global in creator func globals");
     }
-    if first.1.to_value().get_type() != NativeFunction::TYPE {
-        panic!("creator func is not a function");
+    Ty::unions(tys)
+}
+
+/// Function types of all the globals defined by the creator functions.
+fn creator_func_tys(creator_funcs: &[for<'a> fn(&'a mut GlobalsBuilder)]) -> Vec<TyFunction> {
+    let mut globals = GlobalsBuilder::new();
+    for creator_func in creator_funcs {
+        globals = globals.with(*creator_func);
+    }
+    let globals = globals.build();
+    globals
+        .iter()
+        .map(|(_, creator)| {
+            if creator.to_value().get_type() != NativeFunction::TYPE {
+                panic!("creator func is not a function");
+            }
+            let ty = Ty::of_value(creator.to_value());
+            ty.as_function()
+                .expect("creator func is not a function")
+                .clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::environment::GlobalsBuilder;
+    use starlark::starlark_module;
+    use starlark::values::StarlarkValue;
+
+    use crate::interpreter::rule_defs::provider::builtin::platform_info::PlatformInfoCallable;
+    use crate::interpreter::rule_defs::provider::builtin::ty::builtin_provider_typechecker_ty;
+    use crate::interpreter::rule_defs::provider::builtin::ty::creator_func_tys;
+
+    #[starlark_module]
+    fn primary_creator(globals: &mut GlobalsBuilder) {
+        fn PlatformInfo(#[starlark(require = named)] label: &str) -> anyhow::Result<String> {
+            Ok(label.to_owned())
+        }
+    }
+
+    #[starlark_module]
+    fn alias_creator(globals: &mut GlobalsBuilder) {
+        fn LegacyPlatformInfo(#[starlark(require = named)] name: &str) -> anyhow::Result<String> {
+            Ok(name.to_owned())
+        }
+    }
+
+    #[test]
+    fn test_multiple_creator_funcs() {
+        let mut tys: Vec<String> = creator_func_tys(&[primary_creator, alias_creator])
+            .iter()
+            .map(|ty| ty.to_string())
+            .collect();
+        tys.sort();
+        assert_eq!(
+            vec![
+                "def(label: str) -> str".to_owned(),
+                "def(name: str) -> str".to_owned(),
+            ],
+            tys
+        );
+
+        let ty = builtin_provider_typechecker_ty::<PlatformInfoCallable>(&[primary_creator]);
+        assert_eq!(1, ty.iter_union().len());
+
+        let ty = builtin_provider_typechecker_ty::<PlatformInfoCallable>(&[
+            primary_creator,
+            alias_creator,
+        ]);
+        let [primary, alias] = ty.iter_union() else {
+            panic!("expected two callables, got `{}`", ty);
+        };
+        assert_ne!(primary, alias);
+        assert_eq!(PlatformInfoCallable::TYPE, primary.to_string());
+        assert_eq!(PlatformInfoCallable::TYPE, alias.to_string());
     }
-    let ty = Ty::of_value(first.1.to_value());
-    let ty_function = ty
-        .as_function()
-        .expect("creator func is not a function")
-        .clone();
-    ty_provider_callable::<C>(ty_function).unwrap()
 }
@@ -288,7 +288,7 @@ impl ProviderCodegen {
         Ok(syn::parse_quote_spanned! {
             self.span=>
             fn typechecker_ty(&self) -> Option<starlark::typing::Ty> {
-                Some(BUILTIN_PROVIDER_TY.callable(#creator_func))
+                Some(BUILTIN_PROVIDER_TY.callable(&[#creator_func]))
             }
         })
     }
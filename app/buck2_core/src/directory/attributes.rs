@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use allocative::Allocative;
+use serde::Serialize;
+
+use super::FingerprintedLeaf;
+
+/// The attribute marking a leaf as executable, see [`AttributedLeaf::is_executable`].
+pub const EXECUTABLE_ATTRIBUTE: &str = "executable";
+
+/// A leaf of a Directory along with extended attributes, i.e. metadata such as permissions that
+/// is not part of its content but matters when materializing it. Walks and manifests carry the
+/// attributes along with the leaf, and [`changed_since_manifest_with_attributes`] reports changes
+/// to the attributes alone separately from changes to the content.
+///
+/// [`changed_since_manifest_with_attributes`]: super::changed_since_manifest_with_attributes
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Allocative)]
+pub struct AttributedLeaf<L> {
+    pub leaf: L,
+    /// Empty for most leaves, so it is not serialized in that case.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl<L> AttributedLeaf<L> {
+    /// A leaf without any attributes.
+    pub fn new(leaf: L) -> Self {
+        Self {
+            leaf,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Marks this leaf as executable, or not.
+    pub fn with_executable(mut self, executable: bool) -> Self {
+        if executable {
+            self.attributes
+                .insert(EXECUTABLE_ATTRIBUTE.to_owned(), "true".to_owned());
+        } else {
+            self.attributes.remove(EXECUTABLE_ATTRIBUTE);
+        }
+        self
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(|v| v.as_str())
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.attribute(EXECUTABLE_ATTRIBUTE) == Some("true")
+    }
+
+    pub fn map_leaf<U>(self, f: impl FnOnce(L) -> U) -> AttributedLeaf<U> {
+        AttributedLeaf {
+            leaf: f(self.leaf),
+            attributes: self.attributes,
+        }
+    }
+}
+
+/// A digest which can be extended with the attributes of a leaf, so that leaves which differ only
+/// in their attributes have different fingerprints.
+pub trait AttributesDigest: Sized {
+    fn with_attributes(&self, attributes: &BTreeMap<String, String>) -> Self;
+}
+
+/// A leaf without attributes has the fingerprint of its content, so wrapping the leaves of a
+/// Directory doesn't change its fingerprint until attributes are set.
+impl<L, H> FingerprintedLeaf<H> for AttributedLeaf<L>
+where
+    L: FingerprintedLeaf<H>,
+    H: AttributesDigest,
+{
+    fn fingerprint(&self) -> H {
+        let fingerprint = self.leaf.fingerprint();
+        if self.attributes.is_empty() {
+            fingerprint
+        } else {
+            fingerprint.with_attributes(&self.attributes)
+        }
+    }
+}
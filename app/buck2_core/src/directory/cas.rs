@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use dupe::Dupe;
+use thiserror::Error;
+
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryHasher;
+use super::FingerprintedDirectory;
+use super::FingerprintedLeaf;
+use super::ImmutableDirectory;
+use crate::fs::paths::file_name::FileNameBuf;
+
+/// An object stored in a content-addressable store by `store`, keyed by its fingerprint.
+#[derive(Debug, Clone)]
+pub enum CasObject<L, H> {
+    Leaf(L),
+    /// The names of the entries of a directory, along with their fingerprints.
+    Dir(Vec<(FileNameBuf, H)>),
+}
+
+pub trait CasWriter<L, H> {
+    /// Whether an object with this fingerprint was already written, in which case it is not
+    /// written again.
+    fn contains(&self, digest: &H) -> bool;
+
+    fn write(&mut self, digest: H, object: CasObject<L, H>);
+}
+
+pub trait CasReader<L, H> {
+    fn read(&self, digest: &H) -> Option<CasObject<L, H>>;
+}
+
+#[derive(Debug, Error)]
+pub enum CasLoadError {
+    #[error("Object `{}` is missing from the CAS", .digest)]
+    Missing { digest: String },
+
+    #[error("Object `{}` is a leaf, expected a directory", .digest)]
+    NotADirectory { digest: String },
+
+    #[error("Directory `{}` was loaded with fingerprint `{}`", .digest, .actual)]
+    FingerprintMismatch { digest: String, actual: String },
+
+    #[error("Directory `{}` contains itself", .digest)]
+    Cycle { digest: String },
+}
+
+/// Write `dir` to `cas`, returning its fingerprint. Subtrees and leaves are written once per
+/// fingerprint: subtrees which are already in `cas` are not traversed.
+pub fn store<D, L, H>(dir: &D, cas: &mut impl CasWriter<L, H>) -> H
+where
+    D: FingerprintedDirectory<L, H> + ?Sized,
+    L: FingerprintedLeaf<H> + Clone,
+    H: DirectoryDigest,
+{
+    let digest = dir.fingerprint();
+    if cas.contains(digest) {
+        return digest.dupe();
+    }
+
+    let mut entries = Vec::new();
+    for (name, entry) in dir.fingerprinted_entries() {
+        let entry_digest = match entry {
+            DirectoryEntry::Dir(d) => store(d, cas),
+            DirectoryEntry::Leaf(l) => {
+                let leaf_digest = l.fingerprint();
+                if !cas.contains(&leaf_digest) {
+                    cas.write(leaf_digest.dupe(), CasObject::Leaf(l.clone()));
+                }
+                leaf_digest
+            }
+        };
+        entries.push((name.to_owned(), entry_digest));
+    }
+    cas.write(digest.dupe(), CasObject::Dir(entries));
+
+    digest.dupe()
+}
+
+/// Read the Directory with fingerprint `root` from `cas`, as written by `store`. Subtrees are read
+/// once per fingerprint. The Directory is fingerprinted with `hasher`, which must be the hasher
+/// the stored Directory was fingerprinted with, so that corrupt objects are detected.
+pub fn load<L, H>(
+    root: &H,
+    cas: &impl CasReader<L, H>,
+    hasher: &impl DirectoryHasher<L, H>,
+) -> Result<ImmutableDirectory<L, H>, CasLoadError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    match read(root, cas)? {
+        CasObject::Dir(entries) => load_dir(
+            root,
+            entries,
+            cas,
+            hasher,
+            &mut HashMap::new(),
+            &mut HashSet::new(),
+        ),
+        CasObject::Leaf(..) => Err(CasLoadError::NotADirectory {
+            digest: root.to_string(),
+        }),
+    }
+}
+
+/// Builds the directory with fingerprint `digest` from its `entries`. `loaded` holds the
+/// directories already built, by fingerprint, and `loading` the fingerprints of the directories
+/// being built, which a corrupt CAS may have a directory contain.
+fn load_dir<L, H>(
+    digest: &H,
+    entries: Vec<(FileNameBuf, H)>,
+    cas: &impl CasReader<L, H>,
+    hasher: &impl DirectoryHasher<L, H>,
+    loaded: &mut HashMap<H, ImmutableDirectory<L, H>>,
+    loading: &mut HashSet<H>,
+) -> Result<ImmutableDirectory<L, H>, CasLoadError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    if !loading.insert(digest.dupe()) {
+        return Err(CasLoadError::Cycle {
+            digest: digest.to_string(),
+        });
+    }
+
+    let mut builder = DirectoryBuilder::empty();
+    for (name, entry_digest) in entries {
+        let entry = match loaded.get(&entry_digest) {
+            Some(dir) => DirectoryEntry::Dir(dir.clone().into_builder()),
+            None => match read(&entry_digest, cas)? {
+                CasObject::Leaf(leaf) => DirectoryEntry::Leaf(leaf),
+                CasObject::Dir(entries) => DirectoryEntry::Dir(
+                    load_dir(&entry_digest, entries, cas, hasher, loaded, loading)?
+                        .into_builder(),
+                ),
+            },
+        };
+        builder
+            .insert(name, entry)
+            .expect("inserting a single path component cannot traverse a leaf");
+    }
+
+    loading.remove(digest);
+
+    let dir = builder.fingerprint(hasher);
+    if dir.fingerprint() != digest {
+        return Err(CasLoadError::FingerprintMismatch {
+            digest: digest.to_string(),
+            actual: dir.fingerprint().to_string(),
+        });
+    }
+    loaded.insert(digest.dupe(), dir.clone());
+    Ok(dir)
+}
+
+fn read<L, H>(digest: &H, cas: &impl CasReader<L, H>) -> Result<CasObject<L, H>, CasLoadError>
+where
+    H: DirectoryDigest,
+{
+    cas.read(digest).ok_or_else(|| CasLoadError::Missing {
+        digest: digest.to_string(),
+    })
+}
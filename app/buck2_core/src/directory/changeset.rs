@@ -0,0 +1,287 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use derivative::Derivative;
+use thiserror::Error;
+
+use super::find;
+use super::Directory;
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryFindError;
+use super::DirectoryInsertError;
+use super::PathAccumulator;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+#[derive(Debug, Error)]
+pub enum DirectoryChangesetError {
+    #[error("Changeset operation {} failed", .index)]
+    Insert {
+        index: usize,
+        #[source]
+        source: DirectoryInsertError,
+    },
+
+    #[error("Changeset operation {} failed", .index)]
+    Find {
+        index: usize,
+        #[source]
+        source: DirectoryFindError,
+    },
+}
+
+/// An operation of a changeset, see `apply_changeset`.
+#[derive(Derivative)]
+#[derivative(Debug(bound = "L: ::std::fmt::Debug"))]
+#[derivative(Clone(bound = "L: ::std::clone::Clone"))]
+pub enum DirectoryOp<L, H>
+where
+    H: DirectoryDigest,
+{
+    /// Insert `entry` at `path`, replacing whatever is there. See `DirectoryBuilder::insert`.
+    Insert {
+        path: ForwardRelativePathBuf,
+        entry: DirectoryEntry<DirectoryBuilder<L, H>, L>,
+    },
+    /// Remove the entry at `path`, which must exist.
+    Remove { path: ForwardRelativePathBuf },
+    /// Move the entry at `from`, which must exist, to `to`, replacing whatever is there.
+    Move {
+        from: ForwardRelativePathBuf,
+        to: ForwardRelativePathBuf,
+    },
+}
+
+/// How to undo an operation applied by `apply_changeset`.
+enum Undo<L, H>
+where
+    H: DirectoryDigest,
+{
+    /// Remove the entry the operation created at this path.
+    Remove(ForwardRelativePathBuf),
+    /// Put back the entry the operation replaced or removed at `path`.
+    Insert {
+        path: ForwardRelativePathBuf,
+        entry: DirectoryEntry<DirectoryBuilder<L, H>, L>,
+    },
+    /// Move the entry at `to` back to `from`, after which `then` undoes the rest of the insert at
+    /// `to`.
+    Move {
+        from: ForwardRelativePathBuf,
+        to: ForwardRelativePathBuf,
+        then: Option<Box<Undo<L, H>>>,
+    },
+}
+
+/// Apply `ops` to `dir` in order, atomically: if any of them fails, the contents of `dir` are left
+/// unchanged.
+///
+/// The operations are applied to `dir` in place. Each one is validated before it changes anything,
+/// and records how to undo it, so that the operations applied before a failing one can be rolled
+/// back without copying `dir`.
+pub fn apply_changeset<L, H>(
+    dir: &mut DirectoryBuilder<L, H>,
+    ops: &[DirectoryOp<L, H>],
+) -> Result<(), DirectoryChangesetError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    let mut undo = Vec::new();
+
+    for (index, op) in ops.iter().enumerate() {
+        if let Err(e) = apply_op(dir, op, &mut undo) {
+            for step in undo.into_iter().rev() {
+                rollback(dir, step);
+            }
+            return Err(e.into_changeset_error(index));
+        }
+    }
+
+    Ok(())
+}
+
+enum OpError {
+    Insert(DirectoryInsertError),
+    Find(DirectoryFindError),
+}
+
+impl OpError {
+    fn into_changeset_error(self, index: usize) -> DirectoryChangesetError {
+        match self {
+            OpError::Insert(source) => DirectoryChangesetError::Insert { index, source },
+            OpError::Find(source) => DirectoryChangesetError::Find { index, source },
+        }
+    }
+}
+
+/// Apply `op` to `dir`, pushing to `undo` how to undo it. If it fails, `dir` is left unchanged.
+fn apply_op<L, H>(
+    dir: &mut DirectoryBuilder<L, H>,
+    op: &DirectoryOp<L, H>,
+    undo: &mut Vec<Undo<L, H>>,
+) -> Result<(), OpError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    match op {
+        DirectoryOp::Insert { path, entry } => {
+            let created = created_by_insert(dir, path).map_err(OpError::Insert)?;
+            let replaced = dir.insert(path, entry.clone()).map_err(OpError::Insert)?;
+            undo.push(insert_undo(path, created, replaced));
+        }
+        DirectoryOp::Remove { path } => {
+            let entry = take(dir, path).map_err(OpError::Find)?;
+            undo.push(Undo::Insert {
+                path: path.clone(),
+                entry,
+            });
+        }
+        DirectoryOp::Move { from, to } => {
+            let entry = take(dir, from).map_err(OpError::Find)?;
+            let created = match created_by_insert(dir, to) {
+                Ok(created) => created,
+                Err(e) => {
+                    dir.insert(from, entry)
+                        .expect("the entry was just taken from there");
+                    return Err(OpError::Insert(e));
+                }
+            };
+            let replaced = dir.insert(to, entry).expect("validated above");
+            let then = match insert_undo(to, created, replaced) {
+                // Taking the entry back removes it already.
+                Undo::Remove(created) if created == *to => None,
+                then => Some(Box::new(then)),
+            };
+            undo.push(Undo::Move {
+                from: from.clone(),
+                to: to.clone(),
+                then,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// How to undo inserting at `path`, given what `created_by_insert` and the insert returned.
+fn insert_undo<L, H>(
+    path: &ForwardRelativePath,
+    created: Option<ForwardRelativePathBuf>,
+    replaced: Option<DirectoryEntry<DirectoryBuilder<L, H>, L>>,
+) -> Undo<L, H>
+where
+    H: DirectoryDigest,
+{
+    match replaced {
+        Some(entry) => Undo::Insert {
+            path: path.to_buf(),
+            entry,
+        },
+        None => Undo::Remove(created.unwrap_or_else(|| path.to_buf())),
+    }
+}
+
+fn rollback<L, H>(dir: &mut DirectoryBuilder<L, H>, undo: Undo<L, H>)
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    match undo {
+        Undo::Remove(path) => {
+            take(dir, &path).expect("the operation created this entry");
+        }
+        Undo::Insert { path, entry } => {
+            dir.insert(&path, entry)
+                .expect("the operation replaced or removed this entry");
+        }
+        Undo::Move { from, to, then } => {
+            let entry = take(dir, &to).expect("the operation moved this entry");
+            if let Some(then) = then {
+                rollback(dir, *then);
+            }
+            dir.insert(&from, entry)
+                .expect("the operation moved this entry");
+        }
+    }
+}
+
+/// The shortest prefix of `path` with no entry in `dir`, which inserting at `path` creates, or
+/// `None` if there is an entry at `path` already. Fails like `DirectoryBuilder::insert` would,
+/// without changing anything.
+fn created_by_insert<L, H>(
+    dir: &DirectoryBuilder<L, H>,
+    path: &ForwardRelativePath,
+) -> Result<Option<ForwardRelativePathBuf>, DirectoryInsertError>
+where
+    H: DirectoryDigest,
+{
+    if path.is_empty() {
+        return Err(DirectoryInsertError::EmptyPath);
+    }
+
+    let mut dir: &dyn Directory<L, H> = dir;
+    let mut prefix = Vec::new();
+    let mut names = path.iter().peekable();
+    while let Some(name) = names.next() {
+        prefix.push(name);
+        match dir.get(name) {
+            Some(DirectoryEntry::Dir(d)) => dir = d,
+            Some(DirectoryEntry::Leaf(..)) if names.peek().is_some() => {
+                let path = prefix[..prefix.len() - 1]
+                    .iter()
+                    .rev()
+                    .fold(PathAccumulator::new(name), |path, name| path.with(name));
+                return Err(DirectoryInsertError::CannotTraverseLeaf { path });
+            }
+            Some(DirectoryEntry::Leaf(..)) => {}
+            None => {
+                let mut created = ForwardRelativePathBuf::empty();
+                for name in prefix {
+                    created.push(name);
+                }
+                return Ok(Some(created));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Remove the entry at `path` and return it.
+fn take<L, H>(
+    dir: &mut DirectoryBuilder<L, H>,
+    path: &ForwardRelativePath,
+) -> Result<DirectoryEntry<DirectoryBuilder<L, H>, L>, DirectoryFindError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    if find(dir, path.iter())?.is_none() {
+        return Err(DirectoryFindError::NotFound {
+            path: path.to_buf(),
+        });
+    }
+
+    let (dir_path, name) = match (path.parent(), path.file_name()) {
+        (Some(dir_path), Some(name)) => (dir_path, name),
+        _ => unreachable!("find rejects empty paths"),
+    };
+    let mut parent = dir;
+    for name in dir_path.iter() {
+        parent = match parent.as_mut().get_mut(name) {
+            Some(DirectoryEntry::Dir(d)) => d,
+            _ => unreachable!("find traversed this path"),
+        };
+    }
+
+    Ok(parent.as_mut().remove(name).expect("find found this entry"))
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use derivative::Derivative;
+use dupe::Dupe;
+
+use super::Directory;
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntries;
+use super::DirectoryEntry;
+use super::DirectoryInsertError;
+use super::DirectoryMkdirError;
+use super::ImmutableDirectory;
+use super::SharedDirectory;
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::IntoFileNameBufIterator;
+
+/// A copy-on-write view of a `SharedDirectory`. Reads are served by the original directory, and
+/// writing at a path only copies the directories along that path: all other subtrees remain
+/// shared with the original. This makes speculative mutations of large directories cheap.
+#[derive(Derivative)]
+#[derivative(Debug(bound = "L: ::std::fmt::Debug"))]
+#[derivative(Clone(bound = "L: ::std::clone::Clone"))]
+pub struct CowDirectory<L, H>
+where
+    H: DirectoryDigest,
+{
+    builder: DirectoryBuilder<L, H>,
+}
+
+impl<L, H> CowDirectory<L, H>
+where
+    H: DirectoryDigest,
+{
+    pub fn new(original: &SharedDirectory<L, H>) -> Self {
+        Self {
+            builder: original.dupe().into_builder(),
+        }
+    }
+
+    /// The subtree at `path`, if it has not been copied, i.e. if it is still shared with the
+    /// original. An empty `path` refers to the whole directory.
+    pub fn shared_subtree(&self, path: &ForwardRelativePath) -> Option<&SharedDirectory<L, H>> {
+        let mut path = path.iter();
+        let mut builder = &self.builder;
+
+        loop {
+            match builder {
+                DirectoryBuilder::Immutable(ImmutableDirectory::Shared(dir)) => {
+                    let mut dir = dir;
+                    for name in path.by_ref() {
+                        match dir.get(name)? {
+                            DirectoryEntry::Dir(d) => dir = d,
+                            DirectoryEntry::Leaf(..) => return None,
+                        }
+                    }
+                    return Some(dir);
+                }
+                DirectoryBuilder::Immutable(ImmutableDirectory::Exclusive(..)) => return None,
+                DirectoryBuilder::Mutable(entries) => match entries.get(path.next()?)? {
+                    DirectoryEntry::Dir(d) => builder = d,
+                    DirectoryEntry::Leaf(..) => return None,
+                },
+            }
+        }
+    }
+
+    pub fn into_builder(self) -> DirectoryBuilder<L, H> {
+        self.builder
+    }
+}
+
+impl<L, H> CowDirectory<L, H>
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    /// Insert the entry `val` at `path`, copying the directories along `path`. See
+    /// `DirectoryBuilder::insert`.
+    pub fn insert(
+        &mut self,
+        path: impl IntoFileNameBufIterator,
+        val: DirectoryEntry<DirectoryBuilder<L, H>, L>,
+    ) -> Result<Option<DirectoryEntry<DirectoryBuilder<L, H>, L>>, DirectoryInsertError> {
+        self.builder.insert(path, val)
+    }
+
+    /// Create a directory at `path`, copying the directories along `path`. See
+    /// `DirectoryBuilder::mkdir`.
+    pub fn mkdir(&mut self, path: impl IntoFileNameBufIterator) -> Result<(), DirectoryMkdirError> {
+        self.builder.mkdir(path)
+    }
+}
+
+impl<L, H> Directory<L, H> for CowDirectory<L, H>
+where
+    H: DirectoryDigest,
+{
+    fn entries(&self) -> DirectoryEntries<'_, L, H> {
+        Directory::entries(&self.builder)
+    }
+
+    fn get<'a>(
+        &'a self,
+        needle: &'_ FileName,
+    ) -> Option<DirectoryEntry<&'a dyn Directory<L, H>, &'a L>> {
+        Directory::get(&self.builder, needle)
+    }
+
+    fn to_builder(&self) -> DirectoryBuilder<L, H>
+    where
+        L: Clone,
+    {
+        self.builder.clone()
+    }
+}
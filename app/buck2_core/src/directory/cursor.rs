@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use dupe::Dupe;
+use thiserror::Error;
+
+use super::Directory;
+use super::DirectoryEntry;
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::file_name::FileNameBuf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+#[derive(Debug, Error)]
+pub enum DirectoryCursorError {
+    #[error("No entry named `{}` in directory: `{}`", .name, .path)]
+    NotFound {
+        path: ForwardRelativePathBuf,
+        name: FileNameBuf,
+    },
+
+    #[error("Cannot descend into a leaf at path: `{}`", .path)]
+    CannotDescendLeaf { path: ForwardRelativePathBuf },
+
+    #[error("Cannot ascend from the root")]
+    AtRoot,
+}
+
+/// A position in a Directory that can move both down into children and back up to parents.
+///
+/// Directories don't know their parents, so the cursor records the path it took from the root,
+/// and ascending walks that path again from the root.
+pub struct DirectoryCursor<'a, L, H> {
+    root: &'a dyn Directory<L, H>,
+    path: Vec<FileNameBuf>,
+    entry: DirectoryEntry<&'a dyn Directory<L, H>, &'a L>,
+}
+
+impl<'a, L, H> DirectoryCursor<'a, L, H> {
+    pub fn new<D: Directory<L, H>>(root: &'a D) -> Self {
+        Self {
+            root,
+            path: Vec::new(),
+            entry: DirectoryEntry::Dir(root),
+        }
+    }
+
+    /// The entry the cursor currently points at.
+    pub fn entry(&self) -> DirectoryEntry<&'a dyn Directory<L, H>, &'a L> {
+        self.entry.dupe()
+    }
+
+    /// The path of the current entry relative to the root.
+    pub fn path(&self) -> ForwardRelativePathBuf {
+        self.path
+            .iter()
+            .collect::<Option<ForwardRelativePathBuf>>()
+            .unwrap_or_else(ForwardRelativePathBuf::empty)
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Move to the child `name` of the current directory.
+    pub fn descend(&mut self, name: &FileName) -> Result<(), DirectoryCursorError> {
+        let dir = match self.entry {
+            DirectoryEntry::Dir(d) => d,
+            DirectoryEntry::Leaf(..) => {
+                return Err(DirectoryCursorError::CannotDescendLeaf { path: self.path() });
+            }
+        };
+
+        match dir.get(name) {
+            Some(entry) => {
+                self.entry = entry;
+                self.path.push(name.to_owned());
+                Ok(())
+            }
+            None => Err(DirectoryCursorError::NotFound {
+                path: self.path(),
+                name: name.to_owned(),
+            }),
+        }
+    }
+
+    /// Move to the parent of the current entry.
+    pub fn ascend(&mut self) -> Result<(), DirectoryCursorError> {
+        if self.path.pop().is_none() {
+            return Err(DirectoryCursorError::AtRoot);
+        }
+
+        let mut entry = DirectoryEntry::Dir(self.root);
+        for name in &self.path {
+            entry = match entry {
+                DirectoryEntry::Dir(d) => d
+                    .get(name)
+                    .expect("Cursor path was valid when descending and the tree is borrowed"),
+                DirectoryEntry::Leaf(..) => unreachable!("Cursor path never traverses a leaf"),
+            };
+        }
+        self.entry = entry;
+
+        Ok(())
+    }
+}
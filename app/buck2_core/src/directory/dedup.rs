@@ -0,0 +1,26 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::DashMapDirectoryInterner;
+use super::DirectoryDigest;
+use super::ImmutableDirectory;
+use super::SharedDirectory;
+
+/// Share identical subtrees of this Directory, so that the memory it uses is proportional to its
+/// unique content rather than its size. Subtrees are considered identical when their fingerprints
+/// match, so the resulting Directory has exactly the same structure.
+///
+/// This interns the Directory into an interner private to it. Subtrees that are already shared
+/// are kept as they are.
+pub fn dedup<L, H>(dir: ImmutableDirectory<L, H>) -> SharedDirectory<L, H>
+where
+    H: DirectoryDigest,
+{
+    dir.shared(&DashMapDirectoryInterner::new())
+}
@@ -14,12 +14,14 @@ use std::marker::PhantomData;
 use allocative::Allocative;
 use derivative::Derivative;
 use derive_more::Display;
+use once_cell::sync::OnceCell;
 use sorted_vector_map::SortedVectorMap;
 
 use super::DirectoryDigest;
 use super::DirectoryEntry;
 use super::DirectoryHasher;
 use super::FingerprintedDirectory;
+use super::LeafSize;
 use crate::fs::paths::file_name::FileNameBuf;
 
 #[derive(Derivative, Display, Allocative)]
@@ -36,6 +38,12 @@ where
 
     pub(super) fingerprint: H,
 
+    /// The total size of the leaves, computed the first time it is requested, see
+    /// [`FingerprintedDirectory::size`].
+    #[derivative(Debug = "ignore")]
+    #[allocative(skip)]
+    pub(super) size: OnceCell<u64>,
+
     #[derivative(Debug = "ignore")]
     pub(super) _hash: PhantomData<H>,
 }
@@ -47,6 +55,23 @@ where
     pub fn fingerprint(&self) -> &H {
         &self.fingerprint
     }
+
+    /// The total size of the leaves of this directory, computed once per directory.
+    pub fn size(&self) -> u64
+    where
+        D: FingerprintedDirectory<L, H>,
+        L: LeafSize,
+    {
+        *self.size.get_or_init(|| {
+            self.entries
+                .values()
+                .map(|entry| match entry {
+                    DirectoryEntry::Dir(dir) => dir.size(),
+                    DirectoryEntry::Leaf(leaf) => leaf.size(),
+                })
+                .sum()
+        })
+    }
 }
 
 impl<D, L, H> DirectoryData<D, L, H>
@@ -63,6 +88,7 @@ where
         Self {
             entries,
             fingerprint,
+            size: OnceCell::new(),
             _hash: PhantomData,
         }
     }
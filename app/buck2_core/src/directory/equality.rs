@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::Directory;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::FingerprintedDirectory;
+
+/// Whether two trees have the same structure and the same leaves. This stops at the first
+/// difference found, so it is cheaper than collecting all the differences.
+///
+/// Fingerprinted trees should use [`trees_equal_fingerprinted`] instead.
+pub fn trees_equal<L, H, A, B>(a: &A, b: &B) -> bool
+where
+    L: PartialEq,
+    A: Directory<L, H>,
+    B: Directory<L, H>,
+{
+    dirs_equal(a, b)
+}
+
+fn dirs_equal<L, H>(a: &dyn Directory<L, H>, b: &dyn Directory<L, H>) -> bool
+where
+    L: PartialEq,
+{
+    a.entries().count() == b.entries().count()
+        && a.entries().all(|(name, entry)| match (entry, b.get(name)) {
+            (DirectoryEntry::Dir(a), Some(DirectoryEntry::Dir(b))) => dirs_equal(a, b),
+            (DirectoryEntry::Leaf(a), Some(DirectoryEntry::Leaf(b))) => a == b,
+            _ => false,
+        })
+}
+
+/// Whether two fingerprinted trees are equal. The fingerprint of a tree covers all of its
+/// contents, so this only compares the top-level fingerprints and never looks at the entries.
+pub fn trees_equal_fingerprinted<L, H, A, B>(a: &A, b: &B) -> bool
+where
+    H: DirectoryDigest,
+    A: FingerprintedDirectory<L, H>,
+    B: FingerprintedDirectory<L, H>,
+{
+    a.fingerprint() == b.fingerprint()
+}
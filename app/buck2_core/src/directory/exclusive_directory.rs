@@ -22,6 +22,7 @@ use super::DirectoryEntry;
 use super::FingerprintedDirectory;
 use super::FingerprintedDirectoryEntries;
 use super::ImmutableDirectory;
+use super::LeafSize;
 use super::SharedDirectory;
 use crate::fs::paths::file_name::FileName;
 use crate::fs::paths::file_name::FileNameBuf;
@@ -49,6 +50,7 @@ where
         let DirectoryData {
             entries,
             fingerprint,
+            size,
             _hash,
         } = self.data;
 
@@ -60,6 +62,7 @@ where
         let new_data = DirectoryData {
             entries,
             fingerprint,
+            size,
             _hash,
         };
 
@@ -95,6 +98,13 @@ where
         self.data.fingerprint()
     }
 
+    pub fn size(&self) -> u64
+    where
+        L: LeafSize,
+    {
+        self.data.size()
+    }
+
     pub fn into_builder(self) -> DirectoryBuilder<L, H> {
         DirectoryBuilder::Immutable(ImmutableDirectory::Exclusive(self))
     }
@@ -7,14 +7,22 @@
  * of this source tree.
  */
 
+use std::any::Any;
+
+use dupe::Dupe;
 use thiserror::Error;
 
 use super::Directory;
+use super::DirectoryDigest;
 use super::DirectoryEntry;
+use super::DirectoryLeaf;
 use super::DirectoryMut;
 use super::FingerprintedDirectory;
+use super::FingerprintedLeaf;
 use super::PathAccumulator;
 use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::file_name::FileNameBuf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
 use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 
 #[derive(Debug, Error)]
@@ -24,8 +32,20 @@ pub enum DirectoryFindError {
 
     #[error("Find would traverse a leaf at path: `{}`", .path)]
     CannotTraverseLeaf { path: PathAccumulator },
+
+    #[error("Path does not exist: `{}`", .path)]
+    NotFound { path: ForwardRelativePathBuf },
+
+    #[error("Path is a leaf, not a directory: `{}`", .path)]
+    NotADirectory { path: ForwardRelativePathBuf },
+
+    #[error("Too many levels of symlinks resolving path: `{}`", .path)]
+    SymlinkLoop { path: ForwardRelativePathBuf },
 }
 
+/// How many symlinks `canonicalize_symlinks` follows before assuming there is a loop.
+const MAX_SYMLINKS_FOLLOWED: usize = 40;
+
 trait FindConflict<T> {
     fn new<'b>(path: &'b FileName, remaining: impl Iterator<Item = &'b FileName>, leaf: T) -> Self;
 
@@ -169,3 +189,259 @@ impl_find!(
     impl_find_mut,
     mut,
 );
+
+/// A leaf that can be viewed as `Any`, so that `find_leaf_as` can downcast it. For a leaf that is
+/// an enum, this is typically the payload of the variant.
+pub trait LeafAsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Find the leaf at `path` and downcast it to `T`. Returns `None` if nothing exists at `path`, if
+/// it is a directory, or if the leaf is not a `T`.
+pub fn find_leaf_as<'a, 'b, T, L, H, D>(
+    dir: &'a D,
+    path: impl IntoIterator<Item = &'b FileName>,
+) -> Result<Option<&'a T>, DirectoryFindError>
+where
+    T: Any,
+    L: LeafAsAny,
+    D: Directory<L, H>,
+{
+    Ok(match find(dir, path)? {
+        Some(DirectoryEntry::Leaf(leaf)) => leaf.as_any().downcast_ref(),
+        Some(DirectoryEntry::Dir(_)) | None => None,
+    })
+}
+
+/// List the immediate children of the directory at `path`, sorted by name. An empty `path` lists
+/// the children of `dir` itself. Unlike `find`, a missing path or a path that resolves to a leaf
+/// is an error.
+pub fn list_children<'a, L, H, D: Directory<L, H>>(
+    dir: &'a D,
+    path: &ForwardRelativePath,
+) -> Result<Vec<(FileNameBuf, DirectoryEntry<&'a dyn Directory<L, H>, &'a L>)>, DirectoryFindError>
+{
+    let dir: &'a dyn Directory<L, H> = if path.is_empty() {
+        dir
+    } else {
+        match find(dir, path.iter())? {
+            Some(DirectoryEntry::Dir(dir)) => dir,
+            Some(DirectoryEntry::Leaf(_)) => {
+                return Err(DirectoryFindError::NotADirectory {
+                    path: path.to_buf(),
+                });
+            }
+            None => {
+                return Err(DirectoryFindError::NotFound {
+                    path: path.to_buf(),
+                });
+            }
+        }
+    };
+
+    let mut children = dir
+        .entries()
+        .map(|(name, entry)| (name.to_owned(), entry))
+        .collect::<Vec<_>>();
+    children.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(children)
+}
+
+/// The deepest directory of `dir` that contains all of `paths`, along with its path. This is the
+/// longest common prefix of `paths`, or its parent if that prefix is a leaf. When `paths` share no
+/// prefix, this is `dir` itself, at the empty path. Returns `None` if the common prefix does not
+/// exist.
+pub fn common_ancestor_dir<'a, L, H, D: Directory<L, H>>(
+    dir: &'a D,
+    paths: &[impl AsRef<ForwardRelativePath>],
+) -> Result<Option<(ForwardRelativePathBuf, &'a dyn Directory<L, H>)>, DirectoryFindError> {
+    let mut prefix: Vec<&FileName> = match paths.first() {
+        Some(first) => first.as_ref().iter().collect(),
+        None => Vec::new(),
+    };
+    for path in paths.iter().skip(1) {
+        let common = prefix
+            .iter()
+            .zip(path.as_ref().iter())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(common);
+    }
+
+    let mut ancestor: &'a dyn Directory<L, H> = dir;
+    let mut depth = 0;
+    for (i, name) in prefix.iter().enumerate() {
+        match ancestor.get(name) {
+            Some(DirectoryEntry::Dir(d)) => ancestor = d,
+            // Only all paths being the same leaf gets here: its directory is the one we want.
+            Some(DirectoryEntry::Leaf(..)) if i + 1 == prefix.len() => break,
+            Some(DirectoryEntry::Leaf(..)) => {
+                return Err(DirectoryFindError::NotADirectory {
+                    path: prefix[..=i]
+                        .iter()
+                        .copied()
+                        .collect::<Option<ForwardRelativePathBuf>>()
+                        .expect("We know there is at least one path component"),
+                });
+            }
+            None => return Ok(None),
+        }
+        depth = i + 1;
+    }
+
+    let path = prefix[..depth]
+        .iter()
+        .copied()
+        .collect::<Option<ForwardRelativePathBuf>>()
+        .unwrap_or_else(ForwardRelativePathBuf::empty);
+    Ok(Some((path, ancestor)))
+}
+
+/// Paths of `dir` that `missing`, a path which does not exist in it, was likely meant to be, for
+/// "did you mean" error messages. This finds the deepest directory of `dir` that `missing` is
+/// under, and suggests at most `max` of its entries, closest first by edit distance to the
+/// component of `missing` which does not exist in it. Returns nothing if `missing` exists.
+pub fn suggest_paths<'a, L, H, D: Directory<L, H>>(
+    dir: &'a D,
+    missing: &ForwardRelativePath,
+    max: usize,
+) -> Vec<ForwardRelativePathBuf> {
+    let components = missing.iter().collect::<Vec<_>>();
+
+    let mut ancestor: &'a dyn Directory<L, H> = dir;
+    let mut depth = 0;
+    while let Some(name) = components.get(depth) {
+        match ancestor.get(name) {
+            Some(DirectoryEntry::Dir(d)) => ancestor = d,
+            Some(DirectoryEntry::Leaf(..)) if depth + 1 == components.len() => return Vec::new(),
+            Some(DirectoryEntry::Leaf(..)) | None => break,
+        }
+        depth += 1;
+    }
+
+    let needle = match components.get(depth) {
+        Some(needle) => needle.as_str(),
+        // `missing` is a directory of `dir`.
+        None => return Vec::new(),
+    };
+
+    let mut candidates = ancestor
+        .entries()
+        .map(|(name, _)| (strsim::levenshtein(needle, name.as_str()), name))
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .take(max)
+        .map(|(_, name)| {
+            components[..depth]
+                .iter()
+                .copied()
+                .chain(std::iter::once(name))
+                .collect::<Option<ForwardRelativePathBuf>>()
+                .expect("We know there is at least one path component")
+        })
+        .collect()
+}
+
+/// The fingerprint of whatever is at `path`: the fingerprint of the directory if it is one, or of
+/// the leaf otherwise. An empty `path` refers to `dir` itself. Returns `None` if nothing exists at
+/// `path`.
+pub fn subtree_fingerprint<'a, L, H, D>(
+    dir: &'a D,
+    path: &ForwardRelativePath,
+) -> Result<Option<H>, DirectoryFindError>
+where
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+    D: FingerprintedDirectory<L, H>,
+{
+    if path.is_empty() {
+        return Ok(Some(dir.fingerprint().dupe()));
+    }
+
+    let entry = find_fingerprinted(dir, path.iter())?;
+    Ok(entry.map(|entry| match entry {
+        DirectoryEntry::Dir(dir) => dir.fingerprint().dupe(),
+        DirectoryEntry::Leaf(leaf) => leaf.fingerprint(),
+    }))
+}
+
+/// Check that `path` exists in `dir`, and return it. Paths in a Directory are already normalized,
+/// so this is the canonical path to the entry as long as the tree has no symlinks. See
+/// `canonicalize_symlinks` for trees that do.
+pub fn canonicalize<L, H, D: Directory<L, H>>(
+    dir: &D,
+    path: &ForwardRelativePath,
+) -> Result<ForwardRelativePathBuf, DirectoryFindError> {
+    if path.is_empty() {
+        return Ok(path.to_buf());
+    }
+
+    match find(dir, path.iter())? {
+        Some(..) => Ok(path.to_buf()),
+        None => Err(DirectoryFindError::NotFound {
+            path: path.to_buf(),
+        }),
+    }
+}
+
+/// Resolve `path` to the canonical path of the entry it refers to, following any symlinks found
+/// along the way, including one at `path` itself. Symlink targets are relative to the root of
+/// `dir`.
+pub fn canonicalize_symlinks<L, H, D: Directory<DirectoryLeaf<L>, H>>(
+    dir: &D,
+    path: &ForwardRelativePath,
+) -> Result<ForwardRelativePathBuf, DirectoryFindError> {
+    let mut resolved = path.to_buf();
+    let mut followed = 0;
+
+    loop {
+        let mut current: &dyn Directory<DirectoryLeaf<L>, H> = dir;
+        let mut next = None;
+        let prefix = |i: usize| {
+            resolved
+                .iter()
+                .take(i + 1)
+                .collect::<Option<ForwardRelativePathBuf>>()
+                .expect("We know there is at least one path component")
+        };
+
+        for (i, name) in resolved.iter().enumerate() {
+            match current.get(name) {
+                Some(DirectoryEntry::Dir(dir)) => current = dir,
+                Some(DirectoryEntry::Leaf(DirectoryLeaf::Symlink { target })) => {
+                    let rest = resolved
+                        .iter()
+                        .skip(i + 1)
+                        .collect::<Option<ForwardRelativePathBuf>>();
+                    next = Some(match rest {
+                        Some(rest) => target.join(rest),
+                        None => target.clone(),
+                    });
+                    break;
+                }
+                Some(DirectoryEntry::Leaf(DirectoryLeaf::File(..))) => {
+                    if resolved.iter().nth(i + 1).is_some() {
+                        return Err(DirectoryFindError::NotADirectory { path: prefix(i) });
+                    }
+                }
+                None => return Err(DirectoryFindError::NotFound { path: prefix(i) }),
+            }
+        }
+
+        match next {
+            None => return Ok(resolved),
+            Some(next) => {
+                followed += 1;
+                if followed > MAX_SYMLINKS_FOLLOWED {
+                    return Err(DirectoryFindError::SymlinkLoop {
+                        path: path.to_buf(),
+                    });
+                }
+                resolved = next;
+            }
+        }
+    }
+}
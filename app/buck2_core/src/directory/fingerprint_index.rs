@@ -0,0 +1,73 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+
+use dupe::Dupe;
+
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryIterator;
+use super::FingerprintedDirectory;
+use super::FingerprintedLeaf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// An index of the paths of the entries of a FingerprintedDirectory by fingerprint, to find
+/// where some content is in the Directory without walking it.
+///
+/// Both leaves and directories are indexed. Building the index walks the whole Directory once.
+/// The index is a snapshot: it does not follow changes to the Directory it was built from, so
+/// check [`FingerprintIndex::is_valid_for`] before using it for a Directory that may have changed.
+#[derive(Debug, Clone)]
+pub struct FingerprintIndex<H> {
+    /// The fingerprint of the Directory this was built from.
+    root: H,
+    paths: HashMap<H, Vec<ForwardRelativePathBuf>>,
+}
+
+impl<H> FingerprintIndex<H>
+where
+    H: DirectoryDigest,
+{
+    pub fn build<D, L>(dir: &D) -> Self
+    where
+        D: FingerprintedDirectory<L, H> + ?Sized,
+        L: FingerprintedLeaf<H>,
+    {
+        let mut paths = HashMap::<H, Vec<ForwardRelativePathBuf>>::new();
+        for (path, entry) in dir.fingerprinted_ordered_walk().with_paths() {
+            let fingerprint = match entry {
+                DirectoryEntry::Dir(d) => d.fingerprint().dupe(),
+                DirectoryEntry::Leaf(l) => l.fingerprint(),
+            };
+            paths.entry(fingerprint).or_default().push(path);
+        }
+
+        Self {
+            root: dir.fingerprint().dupe(),
+            paths,
+        }
+    }
+
+    /// The paths of the entries with this fingerprint, sorted. This is empty if there are none.
+    pub fn paths_with_fingerprint(&self, fingerprint: &H) -> &[ForwardRelativePathBuf] {
+        self.paths
+            .get(fingerprint)
+            .map_or(&[], |paths| paths.as_slice())
+    }
+
+    /// Whether this index was built from a Directory with the same content as `dir`, i.e.
+    /// whether it is still accurate for `dir`.
+    pub fn is_valid_for<D, L>(&self, dir: &D) -> bool
+    where
+        D: FingerprintedDirectory<L, H> + ?Sized,
+    {
+        dir.fingerprint() == &self.root
+    }
+}
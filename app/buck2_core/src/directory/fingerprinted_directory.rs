@@ -13,6 +13,7 @@ use super::DirectoryDigest;
 use super::DirectoryEntry;
 use super::FingerprintedOrderedDirectoryWalk;
 use super::FingerprintedUnorderedDirectoryWalk;
+use super::LeafSize;
 use crate::fs::paths::file_name::FileName;
 
 pub type FingerprintedDirectoryEntries<'a, L, H> = Box<
@@ -43,6 +44,28 @@ pub trait FingerprintedDirectory<L, H> {
     fn fingerprint(&self) -> &H
     where
         H: DirectoryDigest;
+
+    /// The total size of the leaves of this directory. Directories built from `DirectoryData`
+    /// compute it once per directory, so that it is O(1) once computed. Others walk their
+    /// entries.
+    fn size(&self) -> u64
+    where
+        L: LeafSize,
+    {
+        self.fingerprinted_entries()
+            .map(|(_, entry)| match entry {
+                DirectoryEntry::Dir(dir) => dir.size(),
+                DirectoryEntry::Leaf(leaf) => leaf.size(),
+            })
+            .sum()
+    }
+}
+
+/// A leaf which carries its own fingerprint, e.g. the digest of a file.
+pub trait FingerprintedLeaf<H> {
+    /// Returned by value, as it may be derived from the leaf rather than stored in it, e.g. for
+    /// [`AttributedLeaf`](super::AttributedLeaf).
+    fn fingerprint(&self) -> H;
 }
 
 impl<'a, L, H> fmt::Debug for &'a dyn FingerprintedDirectory<L, H> {
@@ -22,6 +22,7 @@ use super::DirectoryEntry;
 use super::ExclusiveDirectory;
 use super::FingerprintedDirectory;
 use super::FingerprintedDirectoryEntries;
+use super::LeafSize;
 use super::SharedDirectory;
 use crate::fs::paths::file_name::FileName;
 use crate::fs::paths::file_name::FileNameBuf;
@@ -128,6 +129,16 @@ where
             Self::Shared(dir) => FingerprintedDirectory::fingerprint(dir),
         }
     }
+
+    fn size(&self) -> u64
+    where
+        L: LeafSize,
+    {
+        match self {
+            Self::Exclusive(dir) => FingerprintedDirectory::size(dir),
+            Self::Shared(dir) => FingerprintedDirectory::size(dir),
+        }
+    }
 }
 
 impl<L, H> PartialEq for ImmutableDirectory<L, H>
@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::Directory;
+use super::DirectoryEntries;
+use super::DirectoryEntry;
+use super::VisitControl;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// Selects the leaves returned by `leaves_where`. Paths are relative to the root that was passed
+/// to `leaves_where`.
+///
+/// This is implemented for `Fn(&ForwardRelativePath, &L) -> bool`, which never prunes.
+pub trait LeafFilter<L> {
+    fn matches(&self, path: &ForwardRelativePath, leaf: &L) -> bool;
+
+    /// Called before descending into the directory at `path`. Returning
+    /// `VisitControl::SkipChildren` excludes everything under it without iterating over it, so
+    /// a filter that only accepts some path prefixes should prune the directories outside them.
+    fn enter_dir(&self, path: &ForwardRelativePath) -> VisitControl {
+        let _ = path;
+        VisitControl::Continue
+    }
+}
+
+impl<L, F> LeafFilter<L> for F
+where
+    F: Fn(&ForwardRelativePath, &L) -> bool,
+{
+    fn matches(&self, path: &ForwardRelativePath, leaf: &L) -> bool {
+        self(path, leaf)
+    }
+}
+
+/// Iterate over the leaves under `dir` that `filter` matches, along with their paths, depth-first.
+/// Leaves within a directory are returned in the order `Directory::entries` returns them.
+pub fn leaves_where<'a, D, L, H, F>(dir: &'a D, filter: F) -> LeavesWhere<'a, L, H, F>
+where
+    D: Directory<L, H> + ?Sized,
+    F: LeafFilter<L>,
+{
+    LeavesWhere {
+        filter,
+        stack: vec![(0, dir.entries())],
+        path: String::new(),
+    }
+}
+
+pub struct LeavesWhere<'a, L, H, F> {
+    filter: F,
+    /// The entries left to visit in each directory being visited, along with the length of the
+    /// path of that directory in `path`.
+    stack: Vec<(usize, DirectoryEntries<'a, L, H>)>,
+    /// The path of the last entry, which is truncated back and extended for each entry so we
+    /// don't allocate a path for entries that are filtered out.
+    path: String,
+}
+
+impl<'a, L, H, F> Iterator for LeavesWhere<'a, L, H, F>
+where
+    F: LeafFilter<L>,
+{
+    type Item = (ForwardRelativePathBuf, &'a L);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (len, entries) = self.stack.last_mut()?;
+            let len = *len;
+            let (name, entry) = match entries.next() {
+                Some(next) => next,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            self.path.truncate(len);
+            if len != 0 {
+                self.path.push('/');
+            }
+            self.path.push_str(name.as_str());
+            let path = ForwardRelativePath::unchecked_new(self.path.as_str());
+
+            match entry {
+                DirectoryEntry::Dir(d) => {
+                    if self.filter.enter_dir(path) == VisitControl::Continue {
+                        self.stack.push((self.path.len(), d.entries()));
+                    }
+                }
+                DirectoryEntry::Leaf(leaf) => {
+                    if self.filter.matches(path, leaf) {
+                        return Some((path.to_buf(), leaf));
+                    }
+                }
+            }
+        }
+    }
+}
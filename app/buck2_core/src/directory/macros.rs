@@ -65,6 +65,13 @@ macro_rules! impl_fingerprinted_directory {
             fn fingerprint(&self) -> &H {
                 $this::fingerprint(self)
             }
+
+            fn size(&self) -> u64
+            where
+                L: LeafSize,
+            {
+                $this::size(self)
+            }
         }
 
         impl<L, H> PartialEq for $this<L, H>
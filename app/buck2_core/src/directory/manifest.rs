@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use dupe::Dupe;
+use serde::Serialize;
+
+use super::AttributedLeaf;
+use super::Directory;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryIterator;
+use super::FingerprintedDirectory;
+use super::FingerprintedLeaf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// A flat listing of the leaves of a Directory, keyed by their path. This is cheap to store and
+/// compare against, since it doesn't require reconstructing the Directory it was created from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectoryManifest<L> {
+    leaves: BTreeMap<ForwardRelativePathBuf, L>,
+}
+
+impl<L> DirectoryManifest<L> {
+    pub fn new(leaves: BTreeMap<ForwardRelativePathBuf, L>) -> Self {
+        Self { leaves }
+    }
+
+    pub fn leaves(&self) -> &BTreeMap<ForwardRelativePathBuf, L> {
+        &self.leaves
+    }
+
+    pub fn into_leaves(self) -> BTreeMap<ForwardRelativePathBuf, L> {
+        self.leaves
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The leaf exists in the Directory but not in the manifest.
+    Added,
+    /// The leaf exists in the manifest but not in the Directory.
+    Removed,
+    /// The leaf exists in both but differs.
+    Modified,
+    /// The leaf exists in both with the same content, but with different attributes. Only
+    /// reported by [`changed_since_manifest_with_attributes`].
+    AttributesModified,
+}
+
+/// Produce a manifest listing all the leaves in this Directory.
+pub fn to_manifest<D, L, H>(dir: &D) -> DirectoryManifest<L>
+where
+    D: Directory<L, H> + ?Sized,
+    L: Clone,
+{
+    let leaves = dir
+        .ordered_walk()
+        .with_paths()
+        .filter_map(|(path, entry)| match entry {
+            DirectoryEntry::Dir(..) => None,
+            DirectoryEntry::Leaf(leaf) => Some((path, leaf.clone())),
+        })
+        .collect();
+
+    DirectoryManifest { leaves }
+}
+
+/// Compare this Directory against a manifest (typically of a previous version of it) and return
+/// the leaf paths that changed, sorted by path.
+pub fn changed_since_manifest<D, L, H>(
+    dir: &D,
+    manifest: &DirectoryManifest<L>,
+) -> Vec<(ForwardRelativePathBuf, ChangeKind)>
+where
+    D: Directory<L, H> + ?Sized,
+    L: Eq,
+{
+    changed_since_manifest_impl(dir, manifest, |_, _| ChangeKind::Modified)
+}
+
+/// Like [`changed_since_manifest`], but leaves whose content is unchanged and whose attributes
+/// changed are reported as [`ChangeKind::AttributesModified`] rather than `Modified`.
+pub fn changed_since_manifest_with_attributes<D, L, H>(
+    dir: &D,
+    manifest: &DirectoryManifest<AttributedLeaf<L>>,
+) -> Vec<(ForwardRelativePathBuf, ChangeKind)>
+where
+    D: Directory<AttributedLeaf<L>, H> + ?Sized,
+    L: Eq,
+{
+    changed_since_manifest_impl(dir, manifest, |baseline, leaf| {
+        if baseline.leaf == leaf.leaf {
+            ChangeKind::AttributesModified
+        } else {
+            ChangeKind::Modified
+        }
+    })
+}
+
+/// `modified` classifies a change to a leaf present in both, given the leaf in the manifest and
+/// in the Directory.
+fn changed_since_manifest_impl<D, L, H>(
+    dir: &D,
+    manifest: &DirectoryManifest<L>,
+    modified: impl Fn(&L, &L) -> ChangeKind,
+) -> Vec<(ForwardRelativePathBuf, ChangeKind)>
+where
+    D: Directory<L, H> + ?Sized,
+    L: Eq,
+{
+    let mut changes = Vec::new();
+    let mut seen = BTreeSet::new();
+
+    let mut walk = dir.ordered_walk();
+    while let Some((path, entry)) = walk.next() {
+        let leaf = match entry {
+            DirectoryEntry::Dir(..) => continue,
+            DirectoryEntry::Leaf(leaf) => leaf,
+        };
+
+        let path = path.get();
+        match manifest.leaves.get_key_value(&path) {
+            Some((path, baseline)) => {
+                if baseline != leaf {
+                    changes.push((path.clone(), modified(baseline, leaf)));
+                }
+                seen.insert(path);
+            }
+            None => changes.push((path, ChangeKind::Added)),
+        }
+    }
+
+    changes.extend(
+        manifest
+            .leaves
+            .keys()
+            .filter(|path| !seen.contains(path))
+            .map(|path| (path.clone(), ChangeKind::Removed)),
+    );
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    changes
+}
+
+/// Compare this Directory against the fingerprints of the paths of a previous version of it and
+/// return the paths that are new or whose fingerprint changed, sorted by path. Paths that no longer
+/// exist are not reported.
+///
+/// `baseline` should have the fingerprints of directories as well as of leaves: directories whose
+/// fingerprint is unchanged are skipped without being descended into, and directories whose
+/// fingerprint changed are descended into to find the leaves that changed. Directories that are
+/// missing from `baseline` are reported as `Added` as a whole.
+pub fn changed_against_fingerprints<D, L, H>(
+    dir: &D,
+    baseline: &HashMap<ForwardRelativePathBuf, H>,
+) -> Vec<(ForwardRelativePathBuf, ChangeKind)>
+where
+    D: FingerprintedDirectory<L, H> + ?Sized,
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+{
+    fn changed<D, L, H>(
+        dir: &D,
+        path: &ForwardRelativePath,
+        baseline: &HashMap<ForwardRelativePathBuf, H>,
+        changes: &mut Vec<(ForwardRelativePathBuf, ChangeKind)>,
+    ) where
+        D: FingerprintedDirectory<L, H> + ?Sized,
+        L: FingerprintedLeaf<H>,
+        H: DirectoryDigest,
+    {
+        for (name, entry) in dir.fingerprinted_entries() {
+            let path = path.join(name);
+            let fingerprint = match &entry {
+                DirectoryEntry::Dir(d) => d.fingerprint().dupe(),
+                DirectoryEntry::Leaf(leaf) => leaf.fingerprint(),
+            };
+
+            match baseline.get(&path) {
+                None => changes.push((path, ChangeKind::Added)),
+                Some(previous) if *previous == fingerprint => {}
+                Some(_) => match entry {
+                    DirectoryEntry::Dir(d) => changed(d, &path, baseline, changes),
+                    DirectoryEntry::Leaf(..) => changes.push((path, ChangeKind::Modified)),
+                },
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    if baseline.get(ForwardRelativePath::empty()) != Some(dir.fingerprint()) {
+        changed(dir, ForwardRelativePath::empty(), baseline, &mut changes);
+    }
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    changes
+}
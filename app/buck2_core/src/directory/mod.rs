@@ -9,8 +9,14 @@
 
 #![allow(clippy::module_inception)]
 
+mod attributes;
 mod builder;
+mod cas;
+mod changeset;
+mod cow_directory;
+mod cursor;
 mod dashmap_directory_interner;
+mod dedup;
 mod directory;
 mod directory_data;
 mod directory_hasher;
@@ -19,22 +25,54 @@ mod directory_mut;
 mod directory_selector;
 mod entries;
 mod entry;
+mod equality;
 mod exclusive_directory;
 mod find;
+mod fingerprint_index;
 mod fingerprinted_directory;
 mod immutable_directory;
+mod leaves;
 mod macros;
+mod manifest;
+mod mount;
 mod no_hasher;
+mod partition;
+mod patch;
 mod path_accumulator;
+mod pattern;
+mod project;
+mod remove;
 mod shared_directory;
+mod size;
+mod streaming_builder;
+mod symlink;
 mod test;
+mod verify;
+mod visitor;
 mod walk;
+mod zip;
 
+pub use attributes::AttributedLeaf;
+pub use attributes::AttributesDigest;
+pub use attributes::EXECUTABLE_ATTRIBUTE;
 pub use builder::DirectoryBuilder;
 pub use builder::DirectoryInsertError;
 pub use builder::DirectoryMergeError;
 pub use builder::DirectoryMkdirError;
+pub use cas::load;
+pub use cas::store;
+pub use cas::CasLoadError;
+pub use cas::CasObject;
+pub use cas::CasReader;
+pub use cas::CasWriter;
+pub use changeset::apply_changeset;
+pub use changeset::DirectoryChangesetError;
+pub use changeset::DirectoryOp;
+pub use cow_directory::CowDirectory;
+pub use cursor::DirectoryCursor;
+pub use cursor::DirectoryCursorError;
 pub use dashmap_directory_interner::DashMapDirectoryInterner;
+pub use dedup::dedup;
 pub use directory::Directory;
 pub use directory::DirectoryEntries;
 pub use directory_data::DirectoryData;
@@ -56,21 +94,64 @@ pub use directory_selector::UnorderedDirectorySearch;
 use entries::FingerprintedOrderedDirectoryEntries;
 use entries::OrderedDirectoryEntries;
 pub use entry::DirectoryEntry;
+pub use equality::trees_equal;
+pub use equality::trees_equal_fingerprinted;
 pub use exclusive_directory::ExclusiveDirectory;
+pub use find::canonicalize;
+pub use find::canonicalize_symlinks;
+pub use find::common_ancestor_dir;
 pub use find::find;
 pub use find::find_fingerprinted;
+pub use find::find_leaf_as;
 pub use find::find_mut;
 pub use find::find_prefix;
 pub use find::find_prefix_fingerprinted;
 pub use find::find_prefix_mut;
+pub use find::list_children;
+pub use find::subtree_fingerprint;
+pub use find::suggest_paths;
 pub use find::DirectoryFindError;
+pub use find::LeafAsAny;
+pub use fingerprint_index::FingerprintIndex;
 pub use fingerprinted_directory::FingerprintedDirectory;
 pub use fingerprinted_directory::FingerprintedDirectoryEntries;
+pub use fingerprinted_directory::FingerprintedLeaf;
 pub use immutable_directory::ImmutableDirectory;
+pub use leaves::leaves_where;
+pub use leaves::LeafFilter;
+pub use leaves::LeavesWhere;
+pub use manifest::changed_against_fingerprints;
+pub use manifest::changed_since_manifest;
+pub use manifest::changed_since_manifest_with_attributes;
+pub use manifest::to_manifest;
+pub use manifest::ChangeKind;
+pub use manifest::DirectoryManifest;
+pub use mount::find_mounted;
+pub use mount::MountLeaf;
+pub use mount::MountedDirectory;
+pub use mount::MountedDirectoryWalk;
+pub use partition::partition_leaves;
+pub use partition::PartitionMetric;
+pub use patch::compute_patch;
 pub use path_accumulator::PathAccumulator;
+pub use pattern::DirectoryPattern;
+pub use pattern::DirectoryPatternError;
+pub use project::project_paths;
+pub use remove::remove_matching;
 pub use shared_directory::SharedDirectory;
 pub use shared_directory::SharedDirectoryData;
 pub use shared_directory::SharedDirectoryInner;
+pub use size::subtree_size;
+pub use size::subtree_size_fingerprinted;
+pub use size::LeafSize;
+pub use streaming_builder::StreamingDirectoryBuilder;
+pub use streaming_builder::StreamingDirectoryBuilderError;
+pub use symlink::DirectoryLeaf;
+pub use verify::verify;
+pub use verify::DirectoryVerifyError;
+pub use visitor::visit;
+pub use visitor::DirectoryVisitor;
+pub use visitor::VisitControl;
 pub use walk::fingerprinted_ordered_entry_walk;
 pub use walk::fingerprinted_unordered_entry_walk;
 pub use walk::ordered_entry_walk;
@@ -80,5 +161,8 @@ pub use walk::FingerprintedOrderedDirectoryWalk;
 pub use walk::FingerprintedUnorderedDirectoryWalk;
 pub use walk::OrderedDirectoryWalk;
 pub use walk::UnorderedDirectoryWalk;
+pub use zip::zip;
+pub use zip::DirectoryZip;
+pub use zip::DirectoryZipItem;
 
 use self::macros::impl_fingerprinted_directory;
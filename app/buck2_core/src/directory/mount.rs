@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt;
+use std::sync::Arc;
+
+use dupe::Dupe;
+
+use super::Directory;
+use super::DirectoryEntry;
+use super::DirectoryFindError;
+use super::DirectoryIterator;
+use super::DirectoryIteratorPathAccessor;
+use super::DirectoryIteratorPathStack;
+use super::OrderedDirectoryEntries;
+use super::PathAccumulator;
+use crate::fs::paths::file_name::FileName;
+
+/// A Directory that can be mounted into another one. Its leaves are `MountLeaf` too, so mounts
+/// can be nested.
+pub type MountedDirectory<L, H> = Arc<dyn Directory<MountLeaf<L, H>, H> + Send + Sync>;
+
+/// A leaf of a Directory that may be a mount point. A mount point provides another Directory at
+/// its path without copying it into the tree, so large trees can be composed cheaply. It is a leaf
+/// as far as the containing Directory is concerned, but `find_mounted` and `MountedDirectoryWalk`
+/// descend into it as if it were a regular subdirectory.
+pub enum MountLeaf<L, H> {
+    File(L),
+    Mount(MountedDirectory<L, H>),
+}
+
+impl<L, H> MountLeaf<L, H> {
+    pub fn mount(dir: impl Directory<MountLeaf<L, H>, H> + Send + Sync + 'static) -> Self {
+        Self::Mount(Arc::new(dir))
+    }
+
+    pub fn as_file(&self) -> Option<&L> {
+        match self {
+            Self::File(l) => Some(l),
+            Self::Mount(..) => None,
+        }
+    }
+
+    /// Resolve an entry of a Directory containing mount points, so that mount points are seen as
+    /// the Directory they provide.
+    pub fn resolve<'a>(
+        entry: DirectoryEntry<&'a dyn Directory<MountLeaf<L, H>, H>, &'a MountLeaf<L, H>>,
+    ) -> DirectoryEntry<&'a dyn Directory<MountLeaf<L, H>, H>, &'a L> {
+        match entry {
+            DirectoryEntry::Dir(d) => DirectoryEntry::Dir(d),
+            DirectoryEntry::Leaf(Self::Mount(d)) => DirectoryEntry::Dir(&**d),
+            DirectoryEntry::Leaf(Self::File(l)) => DirectoryEntry::Leaf(l),
+        }
+    }
+}
+
+impl<L: Clone, H> Clone for MountLeaf<L, H> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::File(l) => Self::File(l.clone()),
+            Self::Mount(d) => Self::Mount(d.dupe()),
+        }
+    }
+}
+
+impl<L: fmt::Debug, H> fmt::Debug for MountLeaf<L, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(l) => f.debug_tuple("File").field(l).finish(),
+            Self::Mount(..) => write!(f, "Mount"),
+        }
+    }
+}
+
+/// Like `find`, but mount points along `path` are traversed, and a mount point at `path` is
+/// returned as the Directory it provides.
+pub fn find_mounted<'a, 'b, L, H, D: Directory<MountLeaf<L, H>, H>>(
+    dir: &'a D,
+    path: impl IntoIterator<Item = &'b FileName>,
+) -> Result<Option<DirectoryEntry<&'a dyn Directory<MountLeaf<L, H>, H>, &'a L>>, DirectoryFindError>
+{
+    let mut path = path.into_iter();
+
+    let path_needle = match path.next() {
+        Some(path_needle) => path_needle,
+        None => return Err(DirectoryFindError::EmptyPath),
+    };
+
+    find_mounted_inner(dir, path_needle, path)
+        .map_err(|path| DirectoryFindError::CannotTraverseLeaf { path })
+}
+
+fn find_mounted_inner<'a, 'b, L, H>(
+    dir: &'a dyn Directory<MountLeaf<L, H>, H>,
+    path_needle: &'b FileName,
+    mut path_rest: impl Iterator<Item = &'b FileName>,
+) -> Result<Option<DirectoryEntry<&'a dyn Directory<MountLeaf<L, H>, H>, &'a L>>, PathAccumulator> {
+    let entry = match dir.get(path_needle) {
+        Some(entry) => MountLeaf::resolve(entry),
+        None => return Ok(None),
+    };
+
+    let next_path_needle = match path_rest.next() {
+        Some(next_path_needle) => next_path_needle,
+        None => return Ok(Some(entry)),
+    };
+
+    match entry {
+        DirectoryEntry::Dir(dir) => find_mounted_inner(dir, next_path_needle, path_rest)
+            .map_err(|acc| acc.with(path_needle)),
+        DirectoryEntry::Leaf(..) => Err(PathAccumulator::new(next_path_needle)),
+    }
+}
+
+struct WalkFrame<'a, L, H> {
+    name: Option<&'a FileName>,
+    entries: OrderedDirectoryEntries<'a, MountLeaf<L, H>, H>,
+}
+
+/// Like `OrderedDirectoryWalk`, but mount points are walked into as if they were regular
+/// subdirectories.
+pub struct MountedDirectoryWalk<'a, L, H> {
+    stack: Vec<WalkFrame<'a, L, H>>,
+}
+
+impl<'a, L, H> MountedDirectoryWalk<'a, L, H> {
+    pub fn new<D>(root: &'a D) -> Self
+    where
+        D: Directory<MountLeaf<L, H>, H> + ?Sized,
+    {
+        Self {
+            stack: vec![WalkFrame {
+                name: None,
+                entries: OrderedDirectoryEntries::from(root.entries()),
+            }],
+        }
+    }
+}
+
+impl<'a, L, H> DirectoryIterator for MountedDirectoryWalk<'a, L, H> {
+    type PathStack = Self;
+    type Item = DirectoryEntry<&'a dyn Directory<MountLeaf<L, H>, H>, &'a L>;
+
+    fn next<'b>(&'b mut self) -> Option<(DirectoryIteratorPathAccessor<'b, Self>, Self::Item)> {
+        loop {
+            let frame = self.stack.last_mut()?;
+
+            if let Some((name, entry)) = frame.entries.next() {
+                let entry = MountLeaf::resolve(entry);
+                let leaf_name = match entry {
+                    DirectoryEntry::Dir(dir) => {
+                        self.stack.push(WalkFrame {
+                            name: Some(name),
+                            entries: OrderedDirectoryEntries::from(dir.entries()),
+                        });
+                        None
+                    }
+                    DirectoryEntry::Leaf(..) => Some(name),
+                };
+
+                return Some((
+                    DirectoryIteratorPathAccessor {
+                        leaf: leaf_name,
+                        stack: self,
+                    },
+                    entry,
+                ));
+            }
+
+            self.stack.pop();
+        }
+    }
+}
+
+impl<'a, L, H> DirectoryIteratorPathStack for MountedDirectoryWalk<'a, L, H> {
+    fn for_each_path<'this, F>(&'this self, mut f: F)
+    where
+        F: FnMut(&'this FileName),
+    {
+        for path in self.stack.iter().filter_map(|frame| frame.name) {
+            f(path);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::Directory;
+use super::DirectoryEntry;
+use super::DirectoryIterator;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// How `partition_leaves` weighs leaves.
+pub enum PartitionMetric<'a, L> {
+    /// Every leaf weighs the same, so partitions have the same number of leaves, give or take one.
+    Count,
+    /// Leaves weigh the given size, e.g. their file size.
+    Size(&'a dyn Fn(&L) -> u64),
+}
+
+impl<'a, L> PartitionMetric<'a, L> {
+    fn weight(&self, leaf: &L) -> u64 {
+        match self {
+            Self::Count => 1,
+            Self::Size(size) => size(leaf),
+        }
+    }
+}
+
+/// Split the leaves under `dir` into `n` groups of roughly equal weight, e.g. to process them on
+/// `n` threads. Each leaf is assigned, in path order, to the lightest group so far, so the paths
+/// in each group are sorted. Some groups are empty if there are fewer than `n` leaves.
+///
+/// This is greedy rather than optimal: with `PartitionMetric::Size`, groups may differ by up to the
+/// weight of the heaviest leaf.
+pub fn partition_leaves<D, L, H>(
+    dir: &D,
+    n: usize,
+    by: PartitionMetric<'_, L>,
+) -> Vec<Vec<ForwardRelativePathBuf>>
+where
+    D: Directory<L, H> + ?Sized,
+{
+    let mut partitions = vec![Vec::new(); n];
+    // The lightest partition is at the top, ties going to the first partition.
+    let mut weights = (0..n).map(|i| Reverse((0, i))).collect::<BinaryHeap<_>>();
+
+    for (path, entry) in dir.ordered_walk().with_paths() {
+        if let DirectoryEntry::Leaf(leaf) = entry {
+            let Reverse((weight, i)) = match weights.pop() {
+                Some(lightest) => lightest,
+                None => break,
+            };
+            partitions[i].push(path);
+            weights.push(Reverse((weight + by.weight(leaf), i)));
+        }
+    }
+
+    partitions
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use dupe::Dupe;
+
+use super::find;
+use super::Directory;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryOp;
+use super::FingerprintedDirectory;
+use super::FingerprintedLeaf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// The fingerprint of an entry, along with whether it is a directory or a leaf.
+type EntryFingerprint<H> = DirectoryEntry<H, H>;
+
+/// Compute the operations that turn `from` into `to` when applied to `from` with
+/// `apply_changeset`.
+///
+/// Subtrees whose fingerprint is unchanged are skipped without being descended into. An entry
+/// that only exists in `to` is moved from an entry that only exists in `from` with the same
+/// fingerprint if there is one, and inserted otherwise. So a renamed subtree is a single `Move`
+/// rather than a removal and an insertion of all its contents.
+///
+/// The operations are the moves, then the insertions, then the removals, each sorted by path.
+pub fn compute_patch<L, H, D>(from: &D, to: &D) -> Vec<DirectoryOp<L, H>>
+where
+    D: Directory<L, H> + FingerprintedDirectory<L, H>,
+    L: FingerprintedLeaf<H> + Clone,
+    H: DirectoryDigest,
+{
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    if from.fingerprint() != to.fingerprint() {
+        diff(
+            from,
+            to,
+            ForwardRelativePath::empty(),
+            &mut removed,
+            &mut added,
+        );
+    }
+    removed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    added.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut sources = HashMap::<EntryFingerprint<H>, VecDeque<ForwardRelativePathBuf>>::new();
+    for (path, fingerprint) in removed {
+        sources.entry(fingerprint).or_default().push_back(path);
+    }
+
+    let mut moves = Vec::new();
+    let mut inserts = Vec::new();
+    for (path, fingerprint) in added {
+        match sources.get_mut(&fingerprint).and_then(|s| s.pop_front()) {
+            Some(from) => moves.push(DirectoryOp::Move { from, to: path }),
+            None => {
+                let entry = find(to, path.iter())
+                    .ok()
+                    .flatten()
+                    .expect("diff found this entry in `to`")
+                    .map_dir(|d| d.to_builder())
+                    .map_leaf(|l| l.clone());
+                inserts.push(DirectoryOp::Insert { path, entry });
+            }
+        }
+    }
+
+    let mut removes = sources.into_values().flatten().collect::<Vec<_>>();
+    removes.sort();
+
+    moves
+        .into_iter()
+        .chain(inserts)
+        .chain(removes.into_iter().map(|path| DirectoryOp::Remove { path }))
+        .collect()
+}
+
+fn fingerprint<L, H>(
+    entry: DirectoryEntry<&dyn FingerprintedDirectory<L, H>, &L>,
+) -> EntryFingerprint<H>
+where
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+{
+    match entry {
+        DirectoryEntry::Dir(d) => DirectoryEntry::Dir(d.fingerprint().dupe()),
+        DirectoryEntry::Leaf(l) => DirectoryEntry::Leaf(l.fingerprint()),
+    }
+}
+
+/// Collect the entries of `from` that are not in `to`, and the entries of `to` that are not in
+/// `from` or replace a different entry there, descending into directories present in both.
+fn diff<L, H>(
+    from: &dyn FingerprintedDirectory<L, H>,
+    to: &dyn FingerprintedDirectory<L, H>,
+    path: &ForwardRelativePath,
+    removed: &mut Vec<(ForwardRelativePathBuf, EntryFingerprint<H>)>,
+    added: &mut Vec<(ForwardRelativePathBuf, EntryFingerprint<H>)>,
+) where
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+{
+    for (name, entry) in from.fingerprinted_entries() {
+        if to.get(name).is_none() {
+            removed.push((path.join(name), fingerprint(entry)));
+        }
+    }
+
+    for (name, entry) in to.fingerprinted_entries() {
+        let path = path.join(name);
+        let previous = match from.get(name) {
+            Some(previous) => previous,
+            None => {
+                added.push((path, fingerprint(entry)));
+                continue;
+            }
+        };
+        match (previous, entry) {
+            (DirectoryEntry::Dir(previous), DirectoryEntry::Dir(d)) => {
+                if previous.fingerprint() != d.fingerprint() {
+                    diff(previous, d, &path, removed, added);
+                }
+            }
+            (previous, entry) => {
+                let entry = fingerprint(entry);
+                if fingerprint(previous) != entry {
+                    added.push((path, entry));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use thiserror::Error;
+
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+
+#[derive(Debug, Error)]
+pub enum DirectoryPatternError {
+    #[error("Pattern is empty")]
+    EmptyPattern,
+
+    #[error("Pattern `{}` has an empty path component", .pattern)]
+    EmptyComponent { pattern: String },
+
+    #[error("Pattern `{}` uses `**` as part of a path component", .pattern)]
+    RecursiveWildcardInComponent { pattern: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternComponent {
+    /// `**`: any number of path components, including none.
+    AnyPath,
+    /// A path component, where `*` matches any sequence of characters and `?` matches any single
+    /// character.
+    Glob(String),
+}
+
+/// A glob over the paths of a Directory, e.g. `**/*.tmp`. Components are separated by `/`, and
+/// `*` and `?` never match a `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryPattern {
+    components: Vec<PatternComponent>,
+}
+
+impl DirectoryPattern {
+    pub fn new(pattern: &str) -> Result<Self, DirectoryPatternError> {
+        if pattern.is_empty() {
+            return Err(DirectoryPatternError::EmptyPattern);
+        }
+
+        let components = pattern
+            .split('/')
+            .map(|component| match component {
+                "" => Err(DirectoryPatternError::EmptyComponent {
+                    pattern: pattern.to_owned(),
+                }),
+                "**" => Ok(PatternComponent::AnyPath),
+                c if c.contains("**") => Err(DirectoryPatternError::RecursiveWildcardInComponent {
+                    pattern: pattern.to_owned(),
+                }),
+                c => Ok(PatternComponent::Glob(c.to_owned())),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { components })
+    }
+
+    pub fn matches(&self, path: &ForwardRelativePath) -> bool {
+        let path = path.iter().collect::<Vec<_>>();
+        matches_components(&self.components, &path)
+    }
+}
+
+fn matches_components(pattern: &[PatternComponent], path: &[&FileName]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternComponent::AnyPath, rest)) => {
+            (0..=path.len()).any(|skip| matches_components(rest, &path[skip..]))
+        }
+        Some((PatternComponent::Glob(glob), rest)) => match path.split_first() {
+            Some((name, path)) => {
+                matches_glob(glob, name.as_str()) && matches_components(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_glob(glob: &str, name: &str) -> bool {
+    let mut glob = glob.chars();
+    let mut name_chars = name.chars();
+    match glob.next() {
+        None => name.is_empty(),
+        Some('*') => name
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(name.len()))
+            .any(|i| matches_glob(glob.as_str(), &name[i..])),
+        Some('?') => {
+            name_chars.next().is_some() && matches_glob(glob.as_str(), name_chars.as_str())
+        }
+        Some(c) => name_chars.next() == Some(c) && matches_glob(glob.as_str(), name_chars.as_str()),
+    }
+}
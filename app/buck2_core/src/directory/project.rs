@@ -0,0 +1,47 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::find;
+use super::Directory;
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryFindError;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+
+/// A new tree containing only the entries of `dir` at `paths`, and the directories leading to
+/// them, e.g. to send a minimal subtree to a remote worker. Directories at `paths` are included
+/// with everything under them. Leaves are cloned, and unchanged subtrees are shared with `dir`
+/// when it is immutable. Unlike `find`, a missing path is an error.
+pub fn project_paths<L, H, D>(
+    dir: &D,
+    paths: &[&ForwardRelativePath],
+) -> Result<DirectoryBuilder<L, H>, DirectoryFindError>
+where
+    L: Clone,
+    H: DirectoryDigest,
+    D: Directory<L, H>,
+{
+    let mut projected = DirectoryBuilder::empty();
+    for path in paths {
+        let entry = match find(dir, path.iter())? {
+            Some(DirectoryEntry::Dir(dir)) => DirectoryEntry::Dir(dir.to_builder()),
+            Some(DirectoryEntry::Leaf(leaf)) => DirectoryEntry::Leaf(leaf.clone()),
+            None => {
+                return Err(DirectoryFindError::NotFound {
+                    path: path.to_buf(),
+                });
+            }
+        };
+        projected
+            .insert(*path, entry)
+            .expect("paths found in the same tree cannot traverse a leaf");
+    }
+    Ok(projected)
+}
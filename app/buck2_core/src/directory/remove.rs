@@ -0,0 +1,71 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::Directory;
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryIterator;
+use super::DirectoryPattern;
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// Remove all the leaves whose path matches `pattern`, as well as the directories that are left
+/// empty as a result (directories that were empty to begin with are kept). Returns the number of
+/// leaves removed.
+///
+/// Subtrees without any matching leaf are left untouched, so they remain shared.
+pub fn remove_matching<L, H>(dir: &mut DirectoryBuilder<L, H>, pattern: &DirectoryPattern) -> usize
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    let matching: Vec<ForwardRelativePathBuf> = dir
+        .ordered_walk()
+        .with_paths()
+        .filter_map(|(path, entry)| match entry {
+            DirectoryEntry::Leaf(..) if pattern.matches(&path) => Some(path),
+            _ => None,
+        })
+        .collect();
+
+    for path in &matching {
+        remove_leaf(dir, path.iter());
+    }
+
+    matching.len()
+}
+
+/// Remove the leaf at `path`, and then any directory this leaves empty. Returns whether `dir` is
+/// now empty.
+fn remove_leaf<'a, L, H>(
+    dir: &mut DirectoryBuilder<L, H>,
+    mut path: impl Iterator<Item = &'a FileName>,
+) -> bool
+where
+    L: Clone,
+    H: DirectoryDigest,
+{
+    let path_needle = match path.next() {
+        Some(path_needle) => path_needle,
+        None => return false,
+    };
+
+    let entries = dir.as_mut();
+    let remove = match entries.get_mut(path_needle) {
+        Some(DirectoryEntry::Dir(d)) => remove_leaf(d, path),
+        Some(DirectoryEntry::Leaf(..)) => true,
+        None => false,
+    };
+    if remove {
+        entries.remove(path_needle);
+    }
+
+    entries.is_empty()
+}
@@ -28,6 +28,7 @@ use super::DirectoryEntry;
 use super::FingerprintedDirectory;
 use super::FingerprintedDirectoryEntries;
 use super::ImmutableDirectory;
+use super::LeafSize;
 use crate::fs::paths::file_name::FileName;
 use crate::fs::paths::file_name::FileNameBuf;
 
@@ -96,6 +97,13 @@ where
         self.inner.data.fingerprint()
     }
 
+    pub fn size(&self) -> u64
+    where
+        L: LeafSize,
+    {
+        self.inner.data.size()
+    }
+
     pub fn into_builder(self) -> DirectoryBuilder<L, H> {
         DirectoryBuilder::Immutable(self.as_immutable())
     }
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::find;
+use super::find_fingerprinted;
+use super::Directory;
+use super::DirectoryEntry;
+use super::DirectoryFindError;
+use super::DirectoryIterator;
+use super::FingerprintedDirectory;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+
+/// A leaf that knows the size of its content, e.g. the length of a file.
+pub trait LeafSize {
+    fn size(&self) -> u64;
+}
+
+/// The total size of the leaves under `path`, or the size of the leaf at `path`. An empty `path`
+/// refers to `dir` itself. Unlike `find`, a missing path is an error.
+///
+/// Directories which are not fingerprinted do not cache their size, so this walks the whole
+/// subtree. See `subtree_size_fingerprinted` for fingerprinted ones.
+pub fn subtree_size<L, H, D>(
+    dir: &D,
+    path: &ForwardRelativePath,
+) -> Result<u64, DirectoryFindError>
+where
+    L: LeafSize,
+    D: Directory<L, H>,
+{
+    let dir: &dyn Directory<L, H> = if path.is_empty() {
+        dir
+    } else {
+        match find(dir, path.iter())? {
+            Some(DirectoryEntry::Dir(dir)) => dir,
+            Some(DirectoryEntry::Leaf(leaf)) => return Ok(leaf.size()),
+            None => {
+                return Err(DirectoryFindError::NotFound {
+                    path: path.to_buf(),
+                });
+            }
+        }
+    };
+
+    Ok(dir
+        .unordered_walk()
+        .without_paths()
+        .map(|entry| match entry {
+            DirectoryEntry::Leaf(leaf) => leaf.size(),
+            DirectoryEntry::Dir(_) => 0,
+        })
+        .sum())
+}
+
+/// Like `subtree_size`, but directories cache their size (see `FingerprintedDirectory::size`), so
+/// that this only walks the subtree the first time its size is requested.
+pub fn subtree_size_fingerprinted<L, H, D>(
+    dir: &D,
+    path: &ForwardRelativePath,
+) -> Result<u64, DirectoryFindError>
+where
+    L: LeafSize,
+    D: FingerprintedDirectory<L, H>,
+{
+    if path.is_empty() {
+        return Ok(dir.size());
+    }
+    match find_fingerprinted(dir, path.iter())? {
+        Some(DirectoryEntry::Dir(dir)) => Ok(dir.size()),
+        Some(DirectoryEntry::Leaf(leaf)) => Ok(leaf.size()),
+        None => Err(DirectoryFindError::NotFound {
+            path: path.to_buf(),
+        }),
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::cmp::Ordering;
+
+use starlark_map::small_map::SmallMap;
+use thiserror::Error;
+
+use super::DirectoryBuilder;
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use crate::fs::paths::file_name::FileNameBuf;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+#[derive(Debug, Error)]
+pub enum StreamingDirectoryBuilderError {
+    #[error("Path is empty")]
+    EmptyPath,
+
+    #[error("Path `{}` is not after the previous path `{}`", .path, .previous)]
+    OutOfOrder {
+        path: ForwardRelativePathBuf,
+        previous: ForwardRelativePathBuf,
+    },
+
+    #[error("Insert conflicts with an existing leaf at path: `{}`", .path)]
+    CannotTraverseLeaf { path: ForwardRelativePathBuf },
+}
+
+type Entries<L, H> = SmallMap<FileNameBuf, DirectoryEntry<DirectoryBuilder<L, H>, L>>;
+
+/// Builds a tree from leaves inserted in path order, e.g. read from a manifest.
+///
+/// Unlike `DirectoryBuilder::insert`, which walks from the root for every path, this keeps the
+/// directories of the last path open, and only closes and opens directories where the next path
+/// diverges from it. Paths are compared component by component, which is the order of
+/// `ordered_walk`, so `a/b` comes before `a.txt`.
+pub struct StreamingDirectoryBuilder<L, H>
+where
+    H: DirectoryDigest,
+{
+    /// The open directories, starting at the root, with their names in their parents.
+    stack: Vec<(Option<FileNameBuf>, Entries<L, H>)>,
+    /// The last path inserted.
+    last: Option<ForwardRelativePathBuf>,
+}
+
+impl<L, H> StreamingDirectoryBuilder<L, H>
+where
+    H: DirectoryDigest,
+{
+    pub fn new() -> Self {
+        Self {
+            stack: vec![(None, SmallMap::new())],
+            last: None,
+        }
+    }
+
+    /// Insert `leaf` at `path`, which must come after the previous path.
+    pub fn insert(
+        &mut self,
+        path: &ForwardRelativePath,
+        leaf: L,
+    ) -> Result<(), StreamingDirectoryBuilderError> {
+        let mut components = path.iter().collect::<Vec<_>>();
+        let name = match components.pop() {
+            Some(name) => name,
+            None => return Err(StreamingDirectoryBuilderError::EmptyPath),
+        };
+
+        if let Some(last) = &self.last {
+            if path.iter().cmp(last.iter()) != Ordering::Greater {
+                return Err(StreamingDirectoryBuilderError::OutOfOrder {
+                    path: path.to_buf(),
+                    previous: last.clone(),
+                });
+            }
+            if path.starts_with(last) {
+                return Err(StreamingDirectoryBuilderError::CannotTraverseLeaf {
+                    path: last.clone(),
+                });
+            }
+        }
+
+        // Keep the open directories shared with the previous path, and open the others.
+        let shared = self.stack[1..]
+            .iter()
+            .zip(components.iter())
+            .take_while(|((open, _), component)| open.as_deref() == Some(**component))
+            .count();
+        self.close_until(shared + 1);
+        for component in &components[shared..] {
+            self.stack
+                .push((Some((*component).to_owned()), SmallMap::new()));
+        }
+
+        self.top()
+            .insert(name.to_owned(), DirectoryEntry::Leaf(leaf));
+        self.last = Some(path.to_buf());
+        Ok(())
+    }
+
+    /// The tree of all the leaves inserted.
+    pub fn finish(mut self) -> DirectoryBuilder<L, H> {
+        self.close_until(1);
+        let (_, root) = self.stack.pop().expect("the root is never closed");
+        DirectoryBuilder::Mutable(root)
+    }
+
+    /// Close open directories, inserting them in their parents, until `depth` are left open.
+    fn close_until(&mut self, depth: usize) {
+        while self.stack.len() > depth {
+            let (name, entries) = self.stack.pop().expect("checked above");
+            let name = name.expect("only the root has no name, and it is never closed");
+            self.top()
+                .insert(name, DirectoryEntry::Dir(DirectoryBuilder::Mutable(entries)));
+        }
+    }
+
+    fn top(&mut self) -> &mut Entries<L, H> {
+        &mut self.stack.last_mut().expect("the root is never closed").1
+    }
+}
+
+impl<L, H> Default for StreamingDirectoryBuilder<L, H>
+where
+    H: DirectoryDigest,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use allocative::Allocative;
+use derive_more::Display;
+use serde::Serialize;
+
+use super::DirectoryEntry;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+/// A leaf of a Directory that may be a symlink. Symlinks are leaves too (we never traverse them),
+/// but unlike opaque leaves they retain their target, so that traversals and manifests can tell
+/// them apart from regular files.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Display, Serialize, Allocative)]
+#[display(bound = "L: ::std::fmt::Display")]
+pub enum DirectoryLeaf<L> {
+    File(L),
+    /// A symlink whose target is relative to the root of the Directory containing it.
+    #[display(fmt = "-> {}", target)]
+    Symlink {
+        target: ForwardRelativePathBuf,
+    },
+}
+
+impl<L> DirectoryLeaf<L> {
+    pub fn symlink(target: ForwardRelativePathBuf) -> Self {
+        Self::Symlink { target }
+    }
+
+    pub fn as_file(&self) -> Option<&L> {
+        match self {
+            Self::File(l) => Some(l),
+            Self::Symlink { .. } => None,
+        }
+    }
+
+    pub fn symlink_target(&self) -> Option<&ForwardRelativePath> {
+        match self {
+            Self::File(..) => None,
+            Self::Symlink { target } => Some(target),
+        }
+    }
+
+    pub fn map_file<U>(self, f: impl FnOnce(L) -> U) -> DirectoryLeaf<U> {
+        match self {
+            Self::File(l) => DirectoryLeaf::File(f(l)),
+            Self::Symlink { target } => DirectoryLeaf::Symlink { target },
+        }
+    }
+}
+
+impl<'a, D, L> DirectoryEntry<D, &'a DirectoryLeaf<L>> {
+    /// The target of this entry, if it is a symlink.
+    pub fn symlink_target(&self) -> Option<&'a ForwardRelativePath> {
+        match self {
+            Self::Dir(..) => None,
+            Self::Leaf(leaf) => leaf.symlink_target(),
+        }
+    }
+}
@@ -9,9 +9,15 @@
 
 #![cfg(test)]
 
+use std::cell::RefCell;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 use allocative::Allocative;
 use assert_matches::assert_matches;
@@ -20,7 +26,9 @@ use dupe::Dupe;
 
 use super::*;
 use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::file_name::FileNameBuf;
 use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 
 #[derive(Clone, Dupe, Debug, Eq, PartialEq, Hash)]
 pub struct NopEntry;
@@ -54,6 +62,21 @@ impl DirectoryHasher<NopEntry, TestDigest> for TestHasher {
     }
 }
 
+impl AttributesDigest for TestDigest {
+    fn with_attributes(&self, attributes: &BTreeMap<String, String>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        attributes.hash(&mut hasher);
+        TestDigest(hasher.finish())
+    }
+}
+
+impl FingerprintedLeaf<TestDigest> for NopEntry {
+    fn fingerprint(&self) -> TestDigest {
+        TestDigest(0)
+    }
+}
+
 type TestDirectoryBuilder = DirectoryBuilder<NopEntry, TestDigest>;
 type NoHasherDirectoryBuilder = DirectoryBuilder<NopEntry, NoDigest>;
 
@@ -634,3 +657,1417 @@ fn test_filter_continues_on_error() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_changed_since_manifest() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/c"), DirectoryEntry::Leaf(2))?;
+    b.insert(path("d"), DirectoryEntry::Leaf(3))?;
+
+    let manifest = to_manifest(&b);
+    assert_eq!(changed_since_manifest(&b, &manifest), vec![]);
+
+    b.insert(path("a/c"), DirectoryEntry::Leaf(4))?;
+    b.insert(path("d"), DirectoryEntry::Dir(DirectoryBuilder::empty()))?;
+    b.insert(path("e/f"), DirectoryEntry::Leaf(5))?;
+
+    assert_eq!(
+        changed_since_manifest(&b, &manifest),
+        vec![
+            (path("a/c").to_buf(), ChangeKind::Modified),
+            (path("d").to_buf(), ChangeKind::Removed),
+            (path("e/f").to_buf(), ChangeKind::Added),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_changed_against_fingerprints() -> anyhow::Result<()> {
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/x/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/x/z"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("e/f"), DirectoryEntry::Leaf(NopEntry))?;
+    let old = b.clone().fingerprint(&TestHasher);
+
+    let mut baseline = old
+        .fingerprinted_ordered_walk()
+        .with_paths()
+        .map(|(p, entry)| {
+            let fingerprint = match entry {
+                DirectoryEntry::Dir(d) => d.fingerprint().dupe(),
+                DirectoryEntry::Leaf(leaf) => leaf.fingerprint(),
+            };
+            (p, fingerprint)
+        })
+        .collect::<HashMap<_, _>>();
+    baseline.insert(ForwardRelativePathBuf::empty(), old.fingerprint().dupe());
+    assert_eq!(changed_against_fingerprints(&old, &baseline), vec![]);
+
+    // If `a` was descended into, this would be reported as added.
+    baseline.remove(path("a/x/y"));
+
+    b.insert(path("b/new"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(
+        path("c"),
+        DirectoryEntry::Dir(TestDirectoryBuilder::empty()),
+    )?;
+    b.insert(path("c/z"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("d/w"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("e"), DirectoryEntry::Leaf(NopEntry))?;
+    let new = b.fingerprint(&TestHasher);
+
+    assert_eq!(
+        changed_against_fingerprints(&new, &baseline),
+        vec![
+            (path("b/new").to_buf(), ChangeKind::Added),
+            (path("c/z").to_buf(), ChangeKind::Added),
+            (path("d").to_buf(), ChangeKind::Added),
+            (path("e").to_buf(), ChangeKind::Modified),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_cursor_descend_ascend() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/d"), DirectoryEntry::Leaf(2))?;
+
+    let mut cursor = DirectoryCursor::new(&b);
+    assert!(cursor.is_root());
+    assert_matches!(cursor.ascend(), Err(DirectoryCursorError::AtRoot));
+
+    cursor.descend(FileName::unchecked_new("a"))?;
+    cursor.descend(FileName::unchecked_new("b"))?;
+    cursor.descend(FileName::unchecked_new("c"))?;
+    assert_eq!(cursor.path(), path("a/b/c").to_buf());
+    assert_matches!(cursor.entry(), DirectoryEntry::Leaf(1));
+    assert_matches!(
+        cursor.descend(FileName::unchecked_new("x")),
+        Err(DirectoryCursorError::CannotDescendLeaf { .. })
+    );
+
+    cursor.ascend()?;
+    cursor.ascend()?;
+    assert_eq!(cursor.path(), path("a").to_buf());
+    assert_matches!(
+        cursor.descend(FileName::unchecked_new("x")),
+        Err(DirectoryCursorError::NotFound { .. })
+    );
+    cursor.descend(FileName::unchecked_new("d"))?;
+    assert_matches!(cursor.entry(), DirectoryEntry::Leaf(2));
+
+    cursor.ascend()?;
+    cursor.ascend()?;
+    assert!(cursor.is_root());
+    assert_matches!(
+        cursor.entry(),
+        DirectoryEntry::Dir(d) => assert_eq!(d.entries().count(), 1)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_visitor_prunes_subtree() -> anyhow::Result<()> {
+    struct Collect {
+        visited: Vec<String>,
+    }
+
+    impl DirectoryVisitor<u32, NoDigest> for Collect {
+        fn visit_dir(
+            &mut self,
+            path: &ForwardRelativePath,
+            _dir: &dyn Directory<u32, NoDigest>,
+        ) -> VisitControl {
+            self.visited.push(path.to_string());
+            if path.as_str() == "a/skip" {
+                VisitControl::SkipChildren
+            } else {
+                VisitControl::Continue
+            }
+        }
+
+        fn visit_leaf(&mut self, path: &ForwardRelativePath, _leaf: &u32) {
+            self.visited.push(path.to_string());
+        }
+    }
+
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/skip/c"), DirectoryEntry::Leaf(2))?;
+    b.insert(path("a/skip/d/e"), DirectoryEntry::Leaf(3))?;
+    b.insert(path("f"), DirectoryEntry::Leaf(4))?;
+
+    let mut visitor = Collect {
+        visited: Vec::new(),
+    };
+    visit(&b, &mut visitor);
+    visitor.visited.sort();
+
+    assert_eq!(visitor.visited, vec!["a", "a/b", "a/skip", "f"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_leaves_where_prunes_by_prefix() -> anyhow::Result<()> {
+    /// Matches the leaves under `prefix`, recording which paths it was asked about.
+    struct UnderPrefix<'a> {
+        prefix: &'static str,
+        seen: &'a RefCell<Vec<String>>,
+    }
+
+    impl LeafFilter<u32> for UnderPrefix<'_> {
+        fn matches(&self, path: &ForwardRelativePath, _leaf: &u32) -> bool {
+            self.seen.borrow_mut().push(path.to_string());
+            path.starts_with(ForwardRelativePath::new(self.prefix).unwrap())
+        }
+
+        fn enter_dir(&self, path: &ForwardRelativePath) -> VisitControl {
+            self.seen.borrow_mut().push(path.to_string());
+            let prefix = ForwardRelativePath::new(self.prefix).unwrap();
+            if path.starts_with(prefix) || prefix.starts_with(path) {
+                VisitControl::Continue
+            } else {
+                VisitControl::SkipChildren
+            }
+        }
+    }
+
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/b/d/e"), DirectoryEntry::Leaf(2))?;
+    b.insert(path("a/x"), DirectoryEntry::Leaf(3))?;
+    b.insert(path("skip/f/g"), DirectoryEntry::Leaf(4))?;
+
+    let seen = RefCell::new(Vec::new());
+    let filter = UnderPrefix {
+        prefix: "a/b",
+        seen: &seen,
+    };
+    let mut leaves = leaves_where(&b, filter)
+        .map(|(p, l)| (p.to_string(), *l))
+        .collect::<Vec<_>>();
+    leaves.sort();
+    assert_eq!(
+        leaves,
+        vec![("a/b/c".to_owned(), 1), ("a/b/d/e".to_owned(), 2)]
+    );
+
+    // `skip` was pruned, so nothing under it was visited.
+    let mut seen = seen.into_inner();
+    seen.sort();
+    assert_eq!(
+        seen,
+        vec!["a", "a/b", "a/b/c", "a/b/d", "a/b/d/e", "a/x", "skip"]
+    );
+
+    // Plain closures filter leaves without pruning.
+    let odd = leaves_where(&b, |_: &ForwardRelativePath, l: &u32| l % 2 == 1)
+        .map(|(p, _)| p.to_string())
+        .collect::<HashSet<_>>();
+    assert_eq!(odd, HashSet::from(["a/b/c".to_owned(), "a/x".to_owned()]));
+
+    Ok(())
+}
+
+#[test]
+fn test_dedup_shares_identical_subtrees() -> anyhow::Result<()> {
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c/f"), DirectoryEntry::Leaf(NopEntry))?;
+
+    let d = b.fingerprint(&TestHasher);
+    let fingerprint = d.fingerprint().dupe();
+    let leaves = d
+        .ordered_walk()
+        .with_paths()
+        .map(|(p, _)| p.to_string())
+        .collect::<Vec<_>>();
+
+    let d = dedup(d);
+    assert_eq!(d.fingerprint(), &fingerprint);
+    assert_eq!(
+        d.ordered_walk()
+            .with_paths()
+            .map(|(p, _)| p.to_string())
+            .collect::<Vec<_>>(),
+        leaves
+    );
+
+    let dir = |d: &SharedDirectory<NopEntry, TestDigest>, name: &str| match d
+        .get(FileName::unchecked_new(name))
+    {
+        Some(DirectoryEntry::Dir(d)) => d.dupe(),
+        _ => panic!("expected a directory at `{}`", name),
+    };
+
+    let a = dir(&d, "a");
+    let b = dir(&d, "b");
+    assert!(a.ptr_eq(&b));
+    // `c` has the same content as `a/x`.
+    assert!(dir(&a, "x").ptr_eq(&dir(&d, "c")));
+
+    Ok(())
+}
+
+#[test]
+fn test_walk_with_symlinks() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<DirectoryLeaf<u32>, NoDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(DirectoryLeaf::File(1)))?;
+    b.insert(
+        path("a/c"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("a/b").to_buf())),
+    )?;
+    b.insert(
+        path("d"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("a").to_buf())),
+    )?;
+
+    let mut it = b.ordered_walk().with_paths();
+    let (p, entry) = it.next().unwrap();
+    assert_eq!(p, path("a").to_buf());
+    assert_eq!(entry.symlink_target(), None);
+    let (p, entry) = it.next().unwrap();
+    assert_eq!(p, path("a/b").to_buf());
+    assert_matches!(entry, DirectoryEntry::Leaf(DirectoryLeaf::File(1)));
+    let (p, entry) = it.next().unwrap();
+    assert_eq!(p, path("a/c").to_buf());
+    assert_eq!(entry.symlink_target(), Some(path("a/b")));
+    let (p, entry) = it.next().unwrap();
+    assert_eq!(p, path("d").to_buf());
+    assert_eq!(entry.symlink_target(), Some(path("a")));
+    assert!(it.next().is_none());
+
+    // Symlinks to directories are not traversed.
+    assert_matches!(
+        find(&b, path("d/b")),
+        Err(DirectoryFindError::CannotTraverseLeaf { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_manifest_with_symlinks() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<DirectoryLeaf<u32>, NoDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(DirectoryLeaf::File(1)))?;
+    b.insert(
+        path("a/c"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("a/b").to_buf())),
+    )?;
+
+    let manifest = to_manifest(&b);
+    assert_eq!(
+        serde_json::to_value(&manifest)?,
+        serde_json::json!({
+            "leaves": {
+                "a/b": { "File": 1 },
+                "a/c": { "Symlink": { "target": "a/b" } },
+            }
+        })
+    );
+
+    // Retargeting a symlink is a modification.
+    b.insert(
+        path("a/c"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("d").to_buf())),
+    )?;
+    assert_eq!(
+        changed_since_manifest(&b, &manifest),
+        vec![(path("a/c").to_buf(), ChangeKind::Modified)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_attributes_only_changes() -> anyhow::Result<()> {
+    let mut a = DirectoryBuilder::<AttributedLeaf<u32>, NoDigest>::empty();
+    a.insert(path("a/b"), DirectoryEntry::Leaf(AttributedLeaf::new(1)))?;
+    a.insert(path("a/c"), DirectoryEntry::Leaf(AttributedLeaf::new(2)))?;
+
+    let mut b = a.clone();
+    b.insert(
+        path("a/b"),
+        DirectoryEntry::Leaf(AttributedLeaf::new(1).with_executable(true)),
+    )?;
+
+    // Walks preserve the attributes.
+    let executable = b
+        .ordered_walk()
+        .with_paths()
+        .filter_map(|(path, entry)| match entry {
+            DirectoryEntry::Leaf(leaf) if leaf.is_executable() => Some(path.get()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(executable, vec![path("a/b").to_buf()]);
+
+    let manifest = to_manifest(&a);
+    assert_eq!(
+        changed_since_manifest_with_attributes(&b, &manifest),
+        vec![(path("a/b").to_buf(), ChangeKind::AttributesModified)]
+    );
+    // Attribute changes are still changes to callers that don't tell them apart.
+    assert_eq!(
+        changed_since_manifest(&b, &manifest),
+        vec![(path("a/b").to_buf(), ChangeKind::Modified)]
+    );
+
+    // Changing the content as well is a plain modification.
+    b.insert(
+        path("a/b"),
+        DirectoryEntry::Leaf(AttributedLeaf::new(3).with_executable(true)),
+    )?;
+    assert_eq!(
+        changed_since_manifest_with_attributes(&b, &manifest),
+        vec![(path("a/b").to_buf(), ChangeKind::Modified)]
+    );
+
+    assert_eq!(
+        serde_json::to_value(to_manifest(&b))?,
+        serde_json::json!({
+            "leaves": {
+                "a/b": { "leaf": 3, "attributes": { "executable": "true" } },
+                "a/c": { "leaf": 2 },
+            }
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_attributed_leaf_fingerprint() {
+    let fingerprint = |leaf: &AttributedLeaf<NopEntry>| -> TestDigest { leaf.fingerprint() };
+
+    let leaf = AttributedLeaf::new(NopEntry);
+    assert_eq!(fingerprint(&leaf), NopEntry.fingerprint());
+
+    let executable = leaf.clone().with_executable(true);
+    assert_ne!(fingerprint(&executable), fingerprint(&leaf));
+    assert_eq!(
+        fingerprint(&executable),
+        fingerprint(&AttributedLeaf::new(NopEntry).with_executable(true))
+    );
+    assert_ne!(
+        fingerprint(&executable),
+        fingerprint(&leaf.with_attribute("mode", "0755"))
+    );
+}
+
+#[test]
+fn test_directory_pattern() -> anyhow::Result<()> {
+    let pattern = DirectoryPattern::new("**/*.tmp")?;
+    assert!(pattern.matches(path("a.tmp")));
+    assert!(pattern.matches(path("a/b/c.tmp")));
+    assert!(!pattern.matches(path("a/b.tmp/c")));
+    assert!(!pattern.matches(path("a.tmpx")));
+
+    let pattern = DirectoryPattern::new("a/**/b?")?;
+    assert!(pattern.matches(path("a/b1")));
+    assert!(pattern.matches(path("a/x/y/b2")));
+    assert!(!pattern.matches(path("a/b")));
+    assert!(!pattern.matches(path("x/a/b1")));
+
+    assert_matches!(
+        DirectoryPattern::new(""),
+        Err(DirectoryPatternError::EmptyPattern)
+    );
+    assert_matches!(
+        DirectoryPattern::new("a//b"),
+        Err(DirectoryPatternError::EmptyComponent { .. })
+    );
+    assert_matches!(
+        DirectoryPattern::new("a/b**"),
+        Err(DirectoryPatternError::RecursiveWildcardInComponent { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_matching() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a.tmp"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/b/c.tmp"), DirectoryEntry::Leaf(2))?;
+    b.insert(path("a/b/d/e.tmp"), DirectoryEntry::Leaf(3))?;
+    b.insert(path("a/f"), DirectoryEntry::Leaf(4))?;
+    b.insert(path("g/h.tmp"), DirectoryEntry::Leaf(5))?;
+    b.mkdir(path("g/empty"))?;
+    b.insert(path("i.tmp/j"), DirectoryEntry::Leaf(6))?;
+
+    let removed = remove_matching(&mut b, &DirectoryPattern::new("**/*.tmp")?);
+    assert_eq!(removed, 4);
+
+    let mut it = b.ordered_walk().with_paths();
+    assert_eq!(it.next().unwrap().0, path("a").to_buf());
+    assert_eq!(it.next().unwrap().0, path("a/f").to_buf());
+    // `g` still contains the directory which was already empty.
+    assert_eq!(it.next().unwrap().0, path("g").to_buf());
+    assert_eq!(it.next().unwrap().0, path("g/empty").to_buf());
+    // Directories are never removed for matching.
+    assert_eq!(it.next().unwrap().0, path("i.tmp").to_buf());
+    assert_eq!(it.next().unwrap().0, path("i.tmp/j").to_buf());
+    assert!(it.next().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_list_children() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/b/z"), DirectoryEntry::Leaf(1))?;
+    b.insert(path("a/b/c/d"), DirectoryEntry::Leaf(2))?;
+    b.insert(path("a/b/m"), DirectoryEntry::Leaf(3))?;
+    b.insert(path("x"), DirectoryEntry::Leaf(4))?;
+
+    let children = list_children(&b, path("a/b"))?;
+    let names = children
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["c", "m", "z"]);
+    assert_matches!(children[0].1, DirectoryEntry::Dir(..));
+    assert_matches!(children[1].1, DirectoryEntry::Leaf(3));
+    assert_matches!(children[2].1, DirectoryEntry::Leaf(1));
+
+    let root = list_children(&b, ForwardRelativePath::empty())?;
+    assert_eq!(root.len(), 2);
+
+    assert_matches!(
+        list_children(&b, path("x")),
+        Err(DirectoryFindError::NotADirectory { .. })
+    );
+    assert_matches!(
+        list_children(&b, path("a/missing")),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+    assert_matches!(
+        list_children(&b, path("x/y")),
+        Err(DirectoryFindError::CannotTraverseLeaf { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_zip() -> anyhow::Result<()> {
+    let mut a = DirectoryBuilder::<u32, NoDigest>::empty();
+    a.insert(path("a/b"), DirectoryEntry::Leaf(1))?;
+    a.insert(path("a/c"), DirectoryEntry::Leaf(2))?;
+    a.insert(path("d"), DirectoryEntry::Leaf(3))?;
+
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    b.insert(path("a/c"), DirectoryEntry::Leaf(4))?;
+    b.insert(path("a/e"), DirectoryEntry::Leaf(5))?;
+    b.insert(path("d/f"), DirectoryEntry::Leaf(6))?;
+
+    let zipped = zip(&a, &b)
+        .map(|(path, a, b)| {
+            let leaf = |e: Option<DirectoryEntry<_, &u32>>| match e {
+                Some(DirectoryEntry::Leaf(l)) => Some(*l),
+                _ => None,
+            };
+            (
+                path.as_str().to_owned(),
+                a.is_some(),
+                b.is_some(),
+                leaf(a),
+                leaf(b),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        zipped,
+        vec![
+            ("a".to_owned(), true, true, None, None),
+            ("a/b".to_owned(), true, false, Some(1), None),
+            ("a/c".to_owned(), true, true, Some(2), Some(4)),
+            ("a/e".to_owned(), false, true, None, Some(5)),
+            ("d".to_owned(), true, true, Some(3), None),
+            ("d/f".to_owned(), false, true, None, Some(6)),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_subtree_fingerprint() -> anyhow::Result<()> {
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/x/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/x/z"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/x/y"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/x/z"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c/y"), DirectoryEntry::Leaf(NopEntry))?;
+    let d = b.fingerprint(&TestHasher);
+
+    let a = subtree_fingerprint(&d, path("a/x"))?;
+    assert!(a.is_some());
+    assert_eq!(a, subtree_fingerprint(&d, path("b/x"))?);
+    assert_ne!(a, subtree_fingerprint(&d, path("c"))?);
+
+    assert_eq!(
+        subtree_fingerprint(&d, ForwardRelativePath::empty())?.as_ref(),
+        Some(d.fingerprint())
+    );
+    assert_eq!(
+        subtree_fingerprint(&d, path("c/y"))?,
+        Some(NopEntry.fingerprint())
+    );
+    assert_eq!(subtree_fingerprint(&d, path("a/missing"))?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_canonicalize() -> anyhow::Result<()> {
+    let mut b = NoHasherDirectoryBuilder::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(NopEntry))?;
+
+    assert_eq!(canonicalize(&b, path("a/b/c"))?, path("a/b/c").to_buf());
+    assert_eq!(canonicalize(&b, path("a/b"))?, path("a/b").to_buf());
+    assert_eq!(
+        canonicalize(&b, ForwardRelativePath::empty())?,
+        ForwardRelativePath::empty().to_buf()
+    );
+    assert_matches!(
+        canonicalize(&b, path("a/x")),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+    assert_matches!(
+        canonicalize(&b, path("a/b/c/d")),
+        Err(DirectoryFindError::CannotTraverseLeaf { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_canonicalize_symlinks() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<DirectoryLeaf<u32>, NoDigest>::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(DirectoryLeaf::File(1)))?;
+    b.insert(
+        path("link_to_dir"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("a/b").to_buf())),
+    )?;
+    b.insert(
+        path("x/link_to_file"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("link_to_dir/c").to_buf())),
+    )?;
+    b.insert(
+        path("loop1"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("loop2").to_buf())),
+    )?;
+    b.insert(
+        path("loop2"),
+        DirectoryEntry::Leaf(DirectoryLeaf::symlink(path("loop1").to_buf())),
+    )?;
+
+    assert_eq!(
+        canonicalize_symlinks(&b, path("a/b/c"))?,
+        path("a/b/c").to_buf()
+    );
+    assert_eq!(
+        canonicalize_symlinks(&b, path("link_to_dir"))?,
+        path("a/b").to_buf()
+    );
+    assert_eq!(
+        canonicalize_symlinks(&b, path("link_to_dir/c"))?,
+        path("a/b/c").to_buf()
+    );
+    assert_eq!(
+        canonicalize_symlinks(&b, path("x/link_to_file"))?,
+        path("a/b/c").to_buf()
+    );
+    assert_matches!(
+        canonicalize_symlinks(&b, path("link_to_dir/missing")),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+    assert_matches!(
+        canonicalize_symlinks(&b, path("a/b/c/d")),
+        Err(DirectoryFindError::NotADirectory { .. })
+    );
+    assert_matches!(
+        canonicalize_symlinks(&b, path("loop1")),
+        Err(DirectoryFindError::SymlinkLoop { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_trees_equal() -> anyhow::Result<()> {
+    let build = |extra: &str| -> anyhow::Result<NoHasherDirectoryBuilder> {
+        let mut b = NoHasherDirectoryBuilder::empty();
+        b.insert(path("a/b/c/d"), DirectoryEntry::Leaf(NopEntry))?;
+        b.insert(path("a/e"), DirectoryEntry::Leaf(NopEntry))?;
+        b.insert(path(extra), DirectoryEntry::Leaf(NopEntry))?;
+        Ok(b)
+    };
+
+    assert!(trees_equal(&build("a/b/c/x")?, &build("a/b/c/x")?));
+    assert!(!trees_equal(&build("a/b/c/x")?, &build("a/b/c/y")?));
+    assert!(!trees_equal(&build("a/b/c/x")?, &build("a/b/x")?));
+
+    // A leaf on one side and a directory on the other.
+    let mut dir = build("a/b/c/x")?;
+    dir.insert(
+        path("a/e"),
+        DirectoryEntry::Dir(NoHasherDirectoryBuilder::empty()),
+    )?;
+    assert!(!trees_equal(&build("a/b/c/x")?, &dir));
+
+    Ok(())
+}
+
+#[test]
+fn test_trees_equal_fingerprinted() -> anyhow::Result<()> {
+    let build = |extra: &str| -> anyhow::Result<ImmutableDirectory<NopEntry, TestDigest>> {
+        let mut b = TestDirectoryBuilder::empty();
+        b.insert(path("a/b/c/d"), DirectoryEntry::Leaf(NopEntry))?;
+        b.insert(path("a/e"), DirectoryEntry::Leaf(NopEntry))?;
+        b.insert(path(extra), DirectoryEntry::Leaf(NopEntry))?;
+        Ok(b.fingerprint(&TestHasher))
+    };
+
+    assert!(trees_equal_fingerprinted(
+        &build("a/b/c/x")?,
+        &build("a/b/c/x")?
+    ));
+    assert!(!trees_equal_fingerprinted(
+        &build("a/b/c/x")?,
+        &build("a/b/c/y")?
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_find_leaf_as() -> anyhow::Result<()> {
+    #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+    enum TestLeaf {
+        Text(String),
+        Number(u32),
+    }
+
+    impl LeafAsAny for TestLeaf {
+        fn as_any(&self) -> &dyn std::any::Any {
+            match self {
+                TestLeaf::Text(text) => text,
+                TestLeaf::Number(number) => number,
+            }
+        }
+    }
+
+    let mut b = DirectoryBuilder::<TestLeaf, NoDigest>::empty();
+    b.insert(
+        path("a/text"),
+        DirectoryEntry::Leaf(TestLeaf::Text("hello".to_owned())),
+    )?;
+    b.insert(path("a/number"), DirectoryEntry::Leaf(TestLeaf::Number(42)))?;
+
+    assert_eq!(
+        find_leaf_as::<String, _, _, _>(&b, path("a/text").iter())?,
+        Some(&"hello".to_owned())
+    );
+    assert_eq!(
+        find_leaf_as::<u32, _, _, _>(&b, path("a/number").iter())?,
+        Some(&42)
+    );
+    assert_eq!(
+        find_leaf_as::<u32, _, _, _>(&b, path("a/text").iter())?,
+        None
+    );
+    assert_eq!(find_leaf_as::<u32, _, _, _>(&b, path("a").iter())?, None);
+    assert_eq!(
+        find_leaf_as::<u32, _, _, _>(&b, path("a/missing").iter())?,
+        None
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_find_mounted() -> anyhow::Result<()> {
+    type Leaf = MountLeaf<NopEntry, NoDigest>;
+
+    let mut nested = DirectoryBuilder::<Leaf, NoDigest>::empty();
+    nested.insert(path("d/e"), DirectoryEntry::Leaf(MountLeaf::File(NopEntry)))?;
+
+    let mut mounted = DirectoryBuilder::<Leaf, NoDigest>::empty();
+    mounted.insert(path("c"), DirectoryEntry::Leaf(MountLeaf::File(NopEntry)))?;
+    mounted.insert(
+        path("nested"),
+        DirectoryEntry::Leaf(MountLeaf::mount(nested)),
+    )?;
+
+    let mut b = DirectoryBuilder::<Leaf, NoDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(MountLeaf::mount(mounted)))?;
+    b.insert(path("x"), DirectoryEntry::Leaf(MountLeaf::File(NopEntry)))?;
+
+    assert_matches!(
+        find_mounted(&b, path("a/b/c").iter()),
+        Ok(Some(DirectoryEntry::Leaf(NopEntry)))
+    );
+    assert_matches!(
+        find_mounted(&b, path("a/b/nested/d/e").iter()),
+        Ok(Some(DirectoryEntry::Leaf(NopEntry)))
+    );
+    assert_matches!(
+        find_mounted(&b, path("a/b").iter()),
+        Ok(Some(DirectoryEntry::Dir(..)))
+    );
+    assert_matches!(find_mounted(&b, path("a/b/missing").iter()), Ok(None));
+    assert_matches!(
+        find_mounted(&b, path("a/b/c/d").iter()),
+        Err(DirectoryFindError::CannotTraverseLeaf { path }) => {
+            assert_eq!(path.to_string(), "a/b/d");
+        }
+    );
+
+    // Without resolving mounts, the mount point is just a leaf.
+    assert_matches!(
+        find(&b, path("a/b/c").iter()),
+        Err(DirectoryFindError::CannotTraverseLeaf { .. })
+    );
+
+    let paths = MountedDirectoryWalk::new(&b)
+        .with_paths()
+        .filter_map(|(path, entry)| entry.leaf().map(|_| path.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["a/b/c", "a/b/nested/d/e", "x"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_cow_directory() -> anyhow::Result<()> {
+    let interner = DashMapDirectoryInterner::new();
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/c/d"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/e"), DirectoryEntry::Leaf(NopEntry))?;
+    let original = b.fingerprint(&TestHasher).shared(&interner);
+
+    let mut cow = CowDirectory::new(&original);
+    assert!(cow.shared_subtree(path("")).unwrap().ptr_eq(&original));
+
+    cow.insert(path("a/x"), DirectoryEntry::Leaf(NopEntry))?;
+
+    let dir = |d: &SharedDirectory<NopEntry, TestDigest>, name: &str| match d
+        .get(FileName::unchecked_new(name))
+    {
+        Some(DirectoryEntry::Dir(d)) => d.dupe(),
+        _ => panic!("expected a directory at `{}`", name),
+    };
+
+    // Only the spine leading to `a/x` was copied.
+    assert!(cow.shared_subtree(path("")).is_none());
+    assert!(cow.shared_subtree(path("a")).is_none());
+    assert!(
+        cow.shared_subtree(path("a/c"))
+            .unwrap()
+            .ptr_eq(&dir(&dir(&original, "a"), "c"))
+    );
+    assert!(
+        cow.shared_subtree(path("b"))
+            .unwrap()
+            .ptr_eq(&dir(&original, "b"))
+    );
+
+    assert_matches!(
+        find(&cow, path("a/x").iter()),
+        Ok(Some(DirectoryEntry::Leaf(NopEntry)))
+    );
+    assert_matches!(
+        find(&cow, path("b/e").iter()),
+        Ok(Some(DirectoryEntry::Leaf(NopEntry)))
+    );
+    assert_matches!(find(&original, path("a/x").iter()), Ok(None));
+
+    Ok(())
+}
+
+#[test]
+fn test_common_ancestor_dir() -> anyhow::Result<()> {
+    let mut b = NoHasherDirectoryBuilder::empty();
+    b.insert(path("a/b/c/d"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/b/c/e"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/b/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("x"), DirectoryEntry::Leaf(NopEntry))?;
+
+    let (ancestor_path, ancestor) =
+        common_ancestor_dir(&b, &[path("a/b/c/d"), path("a/b/c/e"), path("a/b/f")])?.unwrap();
+    assert_eq!(ancestor_path, path("a/b").to_buf());
+    assert_matches!(
+        ancestor.get(FileName::unchecked_new("f")),
+        Some(DirectoryEntry::Leaf(NopEntry))
+    );
+
+    let (ancestor_path, _) = common_ancestor_dir(&b, &[path("a/b/c/d"), path("a/b/c/d")])?.unwrap();
+    assert_eq!(ancestor_path, path("a/b/c").to_buf());
+
+    let (ancestor_path, _) = common_ancestor_dir(&b, &[path("a/b/f"), path("x")])?.unwrap();
+    assert_eq!(ancestor_path, ForwardRelativePath::empty().to_buf());
+
+    assert_matches!(
+        common_ancestor_dir(&b, &[path("a/y/z"), path("a/y/w")]),
+        Ok(None)
+    );
+    assert_matches!(
+        common_ancestor_dir(&b, &[path("x/y"), path("x/z")]),
+        Err(DirectoryFindError::NotADirectory { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_verify() -> anyhow::Result<()> {
+    #[derive(Clone, Debug)]
+    struct Blob {
+        content: &'static str,
+        digest: TestDigest,
+    }
+
+    impl Blob {
+        fn new(content: &'static str) -> Self {
+            Self {
+                content,
+                digest: Self::digest(content),
+            }
+        }
+
+        fn digest(content: &str) -> TestDigest {
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            TestDigest(hasher.finish())
+        }
+    }
+
+    impl FingerprintedLeaf<TestDigest> for Blob {
+        fn fingerprint(&self) -> TestDigest {
+            self.digest.dupe()
+        }
+    }
+
+    impl DirectoryHasher<Blob, TestDigest> for TestHasher {
+        fn hash_entries<'a, D, I>(&self, entries: I) -> TestDigest
+        where
+            I: IntoIterator<Item = (&'a FileName, DirectoryEntry<&'a D, &'a Blob>)>,
+            D: FingerprintedDirectory<Blob, TestDigest> + 'a,
+        {
+            let mut hasher = DefaultHasher::new();
+
+            let mut entries = entries
+                .into_iter()
+                .map(|(name, entry)| {
+                    let entry = entry
+                        .map_dir(|d| d.fingerprint())
+                        .map_leaf(|l| l.fingerprint());
+                    (name, entry)
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(name, _)| *name);
+
+            entries.hash(&mut hasher);
+            TestDigest(hasher.finish())
+        }
+    }
+
+    let content_digest = |blob: &Blob| Blob::digest(blob.content);
+
+    let mut b = DirectoryBuilder::<Blob, TestDigest>::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(Blob::new("b")))?;
+    b.insert(path("a/c"), DirectoryEntry::Leaf(Blob::new("c")))?;
+    b.insert(path("d"), DirectoryEntry::Leaf(Blob::new("d")))?;
+    let valid = b.clone().fingerprint(&TestHasher);
+    let root = FingerprintedDirectory::fingerprint(&valid).dupe();
+
+    verify(&valid, &TestHasher, content_digest, &root)?;
+    assert_matches!(
+        verify(&valid, &TestHasher, content_digest, &TestDigest(0)),
+        Err(DirectoryVerifyError { path, .. }) => {
+            assert!(path.is_empty());
+        }
+    );
+
+    // The content of the leaf changes, but not its fingerprint, so neither does the root.
+    b.insert(
+        path("a/c"),
+        DirectoryEntry::Leaf(Blob {
+            content: "corrupted",
+            digest: Blob::digest("c"),
+        }),
+    )?;
+    let corrupted = b.fingerprint(&TestHasher);
+    assert_eq!(FingerprintedDirectory::fingerprint(&corrupted), &root);
+    assert_matches!(
+        verify(&corrupted, &TestHasher, content_digest, &root),
+        Err(DirectoryVerifyError { path, .. }) => {
+            assert_eq!(path.as_str(), "a/c");
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_subtree_size() -> anyhow::Result<()> {
+    /// The number of times the size of a `File` was requested.
+    static SIZES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone, Hash)]
+    struct File(u64);
+
+    impl LeafSize for File {
+        fn size(&self) -> u64 {
+            SIZES.fetch_add(1, Ordering::Relaxed);
+            self.0
+        }
+    }
+
+    impl DirectoryHasher<File, TestDigest> for TestHasher {
+        fn hash_entries<'a, D, I>(&self, entries: I) -> TestDigest
+        where
+            I: IntoIterator<Item = (&'a FileName, DirectoryEntry<&'a D, &'a File>)>,
+            D: FingerprintedDirectory<File, TestDigest> + 'a,
+        {
+            let mut hasher = DefaultHasher::new();
+
+            let mut entries = entries
+                .into_iter()
+                .map(|(name, entry)| (name, entry.map_dir(|d| d.fingerprint())))
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(name, _)| *name);
+
+            entries.hash(&mut hasher);
+            TestDigest(hasher.finish())
+        }
+    }
+
+    let mut b = DirectoryBuilder::<File, NoDigest>::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(File(3)))?;
+    b.insert(path("a/b/d"), DirectoryEntry::Leaf(File(5)))?;
+    b.insert(path("a/e"), DirectoryEntry::Leaf(File(7)))?;
+    b.insert(path("f"), DirectoryEntry::Leaf(File(11)))?;
+    b.mkdir(path("g"))?;
+
+    assert_eq!(subtree_size(&b, path("a/b"))?, 3 + 5);
+    assert_eq!(subtree_size(&b, path("a"))?, 3 + 5 + 7);
+    assert_eq!(subtree_size(&b, ForwardRelativePath::empty())?, 3 + 5 + 7 + 11);
+    assert_eq!(subtree_size(&b, path("a/e"))?, 7);
+    assert_eq!(subtree_size(&b, path("g"))?, 0);
+    assert_matches!(
+        subtree_size(&b, path("a/missing")),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+
+    let d = b.fingerprint(&TestHasher);
+    SIZES.store(0, Ordering::Relaxed);
+    assert_eq!(subtree_size_fingerprinted(&d, path("a/b"))?, 3 + 5);
+    assert_eq!(SIZES.load(Ordering::Relaxed), 2);
+    // Only the leaves not under `a/b` are visited, and only once.
+    assert_eq!(
+        subtree_size_fingerprinted(&d, ForwardRelativePath::empty())?,
+        3 + 5 + 7 + 11
+    );
+    assert_eq!(subtree_size_fingerprinted(&d, path("a"))?, 3 + 5 + 7);
+    assert_eq!(SIZES.load(Ordering::Relaxed), 4);
+    assert_eq!(subtree_size_fingerprinted(&d, path("a/e"))?, 7);
+    assert_eq!(subtree_size_fingerprinted(&d, path("g"))?, 0);
+    assert_matches!(
+        subtree_size_fingerprinted(&d, path("a/missing")),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_project_paths() -> anyhow::Result<()> {
+    let mut b = NoHasherDirectoryBuilder::empty();
+    b.insert(path("a/b/c"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/b/d"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("a/e"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("g/h"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("g/i/j"), DirectoryEntry::Leaf(NopEntry))?;
+
+    let projected = project_paths(&b, &[path("a/b/c"), path("g")])?;
+    let paths = projected
+        .ordered_walk()
+        .with_paths()
+        .map(|(p, _)| p.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["a", "a/b", "a/b/c", "g", "g/h", "g/i", "g/i/j"]);
+
+    assert_matches!(
+        project_paths(&b, &[path("a/missing")]),
+        Err(DirectoryFindError::NotFound { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_builder() -> anyhow::Result<()> {
+    let mut paths = (0..1000)
+        .map(|i| format!("d{}/e{}/f{}", i % 7, i % 13, i))
+        .chain((0..10).map(|i| format!("d{}.txt", i)))
+        .map(|p| ForwardRelativePathBuf::unchecked_new(p))
+        .collect::<Vec<_>>();
+    paths.sort_by(|a, b| a.iter().cmp(b.iter()));
+
+    let mut naive = NoHasherDirectoryBuilder::empty();
+    let mut streaming = StreamingDirectoryBuilder::new();
+    for path in &paths {
+        let path: &ForwardRelativePath = path;
+        naive.insert(path, DirectoryEntry::Leaf(NopEntry))?;
+        streaming.insert(path, NopEntry)?;
+    }
+    let streaming = streaming.finish();
+    assert!(trees_equal(&naive, &streaming));
+    assert_eq!(
+        naive.ordered_walk().with_paths().map(|(p, _)| p).collect::<Vec<_>>(),
+        streaming
+            .ordered_walk()
+            .with_paths()
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>()
+    );
+
+    let mut b = StreamingDirectoryBuilder::<_, NoDigest>::new();
+    b.insert(path("a/c"), NopEntry)?;
+    assert_matches!(
+        b.insert(path("a/b"), NopEntry),
+        Err(StreamingDirectoryBuilderError::OutOfOrder { .. })
+    );
+    assert_matches!(
+        b.insert(path("a/c"), NopEntry),
+        Err(StreamingDirectoryBuilderError::OutOfOrder { .. })
+    );
+    assert_matches!(
+        b.insert(path("a/c/d"), NopEntry),
+        Err(StreamingDirectoryBuilderError::CannotTraverseLeaf { .. })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_suggest_paths() -> anyhow::Result<()> {
+    let mut b = NoHasherDirectoryBuilder::empty();
+    b.insert(path("src/main.rs"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("src/lib.rs"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("src/util/mod.rs"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("README.md"), DirectoryEntry::Leaf(NopEntry))?;
+
+    assert_eq!(
+        suggest_paths(&b, path("src/mian.rs"), 1),
+        vec![path("src/main.rs").to_buf()]
+    );
+    assert_eq!(
+        suggest_paths(&b, path("src/lib.rs/x"), 2),
+        vec![path("src/lib.rs").to_buf(), path("src/main.rs").to_buf()]
+    );
+    // Suggestions are at the level of the first component which does not exist.
+    assert_eq!(
+        suggest_paths(&b, path("scr/main.rs"), 1),
+        vec![path("src").to_buf()]
+    );
+    assert_eq!(suggest_paths(&b, path("src/main.rs"), 1), Vec::new());
+    assert_eq!(suggest_paths(&b, path("src/util"), 1), Vec::new());
+
+    Ok(())
+}
+
+#[test]
+fn test_compute_patch() -> anyhow::Result<()> {
+    let mut from = TestDirectoryBuilder::empty();
+    from.insert(path("a/x"), DirectoryEntry::Leaf(NopEntry))?;
+    from.insert(path("a/y"), DirectoryEntry::Leaf(NopEntry))?;
+    from.insert(path("d"), DirectoryEntry::Leaf(NopEntry))?;
+    from.insert(path("e/f"), DirectoryEntry::Leaf(NopEntry))?;
+    from.insert(path("old/q/r"), DirectoryEntry::Leaf(NopEntry))?;
+    let from = from.fingerprint(&TestHasher);
+
+    let mut to = TestDirectoryBuilder::empty();
+    to.insert(path("b/x"), DirectoryEntry::Leaf(NopEntry))?;
+    to.insert(path("b/y"), DirectoryEntry::Leaf(NopEntry))?;
+    to.insert(path("c/z"), DirectoryEntry::Leaf(NopEntry))?;
+    to.insert(path("c/w"), DirectoryEntry::Leaf(NopEntry))?;
+    to.insert(path("e/f"), DirectoryEntry::Leaf(NopEntry))?;
+    to.insert(path("e/g"), DirectoryEntry::Leaf(NopEntry))?;
+    let to = to.fingerprint(&TestHasher);
+
+    let patch = compute_patch(&from, &to);
+    let ops = patch
+        .iter()
+        .map(|op| match op {
+            DirectoryOp::Insert { path, .. } => format!("insert {}", path),
+            DirectoryOp::Remove { path } => format!("remove {}", path),
+            DirectoryOp::Move { from, to } => format!("move {} {}", from, to),
+        })
+        .collect::<Vec<_>>();
+    // The renamed subtree is moved as a whole, and so is the leaf identical to the new one.
+    assert_eq!(
+        ops,
+        vec!["move a b", "move d e/g", "insert c", "remove old"]
+    );
+
+    let mut patched = from.clone().into_builder();
+    apply_changeset(&mut patched, &patch)?;
+    assert_eq!(patched.fingerprint(&TestHasher), to);
+
+    assert_matches!(compute_patch(&to, &to).as_slice(), []);
+
+    Ok(())
+}
+
+#[test]
+fn test_apply_changeset() -> anyhow::Result<()> {
+    let mut b = NoHasherDirectoryBuilder::empty();
+    b.insert(path("a/b"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c"), DirectoryEntry::Leaf(NopEntry))?;
+
+    apply_changeset(
+        &mut b,
+        &[
+            DirectoryOp::Insert {
+                path: path("d/e").to_buf(),
+                entry: DirectoryEntry::Leaf(NopEntry),
+            },
+            DirectoryOp::Move {
+                from: path("a").to_buf(),
+                to: path("f/a").to_buf(),
+            },
+            DirectoryOp::Remove {
+                path: path("c").to_buf(),
+            },
+        ],
+    )?;
+    let paths = b
+        .ordered_walk()
+        .with_paths()
+        .filter_map(|(path, entry)| match entry {
+            DirectoryEntry::Leaf(..) => Some(path.to_string()),
+            DirectoryEntry::Dir(..) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["d/e", "f/a/b"]);
+
+    let before = b.clone();
+    assert_matches!(
+        apply_changeset(
+            &mut b,
+            &[
+                DirectoryOp::Remove {
+                    path: path("d").to_buf(),
+                },
+                DirectoryOp::Move {
+                    from: path("missing").to_buf(),
+                    to: path("g").to_buf(),
+                },
+            ],
+        ),
+        Err(DirectoryChangesetError::Find {
+            index: 1,
+            source: DirectoryFindError::NotFound { .. }
+        })
+    );
+    assert!(trees_equal(&b, &before));
+
+    // Directories created and entries replaced or moved by earlier operations are rolled back.
+    assert_matches!(
+        apply_changeset(
+            &mut b,
+            &[
+                DirectoryOp::Insert {
+                    path: path("g/h/i").to_buf(),
+                    entry: DirectoryEntry::Leaf(NopEntry),
+                },
+                DirectoryOp::Insert {
+                    path: path("d/e").to_buf(),
+                    entry: DirectoryEntry::Leaf(NopEntry),
+                },
+                DirectoryOp::Move {
+                    from: path("f").to_buf(),
+                    to: path("d").to_buf(),
+                },
+                DirectoryOp::Move {
+                    from: path("g/h/i").to_buf(),
+                    to: path("d/a/b/j").to_buf(),
+                },
+            ],
+        ),
+        Err(DirectoryChangesetError::Insert {
+            index: 3,
+            source: DirectoryInsertError::CannotTraverseLeaf { .. }
+        })
+    );
+    assert!(trees_equal(&b, &before));
+
+    Ok(())
+}
+
+#[test]
+fn test_fingerprint_index() -> anyhow::Result<()> {
+    let mut x = TestDirectoryBuilder::empty();
+    x.insert(path("f"), DirectoryEntry::Leaf(NopEntry))?;
+    let x = x.fingerprint(&TestHasher);
+
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c/g"), DirectoryEntry::Leaf(NopEntry))?;
+    let d = b.clone().fingerprint(&TestHasher);
+
+    let index = FingerprintIndex::build(&d);
+    let paths = |fingerprint: &TestDigest| {
+        index
+            .paths_with_fingerprint(fingerprint)
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(paths(x.fingerprint()), vec!["a/x", "b/x"]);
+    assert_eq!(paths(&NopEntry.fingerprint()), vec!["a/x/f", "b/x/f", "c/g"]);
+    assert_eq!(paths(&TestDigest(1)), Vec::<String>::new());
+
+    assert!(index.is_valid_for(&d));
+    b.insert(path("c/h"), DirectoryEntry::Leaf(NopEntry))?;
+    assert!(!index.is_valid_for(&b.fingerprint(&TestHasher)));
+
+    Ok(())
+}
+
+#[test]
+fn test_partition_leaves() -> anyhow::Result<()> {
+    let mut b = DirectoryBuilder::<u32, NoDigest>::empty();
+    let mut weights = HashMap::new();
+    for (i, p) in [
+        "a/b/c", "a/b/d", "a/e", "f", "g/h/i", "g/h/j", "g/k", "l", "m/n", "m/o",
+    ]
+    .iter()
+    .enumerate()
+    {
+        b.insert(path(p), DirectoryEntry::Leaf(i as u32 + 1))?;
+        weights.insert(p.to_string(), i as u64 + 1);
+    }
+
+    let partitions = partition_leaves(&b, 3, PartitionMetric::Count);
+    assert_eq!(partitions.len(), 3);
+    let sizes = partitions.iter().map(|p| p.len()).collect::<Vec<_>>();
+    assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+    let mut all = partitions
+        .iter()
+        .flatten()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>();
+    all.sort();
+    assert_eq!(
+        all,
+        b.ordered_walk()
+            .with_paths()
+            .filter_map(|(p, e)| match e {
+                DirectoryEntry::Leaf(..) => Some(p.to_string()),
+                DirectoryEntry::Dir(..) => None,
+            })
+            .collect::<Vec<_>>()
+    );
+
+    // Weights are 1 to 10, which add up to 55, and no leaf weighs more than 10.
+    let size = |l: &u32| *l as u64;
+    let partitions = partition_leaves(&b, 3, PartitionMetric::Size(&size));
+    for p in &partitions {
+        let weight = p.iter().map(|p| weights[p.as_str()]).sum::<u64>();
+        assert!((55 / 3 - 10..=55 / 3 + 10).contains(&weight), "{}", weight);
+    }
+
+    assert_eq!(partition_leaves(&b, 0, PartitionMetric::Count).len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_cas_round_trip() -> anyhow::Result<()> {
+    #[derive(Default)]
+    struct TestCas {
+        objects: HashMap<TestDigest, CasObject<NopEntry, TestDigest>>,
+        writes: usize,
+    }
+
+    impl CasWriter<NopEntry, TestDigest> for TestCas {
+        fn contains(&self, digest: &TestDigest) -> bool {
+            self.objects.contains_key(digest)
+        }
+
+        fn write(&mut self, digest: TestDigest, object: CasObject<NopEntry, TestDigest>) {
+            self.writes += 1;
+            self.objects.insert(digest, object);
+        }
+    }
+
+    impl CasReader<NopEntry, TestDigest> for TestCas {
+        fn read(&self, digest: &TestDigest) -> Option<CasObject<NopEntry, TestDigest>> {
+            self.objects.get(digest).cloned()
+        }
+    }
+
+    let mut b = TestDirectoryBuilder::empty();
+    b.insert(path("a/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("b/x/f"), DirectoryEntry::Leaf(NopEntry))?;
+    b.insert(path("c/f"), DirectoryEntry::Leaf(NopEntry))?;
+    let d = b.fingerprint(&TestHasher);
+
+    let mut cas = TestCas::default();
+    let root = store(&d, &mut cas);
+    assert_eq!(&root, d.fingerprint());
+    // `a` and `b` are identical, and `x` is identical to `c`, so only the root, `a`, `x` and `f`
+    // are written.
+    assert_eq!(cas.writes, 4);
+
+    let loaded = load(&root, &cas, &TestHasher)?;
+    assert!(trees_equal(&loaded, &d));
+    assert_eq!(loaded.fingerprint(), d.fingerprint());
+
+    // Storing it again writes nothing.
+    store(&loaded, &mut cas);
+    assert_eq!(cas.writes, 4);
+
+    assert_matches!(
+        load(&TestDigest(1), &cas, &TestHasher),
+        Err(CasLoadError::Missing { .. })
+    );
+    assert_matches!(
+        load(&NopEntry.fingerprint(), &cas, &TestHasher),
+        Err(CasLoadError::NotADirectory { .. })
+    );
+
+    // A corrupt directory that contains itself.
+    cas.write(
+        TestDigest(2),
+        CasObject::Dir(vec![(FileNameBuf::unchecked_new("a"), TestDigest(2))]),
+    );
+    assert_matches!(
+        load(&TestDigest(2), &cas, &TestHasher),
+        Err(CasLoadError::Cycle { .. })
+    );
+
+    Ok(())
+}
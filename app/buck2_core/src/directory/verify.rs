@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use dupe::Dupe;
+use thiserror::Error;
+
+use super::DirectoryDigest;
+use super::DirectoryEntry;
+use super::DirectoryHasher;
+use super::FingerprintedDirectory;
+use super::FingerprintedDirectoryEntries;
+use super::FingerprintedLeaf;
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+#[derive(Debug, Error)]
+#[error("Fingerprint mismatch at path: `{}`, expected `{}`, computed `{}`", .path, .expected, .actual)]
+pub struct DirectoryVerifyError<H: DirectoryDigest> {
+    /// The path of the first entry whose fingerprint does not match, empty for the root.
+    pub path: ForwardRelativePathBuf,
+    /// The fingerprint stored in the tree, or the expected root fingerprint.
+    pub expected: H,
+    /// The fingerprint computed from the content of the entry.
+    pub actual: H,
+}
+
+/// Check that the fingerprints of `dir` are those of its content, e.g. after deserializing it, and
+/// that its root fingerprint is `expected_root`. Fingerprints are recomputed bottom-up: leaves with
+/// `leaf_fingerprint`, and directories with `hasher` from the fingerprints of their entries. This
+/// stops at the first mismatch, visiting entries in path order.
+pub fn verify<L, H, D>(
+    dir: &D,
+    hasher: &impl DirectoryHasher<L, H>,
+    leaf_fingerprint: impl Fn(&L) -> H,
+    expected_root: &H,
+) -> Result<(), DirectoryVerifyError<H>>
+where
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+    D: FingerprintedDirectory<L, H>,
+{
+    let mut path = Vec::new();
+    verify_dir(dir, hasher, &leaf_fingerprint, &mut path)?;
+
+    if dir.fingerprint() != expected_root {
+        return Err(DirectoryVerifyError {
+            path: ForwardRelativePathBuf::empty(),
+            expected: expected_root.dupe(),
+            actual: dir.fingerprint().dupe(),
+        });
+    }
+    Ok(())
+}
+
+fn verify_dir<'a, L, H>(
+    dir: &'a dyn FingerprintedDirectory<L, H>,
+    hasher: &impl DirectoryHasher<L, H>,
+    leaf_fingerprint: &impl Fn(&L) -> H,
+    path: &mut Vec<&'a FileName>,
+) -> Result<(), DirectoryVerifyError<H>>
+where
+    L: FingerprintedLeaf<H>,
+    H: DirectoryDigest,
+{
+    let mut entries = dir.fingerprinted_entries().collect::<Vec<_>>();
+    entries.sort_by_key(|(name, _)| *name);
+
+    for (name, entry) in &entries {
+        path.push(*name);
+        match entry {
+            DirectoryEntry::Dir(d) => verify_dir(*d, hasher, leaf_fingerprint, path)?,
+            DirectoryEntry::Leaf(leaf) => {
+                check(path, &leaf.fingerprint(), leaf_fingerprint(leaf))?;
+            }
+        }
+        path.pop();
+    }
+
+    // `DirectoryHasher` takes sized directories, so erased ones are wrapped.
+    let entries = entries
+        .into_iter()
+        .map(|(name, entry)| (name, entry.map_dir(ErasedDirectory)))
+        .collect::<Vec<_>>();
+    let actual = hasher.hash_entries(
+        entries
+            .iter()
+            .map(|(name, entry)| (*name, entry.as_ref().map_leaf(|leaf| *leaf))),
+    );
+    check(path, dir.fingerprint(), actual)
+}
+
+fn check<H: DirectoryDigest>(
+    path: &[&FileName],
+    expected: &H,
+    actual: H,
+) -> Result<(), DirectoryVerifyError<H>> {
+    if *expected == actual {
+        return Ok(());
+    }
+    Err(DirectoryVerifyError {
+        path: path
+            .iter()
+            .copied()
+            .collect::<Option<ForwardRelativePathBuf>>()
+            .unwrap_or_else(ForwardRelativePathBuf::empty),
+        expected: expected.dupe(),
+        actual,
+    })
+}
+
+struct ErasedDirectory<'a, L, H>(&'a dyn FingerprintedDirectory<L, H>);
+
+impl<'a, L, H> FingerprintedDirectory<L, H> for ErasedDirectory<'a, L, H> {
+    fn fingerprinted_entries(&self) -> FingerprintedDirectoryEntries<'_, L, H> {
+        self.0.fingerprinted_entries()
+    }
+
+    fn get<'b>(
+        &'b self,
+        needle: &'_ FileName,
+    ) -> Option<DirectoryEntry<&'b dyn FingerprintedDirectory<L, H>, &'b L>> {
+        self.0.get(needle)
+    }
+
+    fn fingerprint(&self) -> &H
+    where
+        H: DirectoryDigest,
+    {
+        self.0.fingerprint()
+    }
+}
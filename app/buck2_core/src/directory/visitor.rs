@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use super::Directory;
+use super::DirectoryEntry;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+
+/// What `visit` should do after visiting a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VisitControl {
+    /// Visit the entries of this directory.
+    Continue,
+    /// Don't visit anything under this directory.
+    SkipChildren,
+}
+
+/// Receives the entries of a Directory as `visit` traverses it. Paths are relative to the root
+/// that was passed to `visit`.
+pub trait DirectoryVisitor<L, H> {
+    fn visit_dir(&mut self, path: &ForwardRelativePath, dir: &dyn Directory<L, H>) -> VisitControl;
+
+    fn visit_leaf(&mut self, path: &ForwardRelativePath, leaf: &L);
+}
+
+/// Traverse all the entries under `dir` depth-first, without collecting them. Entries within a
+/// directory are visited in the order `Directory::entries` returns them. The root itself is not
+/// visited.
+pub fn visit<D, L, H, V>(dir: &D, visitor: &mut V)
+where
+    D: Directory<L, H> + ?Sized,
+    V: DirectoryVisitor<L, H> + ?Sized,
+{
+    let mut path = String::new();
+    visit_entries(dir, visitor, &mut path);
+}
+
+fn visit_entries<D, L, H, V>(dir: &D, visitor: &mut V, path: &mut String)
+where
+    D: Directory<L, H> + ?Sized,
+    V: DirectoryVisitor<L, H> + ?Sized,
+{
+    for (name, entry) in dir.entries() {
+        // Extend the path in place and truncate it back afterwards, so we don't allocate a path
+        // per entry.
+        let len = path.len();
+        if !path.is_empty() {
+            path.push('/');
+        }
+        path.push_str(name.as_str());
+
+        match entry {
+            DirectoryEntry::Dir(d) => {
+                let control =
+                    visitor.visit_dir(ForwardRelativePath::unchecked_new(path.as_str()), d);
+                if control == VisitControl::Continue {
+                    visit_entries(d, visitor, path);
+                }
+            }
+            DirectoryEntry::Leaf(leaf) => {
+                visitor.visit_leaf(ForwardRelativePath::unchecked_new(path.as_str()), leaf);
+            }
+        }
+
+        path.truncate(len);
+    }
+}
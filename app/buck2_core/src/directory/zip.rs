@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use super::Directory;
+use super::DirectoryEntry;
+use crate::fs::paths::file_name::FileName;
+use crate::fs::paths::forward_rel_path::ForwardRelativePath;
+use crate::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+
+type ZipEntry<'a, L, H> = DirectoryEntry<&'a dyn Directory<L, H>, &'a L>;
+
+/// An item yielded by [`DirectoryZip`]: a path, and the entry found at that path in each of the
+/// two trees (if any).
+pub type DirectoryZipItem<'a, L, H> = (
+    ForwardRelativePathBuf,
+    Option<ZipEntry<'a, L, H>>,
+    Option<ZipEntry<'a, L, H>>,
+);
+
+/// Walks two directories side by side. See [`zip`].
+pub struct DirectoryZip<'a, L, H> {
+    stack: Vec<std::vec::IntoIter<DirectoryZipItem<'a, L, H>>>,
+}
+
+impl<'a, L, H> DirectoryZip<'a, L, H> {
+    fn frame(
+        path: &ForwardRelativePath,
+        a: Option<&'a dyn Directory<L, H>>,
+        b: Option<&'a dyn Directory<L, H>>,
+    ) -> std::vec::IntoIter<DirectoryZipItem<'a, L, H>> {
+        let mut merged = BTreeMap::<&'a FileName, (_, _)>::new();
+        for (name, entry) in a.into_iter().flat_map(|d| d.entries()) {
+            merged.entry(name).or_insert((None, None)).0 = Some(entry);
+        }
+        for (name, entry) in b.into_iter().flat_map(|d| d.entries()) {
+            merged.entry(name).or_insert((None, None)).1 = Some(entry);
+        }
+
+        merged
+            .into_iter()
+            .map(|(name, (a, b))| (path.join(name), a, b))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl<'a, L, H> Iterator for DirectoryZip<'a, L, H> {
+    type Item = DirectoryZipItem<'a, L, H>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.stack.last_mut()?;
+
+            if let Some((path, a, b)) = frame.next() {
+                let dir_a = match a {
+                    Some(DirectoryEntry::Dir(d)) => Some(d),
+                    _ => None,
+                };
+                let dir_b = match b {
+                    Some(DirectoryEntry::Dir(d)) => Some(d),
+                    _ => None,
+                };
+                if dir_a.is_some() || dir_b.is_some() {
+                    self.stack.push(Self::frame(&path, dir_a, dir_b));
+                }
+                return Some((path, a, b));
+            }
+
+            self.stack.pop();
+        }
+    }
+}
+
+/// Walk two directories in lockstep, in path order. For every path present in either tree, yield
+/// the path along with the entry found there in `a` and in `b`. Directories are yielded before
+/// their contents, and we descend into a path if it is a directory on either side.
+///
+/// This is the primitive on top of which diffing, intersecting or merging trees can be built.
+pub fn zip<'a, L, H, A: Directory<L, H>, B: Directory<L, H>>(
+    a: &'a A,
+    b: &'a B,
+) -> DirectoryZip<'a, L, H> {
+    let root = DirectoryZip::frame(ForwardRelativePath::empty(), Some(a), Some(b));
+    DirectoryZip { stack: vec![root] }
+}
@@ -79,6 +79,9 @@ impl BuckDiceTracker {
                         Some(DiceEvent::CheckDepsFinished{key_type}) => {
                             states.entry(key_type).or_insert_with(DiceKeyState::default).check_deps_finished += 1;
                         }
+                        Some(DiceEvent::InvalidationWave{..}) => {
+                            // Not part of the key state snapshot.
+                        }
                         None => {
                             // This indicates that the sender side has been dropped and we can exit.
                             break;
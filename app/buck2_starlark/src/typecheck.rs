@@ -33,6 +33,7 @@ use dupe::Dupe;
 use starlark::environment::Globals;
 use starlark::typing::AstModuleTypecheck;
 use starlark::typing::Interface;
+use starlark::typing::TypecheckOutput;
 
 use crate::util::environment::Environment;
 use crate::util::paths::starlark_files;
@@ -123,27 +124,50 @@ impl<'a> Cache<'a> {
         let globals = self
             .get_oracle(path_ref.cell(), path_ref.file_type())
             .await?;
-        let (errors, bindings, interface, approxiomations) = ast.typecheck(&globals, &loads);
+        let output = ast.typecheck_with_warnings(&globals, &loads);
+        write_output(output, self.stdout, self.stderr)
+    }
+}
 
-        if !approxiomations.is_empty() {
-            writeln!(self.stderr, "\n\nAPPROXIMATIONS:")?;
-            for x in approxiomations {
-                writeln!(self.stderr, "{x}")?;
-            }
+/// Reports the output of typechecking a module, returning its interface if there are no errors.
+fn write_output(
+    output: TypecheckOutput,
+    stdout: &mut (dyn Write + Send + Sync),
+    stderr: &mut (dyn Write + Send + Sync),
+) -> anyhow::Result<Interface> {
+    let TypecheckOutput {
+        errors,
+        typemap: bindings,
+        interface,
+        approximations,
+        warnings,
+    } = output;
+
+    if !warnings.is_empty() {
+        writeln!(stderr, "\n\nWARNINGS:")?;
+        for x in warnings {
+            writeln!(stderr, "{x}")?;
         }
+    }
 
-        writeln!(self.stderr, "\n\nBINDINGS:\n{bindings}")?;
+    if !approximations.is_empty() {
+        writeln!(stderr, "\n\nAPPROXIMATIONS:")?;
+        for x in approximations {
+            writeln!(stderr, "{x}")?;
+        }
+    }
 
-        let errors_count = errors.len();
-        if errors_count == 0 {
-            Ok(interface)
-        } else {
-            writeln!(self.stdout, "\n\nERRORS:")?;
-            for x in errors {
-                writeln!(self.stdout, "{x}")?;
-            }
-            Err(anyhow::anyhow!("Detected {errors_count} errors"))
+    writeln!(stderr, "\n\nBINDINGS:\n{bindings}")?;
+
+    let errors_count = errors.len();
+    if errors_count == 0 {
+        Ok(interface)
+    } else {
+        writeln!(stdout, "\n\nERRORS:")?;
+        for x in errors {
+            writeln!(stdout, "{x}")?;
         }
+        Err(anyhow::anyhow!("Detected {errors_count} errors"))
     }
 }
 
@@ -188,3 +212,89 @@ impl StarlarkOpaqueSubcommand for StarlarkTypecheckCommand {
         &self.common_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt;
+
+    use allocative::Allocative;
+    use dupe::Dupe;
+    use starlark::environment::GlobalsBuilder;
+    use starlark::starlark_simple_value;
+    use starlark::syntax::AstModule;
+    use starlark::syntax::Dialect;
+    use starlark::typing::AstModuleTypecheck;
+    use starlark::typing::Ty;
+    use starlark::typing::TyStarlarkValue;
+    use starlark::typing::TyUser;
+    use starlark::typing::TyUserParams;
+    use starlark::values::starlark_value;
+    use starlark::values::typing::TypeInstanceId;
+    use starlark::values::NoSerialize;
+    use starlark::values::ProvidesStaticType;
+    use starlark::values::StarlarkValue;
+
+    use crate::typecheck::write_output;
+
+    /// A global which is a deprecated type.
+    #[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+    struct OldType(#[allocative(skip)] Ty);
+
+    starlark_simple_value!(OldType);
+
+    impl fmt::Display for OldType {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "OldType")
+        }
+    }
+
+    #[starlark_value(type = "OldType")]
+    impl<'v> StarlarkValue<'v> for OldType {
+        fn eval_type(&self) -> Option<Ty> {
+            Some(self.0.dupe())
+        }
+    }
+
+    #[test]
+    fn test_deprecated_type_warning() {
+        let ty = Ty::custom(
+            TyUser::new(
+                "OldType".to_owned(),
+                TyStarlarkValue::new::<OldType>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    deprecated: Some("use NewType instead".to_owned()),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        let mut globals = GlobalsBuilder::standard();
+        globals.set("OldType", OldType(ty));
+        let globals = globals.build();
+
+        let ast = AstModule::parse(
+            "test.bzl",
+            r#"
+def f(x: OldType) -> None:
+    pass
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let output = ast.typecheck_with_warnings(&globals, &HashMap::new());
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        write_output(output, &mut stdout, &mut stderr).unwrap();
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert!(stderr.contains("WARNINGS:"), "{}", stderr);
+        assert!(
+            stderr.contains("Type `OldType` is deprecated: use NewType instead"),
+            "{}",
+            stderr
+        );
+    }
+}
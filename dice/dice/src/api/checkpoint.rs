@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Checkpointing the progress of long computations, so that they resume rather than restart
+//! after being interrupted, e.g. by a daemon restart.
+
+use std::hash::Hasher;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use fnv::FnvHasher;
+use more_futures::cancellation::CancellationContext;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::api::computations::DiceComputations;
+use crate::api::key::Key;
+use crate::api::retry::DiceRetryPolicy;
+
+/// Enables checkpoints of the computations of keys that provide a `Key::checkpoint_id`.
+///
+/// Such computations save their progress with `DiceComputations::checkpoint` as they go, and
+/// restore it with `DiceComputations::resume_checkpoint` when they start. Checkpoints are files
+/// in `dir`, so they survive the DICE instance: a computation interrupted by a daemon restart
+/// resumes from its last checkpoint when the key is computed again. The checkpoint of a key is
+/// removed once its computation completes.
+///
+/// To enable checkpoints, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(CheckpointConfig::new(checkpoint_dir));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CheckpointConfig {
+    dir: PathBuf,
+}
+
+/// The content of a checkpoint file.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    /// The key type and `Key::checkpoint_id` of the computation, to tell apart computations
+    /// whose file names collide.
+    key_type: String,
+    checkpoint_id: String,
+    /// Fingerprint of the inputs the state was computed from.
+    inputs: u64,
+    state: Vec<u8>,
+}
+
+impl CheckpointConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The file of the checkpoint of the computation of a key of type `K`. It is named after a
+    /// digest of the key type and the checkpoint id, so ids can't escape `dir` or collide with
+    /// the ids of other key types.
+    fn path<K: Key>(&self, checkpoint_id: &str) -> PathBuf {
+        let mut hasher = FnvHasher::default();
+        hasher.write(std::any::type_name::<K>().as_bytes());
+        hasher.write_u8(0);
+        hasher.write(checkpoint_id.as_bytes());
+        self.dir.join(format!("{:016x}", hasher.finish()))
+    }
+
+    pub(crate) async fn write<K: Key, S: Serialize>(
+        &self,
+        checkpoint_id: &str,
+        inputs: u64,
+        state: &S,
+    ) -> anyhow::Result<()> {
+        let checkpoint = Checkpoint {
+            key_type: std::any::type_name::<K>().to_owned(),
+            checkpoint_id: checkpoint_id.to_owned(),
+            inputs,
+            state: bincode::serialize(state)?,
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // Write then rename, so that an interrupted write leaves the previous checkpoint intact.
+        let path = self.path::<K>(checkpoint_id);
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bincode::serialize(&checkpoint)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    /// The state of the checkpoint, if there is one and it was computed from the same `inputs`.
+    pub(crate) async fn read<K: Key, S: DeserializeOwned>(
+        &self,
+        checkpoint_id: &str,
+        inputs: u64,
+    ) -> anyhow::Result<Option<S>> {
+        let bytes = match tokio::fs::read(self.path::<K>(checkpoint_id)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let checkpoint: Checkpoint = bincode::deserialize(&bytes)?;
+        if checkpoint.key_type != std::any::type_name::<K>()
+            || checkpoint.checkpoint_id != checkpoint_id
+            || checkpoint.inputs != inputs
+        {
+            return Ok(None);
+        }
+        Ok(Some(bincode::deserialize(&checkpoint.state)?))
+    }
+
+    /// Computes `key`, removing its checkpoint once the computation completes.
+    pub(crate) async fn compute<K: Key>(
+        key: &K,
+        ctx: &mut DiceComputations,
+        cancellations: &CancellationContext,
+    ) -> K::Value {
+        let value = DiceRetryPolicy::compute(key, ctx, cancellations).await;
+        if let (Ok(config), Some(checkpoint_id)) = (
+            ctx.global_data().get::<CheckpointConfig>(),
+            key.checkpoint_id(),
+        ) {
+            // Leftover checkpoints are only resumed by computations with the same inputs, so
+            // failing to remove it is not fatal.
+            let _ignored = tokio::fs::remove_file(config.path::<K>(&checkpoint_id)).await;
+        }
+        value
+    }
+}
@@ -11,16 +11,40 @@ use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use allocative::Allocative;
+use dupe::Dupe;
+use futures::future;
 use futures::future::BoxFuture;
-
+use futures::stream;
+use futures::FutureExt;
+use futures::Stream;
+use futures::StreamExt;
+use more_futures::cancellation::CancellationContext;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::api::checkpoint::CheckpointConfig;
 use crate::api::data::DiceData;
+use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
+use crate::api::file_dependency::FileDependencyKey;
+use crate::api::graph_stats::TransactionGraphStats;
+use crate::api::history::ValueChange;
 use crate::api::key::Key;
 use crate::api::opaque::OpaqueValue;
+use crate::api::progress::ComputeProgress;
+use crate::api::progress::ProgressReceiver;
+use crate::api::projection::MappedProjectionKey;
+use crate::api::resource_usage::RequestResourceReport;
 use crate::api::user_data::UserComputationData;
 use crate::ctx::DiceComputationsImpl;
+use crate::legacy::cycles::RequestedKey;
+use crate::versions::VersionNumber;
+use crate::HashMap;
+use crate::HashSet;
 use crate::UserCycleDetectorGuard;
 
 /// The context for computations to register themselves, and request for additional dependencies.
@@ -68,6 +92,287 @@ impl DiceComputations {
         self.0.compute_opaque(key)
     }
 
+    /// Computes `key` and maps its value with `f`.
+    ///
+    /// This is backed by a projection of `key`, so the mapped value is cached, and the current
+    /// computation is only invalidated when the mapped value changes rather than the whole value.
+    /// `f` must be pure, and must not capture anything (e.g. a function or a non-capturing
+    /// closure), since the projection is identified by the type of `f` alone. Passing a capturing
+    /// closure is a compile error.
+    pub async fn compute_mapped<K, F, T>(&self, key: &K, f: F) -> DiceResult<T>
+    where
+        K: Key,
+        F: Fn(&K::Value) -> T + Copy + Send + Sync + 'static,
+        T: Allocative + Dupe + Eq + Send + Sync + 'static,
+    {
+        self.compute_opaque(key)
+            .await?
+            .projection(&MappedProjectionKey::new(f))
+    }
+
+    /// Computes `key`, unless `cancel` completes first, in which case `None` is returned.
+    ///
+    /// On cancellation the request for `key` is dropped, which aborts the computation as long as
+    /// no other request is waiting on it.
+    pub async fn compute_until<K, F>(&self, key: &K, cancel: F) -> DiceResult<Option<K::Value>>
+    where
+        K: Key,
+        F: Future,
+    {
+        let compute = self.compute(key);
+        futures::pin_mut!(compute, cancel);
+
+        match future::select(compute, cancel).await {
+            future::Either::Left((res, _cancel)) => res.map(Some),
+            future::Either::Right((_, compute)) => {
+                drop(compute);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Computes `key`, streaming the progress of type `P` reported while it is computed, followed
+    /// by the result.
+    ///
+    /// Only the progress reported by the computation of `key` itself is streamed, not that of its
+    /// deps. A cached `key` reports no progress, and neither does a computation of `key` that was
+    /// already running before this was called.
+    pub fn compute_with_progress<'a, K, P>(
+        &'a self,
+        key: &K,
+    ) -> impl Stream<Item = ComputeProgress<P, DiceResult<K::Value>>> + 'a
+    where
+        K: Key,
+        P: Send + Sync + 'static,
+    {
+        enum State<F, P, V> {
+            Computing(F, ProgressReceiver<P>),
+            Draining(ProgressReceiver<P>, V),
+            Finished,
+        }
+
+        let progress = self.per_transaction_data().progress.subscribe::<K, P>(key);
+        let compute = Box::pin(self.compute(key));
+
+        stream::unfold(State::Computing(compute, progress), |state| async move {
+            match state {
+                State::Computing(mut compute, mut progress) => {
+                    tokio::select! {
+                        biased;
+                        Some(p) = progress.recv() => Some((
+                            ComputeProgress::Progress(p),
+                            State::Computing(compute, progress),
+                        )),
+                        res = &mut compute => match progress.try_recv() {
+                            Some(p) => Some((
+                                ComputeProgress::Progress(p),
+                                State::Draining(progress, res),
+                            )),
+                            None => Some((ComputeProgress::Done(res), State::Finished)),
+                        },
+                    }
+                }
+                // Progress reported just before the computation finished may not have been
+                // received yet.
+                State::Draining(mut progress, res) => match progress.try_recv() {
+                    Some(p) => Some((ComputeProgress::Progress(p), State::Draining(progress, res))),
+                    None => Some((ComputeProgress::Done(res), State::Finished)),
+                },
+                State::Finished => None,
+            }
+        })
+    }
+
+    /// Reports progress of the current computation to the streams of `compute_with_progress`.
+    /// This does not record any dependencies.
+    pub fn report_progress<P>(&self, progress: P)
+    where
+        P: Send + Sync + 'static,
+    {
+        self.0.report_progress(progress)
+    }
+
+    /// Whether `key` has a valid value in this transaction, without computing it. No dependency
+    /// on `key` is recorded, so this is only meant for diagnostics and heuristics.
+    ///
+    /// A key whose value might still be valid but whose deps would need to be checked first is
+    /// reported as not cached.
+    pub fn is_cached<'a, K>(&'a self, key: &K) -> impl Future<Output = bool> + 'a
+    where
+        K: Key,
+    {
+        self.0.is_cached(key)
+    }
+
+    /// The number of direct dependencies `key` recorded when it was computed, or `None` if it has
+    /// no valid value in this transaction. No dependency on `key` is recorded.
+    ///
+    /// This is a diagnostic for finding over-connected keys, which are invalidated too easily.
+    pub fn dep_count<'a, K>(&'a self, key: &K) -> impl Future<Output = Option<usize>> + 'a
+    where
+        K: Key,
+    {
+        self.0.dep_count(key)
+    }
+
+    /// The number of distinct keys that transitively depend on `key`, i.e. how many keys would
+    /// need to be recomputed if `key` were invalidated now. Nothing is invalidated, and this is `0`
+    /// if `key` has no valid value in this transaction.
+    ///
+    /// This lets tooling warn before changes that would cause large rebuilds.
+    pub fn invalidation_impact<'a, K>(&'a self, key: &K) -> impl Future<Output = usize> + 'a
+    where
+        K: Key,
+    {
+        self.0.invalidation_impact(key)
+    }
+
+    /// The versions at which the value of `key` changed, and the versions at which it was
+    /// invalidated but recomputed to an equal value, in version order. Nothing is computed and no
+    /// dependency on `key` is recorded.
+    ///
+    /// This is a debugging aid for finding keys that churn. Only versions whose values are still
+    /// stored are reported, and only legacy dice keeps this history, so modern dice reports none.
+    pub fn value_history<K>(&self, key: &K) -> Vec<(VersionNumber, ValueChange)>
+    where
+        K: Key,
+    {
+        self.0.value_history(key)
+    }
+
+    /// Returns the value of `key` without recomputing it, as long as that value was valid at most
+    /// `max_versions_behind` versions before the version of this transaction, counted in
+    /// committed versions (`VersionNumber`s), so 0 only accepts a value valid at this version.
+    /// Otherwise `key` is computed as with `compute`.
+    ///
+    /// This trades freshness for latency, so is only meant for reads that can tolerate a
+    /// slightly outdated value. When a stale value is returned, the dependency on `key` is recorded
+    /// as transient: the value of the requesting computation is only reused at this version, and
+    /// it is recomputed at later ones. Only the modern engine keeps stale values, so legacy dice
+    /// always computes.
+    pub async fn compute_allowing_stale<K>(
+        &self,
+        key: &K,
+        max_versions_behind: usize,
+    ) -> DiceResult<K::Value>
+    where
+        K: Key,
+    {
+        if let Some(value) = self.0.get_allowing_stale(key, max_versions_behind).await {
+            return Ok(value);
+        }
+        self.compute(key).await
+    }
+
+    /// Recomputes `key` even if it is already cached, and compares the result with the cached
+    /// value using `Key::equality`. If the two are equal, the cached value is returned, otherwise
+    /// the recomputed one is.
+    ///
+    /// This is a debugging aid, e.g. for checking whether a key is deterministic. The dependency
+    /// on `key` is recorded as with `compute`, which also waits for any in-flight computation of
+    /// `key`. The recomputation then runs outside of the graph: the deps it requests are served
+    /// from the cache as usual, but are not recorded, neither as deps of `key` nor as deps of
+    /// this computation. The recomputed value is never stored, so dependents of `key` are not
+    /// invalidated by this, even if the value changed.
+    pub async fn compute_forced<K>(&self, key: &K) -> DiceResult<K::Value>
+    where
+        K: Key,
+    {
+        let cached = self.compute(key).await?;
+
+        let mut detached = DiceComputations(self.0.detached_for_key(key)?);
+        let recomputed = key
+            .compute(&mut detached, CancellationContext::never_cancelled())
+            .await;
+        if K::equality(&cached, &recomputed) {
+            Ok(cached)
+        } else {
+            Ok(recomputed)
+        }
+    }
+
+    /// Computes the given keys in dependency order, so that each computation finds its
+    /// dependencies already cached. `edges` are `(dependent, dependency)` pairs known by the
+    /// caller; a key is only requested once all its dependencies have been computed, and keys
+    /// whose dependencies are all computed are requested in parallel.
+    ///
+    /// Keys that only appear in `edges` are computed as opaque values, so they are prebuilt but
+    /// not recorded as dependencies. Returns the values of `keys`, in order.
+    /// It is an error for `edges` to form a cycle.
+    pub async fn compute_topo<K>(&self, keys: &[K], edges: &[(K, K)]) -> DiceResult<Vec<K::Value>>
+    where
+        K: Key,
+    {
+        let layers = topo_layers(keys, edges)?;
+
+        let requested: HashSet<&K> = keys.iter().collect();
+        let mut values: HashMap<&K, K::Value> = HashMap::default();
+        for layer in layers {
+            let results = future::join_all(layer.iter().map(|k| {
+                if requested.contains(k) {
+                    self.compute(*k).map(|r| r.map(Some)).left_future()
+                } else {
+                    self.compute_opaque(*k)
+                        .map(|r| r.map(|_| None))
+                        .right_future()
+                }
+            }))
+            .await;
+            for (k, res) in layer.into_iter().zip(results) {
+                if let Some(v) = res? {
+                    values.insert(k, v);
+                }
+            }
+        }
+
+        Ok(keys.iter().map(|k| values[k].dupe()).collect())
+    }
+
+    /// Computes `keys` concurrently, for keys whose values are fallible. Returns the values in
+    /// order if all of them succeed, or else the first error produced. Once a key fails, the
+    /// requests for the remaining keys are dropped, which aborts their computations as long as no
+    /// other request is waiting on them.
+    pub async fn compute_all_or_first_error<K, T, E>(
+        &self,
+        keys: &[K],
+    ) -> DiceResult<Result<Vec<T>, E>>
+    where
+        K: Key<Value = Result<T, E>>,
+    {
+        let computes = keys.iter().map(|k| {
+            self.compute(k).map(|res| match res {
+                Ok(Ok(v)) => Ok(v),
+                Ok(Err(e)) => Err(Ok(e)),
+                Err(e) => Err(Err(e)),
+            })
+        });
+
+        match future::try_join_all(computes).await {
+            Ok(values) => Ok(Ok(values)),
+            Err(Ok(e)) => Ok(Err(e)),
+            Err(Err(e)) => Err(e),
+        }
+    }
+
+    /// Computes the keys produced by `keys` as they arrive, so the set of keys does not need to
+    /// be known upfront. At most `concurrency` keys are computed at once, and the results are
+    /// yielded as the computations complete, which is not necessarily the order of `keys`.
+    ///
+    /// Panics if `concurrency` is 0, since no key would ever be computed.
+    pub fn compute_stream<'a, K, S>(
+        &'a self,
+        keys: S,
+        concurrency: usize,
+    ) -> impl Stream<Item = DiceResult<K::Value>> + 'a
+    where
+        K: Key,
+        S: Stream<Item = K> + 'a,
+    {
+        assert!(concurrency > 0, "`compute_stream` requires a positive concurrency");
+        keys.map(move |k| self.compute(&k))
+            .buffer_unordered(concurrency)
+    }
+
     /// Computes all the given tasks in parallel, returning an unordered Stream
     pub fn compute_many<'a, T: 'a>(
         &'a self,
@@ -87,6 +392,46 @@ impl DiceComputations {
         self.0.compute2(compute1, compute2)
     }
 
+    /// Records that the current computation read the file at `path` outside of DICE, so that it is
+    /// invalidated when the embedder's file watcher reports the file changed with
+    /// `DiceTransactionUpdater::files_changed`.
+    pub async fn record_file_dependency(&self, path: impl Into<PathBuf>) -> DiceResult<()> {
+        self.compute(&FileDependencyKey::new(path)).await
+    }
+
+    /// Saves the progress of the computation of `key`, the key being computed, so that it resumes
+    /// from `state` if it is interrupted. `inputs` is a fingerprint of the values `state` was
+    /// computed from, typically the values of the keys computed so far: the checkpoint is only
+    /// resumed when they are unchanged.
+    ///
+    /// This does nothing unless `CheckpointConfig` is set and `key` has a `Key::checkpoint_id`.
+    pub async fn checkpoint<K: Key, S: Serialize>(
+        &self,
+        key: &K,
+        inputs: u64,
+        state: &S,
+    ) -> anyhow::Result<()> {
+        match (self.global_data().get::<CheckpointConfig>(), key.checkpoint_id()) {
+            (Ok(config), Some(checkpoint_id)) => {
+                config.write::<K, _>(&checkpoint_id, inputs, state).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The state last saved by `checkpoint` for `key` with the same `inputs`, by this computation
+    /// or by an interrupted one, possibly of a previous DICE instance.
+    pub async fn resume_checkpoint<K: Key, S: DeserializeOwned>(
+        &self,
+        key: &K,
+        inputs: u64,
+    ) -> anyhow::Result<Option<S>> {
+        match (self.global_data().get::<CheckpointConfig>(), key.checkpoint_id()) {
+            (Ok(config), Some(checkpoint_id)) => config.read::<K, _>(&checkpoint_id, inputs).await,
+            _ => Ok(None),
+        }
+    }
+
     /// Data that is static per the entire lifetime of Dice. These data are initialized at the
     /// time that Dice is initialized via the constructor.
     pub fn global_data(&self) -> &DiceData {
@@ -101,6 +446,20 @@ impl DiceComputations {
         self.0.per_transaction_data()
     }
 
+    /// The number of keys computed, and the time spent computing them, for the current request,
+    /// i.e. since the transaction was committed with its `UserComputationData`. This is meant for
+    /// quotas and fairness between the requests sharing a DICE.
+    pub fn request_resource_report(&self) -> RequestResourceReport {
+        self.per_transaction_data().resource_usage.report()
+    }
+
+    /// The size of the graph computed for the current transaction, i.e. since it was committed
+    /// with its `UserComputationData`. This is meant for finding requests which build
+    /// pathologically large graphs.
+    pub fn transaction_graph_stats(&self) -> TransactionGraphStats {
+        self.per_transaction_data().graph_stats.stats()
+    }
+
     /// Gets the current cycle guard if its set. If it's set but a different type, an error will be returned.
     pub fn cycle_guard<T: UserCycleDetectorGuard>(&self) -> DiceResult<Option<&T>> {
         self.0.cycle_guard()
@@ -113,6 +472,55 @@ impl DiceComputations {
     }
 }
 
+/// Groups the nodes of the DAG given by `keys` and `edges` (`(dependent, dependency)` pairs)
+/// into layers, such that every node's dependencies are in earlier layers.
+fn topo_layers<'k, K: Key>(keys: &'k [K], edges: &'k [(K, K)]) -> DiceResult<Vec<Vec<&'k K>>> {
+    // Number of not yet visited dependencies per key.
+    let mut pending: HashMap<&K, usize> = HashMap::default();
+    let mut dependents: HashMap<&K, Vec<&K>> = HashMap::default();
+    for k in keys {
+        pending.entry(k).or_insert(0);
+    }
+    for (dependent, dependency) in edges {
+        *pending.entry(dependent).or_insert(0) += 1;
+        pending.entry(dependency).or_insert(0);
+        dependents.entry(dependency).or_default().push(dependent);
+    }
+
+    let mut layers = Vec::new();
+    let mut layer: Vec<&K> = pending
+        .iter()
+        .filter(|(_, n)| **n == 0)
+        .map(|(k, _)| *k)
+        .collect();
+    while !layer.is_empty() {
+        let mut next = Vec::new();
+        for k in &layer {
+            pending.remove(k);
+            for dependent in dependents.get(k).into_iter().flatten() {
+                let n = pending.get_mut(dependent).unwrap();
+                *n -= 1;
+                if *n == 0 {
+                    next.push(*dependent);
+                }
+            }
+        }
+        layers.push(layer);
+        layer = next;
+    }
+
+    if !pending.is_empty() {
+        return Err(DiceError::cyclic_topo_input(
+            pending
+                .into_keys()
+                .map(|k| Arc::new(k.clone()) as Arc<dyn RequestedKey>)
+                .collect(),
+        ));
+    }
+
+    Ok(layers)
+}
+
 /// For a `compute_many` and `compute2` request, the DiceComputations provided to each lambda
 /// is a reference that's only available for some specific lifetime `'x`. This is express as a
 /// higher rank lifetime bound `for <'x>` in rust. However, `for <'x>` bounds do not have constraints
@@ -186,6 +186,7 @@
 //! ```
 
 use std::fmt::Debug;
+use std::io::Read;
 use std::io::Write;
 use std::sync::Arc;
 
@@ -194,6 +195,7 @@ use futures::future::Future;
 use serde::Serializer;
 
 use crate::api::cycles::DetectCycles;
+use crate::api::persist::PersistableKey;
 use crate::api::transaction::DiceTransactionUpdater;
 use crate::api::user_data::UserComputationData;
 use crate::metrics::Metrics;
@@ -264,6 +266,37 @@ impl Dice {
         self.implementation.metrics()
     }
 
+    /// The metrics, including the aggregate metrics per key type, in the Prometheus text
+    /// exposition format.
+    pub fn prometheus_metrics(&self) -> String {
+        self.metrics().to_prometheus()
+    }
+
+    /// Writes the values of all keys of type `K` that are cached at the current version, along
+    /// with their deps, so that they can be reloaded into another DICE with `load_cache`. Values
+    /// with deps that aren't of type `K` are skipped. Returns the number of values written.
+    ///
+    /// Only supported by modern DICE.
+    pub async fn persist_cache<K>(&self, writer: impl Write) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        self.implementation.persist_cache::<K>(writer).await
+    }
+
+    /// Reads values written by `persist_cache`, and installs them as valid at version 0, so
+    /// they are not recomputed until they or their deps are invalidated. This is meant to be
+    /// called right after building DICE, before any transaction. Returns the number of values
+    /// loaded.
+    ///
+    /// Only supported by modern DICE.
+    pub async fn load_cache<K>(&self, reader: impl Read) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        self.implementation.load_cache::<K>(reader).await
+    }
+
     /// Wait until all active versions have exited.
     pub fn wait_for_idle(&self) -> impl Future<Output = ()> + 'static {
         self.implementation.wait_for_idle()
@@ -47,6 +47,14 @@ impl DiceError {
     pub fn duplicate_activation_data() -> Self {
         DiceError(Arc::new(DiceErrorImpl::DuplicateActivationData))
     }
+
+    pub fn invalid_result(key: Arc<dyn RequestedKey>, message: String) -> Self {
+        DiceError(Arc::new(DiceErrorImpl::InvalidResult { key, message }))
+    }
+
+    pub fn cyclic_topo_input(cyclic_keys: Vec<Arc<dyn RequestedKey>>) -> Self {
+        DiceError(Arc::new(DiceErrorImpl::CyclicTopoInput(cyclic_keys)))
+    }
 }
 
 #[derive(Debug, Error, Allocative)]
@@ -75,6 +83,13 @@ pub(crate) enum DiceErrorImpl {
     },
     #[error("Activation data was already provided for this key")]
     DuplicateActivationData,
+    #[error("Edges passed to `compute_topo` form a cycle: `{}`", .0.iter().join(", "))]
+    CyclicTopoInput(Vec<Arc<dyn RequestedKey>>),
+    #[error("Value of key `{key}` is invalid: {message}")]
+    InvalidResult {
+        key: Arc<dyn RequestedKey>,
+        message: String,
+    },
 }
 
 pub type DiceResult<T> = Result<T, DiceError>;
@@ -7,8 +7,12 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+
 use allocative::Allocative;
 
+use crate::versions::VersionNumber;
+
 #[derive(Allocative, PartialEq, Eq, Debug)]
 pub enum DiceEvent {
     /// Computation started.
@@ -22,6 +26,14 @@ pub enum DiceEvent {
 
     /// Checking dependencies has finished.
     CheckDepsFinished { key_type: &'static str },
+
+    /// A transaction's changes were committed at `version`, invalidating `invalidated_count`
+    /// nodes that transitively depend on the changed keys.
+    InvalidationWave {
+        version: VersionNumber,
+        invalidated_count: usize,
+        duration: Duration,
+    },
 }
 
 pub trait DiceEventListener: Allocative + Send + Sync + 'static {
@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Observing values evicted from the DICE cache.
+
+use std::sync::Arc;
+
+use allocative::Allocative;
+use dupe::Dupe;
+
+use crate::api::key::KeyDescription;
+use crate::versions::VersionNumber;
+
+/// Callback invoked when a cached value is evicted because its key would otherwise store more
+/// values than its `StorageType` allows. Useful to tell whether eviction is too aggressive,
+/// causing recomputes, or too lax.
+///
+/// To observe evictions, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(EvictionObserver::new(|key, version| println!("evicted {key} at {version}")));
+/// ```
+///
+/// The callback runs on the thread that owns the DICE graph, so it must be cheap.
+/// Only the modern engine reports evictions.
+#[derive(Clone, Dupe, Allocative)]
+pub struct EvictionObserver(
+    #[allocative(skip)] Arc<dyn Fn(&dyn KeyDescription, VersionNumber) + Send + Sync>,
+);
+
+impl EvictionObserver {
+    pub fn new(
+        on_evict: impl Fn(&dyn KeyDescription, VersionNumber) + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(on_evict))
+    }
+
+    /// Reports that the value of `key` stored at `version` was evicted.
+    pub(crate) fn on_evict(&self, key: &dyn KeyDescription, version: VersionNumber) {
+        (self.0)(key, version)
+    }
+}
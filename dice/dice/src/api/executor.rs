@@ -0,0 +1,47 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Choosing where DICE spawns its computations.
+
+use std::sync::Arc;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use more_futures::spawner::Spawner;
+
+use crate::api::user_data::UserComputationData;
+
+/// The executor on which DICE spawns all its computations, for embedding DICE in an async
+/// runtime other than the ambient `tokio` one.
+///
+/// To use it, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(DiceExecutor::new(Arc::new(MySpawner)));
+/// ```
+///
+/// It replaces the `spawner` of the `UserComputationData` of every transaction, unless the
+/// transaction sets a spawner of its own.
+#[derive(Clone, Dupe, Allocative)]
+pub struct DiceExecutor(#[allocative(skip)] Arc<dyn Spawner<UserComputationData>>);
+
+impl DiceExecutor {
+    pub fn new(spawner: Arc<dyn Spawner<UserComputationData>>) -> Self {
+        Self(spawner)
+    }
+
+    /// Makes computations of the transaction with the given data spawn on this executor, unless
+    /// the data has a spawner set by the user.
+    pub(crate) fn apply(&self, extra: &mut UserComputationData) {
+        if extra.has_default_spawner() {
+            extra.spawner = self.0.dupe();
+        }
+    }
+}
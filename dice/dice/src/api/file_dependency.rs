@@ -0,0 +1,64 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Dependencies of computations on files read outside of DICE, see
+//! `DiceComputations::record_file_dependency`.
+
+use std::fmt;
+use std::path::Component;
+use std::path::PathBuf;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use more_futures::cancellation::CancellationContext;
+
+use crate::api::computations::DiceComputations;
+use crate::api::key::Key;
+
+/// Stands for a file in the graph. Computations which read the file depend on it, and the
+/// embedder's file watcher records it as changed when the file changes, which invalidates them.
+#[derive(Allocative, Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct FileDependencyKey(#[allocative(skip)] PathBuf);
+
+impl FileDependencyKey {
+    /// The key of the file at `path`, without `.` components, so that `./a` and `a` are the same
+    /// file.
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        let path: PathBuf = path.into();
+        Self(
+            path.components()
+                .filter(|c| *c != Component::CurDir)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for FileDependencyKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileDependency({})", self.0.display())
+    }
+}
+
+#[async_trait]
+impl Key for FileDependencyKey {
+    type Value = ();
+
+    async fn compute(
+        &self,
+        _ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+    }
+
+    fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+        // The key is only recomputed when the file changed, which must invalidate the
+        // computations that read it.
+        false
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The size of the computation graph a transaction built, for capacity planning.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use dupe::Dupe;
+
+/// The graph a transaction built, see `DiceComputations::transaction_graph_stats`.
+#[derive(Clone, Copy, Dupe, Debug, Default, Eq, PartialEq)]
+pub struct TransactionGraphStats {
+    /// The number of keys computed rather than reused from the cache.
+    pub nodes: u64,
+    /// The number of dependencies recorded by the computed keys.
+    pub edges: u64,
+    /// The largest number of keys being computed at the same time, including keys waiting for
+    /// the keys they requested.
+    pub peak_concurrency: u64,
+}
+
+/// Accumulates the graph built by the transaction of a `UserComputationData`.
+#[derive(Default)]
+pub struct TransactionGraphCounters {
+    nodes: AtomicU64,
+    edges: AtomicU64,
+    running: AtomicU64,
+    peak_running: AtomicU64,
+}
+
+impl TransactionGraphCounters {
+    /// The totals accumulated so far.
+    pub fn stats(&self) -> TransactionGraphStats {
+        TransactionGraphStats {
+            nodes: self.nodes.load(Ordering::Relaxed),
+            edges: self.edges.load(Ordering::Relaxed),
+            peak_concurrency: self.peak_running.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Called by the engines when they start computing a key. The key stops counting towards
+    /// the concurrency when the returned guard is dropped, including when it is cancelled.
+    pub(crate) fn computation_started(&self) -> RunningComputation<'_> {
+        let running = self.running.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_running.fetch_max(running, Ordering::Relaxed);
+        RunningComputation { counters: self }
+    }
+}
+
+/// A key being computed, see `TransactionGraphCounters::computation_started`.
+pub(crate) struct RunningComputation<'a> {
+    counters: &'a TransactionGraphCounters,
+}
+
+impl RunningComputation<'_> {
+    /// Records the computed key and its `deps` dependencies.
+    pub(crate) fn finished(self, deps: usize) {
+        self.counters.nodes.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .edges
+            .fetch_add(deps as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for RunningComputation<'_> {
+    fn drop(&mut self) {
+        self.counters.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,24 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Inspecting how the value of a key evolved across versions.
+
+use allocative::Allocative;
+use dupe::Dupe;
+
+/// What happened to the value of a key at a version, as reported by
+/// `DiceComputations::value_history`.
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq, Hash, Allocative)]
+pub enum ValueChange {
+    /// A new value, different from the previous one, became valid at this version.
+    Changed,
+    /// The key was invalidated at this version, but recomputing it produced a value equal to the
+    /// previous one, so the previous value was reused and dependents were not invalidated.
+    Unchanged,
+}
@@ -64,4 +64,8 @@ where
         // if we store more than usize max value, we are in trouble.
         StorageType::LastN(usize::max_value())
     }
+
+    fn is_injected() -> bool {
+        true
+    }
 }
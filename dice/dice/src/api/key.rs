@@ -7,9 +7,11 @@
  * of this source tree.
  */
 
+use std::any::Any;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use async_trait::async_trait;
@@ -17,6 +19,8 @@ use dupe::Dupe;
 use more_futures::cancellation::CancellationContext;
 
 use crate::api::computations::DiceComputations;
+use crate::api::resource_class::ResourceClass;
+use crate::api::spill::SpillCodec;
 use crate::api::storage_type::StorageType;
 use crate::introspection::graph::short_type_name;
 
@@ -62,7 +66,119 @@ pub trait Key: Allocative + Debug + Display + Clone + Eq + Hash + Send + Sync +
         true
     }
 
+    /// If `true`, the computed value is an error that may not happen again, e.g. a network
+    /// timeout, and DICE retries the computation as configured by the `DiceRetryPolicy` set in the
+    /// `DiceData`. Only the value of the last attempt is cached, and if it is still a transient
+    /// error, it isn't shared with the keys with the same `content_key`.
+    ///
+    /// The default is `false`. This is typically overridden by keys whose value is a `Result`.
+    fn is_transient_error(_x: &Self::Value) -> bool {
+        false
+    }
+
+    /// Checks an invariant of the values of this key, returning a description of the violation
+    /// if there is one. DICE fails requests for values which violate it, to catch values
+    /// corrupted by bugs early, e.g. in `Key::equality`.
+    ///
+    /// This only runs in debug builds, or when `ValidateResults` is set in the `DiceData`.
+    /// The default accepts all values.
+    fn validate_result(_x: &Self::Value) -> Result<(), String> {
+        Ok(())
+    }
+
     fn storage_type() -> StorageType {
         StorageType::LastN(1)
     }
+
+    /// If `true`, the key is expected to rarely change even though its dependencies may.
+    /// DICE will retain more versions of its value (see `StorageType::for_key`), so that when
+    /// its dependencies are invalidated, it is re-validated against the existing value via
+    /// the early-cutoff (dependency equality) check rather than recomputed.
+    ///
+    /// The default is `false`.
+    fn pin() -> bool {
+        false
+    }
+
+    /// A digest of the meaningful input of this key. Keys of the same type with equal content keys
+    /// share a single computation, even if the keys themselves are not equal: the value computed
+    /// for one is reused for the others.
+    ///
+    /// The value must therefore be fully determined by the content key. Keys that reuse another
+    /// key's value depend on that key, so they are recomputed whenever it is.
+    ///
+    /// The value is kept across transactions, even when the key that computed it is recomputed
+    /// because its dependencies changed, until it is invalidated with
+    /// `DiceTransactionUpdater::invalidate_content_key` or that key's value is evicted from the
+    /// cache. This makes it suitable for expensive results that stay valid across many
+    /// transactions, like parsed toolchains.
+    ///
+    /// The default is `None`, meaning the key is only deduplicated with equal keys.
+    fn content_key(&self) -> Option<ContentKey> {
+        None
+    }
+
+    /// The resource computations of this key contend on, like network connections. At most as
+    /// many computations of a class run concurrently as the `DiceResourceLimits` set in the
+    /// `DiceData` allow, while computations of other classes proceed.
+    ///
+    /// The default is `None`, meaning computations of this key are not limited.
+    fn resource_class(&self) -> Option<ResourceClass> {
+        None
+    }
+
+    /// How to write values of this key to disk, so that DICE can spill them when the cache holds
+    /// too much memory and reload them on the next access. See `SpillConfig`.
+    ///
+    /// The default is `None`, meaning values of this key are always kept in memory.
+    fn spill_codec() -> Option<SpillCodec<Self::Value>> {
+        None
+    }
+
+    /// A name identifying the computation of this key across DICE instances, used to name the
+    /// file of its checkpoints. It only needs to be unique among keys of the same type. Long
+    /// computations which save their progress with `DiceComputations::checkpoint` must provide
+    /// it. See `CheckpointConfig`.
+    ///
+    /// The default is `None`, meaning the computation of this key is not checkpointed.
+    fn checkpoint_id(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether this is an `InjectedKey`, whose value is only ever set with `changed_to` and is
+    /// never computed. Implemented by the `InjectedKey` blanket impl, not by users.
+    #[doc(hidden)]
+    fn is_injected() -> bool {
+        false
+    }
+}
+
+/// A digest identifying the content a key computes over. See `Key::content_key`.
+#[derive(Allocative, Clone, Dupe, Debug, Eq, Hash, PartialEq)]
+pub struct ContentKey(Arc<[u8]>);
+
+impl ContentKey {
+    pub fn new(digest: impl AsRef<[u8]>) -> Self {
+        Self(Arc::from(digest.as_ref()))
+    }
+}
+
+/// A type-erased view of a `Key`, used to select keys without knowing their types, e.g. by
+/// `DiceTransactionUpdater::invalidate_matching`.
+pub trait KeyDescription: Display + Send + Sync + 'static {
+    /// Short informative name of the key type, see `Key::key_type_name`.
+    fn key_type_name(&self) -> &'static str;
+
+    /// The key itself, so it can be downcast to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<K: Key> KeyDescription for K {
+    fn key_type_name(&self) -> &'static str {
+        K::key_type_name()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
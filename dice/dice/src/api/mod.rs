@@ -10,17 +10,33 @@
 //! Public DICE API
 
 pub mod activation_tracker;
+pub mod checkpoint;
 pub mod computations;
 pub mod cycles;
 pub mod data;
 pub mod dice;
 pub mod error;
 pub mod events;
+pub mod evict;
+pub mod executor;
+pub mod file_dependency;
+pub mod graph_stats;
+pub mod history;
 pub mod injected;
 pub mod key;
 pub mod opaque;
+pub mod persist;
+pub mod priority;
+pub mod progress;
 pub mod projection;
+pub mod replay;
+pub mod resource_class;
+pub mod resource_usage;
+pub mod retry;
+pub mod span_tree;
+pub mod spill;
 pub mod storage_type;
 pub mod transaction;
 pub mod user_data;
+pub mod validation;
 pub mod which;
@@ -0,0 +1,38 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Persisting computed values across DICE instances, e.g. to keep a warm cache across daemon
+//! restarts.
+
+use thiserror::Error;
+
+use crate::api::key::Key;
+
+/// A `Key` whose values can be written out by `Dice::persist_cache` and read back by
+/// `Dice::load_cache`. Keys that don't implement this are never persisted.
+///
+/// Values are persisted together with their deps, so a loaded value is recomputed once any of
+/// them is invalidated. Only values whose deps are all persisted keys of the same type can be
+/// restored like that, so values depending on any other key are not persisted.
+pub trait PersistableKey: Key {
+    /// Serializes this key together with its value.
+    fn to_bytes(&self, value: &Self::Value) -> Vec<u8>;
+
+    /// Deserializes a key and value previously written by `to_bytes`. Returns `None` if the
+    /// bytes can't be read, e.g. because they were written by an incompatible version.
+    fn from_bytes(bytes: &[u8]) -> Option<(Self, Self::Value)>;
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum PersistCacheError {
+    #[error("Persisting the cache is not supported by legacy DICE")]
+    Unsupported,
+    #[error("Invalid persisted cache entry for key type `{0}`")]
+    InvalidEntry(&'static str),
+}
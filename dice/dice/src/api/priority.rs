@@ -0,0 +1,274 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Pausing background computations while interactive ones are running.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Duration;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use parking_lot::Mutex;
+use tokio::time::Sleep;
+
+use crate::HashMap;
+
+/// The priority of the computations of a transaction, set in its `UserComputationData`. It only
+/// matters if DICE was built with a `DicePreemption`.
+#[derive(Clone, Copy, Dupe, Debug, Default, PartialEq, Eq, Hash, Allocative)]
+pub enum DicePriority {
+    /// Computations someone is waiting for.
+    #[default]
+    Interactive,
+    /// Computations nobody is waiting for yet, e.g. warming the graph. They are paused while
+    /// interactive computations are running.
+    Background,
+}
+
+/// Makes background computations yield to interactive ones, see `DicePriority`.
+///
+/// To use it, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(DicePreemption::new(Duration::from_millis(100)));
+/// ```
+///
+/// Background computations are paused at their next await point whenever interactive
+/// computations are running, and resume once none are. A background computation that an
+/// interactive computation waits for becomes interactive, along with the computations it waits
+/// for, so it isn't paused while it is holding up interactive work. A paused computation still
+/// makes progress once every `max_pause` regardless, so that interactive work can't starve it.
+#[derive(Clone, Dupe, Allocative)]
+pub struct DicePreemption(#[allocative(skip)] Arc<DicePreemptionState>);
+
+struct DicePreemptionState {
+    max_pause: Duration,
+    /// The number of interactive computations which were spawned or raised to interactive and
+    /// have not finished.
+    interactive: AtomicUsize,
+    /// The waker of each paused background computation by its id, woken when there are no
+    /// interactive computations.
+    paused: Mutex<HashMap<usize, Waker>>,
+    next_id: AtomicUsize,
+}
+
+impl DicePreemptionState {
+    fn finish_interactive(&self) {
+        if self.interactive.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let paused = std::mem::take(&mut *self.paused.lock());
+            for waker in paused.into_values() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+const BACKGROUND: u8 = 0;
+const INTERACTIVE: u8 = 1;
+const DONE: u8 = 2;
+
+thread_local! {
+    /// The computation being polled on this thread, if it is scheduled by a `DicePreemption`.
+    static CURRENT: RefCell<Option<PreemptionTask>> = RefCell::new(None);
+}
+
+impl DicePreemption {
+    pub fn new(max_pause: Duration) -> Self {
+        Self(Arc::new(DicePreemptionState {
+            max_pause,
+            interactive: AtomicUsize::new(0),
+            paused: Mutex::new(HashMap::default()),
+            next_id: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Schedules a computation spawned by a transaction with `priority`. Computations spawned by
+    /// an interactive computation are interactive too, whatever their transaction.
+    pub(crate) fn task(&self, priority: DicePriority) -> PreemptionTask {
+        let interactive = CURRENT.with(|current| match &*current.borrow() {
+            Some(spawner) => spawner.is_interactive(),
+            None => priority == DicePriority::Interactive,
+        });
+        let priority = if interactive {
+            self.0.interactive.fetch_add(1, Ordering::SeqCst);
+            INTERACTIVE
+        } else {
+            BACKGROUND
+        };
+        PreemptionTask(Arc::new(PreemptionTaskData {
+            state: self.0.dupe(),
+            id: self.0.next_id.fetch_add(1, Ordering::Relaxed),
+            priority: AtomicU8::new(priority),
+            deps: Mutex::new(Vec::new()),
+        }))
+    }
+}
+
+/// A computation scheduled by a `DicePreemption`, shared by the computations waiting for it.
+#[derive(Clone, Dupe)]
+pub(crate) struct PreemptionTask(Arc<PreemptionTaskData>);
+
+struct PreemptionTaskData {
+    state: Arc<DicePreemptionState>,
+    id: usize,
+    /// `BACKGROUND`, `INTERACTIVE`, or `DONE` once the computation is dropped.
+    priority: AtomicU8,
+    /// The background computations this one waits for, which become interactive if this one does.
+    deps: Mutex<Vec<PreemptionTask>>,
+}
+
+impl PreemptionTaskData {
+    fn should_pause(&self) -> bool {
+        self.priority.load(Ordering::SeqCst) == BACKGROUND
+            && self.state.interactive.load(Ordering::SeqCst) > 0
+    }
+}
+
+impl Drop for PreemptionTaskData {
+    fn drop(&mut self) {
+        // The computation was never scheduled, e.g. it was cancelled before it was first polled.
+        if *self.priority.get_mut() == INTERACTIVE {
+            self.state.finish_interactive();
+        }
+    }
+}
+
+impl PreemptionTask {
+    /// Wraps the future of the computation.
+    pub(crate) fn schedule<F: Future>(&self, fut: F) -> Preemptible<F> {
+        Preemptible {
+            fut: Box::pin(fut),
+            task: self.dupe(),
+            pause: None,
+        }
+    }
+
+    /// Records that the computation being polled on this thread, or a transaction with
+    /// `priority` if none is, waits for this computation, so that this one inherits its priority.
+    pub(crate) fn waited_on(&self, priority: DicePriority) {
+        CURRENT.with(|current| match &*current.borrow() {
+            Some(waiter) => {
+                if !waiter.is_interactive() {
+                    waiter.0.deps.lock().push(self.dupe());
+                }
+                // The waiter may have become interactive before this was recorded.
+                if waiter.is_interactive() {
+                    self.raise();
+                }
+            }
+            None => {
+                if priority == DicePriority::Interactive {
+                    self.raise();
+                }
+            }
+        })
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.0.priority.load(Ordering::SeqCst) == INTERACTIVE
+    }
+
+    /// Makes this computation and the computations it waits for interactive.
+    fn raise(&self) {
+        let state = &self.0.state;
+        // Counted first, so that the count never goes below the interactive computations.
+        state.interactive.fetch_add(1, Ordering::SeqCst);
+        if self
+            .0
+            .priority
+            .compare_exchange(BACKGROUND, INTERACTIVE, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            // Already interactive, or finished.
+            state.finish_interactive();
+            return;
+        }
+        if let Some(waker) = state.paused.lock().remove(&self.0.id) {
+            waker.wake();
+        }
+        for dep in std::mem::take(&mut *self.0.deps.lock()) {
+            dep.raise();
+        }
+    }
+}
+
+/// Sets the computation being polled on this thread, restoring the previous one when dropped.
+struct CurrentTask(Option<PreemptionTask>);
+
+impl CurrentTask {
+    fn enter(task: PreemptionTask) -> Self {
+        Self(CURRENT.with(|current| current.replace(Some(task))))
+    }
+}
+
+impl Drop for CurrentTask {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+pub(crate) struct Preemptible<F> {
+    fut: Pin<Box<F>>,
+    task: PreemptionTask,
+    /// The timer bounding the current pause, if the computation is paused.
+    pause: Option<Pin<Box<Sleep>>>,
+}
+
+impl<F: Future> Future for Preemptible<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let task = &this.task.0;
+        if task.should_pause() {
+            let max_pause = task.state.max_pause;
+            let timer = this
+                .pause
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(max_pause)));
+            if timer.as_mut().poll(cx).is_pending() {
+                // Replaces the waker of the previous poll, if any.
+                task.state.paused.lock().insert(task.id, cx.waker().clone());
+                // The last interactive computation may have finished, or this computation may
+                // have become interactive, before the waker was registered.
+                if task.should_pause() {
+                    return Poll::Pending;
+                }
+            }
+        }
+        if this.pause.take().is_some() {
+            task.state.paused.lock().remove(&task.id);
+        }
+
+        let _current = CurrentTask::enter(this.task.dupe());
+        this.fut.as_mut().poll(cx)
+    }
+}
+
+impl<F> Drop for Preemptible<F> {
+    fn drop(&mut self) {
+        let task = &self.task.0;
+        if task.priority.swap(DONE, Ordering::SeqCst) == INTERACTIVE {
+            task.state.finish_interactive();
+        }
+        if self.pause.is_some() {
+            task.state.paused.lock().remove(&task.id);
+        }
+        task.deps.lock().clear();
+    }
+}
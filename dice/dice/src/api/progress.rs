@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Intermediate progress reported by computations, e.g. for driving progress bars.
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+
+use crate::api::key::Key;
+
+type ErasedProgress = Arc<dyn Any + Send + Sync>;
+
+/// Per transaction subscriptions to the progress of keys, streamed by
+/// `DiceComputations::compute_with_progress`.
+///
+/// Progress is not part of the dice graph, so reporting it never records dependencies.
+#[derive(Default)]
+pub struct DiceProgress {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+struct Subscriber {
+    /// Whether this subscribes to the progress of the given key.
+    matches: Box<dyn Fn(&dyn Any) -> bool + Send + Sync>,
+    tx: mpsc::UnboundedSender<ErasedProgress>,
+}
+
+impl DiceProgress {
+    /// Where to report the progress of a computation of `key` that is starting. Only the
+    /// subscribers to `key` at this point receive the progress of this computation.
+    pub(crate) fn for_key(&self, key: &dyn Any) -> KeyProgress {
+        let mut subscribers = self.subscribers.lock();
+        if subscribers.is_empty() {
+            return KeyProgress::default();
+        }
+        subscribers.retain(|subscriber| !subscriber.tx.is_closed());
+        KeyProgress(
+            subscribers
+                .iter()
+                .filter(|subscriber| (subscriber.matches)(key))
+                .map(|subscriber| subscriber.tx.clone())
+                .collect(),
+        )
+    }
+
+    pub(crate) fn subscribe<K, P>(&self, key: &K) -> ProgressReceiver<P>
+    where
+        K: Key,
+        P: Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let key = key.clone();
+        self.subscribers.lock().push(Subscriber {
+            matches: Box::new(move |other| other.downcast_ref::<K>() == Some(&key)),
+            tx,
+        });
+        ProgressReceiver {
+            rx,
+            _progress: PhantomData,
+        }
+    }
+}
+
+/// The subscribers to the progress of a single computation of a key.
+#[derive(Default)]
+pub(crate) struct KeyProgress(Vec<mpsc::UnboundedSender<ErasedProgress>>);
+
+impl KeyProgress {
+    pub(crate) fn report<P: Send + Sync + 'static>(&self, progress: P) {
+        if self.0.is_empty() {
+            return;
+        }
+        let progress: ErasedProgress = Arc::new(progress);
+        for tx in &self.0 {
+            // ignore error if the stream was dropped.
+            let _ = tx.send(progress.clone());
+        }
+    }
+}
+
+/// Receives the progress events of type `P`, skipping events of any other type.
+pub(crate) struct ProgressReceiver<P> {
+    rx: mpsc::UnboundedReceiver<ErasedProgress>,
+    _progress: PhantomData<fn() -> P>,
+}
+
+impl<P: Send + Sync + 'static> ProgressReceiver<P> {
+    pub(crate) async fn recv(&mut self) -> Option<Arc<P>> {
+        loop {
+            if let Ok(progress) = self.rx.recv().await?.downcast() {
+                return Some(progress);
+            }
+        }
+    }
+
+    pub(crate) fn try_recv(&mut self) -> Option<Arc<P>> {
+        loop {
+            if let Ok(progress) = self.rx.try_recv().ok()?.downcast() {
+                return Some(progress);
+            }
+        }
+    }
+}
+
+/// An item of the stream returned by `DiceComputations::compute_with_progress`.
+#[derive(Debug)]
+pub enum ComputeProgress<P, V> {
+    /// Progress reported while the key was being computed.
+    Progress(Arc<P>),
+    /// The result of the computation. This is always the last item.
+    Done(V),
+}
@@ -9,11 +9,15 @@
 
 //! "Projection" keys: synchronously computed keys from "opaque" values.
 
+use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::hash::Hasher;
+use std::marker::PhantomData;
 
 use allocative::Allocative;
+use allocative::Visitor;
 use dupe::Dupe;
 
 use crate::api::data::DiceData;
@@ -62,6 +66,93 @@ pub trait ProjectionKey:
     }
 }
 
+/// Projection key backing `DiceComputations::compute_mapped`.
+///
+/// `F` is required to be zero-sized, so all the keys of the same type are equal: the type of `F`
+/// identifies the function.
+pub(crate) struct MappedProjectionKey<K, F, T> {
+    f: F,
+    _marker: PhantomData<fn(K) -> T>,
+}
+
+impl<K, F, T> MappedProjectionKey<K, F, T>
+where
+    K: Key,
+    F: Fn(&K::Value) -> T,
+{
+    /// Fails to compile (at monomorphization) when `F` captures anything.
+    const ZERO_SIZED: () = assert!(
+        std::mem::size_of::<F>() == 0,
+        "mapping function must not capture anything"
+    );
+
+    pub(crate) fn new(f: F) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ZERO_SIZED;
+        Self {
+            f,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, F: Copy, T> Clone for MappedProjectionKey<K, F, T> {
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, F, T> PartialEq for MappedProjectionKey<K, F, T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<K, F, T> Eq for MappedProjectionKey<K, F, T> {}
+
+impl<K, F, T> Hash for MappedProjectionKey<K, F, T> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl<K, F, T> Display for MappedProjectionKey<K, F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mapped({})", std::any::type_name::<F>())
+    }
+}
+
+impl<K, F, T> Debug for MappedProjectionKey<K, F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<K, F, T> Allocative for MappedProjectionKey<K, F, T> {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut Visitor<'b>) {
+        visitor.enter_self_sized::<Self>().exit();
+    }
+}
+
+impl<K, F, T> ProjectionKey for MappedProjectionKey<K, F, T>
+where
+    K: Key,
+    F: Fn(&K::Value) -> T + Copy + Send + Sync + 'static,
+    T: Allocative + Dupe + Eq + Send + Sync + 'static,
+{
+    type DeriveFromKey = K;
+    type Value = T;
+
+    fn compute(&self, derive_from: &K::Value, _ctx: &DiceProjectionComputations) -> T {
+        (self.f)(derive_from)
+    }
+
+    fn equality(x: &T, y: &T) -> bool {
+        x == y
+    }
+}
+
 /// Context for projection key computation.
 ///
 /// Only provide access to globals.
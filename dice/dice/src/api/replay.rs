@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Recording the computations triggered by root computations, to check that they are
+//! deterministic across runs.
+
+use parking_lot::Mutex;
+use thiserror::Error;
+
+use crate::HashMap;
+use crate::HashSet;
+
+/// Records which keys were computed on behalf of each root computation of a transaction, i.e.
+/// each key requested directly from the transaction.
+///
+/// To record a transaction, set it in the `UserComputationData` of the transaction:
+///
+/// ```ignore
+/// let log = Arc::new(ReplayLog::new());
+/// let ctx = dice
+///     .updater_with_data(UserComputationData {
+///         replay_log: Some(log.dupe()),
+///         ..Default::default()
+///     })
+///     .commit()
+///     .await;
+/// ```
+///
+/// Keys are identified by their `Display`, so logs of different runs can be compared with
+/// `ReplayLog::verify`.
+#[derive(Default)]
+pub struct ReplayLog {
+    state: Mutex<ReplayLogState>,
+}
+
+#[derive(Default)]
+struct ReplayLogState {
+    /// Keys requested directly from the transaction, in request order.
+    roots: Vec<String>,
+    /// Keys requested by the computation of each key, in request order.
+    requests: HashMap<String, Vec<String>>,
+    /// Keys which were computed rather than reused.
+    computed: HashSet<String>,
+}
+
+/// The keys computed on behalf of a root computation, see `ReplayLog::entries`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayEntry {
+    pub root: String,
+    pub computed: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("Replay diverged at root computation {index}: expected {expected:?}, but got {actual:?}")]
+pub struct ReplayMismatch {
+    pub index: usize,
+    pub expected: Option<ReplayEntry>,
+    pub actual: Option<ReplayEntry>,
+}
+
+impl ReplayLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_root(&self, key: String) {
+        self.state.lock().roots.push(key);
+    }
+
+    pub(crate) fn record_request(&self, parent: String, key: String) {
+        self.state
+            .lock()
+            .requests
+            .entry(parent)
+            .or_default()
+            .push(key);
+    }
+
+    pub(crate) fn record_computed(&self, key: String) {
+        self.state.lock().computed.insert(key);
+    }
+
+    /// The root computations in the order they were requested, each with the keys computed
+    /// during the transaction which it transitively requested, in depth-first request order.
+    ///
+    /// The order of the keys is deterministic as long as each computation requests its
+    /// dependencies in a deterministic order. Dependencies requested concurrently, e.g. with
+    /// `compute_many`, are recorded in the order the requests were made.
+    pub fn entries(&self) -> Vec<ReplayEntry> {
+        fn visit(
+            key: &str,
+            state: &ReplayLogState,
+            visited: &mut HashSet<String>,
+            computed: &mut Vec<String>,
+        ) {
+            for dep in state.requests.get(key).into_iter().flatten() {
+                if state.computed.contains(dep) && visited.insert(dep.clone()) {
+                    computed.push(dep.clone());
+                    visit(dep, state, visited, computed);
+                }
+            }
+        }
+
+        let state = self.state.lock();
+        state
+            .roots
+            .iter()
+            .map(|root| {
+                let mut computed = Vec::new();
+                visit(root, &state, &mut HashSet::default(), &mut computed);
+                ReplayEntry {
+                    root: root.clone(),
+                    computed,
+                }
+            })
+            .collect()
+    }
+
+    /// Checks that the same computations were recorded as in `expected`, typically the
+    /// `entries` of a previous run, and returns the first root computation that differs.
+    pub fn verify(&self, expected: &[ReplayEntry]) -> Result<(), ReplayMismatch> {
+        let actual = self.entries();
+        for index in 0..std::cmp::max(expected.len(), actual.len()) {
+            let (expected, actual) = (expected.get(index), actual.get(index));
+            if expected != actual {
+                return Err(ReplayMismatch {
+                    index,
+                    expected: expected.cloned(),
+                    actual: actual.cloned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
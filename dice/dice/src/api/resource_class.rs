@@ -0,0 +1,198 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Limiting how many computations contending on a resource run concurrently.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use derive_more::Display;
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+use parking_lot::Mutex;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+use crate::api::computations::DiceComputations;
+use crate::api::key::Key;
+
+/// A resource computations contend on, like network connections. See `Key::resource_class`.
+#[derive(Clone, Copy, Dupe, Debug, Display, Eq, Hash, PartialEq)]
+pub struct ResourceClass(pub &'static str);
+
+/// How many computations of each `ResourceClass` may run concurrently.
+///
+/// To enable limits, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(DiceResourceLimits::new().with_limit(ResourceClass("network"), 8));
+/// ```
+///
+/// Computations of a class without a limit, or of no class, are not limited. Each attempt of a
+/// computation (see `DiceRetryPolicy`) takes a permit of its class, which it gives up while it
+/// waits for the keys it requests, so keys of a limited class can depend on keys of the same class.
+#[derive(Clone, Default, Debug)]
+pub struct DiceResourceLimits {
+    semaphores: HashMap<ResourceClass, Arc<Semaphore>>,
+}
+
+thread_local! {
+    /// The permit of the computation being polled on this thread, if its class is limited.
+    static CURRENT: RefCell<Option<Arc<HeldPermit>>> = RefCell::new(None);
+}
+
+impl DiceResourceLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// At most `limit` computations of `class` run concurrently.
+    pub fn with_limit(mut self, class: ResourceClass, limit: usize) -> Self {
+        self.semaphores.insert(class, Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// Runs an attempt at computing `key` once a permit of its resource class is available, if
+    /// its class is limited by the limits set in the `DiceData` of `ctx`.
+    pub(crate) async fn compute<K: Key>(
+        key: &K,
+        ctx: &mut DiceComputations,
+        cancellations: &CancellationContext,
+    ) -> K::Value {
+        let semaphore = key.resource_class().and_then(|class| {
+            ctx.global_data()
+                .get::<DiceResourceLimits>()
+                .ok()
+                .and_then(|limits| limits.semaphores.get(&class).map(|s| s.dupe()))
+        });
+        match semaphore {
+            Some(semaphore) => {
+                let permit = semaphore
+                    .dupe()
+                    .acquire_owned()
+                    .await
+                    .expect("resource class semaphores are never closed");
+                HoldingPermit {
+                    fut: Box::pin(key.compute(ctx, cancellations)),
+                    held: Arc::new(HeldPermit {
+                        semaphore,
+                        permit: Mutex::new(Some(permit)),
+                        waiting: AtomicUsize::new(0),
+                    }),
+                }
+                .await
+            }
+            None => key.compute(ctx, cancellations).await,
+        }
+    }
+
+    /// Gives up the permit of the computation being polled, if any, while it waits for `fut`,
+    /// the request of another key. It must be called where the request is made, so that the
+    /// requesting computation is the one being polled.
+    pub(crate) fn release_while<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+        let held = CURRENT.with(|current| current.borrow().dupe());
+        async move {
+            match held {
+                Some(held) => {
+                    let waiting = held.wait();
+                    let output = fut.await;
+                    drop(waiting);
+                    held.reacquire().await;
+                    output
+                }
+                None => fut.await,
+            }
+        }
+    }
+}
+
+/// The permit of a computation of a limited class.
+struct HeldPermit {
+    semaphore: Arc<Semaphore>,
+    /// `None` while the computation waits for the keys it requested.
+    permit: Mutex<Option<OwnedSemaphorePermit>>,
+    /// The number of requests the computation is waiting for.
+    waiting: AtomicUsize,
+}
+
+impl HeldPermit {
+    fn wait(self: &Arc<Self>) -> Waiting {
+        if self.waiting.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.permit.lock().take();
+        }
+        Waiting(self.dupe())
+    }
+
+    /// Takes the permit back, unless the computation is still waiting for other requests. If a
+    /// request is cancelled instead, the permit is taken back after the next one.
+    async fn reacquire(&self) {
+        if self.waiting.load(Ordering::SeqCst) > 0 || self.permit.lock().is_some() {
+            return;
+        }
+        let permit = self
+            .semaphore
+            .dupe()
+            .acquire_owned()
+            .await
+            .expect("resource class semaphores are never closed");
+        // Another request may have been made, or its result taken the permit back, meanwhile.
+        if self.waiting.load(Ordering::SeqCst) == 0 {
+            self.permit.lock().get_or_insert(permit);
+        }
+    }
+}
+
+/// Counts a request the computation is waiting for.
+struct Waiting(Arc<HeldPermit>);
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Makes the permit of a computation the current one while it is polled.
+struct HoldingPermit<F> {
+    fut: Pin<Box<F>>,
+    held: Arc<HeldPermit>,
+}
+
+impl<F: Future> Future for HoldingPermit<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let _current = CurrentPermit::enter(this.held.dupe());
+        this.fut.as_mut().poll(cx)
+    }
+}
+
+/// Sets the permit of the computation being polled on this thread, restoring the previous one
+/// when dropped.
+struct CurrentPermit(Option<Arc<HeldPermit>>);
+
+impl CurrentPermit {
+    fn enter(held: Arc<HeldPermit>) -> Self {
+        Self(CURRENT.with(|current| current.replace(Some(held))))
+    }
+}
+
+impl Drop for CurrentPermit {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Attributing the computations of a shared DICE to the requests which triggered them.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+use parking_lot::Mutex;
+
+use crate::api::checkpoint::CheckpointConfig;
+use crate::api::computations::DiceComputations;
+use crate::api::key::Key;
+
+/// The computations a request triggered, see `DiceComputations::request_resource_report`.
+#[derive(Clone, Copy, Dupe, Debug, Default, Eq, PartialEq)]
+pub struct RequestResourceReport {
+    /// The number of keys computed rather than reused from the cache.
+    pub computations: u64,
+    /// The time spent computing them. This excludes the time computations spent waiting for the
+    /// keys they requested, so the time of nested computations is only counted once.
+    pub compute_time: Duration,
+}
+
+/// Accumulates the computations triggered by the request of a `UserComputationData`. Computations
+/// whose values are reused by other requests are only attributed to the request that triggered
+/// them.
+#[derive(Default)]
+pub struct RequestResourceUsage {
+    computations: AtomicU64,
+    compute_nanos: AtomicU64,
+}
+
+impl RequestResourceUsage {
+    /// The totals accumulated so far.
+    pub fn report(&self) -> RequestResourceReport {
+        RequestResourceReport {
+            computations: self.computations.load(Ordering::Relaxed),
+            compute_time: Duration::from_nanos(self.compute_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Computes `key`, attributing the computation to the request of `ctx`.
+    pub(crate) async fn compute<K: Key>(
+        key: &K,
+        ctx: &mut DiceComputations,
+        cancellations: &CancellationContext,
+    ) -> K::Value {
+        let timer = Arc::new(Mutex::new(WaitTimer::default()));
+        let start = Instant::now();
+        let value = Timed {
+            fut: Box::pin(CheckpointConfig::compute(key, ctx, cancellations)),
+            timer: timer.dupe(),
+        }
+        .await;
+        let elapsed = start.elapsed().saturating_sub(timer.lock().waited);
+
+        let usage = &ctx.per_transaction_data().resource_usage;
+        usage.computations.fetch_add(1, Ordering::Relaxed);
+        usage
+            .compute_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        value
+    }
+
+    /// Excludes the time the computation being polled waits for `fut`, the request of another
+    /// key, from its compute time. It must be called where the request is made, so that the
+    /// requesting computation is the one being polled.
+    pub(crate) fn exclude_while<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+        let timer = CURRENT.with(|current| current.borrow().dupe());
+        async move {
+            let _waiting = timer.map(Waiting::start);
+            fut.await
+        }
+    }
+}
+
+thread_local! {
+    /// The timer of the computation being polled on this thread.
+    static CURRENT: RefCell<Option<Arc<Mutex<WaitTimer>>>> = RefCell::new(None);
+}
+
+/// The time a computation spent waiting for the keys it requested.
+#[derive(Default)]
+struct WaitTimer {
+    /// The number of requests the computation is waiting for.
+    waiting: usize,
+    /// When the computation started waiting for them.
+    since: Option<Instant>,
+    waited: Duration,
+}
+
+/// Counts a request the computation is waiting for.
+struct Waiting(Arc<Mutex<WaitTimer>>);
+
+impl Waiting {
+    fn start(timer: Arc<Mutex<WaitTimer>>) -> Self {
+        let mut state = timer.lock();
+        state.waiting += 1;
+        if state.waiting == 1 {
+            state.since = Some(Instant::now());
+        }
+        drop(state);
+        Self(timer)
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.waiting -= 1;
+        if state.waiting == 0 {
+            if let Some(since) = state.since.take() {
+                state.waited += since.elapsed();
+            }
+        }
+    }
+}
+
+/// Makes the timer of a computation the current one while it is polled.
+struct Timed<F> {
+    fut: Pin<Box<F>>,
+    timer: Arc<Mutex<WaitTimer>>,
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let _current = CurrentTimer::enter(this.timer.dupe());
+        this.fut.as_mut().poll(cx)
+    }
+}
+
+/// Sets the timer of the computation being polled on this thread, restoring the previous one
+/// when dropped.
+struct CurrentTimer(Option<Arc<Mutex<WaitTimer>>>);
+
+impl CurrentTimer {
+    fn enter(timer: Arc<Mutex<WaitTimer>>) -> Self {
+        Self(CURRENT.with(|current| current.replace(Some(timer))))
+    }
+}
+
+impl Drop for CurrentTimer {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
@@ -0,0 +1,75 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Retrying computations which fail with transient errors.
+
+use std::time::Duration;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+
+use crate::api::computations::DiceComputations;
+use crate::api::key::Key;
+use crate::api::resource_class::DiceResourceLimits;
+
+/// How often, and how fast, DICE retries computations whose value is a transient error, see
+/// `Key::is_transient_error`.
+///
+/// To enable retries, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(DiceRetryPolicy::new(3, Duration::from_millis(100)));
+/// ```
+///
+/// A failed computation is retried after `backoff`, and the wait doubles on each subsequent
+/// retry. It doesn't hold the permit of its resource class while it waits, see
+/// `DiceResourceLimits`. Once `max_retries` retries failed, the last error is the value of the key.
+/// Without a policy, computations are never retried.
+#[derive(Clone, Copy, Dupe, Debug, Allocative)]
+pub struct DiceRetryPolicy {
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl DiceRetryPolicy {
+    pub fn new(max_retries: usize, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+        }
+    }
+
+    /// Computes `key` with the policy set in the `DiceData` of `ctx`, if any.
+    pub(crate) async fn compute<K: Key>(
+        key: &K,
+        ctx: &mut DiceComputations,
+        cancellations: &CancellationContext,
+    ) -> K::Value {
+        let policy = match ctx.global_data().get::<DiceRetryPolicy>() {
+            Ok(policy) => *policy,
+            Err(_) => return DiceResourceLimits::compute(key, ctx, cancellations).await,
+        };
+
+        let mut backoff = policy.backoff;
+        let mut retries = 0;
+        loop {
+            // Dependencies requested by failed attempts stay recorded, which at worst causes
+            // unnecessary recomputes.
+            let value = DiceResourceLimits::compute(key, ctx, cancellations).await;
+            if retries == policy.max_retries || !K::is_transient_error(&value) {
+                return value;
+            }
+            retries += 1;
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+}
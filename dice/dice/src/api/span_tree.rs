@@ -0,0 +1,164 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Recording the nested timings of the computations of a transaction, e.g. to render them as a
+//! flamegraph.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::HashMap;
+use crate::HashSet;
+
+/// Records how long each key took to compute, and which keys each computation requested, so the
+/// time of each root computation can be broken down into a tree of its sub-computations.
+///
+/// To record a transaction, set it in the `UserComputationData` of the transaction:
+///
+/// ```ignore
+/// let recorder = Arc::new(SpanTreeRecorder::new());
+/// let ctx = dice
+///     .updater_with_data(UserComputationData {
+///         span_tree: Some(recorder.dupe()),
+///         ..Default::default()
+///     })
+///     .commit()
+///     .await;
+/// ```
+///
+/// Keys are identified by their `Display`.
+#[derive(Default)]
+pub struct SpanTreeRecorder {
+    state: Mutex<SpanTreeState>,
+}
+
+#[derive(Default)]
+struct SpanTreeState {
+    /// Keys requested directly from the transaction, in request order.
+    roots: Vec<String>,
+    /// Keys requested by the computation of each key, in request order.
+    requests: HashMap<String, Vec<String>>,
+    /// The time spent computing each key which was computed rather than reused, including the
+    /// time spent waiting for the keys it requested.
+    durations: HashMap<String, Duration>,
+}
+
+/// The timing of the computation of a key, and of the sub-computations it caused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanTree {
+    pub key: String,
+    /// The time spent computing `key`, including its children.
+    pub total_time: Duration,
+    /// The time spent computing `key` outside of its children. Children computed concurrently
+    /// may add up to more than `total_time`, in which case this is zero.
+    pub self_time: Duration,
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTreeRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_root(&self, key: String) {
+        self.state.lock().roots.push(key);
+    }
+
+    pub(crate) fn record_request(&self, parent: String, key: String) {
+        self.state
+            .lock()
+            .requests
+            .entry(parent)
+            .or_default()
+            .push(key);
+    }
+
+    pub(crate) fn record_computed(&self, key: String, duration: Duration) {
+        self.state.lock().durations.insert(key, duration);
+    }
+
+    /// The trees of the root computations which were computed in the transaction, in the order
+    /// they were requested.
+    ///
+    /// A key requested by several computations only appears under the first of them in
+    /// depth-first request order, like it is only computed once. Keys which were reused rather
+    /// than computed are omitted.
+    pub fn trees(&self) -> Vec<SpanTree> {
+        fn build(
+            key: &str,
+            total_time: Duration,
+            state: &SpanTreeState,
+            visited: &mut HashSet<String>,
+        ) -> SpanTree {
+            let mut children = Vec::new();
+            for dep in state.requests.get(key).into_iter().flatten() {
+                if let Some(duration) = state.durations.get(dep) {
+                    if visited.insert(dep.clone()) {
+                        children.push(build(dep, *duration, state, visited));
+                    }
+                }
+            }
+            let children_time = children.iter().map(|c| c.total_time).sum();
+            SpanTree {
+                key: key.to_owned(),
+                total_time,
+                self_time: total_time.saturating_sub(children_time),
+                children,
+            }
+        }
+
+        let state = self.state.lock();
+        let mut visited = HashSet::default();
+        let mut trees = Vec::new();
+        for root in &state.roots {
+            if let Some(duration) = state.durations.get(root) {
+                if visited.insert(root.clone()) {
+                    trees.push(build(root, *duration, &state, &mut visited));
+                }
+            }
+        }
+        trees
+    }
+
+    /// Renders all the recorded trees as folded stacks, see `SpanTree::folded_stacks`.
+    pub fn folded_stacks(&self) -> String {
+        let mut out = String::new();
+        for tree in self.trees() {
+            tree.write_folded_stacks("", &mut out);
+        }
+        out
+    }
+}
+
+impl SpanTree {
+    /// Renders this tree in the folded stacks format taken by flamegraph tools: one line per
+    /// node, with the keys from the root to the node separated by `;`, followed by the self time
+    /// of the node in microseconds.
+    ///
+    /// Keys are not escaped, so keys whose `Display` contains `;` will show up as several frames.
+    pub fn folded_stacks(&self) -> String {
+        let mut out = String::new();
+        self.write_folded_stacks("", &mut out);
+        out
+    }
+
+    fn write_folded_stacks(&self, prefix: &str, out: &mut String) {
+        let stack = if prefix.is_empty() {
+            self.key.clone()
+        } else {
+            format!("{};{}", prefix, self.key)
+        };
+        writeln!(out, "{} {}", stack, self.self_time.as_micros()).unwrap();
+        for child in &self.children {
+            child.write_folded_stacks(&stack, out);
+        }
+    }
+}
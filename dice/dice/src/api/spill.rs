@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Spilling cached values to disk to bound the memory used by the DICE cache.
+
+use std::path::PathBuf;
+
+/// How values of a key are written to disk when spilled, and read back. See `Key::spill_codec`.
+pub struct SpillCodec<V> {
+    pub serialize: fn(&V) -> Vec<u8>,
+    /// Returns `None` if the bytes can't be read.
+    pub deserialize: fn(&[u8]) -> Option<V>,
+}
+
+/// Enables spilling of the values of keys that provide a `Key::spill_codec`.
+///
+/// Once the spillable values held by the cache take more than `memory_limit` bytes (as measured
+/// by `Allocative`), the least recently used ones are written to files in `dir` and dropped from
+/// memory. A spilled value is transparently reloaded the next time its key is requested, and
+/// recomputed if it can't be. Each DICE instance writes to its own directory in `dir`, which is
+/// removed when it is dropped.
+///
+/// To enable spilling, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(SpillConfig::new(spill_dir, 1 << 30));
+/// ```
+///
+/// Spilling only drops the copy of the value held by the cache: values still referenced by
+/// ongoing computations stay in memory until they are done with them. Only the modern engine
+/// spills values.
+#[derive(Clone, Debug)]
+pub struct SpillConfig {
+    pub(crate) dir: PathBuf,
+    pub(crate) memory_limit: usize,
+}
+
+impl SpillConfig {
+    pub fn new(dir: impl Into<PathBuf>, memory_limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            memory_limit,
+        }
+    }
+}
@@ -7,10 +7,14 @@
  * of this source tree.
  */
 
+use std::cmp;
+
 use allocative::Allocative;
 use dupe::Dupe;
 use gazebo::variants::UnpackVariants;
 
+use crate::api::key::Key;
+
 /// Storage type for a cached entry.
 /// The oldest entry will be evicted once the cache stores more than N entries of the same key
 /// request to compute them. TODO think about whether or not we can
@@ -19,3 +23,19 @@ use gazebo::variants::UnpackVariants;
 pub enum StorageType {
     LastN(usize),
 }
+
+/// Minimum number of entries retained for keys that are pinned with `Key::pin`.
+const PINNED_KEY_NUM_TO_KEEP: usize = 4;
+
+impl StorageType {
+    /// The storage type DICE uses for the given key, taking `Key::pin` into account.
+    pub(crate) fn for_key<K: Key>() -> StorageType {
+        let storage_type = K::storage_type();
+        if K::pin() {
+            let StorageType::LastN(num_to_keep) = storage_type;
+            StorageType::LastN(cmp::max(num_to_keep, PINNED_KEY_NUM_TO_KEEP))
+        } else {
+            storage_type
+        }
+    }
+}
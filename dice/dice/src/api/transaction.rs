@@ -7,20 +7,30 @@
  * of this source tree.
  */
 
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
 use std::future::Future;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use dupe::Dupe;
+use parking_lot::Mutex;
 
 use crate::api::computations::DiceComputations;
 use crate::api::error::DiceResult;
+use crate::api::file_dependency::FileDependencyKey;
+use crate::api::key::ContentKey;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
 use crate::api::user_data::UserComputationData;
 use crate::transaction::DiceTransactionImpl;
 use crate::transaction_update::DiceTransactionUpdaterImpl;
 use crate::versions::VersionNumber;
+use crate::HashSet;
 
 /// The struct for which we build transactions. This is where changes are recorded, and committed
 /// to DICE, which returns the Transaction where we spawn computations.
@@ -58,11 +68,60 @@ impl DiceTransactionUpdater {
         self.0.changed_to(changed)
     }
 
+    /// Records the files at `paths` as changed, so that the computations which read them (see
+    /// `DiceComputations::record_file_dependency`), and any dependents will be recomputed. This is
+    /// meant to be called by the embedder's file watcher.
+    ///
+    /// Like with `invalidate_matching`, only the files known to the graph when this is committed
+    /// are recorded, so that files nothing read don't grow the graph.
+    pub fn files_changed(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        let changed = paths
+            .into_iter()
+            .map(FileDependencyKey::new)
+            .collect::<HashSet<_>>();
+        self.0.invalidate_matching(move |key| {
+            key.as_any()
+                .downcast_ref::<FileDependencyKey>()
+                .map_or(false, |key| changed.contains(key))
+        })
+    }
+
+    /// Records all currently known `Key`s for which `pred` returns `true` as changed, so that
+    /// they, and any dependents will be recomputed. This is useful to reload all keys of a type
+    /// without enumerating them.
+    ///
+    /// Keys are selected when the changes are committed. Keys recorded via 'changed' or
+    /// 'changed_to' take precedence over this. `InjectedKey`s are never selected, since they
+    /// cannot be recomputed.
+    pub fn invalidate_matching<F>(&mut self, pred: F)
+    where
+        F: Fn(&dyn KeyDescription) -> bool + Send + Sync + 'static,
+    {
+        self.0.invalidate_matching(pred)
+    }
+
+    /// Invalidates the value computed for `content_key` by keys of type `K` (see
+    /// `Key::content_key`). That value is otherwise reused across transactions regardless of
+    /// the changes to the graph, so this is the only way to make those keys compute it again.
+    ///
+    /// The value is dropped when this is committed, unless it was computed at the committed
+    /// version or later, and the keys with that content key are recorded as changed like with
+    /// `invalidate_matching`, so that their dependents are recomputed too. Transactions at earlier
+    /// versions, and dropping this updater without committing, keep reusing the value.
+    pub fn invalidate_content_key<K: Key>(&mut self, content_key: ContentKey) {
+        self.0.invalidate_content_key::<K>(content_key)
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version.
     pub fn commit(self) -> impl Future<Output = DiceTransaction> {
         self.0.commit()
     }
 
+    /// Same as `commit`, but also reports what the committed changes did to their keys.
+    pub fn commit_with_report(self) -> impl Future<Output = (DiceTransaction, CommitReport)> {
+        self.0.commit_with_report()
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version,
     /// replacing the user data with the given set
     pub fn commit_with_data(
@@ -77,6 +136,64 @@ impl DiceTransactionUpdater {
     }
 }
 
+/// What committing a set of changes did to the changed keys, as returned by
+/// `DiceTransactionUpdater::commit_with_report`. Keys are identified by their `Display`, and
+/// each list is sorted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitReport {
+    /// Keys that got a new value or were invalidated, so their dependents are invalidated.
+    pub changed: Vec<String>,
+    /// Keys that were changed to a value equal to their previous one, so nothing depending on
+    /// them is invalidated.
+    pub unchanged: Vec<String>,
+    /// Keys that had no previous state. The legacy engine does not distinguish these, and
+    /// reports them as `changed`.
+    pub new: Vec<String>,
+    /// Keys recomputed on the returned transaction that computed a value equal to their previous
+    /// one, so their dependents were not recomputed. This is filled in as computations run.
+    pub early_cutoff: EarlyCutoffs,
+}
+
+impl CommitReport {
+    pub(crate) fn sort(&mut self) {
+        self.changed.sort();
+        self.unchanged.sort();
+        self.new.sort();
+    }
+}
+
+/// The keys that stopped an invalidation at the early cutoff, recorded while computing on the
+/// transaction returned by `DiceTransactionUpdater::commit_with_report`.
+#[derive(Allocative, Clone, Dupe, Default)]
+pub struct EarlyCutoffs(#[allocative(skip)] Arc<Mutex<Vec<String>>>);
+
+impl EarlyCutoffs {
+    pub(crate) fn record(&self, key: &dyn Display) {
+        self.0.lock().push(key.to_string());
+    }
+
+    /// The keys recorded so far, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = self.0.lock().clone();
+        keys.sort();
+        keys
+    }
+}
+
+impl Debug for EarlyCutoffs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.keys()).finish()
+    }
+}
+
+impl PartialEq for EarlyCutoffs {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys() == other.keys()
+    }
+}
+
+impl Eq for EarlyCutoffs {}
+
 /// The base struct for which all computations start. This is clonable, and dupe, and can be
 /// moved to different runtimes to start computations.
 /// All computations on this transaction will see only changes at the most-up-to-date version at
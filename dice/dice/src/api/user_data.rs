@@ -8,16 +8,26 @@
  */
 
 use std::any::Any;
+use std::fmt::Display;
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
+use dupe::Dupe;
 use more_futures::spawner::Spawner;
 use more_futures::spawner::TokioSpawner;
+use once_cell::sync::Lazy;
 
 use crate::api::activation_tracker::ActivationTracker;
 use crate::api::data::DiceData;
 use crate::api::events::DiceEvent;
 use crate::api::events::DiceEventListener;
+use crate::api::graph_stats::TransactionGraphCounters;
+use crate::api::priority::DicePriority;
+use crate::api::progress::DiceProgress;
+use crate::api::replay::ReplayLog;
+use crate::api::resource_usage::RequestResourceUsage;
+use crate::api::span_tree::SpanTreeRecorder;
 
 /// Includes all user related computation-specific data.
 #[derive(Allocative)]
@@ -37,6 +47,30 @@ pub struct UserComputationData {
     #[allocative(skip)]
     pub activation_tracker: Option<Arc<dyn ActivationTracker>>,
 
+    /// Priority of the computations of the transaction, see `DicePreemption`.
+    pub priority: DicePriority,
+
+    /// Progress reported by computations, see `DiceComputations::compute_with_progress`.
+    #[allocative(skip)]
+    pub progress: DiceProgress,
+
+    /// If set, the computations of the transaction are recorded in it.
+    #[allocative(skip)]
+    pub replay_log: Option<Arc<ReplayLog>>,
+
+    /// If set, the timings of the computations of the transaction are recorded in it.
+    #[allocative(skip)]
+    pub span_tree: Option<Arc<SpanTreeRecorder>>,
+
+    /// The computations triggered by the transaction, see
+    /// `DiceComputations::request_resource_report`.
+    #[allocative(skip)]
+    pub resource_usage: RequestResourceUsage,
+
+    /// The graph built by the transaction, see `DiceComputations::transaction_graph_stats`.
+    #[allocative(skip)]
+    pub graph_stats: TransactionGraphCounters,
+
     /// We require that UserComputationData always be constructed with `..Default::default()`
     pub _requires_default: RequireDefault,
 }
@@ -66,10 +100,68 @@ pub trait UserCycleDetectorGuard: Send + Sync + 'static {
 #[derive(Allocative)]
 pub struct RequireDefault(());
 
+/// The spawner of `UserComputationData` unless the user sets one. It is shared, so that it can be
+/// told apart from the spawners set by users.
+static DEFAULT_SPAWNER: Lazy<Arc<dyn Spawner<UserComputationData>>> =
+    Lazy::new(|| Arc::new(TokioSpawner));
+
 impl UserComputationData {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Whether `spawner` is still the default one, rather than one set by the user.
+    pub(crate) fn has_default_spawner(&self) -> bool {
+        // Compare the data pointers only, vtables of the same type may differ.
+        Arc::as_ptr(&self.spawner) as *const () == Arc::as_ptr(&DEFAULT_SPAWNER) as *const ()
+    }
+
+    /// Whether the requests and computations of the transaction are recorded, by `replay_log` or
+    /// `span_tree`.
+    pub(crate) fn records_requests(&self) -> bool {
+        self.replay_log.is_some() || self.span_tree.is_some()
+    }
+
+    /// Records that `key` was requested by the computation of `parent`, or directly from the
+    /// transaction if `parent` is `None`.
+    pub(crate) fn record_request(&self, parent: Option<&dyn Display>, key: &dyn Display) {
+        if !self.records_requests() {
+            return;
+        }
+        let key = key.to_string();
+        match parent.map(|parent| parent.to_string()) {
+            None => {
+                if let Some(replay_log) = &self.replay_log {
+                    replay_log.record_root(key.clone());
+                }
+                if let Some(span_tree) = &self.span_tree {
+                    span_tree.record_root(key);
+                }
+            }
+            Some(parent) => {
+                if let Some(replay_log) = &self.replay_log {
+                    replay_log.record_request(parent.clone(), key.clone());
+                }
+                if let Some(span_tree) = &self.span_tree {
+                    span_tree.record_request(parent, key);
+                }
+            }
+        }
+    }
+
+    /// Records that the computation of `key` started.
+    pub(crate) fn record_computing(&self, key: &dyn Display) {
+        if let Some(replay_log) = &self.replay_log {
+            replay_log.record_computed(key.to_string());
+        }
+    }
+
+    /// Records that the computation of `key` took `duration`.
+    pub(crate) fn record_computed(&self, key: &dyn Display, duration: Duration) {
+        if let Some(span_tree) = &self.span_tree {
+            span_tree.record_computed(key.to_string(), duration);
+        }
+    }
 }
 
 impl Default for UserComputationData {
@@ -77,9 +169,15 @@ impl Default for UserComputationData {
         Self {
             data: DiceData::new(),
             tracker: Arc::new(NoOpTracker),
-            spawner: Arc::new(TokioSpawner),
+            spawner: DEFAULT_SPAWNER.dupe(),
             cycle_detector: None,
             activation_tracker: None,
+            priority: DicePriority::default(),
+            progress: DiceProgress::default(),
+            replay_log: None,
+            span_tree: None,
+            resource_usage: RequestResourceUsage::default(),
+            graph_stats: TransactionGraphCounters::default(),
             _requires_default: RequireDefault(()),
         }
     }
@@ -0,0 +1,36 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Checking the invariants of computed values, see `Key::validate_result`.
+
+use allocative::Allocative;
+use dupe::Dupe;
+
+use crate::api::data::DiceData;
+
+/// Enables `Key::validate_result` in release builds. Debug builds always validate results.
+///
+/// To enable it, set it in the `DiceData` when building DICE:
+///
+/// ```ignore
+/// let mut builder = Dice::modern();
+/// builder.set(ValidateResults);
+/// ```
+///
+/// Every value requested through `DiceComputations`, including the values only read through
+/// `compute_opaque` and its projections, is then validated, whether it was just computed or
+/// reused from the cache, so this is meant for debugging rather than production.
+#[derive(Clone, Copy, Dupe, Debug, Allocative)]
+pub struct ValidateResults;
+
+impl ValidateResults {
+    pub(crate) fn enabled(data: &DiceData) -> bool {
+        cfg!(debug_assertions) || data.get::<ValidateResults>().is_ok()
+    }
+}
@@ -20,11 +20,16 @@ use more_futures::owning_future::OwningFuture;
 use crate::api::computations::DiceComputations;
 use crate::api::computations::DiceComputationsParallel;
 use crate::api::data::DiceData;
+use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
+use crate::api::history::ValueChange;
 use crate::api::key::Key;
 use crate::api::opaque::OpaqueValue;
+use crate::api::resource_class::DiceResourceLimits;
+use crate::api::resource_usage::RequestResourceUsage;
 use crate::api::user_data::UserComputationData;
 use crate::api::user_data::UserCycleDetectorGuard;
+use crate::api::validation::ValidateResults;
 use crate::impls::ctx::ModernComputeCtx;
 use crate::legacy::ctx::DiceComputationsImplLegacy;
 use crate::opaque::OpaqueValueImpl;
@@ -47,13 +52,8 @@ impl DiceComputationsImpl {
     where
         K: Key,
     {
-        match self {
-            DiceComputationsImpl::Legacy(delegate) => delegate
-                .compute_opaque(key)
-                .map(|r| r.map(|x| x.into_value()))
-                .left_future(),
-            DiceComputationsImpl::Modern(delegate) => delegate.compute(key).right_future(),
-        }
+        self.compute_opaque_impl(key)
+            .map(|r| r.map(|opaque| opaque.into_value()))
     }
 
     /// Compute "opaque" value where the value is only accessible via projections.
@@ -67,18 +67,132 @@ impl DiceComputationsImpl {
     where
         K: Key,
     {
-        match self {
+        self.compute_opaque_impl(key)
+            .map(|r| r.map(OpaqueValue::new))
+    }
+
+    /// Computes `key`, validating its value, whether it was just computed or reused from the
+    /// cache, if `ValidateResults` is enabled.
+    fn compute_opaque_impl<'a, K>(
+        &'a self,
+        key: &K,
+    ) -> impl Future<Output = DiceResult<OpaqueValueImpl<'a, K>>> + 'a
+    where
+        K: Key,
+    {
+        // Only clone the key when it is needed to report an invalid value.
+        let validated = ValidateResults::enabled(self.global_data()).then(|| key.clone());
+        let opaque = match self {
             DiceComputationsImpl::Legacy(delegate) => delegate
                 .compute_opaque(key)
-                .map(|r| r.map(|x| OpaqueValue::new(OpaqueValueImpl::Legacy(x))))
+                .map(|r| r.map(OpaqueValueImpl::Legacy))
                 .left_future(),
             DiceComputationsImpl::Modern(delegate) => delegate
                 .compute_opaque(key)
-                .map(|r| r.map(|x| OpaqueValue::new(OpaqueValueImpl::Modern(x))))
+                .map(|r| r.map(OpaqueValueImpl::Modern))
+                .right_future(),
+        };
+        let opaque = RequestResourceUsage::exclude_while(DiceResourceLimits::release_while(opaque));
+        opaque.map(move |r| {
+            let opaque = r?;
+            if let Some(key) = validated {
+                K::validate_result(opaque.value())
+                    .map_err(|message| DiceError::invalid_result(Arc::new(key), message))?;
+            }
+            Ok(opaque)
+        })
+    }
+
+    /// A context for computing `key` outside of the cache. Deps requested through it are not
+    /// recorded as deps of this context.
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> DiceResult<DiceComputationsImpl>
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => {
+                Ok(DiceComputationsImpl::Legacy(delegate.detached_for_key(key)?))
+            }
+            DiceComputationsImpl::Modern(delegate) => {
+                Ok(DiceComputationsImpl::Modern(delegate.detached_for_key(key)))
+            }
+        }
+    }
+
+    /// Whether `key` has a valid value in this transaction, without computing it or recording
+    /// a dependency on it.
+    pub(crate) fn is_cached<'a, K>(&'a self, key: &K) -> impl Future<Output = bool> + 'a
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => {
+                futures::future::ready(delegate.is_cached(key)).left_future()
+            }
+            DiceComputationsImpl::Modern(delegate) => delegate.is_cached(key).right_future(),
+        }
+    }
+
+    /// The number of direct dependencies of the valid value of `key` in this transaction, without
+    /// computing it or recording a dependency on it.
+    pub(crate) fn dep_count<'a, K>(&'a self, key: &K) -> impl Future<Output = Option<usize>> + 'a
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => {
+                futures::future::ready(delegate.dep_count(key)).left_future()
+            }
+            DiceComputationsImpl::Modern(delegate) => delegate.dep_count(key).right_future(),
+        }
+    }
+
+    /// The number of distinct keys that transitively depend on the valid value of `key` at this
+    /// version, without invalidating anything.
+    pub(crate) fn invalidation_impact<'a, K>(&'a self, key: &K) -> impl Future<Output = usize> + 'a
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => {
+                futures::future::ready(delegate.invalidation_impact(key)).left_future()
+            }
+            DiceComputationsImpl::Modern(delegate) => {
+                delegate.invalidation_impact(key).right_future()
+            }
+        }
+    }
+
+    /// The value of `key` if it was valid at most `max_versions_behind` versions ago, without
+    /// computing it. Legacy dice does not keep stale values around.
+    pub(crate) fn get_allowing_stale<'a, K>(
+        &'a self,
+        key: &'a K,
+        max_versions_behind: usize,
+    ) -> impl Future<Output = Option<K::Value>> + 'a
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(_) => futures::future::ready(None).left_future(),
+            DiceComputationsImpl::Modern(delegate) => delegate
+                .get_allowing_stale(key, max_versions_behind)
                 .right_future(),
         }
     }
 
+    /// The versions at which the value of `key` changed or was verified unchanged. Only legacy
+    /// dice keeps a per-key history of values.
+    pub(crate) fn value_history<K>(&self, key: &K) -> Vec<(VersionNumber, ValueChange)>
+    where
+        K: Key,
+    {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => delegate.value_history(key),
+            DiceComputationsImpl::Modern(_) => Vec::new(),
+        }
+    }
+
     /// Computes all the given tasks in parallel, returning an unordered Stream
     pub(crate) fn compute_many<'a, T: 'a>(
         &'a self,
@@ -173,6 +287,13 @@ impl DiceComputationsImpl {
         }
     }
 
+    pub(crate) fn report_progress<P: Send + Sync + 'static>(&self, progress: P) {
+        match self {
+            DiceComputationsImpl::Legacy(delegate) => delegate.report_progress(progress),
+            DiceComputationsImpl::Modern(delegate) => delegate.report_progress(progress),
+        }
+    }
+
     pub(crate) fn get_version(&self) -> VersionNumber {
         match self {
             DiceComputationsImpl::Legacy(delegate) => delegate.get_version(),
@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::any::Any;
+use std::any::TypeId;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use allocative::Visitor;
+use dashmap::DashMap;
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+use tokio::sync::OnceCell;
+
+use crate::api::computations::DiceComputations;
+use crate::api::key::ContentKey;
+use crate::api::key::Key;
+use crate::api::resource_usage::RequestResourceUsage;
+use crate::versions::VersionNumber;
+
+/// A type-erased key or value held by the cache.
+trait Shared: Allocative + Send + Sync + 'static {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Allocative + Send + Sync + 'static> Shared for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A content key of a particular key type.
+#[derive(Allocative, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ContentKeyId {
+    #[allocative(skip)]
+    key_type: TypeId,
+    content_key: ContentKey,
+}
+
+impl ContentKeyId {
+    pub(crate) fn new<K: Key>(content_key: ContentKey) -> Self {
+        Self {
+            key_type: TypeId::of::<K>(),
+            content_key,
+        }
+    }
+}
+
+struct ContentKeyEntry {
+    /// The first key computed for the content key. The other keys request it to reuse its value,
+    /// so they depend on it like on any other key.
+    producer: Arc<dyn Shared>,
+    /// The version the producer was first computed at.
+    version: VersionNumber,
+    /// The value of the producer, reused when the producer itself is recomputed.
+    value: OnceCell<Arc<dyn Shared>>,
+}
+
+impl Allocative for ContentKeyEntry {
+    fn visit<'a, 'b: 'a>(&self, visitor: &'a mut Visitor<'b>) {
+        let mut visitor = visitor.enter_self_sized::<Self>();
+        visitor.visit_field(allocative::Key::new("producer"), &self.producer);
+        if let Some(value) = self.value.get() {
+            visitor.visit_field(allocative::Key::new("value"), value);
+        }
+        visitor.exit();
+    }
+}
+
+/// The values computed for each content key, shared by all the keys of a type that have it.
+/// Values are kept across transactions, since they only depend on the content key, until they are
+/// invalidated by a commit (see `invalidate`) or the key that computed them is evicted (see
+/// `evicted`).
+#[derive(Allocative, Default)]
+pub(crate) struct ContentKeyCache {
+    values: DashMap<ContentKeyId, Arc<ContentKeyEntry>>,
+}
+
+impl ContentKeyCache {
+    /// Computes `key`, unless a key of the same type with the same content key was already
+    /// computed or is being computed, in which case its value is reused.
+    pub(crate) async fn compute<K: Key>(
+        &self,
+        key: &K,
+        ctx: &mut DiceComputations,
+        cancellations: &CancellationContext,
+    ) -> K::Value {
+        let id = match key.content_key() {
+            Some(content_key) => ContentKeyId::new::<K>(content_key),
+            None => return RequestResourceUsage::compute(key, ctx, cancellations).await,
+        };
+
+        let version = ctx.0.get_version();
+        let entry = self
+            .values
+            .entry(id.clone())
+            .or_insert_with(|| {
+                Arc::new(ContentKeyEntry {
+                    producer: Arc::new(key.clone()),
+                    version,
+                    value: OnceCell::new(),
+                })
+            })
+            .dupe();
+
+        if entry.version > version {
+            // The value may have been computed from changes this version doesn't have.
+            return RequestResourceUsage::compute(key, ctx, cancellations).await;
+        }
+
+        let producer = (*entry.producer)
+            .as_any()
+            .downcast_ref::<K>()
+            .expect("entries are stored per key type");
+        if producer != key {
+            // Requesting the producer records it as a dependency, so this key is recomputed
+            // whenever the producer is, and its value is held by the graph like any other.
+            return match ctx.compute(producer).await {
+                Ok(value) => value,
+                // e.g. the producer was cancelled, so compute the value independently.
+                Err(_) => RequestResourceUsage::compute(key, ctx, cancellations).await,
+            };
+        }
+
+        let value = entry
+            .value
+            .get_or_init(|| async move {
+                Arc::new(RequestResourceUsage::compute(key, ctx, cancellations).await)
+                    as Arc<dyn Shared>
+            })
+            .await;
+        let value = (**value)
+            .as_any()
+            .downcast_ref::<K::Value>()
+            .expect("values are stored per key type")
+            .dupe();
+
+        if !K::validity(&value) || K::is_transient_error(&value) {
+            // Transient values, and transient errors that outlived the retries, are not reused by
+            // later computations.
+            self.values.remove_if(&id, |_, v| Arc::ptr_eq(v, &entry));
+        }
+
+        value
+    }
+
+    /// Drops the value computed for `id` by a version before `version`, the version an
+    /// invalidation was committed at, so that it is computed again the next time it is requested.
+    /// Values computed at `version` or later already see the committed changes.
+    pub(crate) fn invalidate(&self, id: &ContentKeyId, version: VersionNumber) {
+        self.values.remove_if(id, |_, entry| entry.version < version);
+    }
+
+    /// Drops the value computed by `key` when a value of `key` is evicted from the graph, so the
+    /// cache doesn't outlive the graph.
+    pub(crate) fn evicted<K: Key>(&self, key: &K) {
+        if let Some(content_key) = key.content_key() {
+            self.values.remove_if(&ContentKeyId::new::<K>(content_key), |_, entry| {
+                (*entry.producer).as_any().downcast_ref::<K>() == Some(key)
+            });
+        }
+    }
+}
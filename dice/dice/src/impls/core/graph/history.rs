@@ -332,6 +332,11 @@ where {
         }
     }
 
+    /// All the versions at which this history was verified, in order.
+    pub(crate) fn verified_versions(&self) -> impl Iterator<Item = VersionNumber> + '_ {
+        self.verified.iter().duped()
+    }
+
     pub(crate) fn latest_dirtied(&self) -> Option<VersionNumber> {
         self.dirtied.iter().max().map(|d| *d.0)
     }
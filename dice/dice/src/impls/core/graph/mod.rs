@@ -13,5 +13,6 @@ pub(crate) mod history;
 #[allow(unused)]
 pub(crate) mod introspection;
 mod nodes;
+pub(crate) mod spill;
 pub(crate) mod storage;
 pub(crate) mod types;
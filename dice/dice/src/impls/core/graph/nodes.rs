@@ -26,6 +26,7 @@ use crate::arc::Arc;
 use crate::impls::core::graph::dependencies::VersionedDependencies;
 use crate::impls::core::graph::dependencies::VersionedRevDependencies;
 use crate::impls::core::graph::history::CellHistory;
+use crate::impls::core::graph::spill::SpillFile;
 use crate::impls::key::DiceKey;
 use crate::impls::value::DiceComputedValue;
 use crate::impls::value::DiceValidValue;
@@ -67,10 +68,19 @@ impl VersionedGraphNode {
 #[derive(Allocative, Clone)] // TODO(bobyf) remove need to clone
 pub(crate) struct OccupiedGraphNode {
     key: DiceKey,
-    res: DiceValidValue,
+    res: NodeValue,
     metadata: NodeMetadata,
 }
 
+/// The value of a node, which is dropped from memory while it is spilled to disk.
+#[derive(Allocative, Clone)]
+enum NodeValue {
+    Resident(DiceValidValue),
+    Spilled(#[allocative(skip)] SpillFile),
+    /// The value was spilled, but could not be reloaded. It is recomputed on the next lookup.
+    Lost,
+}
+
 /// Meta data about a DICE node, which are its edges and history information
 #[derive(Allocative, Clone)] // TODO(bobyf) remove need to clone
 pub(crate) struct NodeMetadata {
@@ -88,7 +98,7 @@ impl OccupiedGraphNode {
     ) -> Self {
         Self {
             key,
-            res,
+            res: NodeValue::Resident(res),
             metadata: NodeMetadata {
                 hist,
                 deps,
@@ -130,13 +140,57 @@ impl OccupiedGraphNode {
         changed_since
     }
 
-    pub(crate) fn val(&self) -> &DiceValidValue {
-        &self.res
+    /// The value, unless it is spilled to disk or lost.
+    pub(crate) fn resident_val(&self) -> Option<&DiceValidValue> {
+        match &self.res {
+            NodeValue::Resident(res) => Some(res),
+            NodeValue::Spilled(_) | NodeValue::Lost => None,
+        }
+    }
+
+    /// The file the value is spilled to, if it is spilled to disk.
+    pub(crate) fn spilled(&self) -> Option<&SpillFile> {
+        match &self.res {
+            NodeValue::Spilled(file) => Some(file),
+            NodeValue::Resident(_) | NodeValue::Lost => None,
+        }
+    }
+
+    /// Drops the value from memory once it has been written to disk.
+    pub(crate) fn mark_spilled(&mut self, file: SpillFile) {
+        self.res = NodeValue::Spilled(file);
+    }
+
+    pub(crate) fn mark_reloaded(&mut self, res: DiceValidValue) {
+        self.res = NodeValue::Resident(res);
+    }
+
+    pub(crate) fn mark_lost(&mut self) {
+        self.res = NodeValue::Lost;
+    }
+
+    /// The computed value, unless the value is spilled to disk or lost.
+    pub(crate) fn computed_val(&self) -> Option<DiceComputedValue> {
+        self.resident_val().map(|res| {
+            DiceComputedValue::new(
+                MaybeValidDiceValue::valid(res.dupe()),
+                Arc::new(self.metadata.hist.clone()),
+            )
+        })
     }
 
-    pub(crate) fn computed_val(&self) -> DiceComputedValue {
+    /// The computed value, where `res` is a value equal to the value of the node. It becomes the
+    /// value of the node if that isn't resident.
+    pub(crate) fn restore_val(&mut self, res: DiceValidValue) -> DiceComputedValue {
+        let res = match &self.res {
+            NodeValue::Resident(res) => res.dupe(),
+            NodeValue::Spilled(_) | NodeValue::Lost => {
+                self.res = NodeValue::Resident(res.dupe());
+                res
+            }
+        };
         DiceComputedValue::new(
-            MaybeValidDiceValue::valid(self.res.dupe()),
+            MaybeValidDiceValue::valid(res),
             Arc::new(self.metadata.hist.clone()),
         )
     }
@@ -0,0 +1,373 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Bookkeeping for spilling graph values to disk, see `SpillConfig`.
+//!
+//! Measuring, writing, reading and removing values is done by a dedicated thread, so that the
+//! core state thread only does the bookkeeping. The results are sent back to the core state as
+//! `StateRequest::Spill`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use dupe::Dupe;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::WeakUnboundedSender;
+
+use crate::api::spill::SpillConfig;
+use crate::impls::core::state::StateRequest;
+use crate::impls::key::DiceKey;
+use crate::impls::value::DiceValidValue;
+use crate::impls::value::ReloadSpilledValue;
+use crate::versions::VersionNumber;
+use crate::HashMap;
+use crate::HashSet;
+
+/// A value written to disk. The file is removed once the last reference to it is dropped, i.e.
+/// once the value is reloaded or its node is dropped from the graph.
+#[derive(Clone, Dupe)]
+pub(crate) struct SpillFile(std::sync::Arc<SpillFileData>);
+
+struct SpillFileData {
+    path: PathBuf,
+    /// Size of the value in memory.
+    size: usize,
+    reload: ReloadSpilledValue,
+    jobs: UnboundedSender<SpillJob>,
+}
+
+impl SpillFile {
+    pub(crate) fn size(&self) -> usize {
+        self.0.size
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &SpillFile) -> bool {
+        std::sync::Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Reads back the value. Returns `None` if the file can't be read or decoded.
+    fn load(&self) -> Option<DiceValidValue> {
+        let bytes = fs::read(&self.0.path).ok()?;
+        (self.0.reload)(&bytes)
+    }
+}
+
+impl Drop for SpillFileData {
+    fn drop(&mut self) {
+        // The spill thread outlives the files, as they hold a sender to it.
+        let _ignored = self
+            .jobs
+            .send(SpillJob::Remove(std::mem::take(&mut self.path)));
+    }
+}
+
+/// The results of the work done by the spill thread.
+pub(crate) enum SpillEvent {
+    /// The resident `value` of `key` at `v` takes `size` bytes.
+    Measured {
+        key: DiceKey,
+        v: VersionNumber,
+        value: DiceValidValue,
+        size: usize,
+    },
+    /// The resident `value` of `key` at `v` was written to `file`, or couldn't be if `None`.
+    Written {
+        key: DiceKey,
+        v: VersionNumber,
+        value: DiceValidValue,
+        file: Option<SpillFile>,
+    },
+    /// The values of `files` were read back, or couldn't be if `None`. `then` is the request that
+    /// waited for them.
+    Reloaded {
+        values: Vec<(DiceKey, VersionNumber, SpillFile, Option<DiceValidValue>)>,
+        then: Box<StateRequest>,
+    },
+}
+
+/// Sends the results of the spill thread back to the core state.
+pub(crate) type SpillNotify = Box<dyn Fn(SpillEvent) + Send>;
+
+enum SpillJob {
+    Measure {
+        key: DiceKey,
+        v: VersionNumber,
+        value: DiceValidValue,
+    },
+    Write {
+        key: DiceKey,
+        v: VersionNumber,
+        value: DiceValidValue,
+        size: usize,
+    },
+    Reload {
+        files: Vec<(DiceKey, VersionNumber, SpillFile)>,
+        then: Box<StateRequest>,
+    },
+    Remove(PathBuf),
+}
+
+/// Tracks the memory held by resident spillable values of the graph, and hands the values to
+/// spill to the spill thread.
+pub(crate) struct SpillStore {
+    memory_limit: usize,
+    jobs: UnboundedSender<SpillJob>,
+    /// Size of each resident spillable value, and when it was last used.
+    resident: HashMap<(DiceKey, VersionNumber), (usize, u64)>,
+    /// The resident spillable values by when they were last used.
+    lru: BTreeMap<u64, (DiceKey, VersionNumber)>,
+    resident_size: usize,
+    clock: u64,
+    /// Values being measured by the spill thread.
+    measuring: HashSet<(DiceKey, VersionNumber)>,
+    /// Values being written by the spill thread, with their sizes. They stay resident until they
+    /// are written.
+    writing: HashMap<(DiceKey, VersionNumber), usize>,
+}
+
+impl SpillStore {
+    pub(crate) fn new(config: SpillConfig, notify: SpillNotify) -> Self {
+        static NEXT_STORE: AtomicUsize = AtomicUsize::new(0);
+
+        // Each store writes to its own directory, so several DICE instances can share `dir`.
+        let dir = config.dir.join(format!(
+            "dice-{}-{}",
+            std::process::id(),
+            NEXT_STORE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let (jobs, rx) = tokio::sync::mpsc::unbounded_channel();
+        let thread = SpillThread {
+            dir,
+            jobs: jobs.downgrade(),
+            notify,
+            next_file: 0,
+        };
+        std::thread::Builder::new()
+            .name("buck2-dice-spill".to_owned())
+            .spawn(move || thread.run(rx))
+            .unwrap();
+
+        Self {
+            memory_limit: config.memory_limit,
+            jobs,
+            resident: HashMap::default(),
+            lru: BTreeMap::new(),
+            resident_size: 0,
+            clock: 0,
+            measuring: HashSet::default(),
+            writing: HashMap::default(),
+        }
+    }
+
+    /// Whether the value of `key` at `v` is tracked, or being measured or written.
+    pub(crate) fn is_tracked(&self, key: DiceKey, v: VersionNumber) -> bool {
+        self.resident.contains_key(&(key, v))
+            || self.measuring.contains(&(key, v))
+            || self.writing.contains_key(&(key, v))
+    }
+
+    /// Records that the resident value of `key` at `v` was just used, if its size is known. A value
+    /// being written stays resident.
+    pub(crate) fn used(&mut self, key: DiceKey, v: VersionNumber) {
+        if let Some(size) = self.forget(key, v) {
+            self.touch(key, v, size);
+        }
+    }
+
+    /// Records that the resident value of `key` at `v`, of the given size, was just used.
+    pub(crate) fn touch(&mut self, key: DiceKey, v: VersionNumber, size: usize) {
+        self.forget(key, v);
+
+        self.clock += 1;
+        self.resident.insert((key, v), (size, self.clock));
+        self.lru.insert(self.clock, (key, v));
+        self.resident_size += size;
+    }
+
+    /// Stops tracking the value of `key` at `v`, returning its size if it was known. A pending
+    /// write of the value is discarded.
+    fn forget(&mut self, key: DiceKey, v: VersionNumber) -> Option<usize> {
+        if let Some(size) = self.writing.remove(&(key, v)) {
+            return Some(size);
+        }
+        let (size, last_used) = self.resident.remove(&(key, v))?;
+        self.lru.remove(&last_used);
+        self.resident_size -= size;
+        Some(size)
+    }
+
+    /// Measures the resident `value` of `key` at `v` on the spill thread.
+    pub(crate) fn measure(&mut self, key: DiceKey, v: VersionNumber, value: DiceValidValue) {
+        self.measuring.insert((key, v));
+        self.send(SpillJob::Measure { key, v, value });
+    }
+
+    /// Records that the measurement of `key` at `v` is done, returning whether it was pending.
+    pub(crate) fn measured(&mut self, key: DiceKey, v: VersionNumber) -> bool {
+        self.measuring.remove(&(key, v))
+    }
+
+    /// Stops tracking the least recently used value and returns it with its size, if the
+    /// resident values are over the memory limit.
+    pub(crate) fn pop_over_limit(&mut self) -> Option<(DiceKey, VersionNumber, usize)> {
+        if self.resident_size <= self.memory_limit {
+            return None;
+        }
+        let (_, (key, v)) = self.lru.pop_first()?;
+        let (size, _) = self.resident.remove(&(key, v)).unwrap();
+        self.resident_size -= size;
+        Some((key, v, size))
+    }
+
+    /// Writes the resident `value` of `key` at `v` to disk on the spill thread.
+    pub(crate) fn write(
+        &mut self,
+        key: DiceKey,
+        v: VersionNumber,
+        value: DiceValidValue,
+        size: usize,
+    ) {
+        self.writing.insert((key, v), size);
+        self.send(SpillJob::Write {
+            key,
+            v,
+            value,
+            size,
+        });
+    }
+
+    /// Records that the write of `key` at `v` is done, returning whether it is still wanted, i.e.
+    /// the value wasn't used since.
+    pub(crate) fn written(&mut self, key: DiceKey, v: VersionNumber) -> bool {
+        self.writing.remove(&(key, v)).is_some()
+    }
+
+    /// Reads back `files` on the spill thread, and sends `then` back along with their values.
+    pub(crate) fn reload(
+        &mut self,
+        files: Vec<(DiceKey, VersionNumber, SpillFile)>,
+        then: StateRequest,
+    ) {
+        self.send(SpillJob::Reload {
+            files,
+            then: Box::new(then),
+        });
+    }
+
+    /// Whether the spill thread is measuring or writing values.
+    #[cfg(test)]
+    pub(crate) fn is_busy(&self) -> bool {
+        !self.measuring.is_empty() || !self.writing.is_empty()
+    }
+
+    fn send(&self, job: SpillJob) {
+        self.jobs.send(job).expect("spill thread died");
+    }
+}
+
+struct SpillThread {
+    dir: PathBuf,
+    /// Handed to the files, so they can be removed when dropped. It is weak so that the thread
+    /// stops once the store and all the files are dropped.
+    jobs: WeakUnboundedSender<SpillJob>,
+    notify: SpillNotify,
+    next_file: usize,
+}
+
+impl SpillThread {
+    fn run(mut self, mut rx: UnboundedReceiver<SpillJob>) {
+        while let Some(job) = rx.blocking_recv() {
+            match job {
+                SpillJob::Measure { key, v, value } => {
+                    if let Some(size) = value.spillable_size() {
+                        (self.notify)(SpillEvent::Measured {
+                            key,
+                            v,
+                            value,
+                            size,
+                        });
+                    }
+                }
+                SpillJob::Write {
+                    key,
+                    v,
+                    value,
+                    size,
+                } => {
+                    let file = self.write(key, v, &value, size);
+                    (self.notify)(SpillEvent::Written {
+                        key,
+                        v,
+                        value,
+                        file,
+                    });
+                }
+                SpillJob::Reload { files, then } => {
+                    let values = files
+                        .into_iter()
+                        .map(|(key, v, file)| {
+                            let value = file.load();
+                            if value.is_none() {
+                                debug!(msg = "failed to reload spilled value", k = ?key, v = %v);
+                            }
+                            (key, v, file, value)
+                        })
+                        .collect();
+                    (self.notify)(SpillEvent::Reloaded { values, then });
+                }
+                SpillJob::Remove(path) => {
+                    let _ignored = fs::remove_file(path);
+                }
+            }
+        }
+
+        let _ignored = fs::remove_dir_all(&self.dir);
+    }
+
+    fn write(
+        &mut self,
+        key: DiceKey,
+        v: VersionNumber,
+        value: &DiceValidValue,
+        size: usize,
+    ) -> Option<SpillFile> {
+        let spilled = value.spill()?;
+        // The store holds a sender while it is sending jobs.
+        let jobs = self.jobs.upgrade()?;
+
+        // A value can be spilled again before the file of its previous spill is removed.
+        self.next_file += 1;
+        let path = self
+            .dir
+            .join(format!("{}-{}-{}", key.index, v, self.next_file));
+        match write_file(&self.dir, &path, &spilled.bytes) {
+            Ok(()) => Some(SpillFile(std::sync::Arc::new(SpillFileData {
+                path,
+                size,
+                reload: spilled.reload,
+                jobs,
+            }))),
+            Err(e) => {
+                debug!(msg = "failed to spill value", k = ?key, v = %v, error = %e);
+                None
+            }
+        }
+    }
+}
+
+fn write_file(dir: &Path, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(path, bytes)
+}
@@ -33,15 +33,19 @@ use crate::impls::core::graph::history::HistoryState;
 use crate::impls::core::graph::nodes::OccupiedGraphNode;
 use crate::impls::core::graph::nodes::VacantGraphNode;
 use crate::impls::core::graph::nodes::VersionedGraphNode;
+use crate::impls::core::graph::spill::SpillEvent;
+use crate::impls::core::graph::spill::SpillStore;
 use crate::impls::core::graph::types::VersionedGraphKey;
 use crate::impls::core::graph::types::VersionedGraphResult;
 use crate::impls::core::graph::types::VersionedGraphResultMismatch;
+use crate::impls::core::state::StateRequest;
 use crate::impls::key::DiceKey;
 use crate::impls::value::DiceComputedValue;
 use crate::impls::value::DiceValidValue;
 use crate::versions::VersionNumber;
 use crate::versions::VersionRanges;
 use crate::HashMap;
+use crate::HashSet;
 
 /// The actual incremental cache that checks versions and dependency's versions
 /// to maintain correct caching based on versions and the versions of its
@@ -57,15 +61,207 @@ pub(crate) struct VersionedGraph {
     /// VacantGraphEntries can only be present when no other entries are present for the key at
     /// any version.
     pub(crate) last_n: HashMap<DiceKey, SortedVectorMap<VersionNumber, VersionedGraphNode>>,
+    #[allocative(skip)]
+    on_evict: Option<EvictionHook>,
+    #[allocative(skip)]
+    spill: Option<SpillStore>,
 }
 
+/// Called with the key and the version of each entry evicted to respect the `StorageType` of
+/// the key.
+pub(crate) type EvictionHook = std::sync::Arc<dyn Fn(DiceKey, VersionNumber) + Send + Sync>;
+
 impl VersionedGraph {
     pub(crate) fn new() -> Self {
         Self {
             last_n: Default::default(),
+            on_evict: None,
+            spill: None,
+        }
+    }
+
+    pub(crate) fn with_eviction_hook(self, on_evict: Option<EvictionHook>) -> Self {
+        Self { on_evict, ..self }
+    }
+
+    pub(crate) fn with_spill_store(self, spill: Option<SpillStore>) -> Self {
+        Self { spill, ..self }
+    }
+
+    /// Reloads the spilled values of `keys` before `then` is handled, as it uses them, and
+    /// records that their values were just used. Returns `then` if there is nothing to reload,
+    /// and otherwise the spill store sends it back with the values once they are read, see
+    /// `spill_event`.
+    pub(crate) fn reload_spilled(
+        &mut self,
+        keys: &[DiceKey],
+        then: StateRequest,
+    ) -> Option<StateRequest> {
+        let Some(spill) = &mut self.spill else {
+            return Some(then);
+        };
+
+        let mut files = Vec::new();
+        for key in keys {
+            let Some(versioned) = self.last_n.get(key) else {
+                continue;
+            };
+            for (v, entry) in versioned.iter() {
+                match entry.unpack_occupied().and_then(|entry| entry.spilled()) {
+                    Some(file) => files.push((*key, *v, file.dupe())),
+                    None => spill.used(*key, *v),
+                }
+            }
+        }
+
+        if files.is_empty() {
+            Some(then)
+        } else {
+            spill.reload(files, then);
+            None
+        }
+    }
+
+    /// Measures the values of `key` that were just added, so they can be spilled once they are
+    /// the least recently used ones.
+    pub(crate) fn update_spilled(&mut self, key: DiceKey) {
+        let (Some(spill), Some(versioned)) = (&mut self.spill, self.last_n.get(&key)) else {
+            return;
+        };
+
+        for (v, entry) in versioned.iter() {
+            if let Some(value) = entry.unpack_occupied().and_then(|entry| entry.resident_val()) {
+                if value.is_spillable() && !spill.is_tracked(key, *v) {
+                    spill.measure(key, *v, value.dupe());
+                }
+            }
+        }
+    }
+
+    /// Handles the work done by the spill store. Returns the request to handle next, if any.
+    pub(crate) fn spill_event(&mut self, event: SpillEvent) -> Option<StateRequest> {
+        let Some(spill) = &mut self.spill else {
+            return None;
+        };
+
+        match event {
+            SpillEvent::Measured {
+                key,
+                v,
+                value,
+                size,
+            } => {
+                if spill.measured(key, v) {
+                    if Self::resident_mut(&mut self.last_n, key, v, &value).is_some() {
+                        spill.touch(key, v, size);
+                        self.spill_over_limit();
+                    } else {
+                        // The value was replaced since, so measure the new one.
+                        self.update_spilled(key);
+                    }
+                }
+                None
+            }
+            SpillEvent::Written {
+                key,
+                v,
+                value,
+                file,
+            } => {
+                // The value may have been used since, in which case the file is dropped, which
+                // removes it.
+                if spill.written(key, v) {
+                    match Self::resident_mut(&mut self.last_n, key, v, &value) {
+                        Some(entry) => {
+                            if let Some(file) = file {
+                                entry.mark_spilled(file);
+                            }
+                        }
+                        // The value was replaced since, so measure the new one.
+                        None => self.update_spilled(key),
+                    }
+                }
+                None
+            }
+            SpillEvent::Reloaded { values, then } => {
+                for (key, v, file, value) in values {
+                    // The value may have been evicted or spilled again since.
+                    let Some(entry) = Self::occupied_mut(&mut self.last_n, key, v) else {
+                        continue;
+                    };
+                    if !entry.spilled().map_or(false, |spilled| spilled.ptr_eq(&file)) {
+                        continue;
+                    }
+                    match value {
+                        Some(value) => {
+                            entry.mark_reloaded(value);
+                            spill.touch(key, v, file.size());
+                        }
+                        None => entry.mark_lost(),
+                    }
+                }
+                self.spill_over_limit();
+                Some(*then)
+            }
+        }
+    }
+
+    /// Sends `then` back once the spill thread is done measuring and writing values, or returns
+    /// it if it is idle.
+    #[cfg(test)]
+    pub(crate) fn wait_for_spills(&mut self, then: StateRequest) -> Option<StateRequest> {
+        match &mut self.spill {
+            Some(spill) if spill.is_busy() => {
+                // Nothing to reload, but it waits for the jobs sent so far.
+                spill.reload(Vec::new(), then);
+                None
+            }
+            _ => Some(then),
+        }
+    }
+
+    /// Spills the least recently used values while the spillable values are over the memory
+    /// limit.
+    fn spill_over_limit(&mut self) {
+        let Some(spill) = &mut self.spill else {
+            return;
+        };
+
+        while let Some((key, v, size)) = spill.pop_over_limit() {
+            // The value may have been evicted since it was last used.
+            if let Some(value) = Self::occupied_mut(&mut self.last_n, key, v)
+                .and_then(|entry| entry.resident_val())
+            {
+                spill.write(key, v, value.dupe(), size);
+            }
         }
     }
 
+    fn occupied_mut(
+        last_n: &mut HashMap<DiceKey, SortedVectorMap<VersionNumber, VersionedGraphNode>>,
+        key: DiceKey,
+        v: VersionNumber,
+    ) -> Option<&mut OccupiedGraphNode> {
+        match last_n.get_mut(&key)?.get_mut(&v)? {
+            VersionedGraphNode::Occupied(entry) => Some(entry),
+            VersionedGraphNode::Vacant(_) => None,
+        }
+    }
+
+    /// The entry of `key` at `v`, if `value` is still its value.
+    fn resident_mut<'a>(
+        last_n: &'a mut HashMap<DiceKey, SortedVectorMap<VersionNumber, VersionedGraphNode>>,
+        key: DiceKey,
+        v: VersionNumber,
+        value: &DiceValidValue,
+    ) -> Option<&'a mut OccupiedGraphNode> {
+        Self::occupied_mut(last_n, key, v).filter(|entry| {
+            entry
+                .resident_val()
+                .map_or(false, |res| res.instance_equal(value))
+        })
+    }
+
     /// gets the cache entry corresponding to the cache entry if up to date.
     /// returns 'None' if entry is missing or versions are out of date.
     pub(crate) fn get(&self, key: VersionedGraphKey) -> VersionedGraphResult {
@@ -73,15 +269,20 @@ impl VersionedGraph {
             key: VersionedGraphKey,
             entry: &OccupiedGraphNode,
         ) -> VersionedGraphResult {
+            // Spilled values are reloaded before lookups, so a value that isn't resident couldn't
+            // be reloaded, and is recomputed.
             match entry.metadata().hist.get_history(&key.v) {
-                HistoryState::Verified => VersionedGraphResult::Match(entry.computed_val()),
-                HistoryState::Unknown(verified_versions) => {
-                    VersionedGraphResult::CheckDeps(VersionedGraphResultMismatch {
-                        entry: entry.val().dupe(),
+                HistoryState::Verified => entry
+                    .computed_val()
+                    .map_or(VersionedGraphResult::Compute, VersionedGraphResult::Match),
+                HistoryState::Unknown(verified_versions) => match entry.resident_val() {
+                    Some(value) => VersionedGraphResult::CheckDeps(VersionedGraphResultMismatch {
+                        entry: value.dupe(),
                         verified_versions,
                         deps_to_validate: entry.metadata().deps.deps(),
-                    })
-                }
+                    }),
+                    None => VersionedGraphResult::Compute,
+                },
                 HistoryState::Dirty => VersionedGraphResult::Compute,
             }
         }
@@ -109,15 +310,15 @@ impl VersionedGraph {
                 // to a different result. TODO add some per ctx result caching for old versions
                 versioned
                     .range((Bound::Included(key.v), Bound::Unbounded))
-                    .find_map(|(v, e)| match e {
-                        VersionedGraphNode::Occupied(e) => Some((v, e)),
+                    .find_map(|(_, e)| match e {
+                        VersionedGraphNode::Occupied(e) => Some((e, e.resident_val()?)),
                         VersionedGraphNode::Vacant(_) => None,
                     })
                     .map_or_else(
                         || VersionedGraphResult::Compute,
-                        |(_, entry)| {
+                        |(entry, value)| {
                             VersionedGraphResult::CheckDeps(VersionedGraphResultMismatch {
-                                entry: entry.val().dupe(),
+                                entry: value.dupe(),
                                 verified_versions: entry.metadata().hist.get_verified_ranges(),
                                 deps_to_validate: entry.metadata().deps.deps(),
                             })
@@ -129,6 +330,94 @@ impl VersionedGraph {
         }
     }
 
+    /// gets the deps of the cache entry if it is up to date.
+    /// returns 'None' if entry is missing or versions are out of date.
+    pub(crate) fn deps(&self, key: VersionedGraphKey) -> Option<Arc<Vec<DiceKey>>> {
+        let (_, node) = self
+            .last_n
+            .get(&key.k)?
+            .range((
+                Bound::Included(VersionNumber::new(0)),
+                Bound::Included(key.v),
+            ))
+            .next_back()?;
+        match node {
+            VersionedGraphNode::Occupied(entry) => {
+                match entry.metadata().hist.get_history(&key.v) {
+                    HistoryState::Verified => Some(entry.metadata().deps.deps()),
+                    _ => None,
+                }
+            }
+            VersionedGraphNode::Vacant(_) => None,
+        }
+    }
+
+    /// the number of distinct entries that transitively depend on the entry at `key`, if it is
+    /// valid at `key.v`, i.e. how many entries invalidating it would dirty. Nothing is invalidated.
+    pub(crate) fn invalidation_impact(&self, key: VersionedGraphKey) -> usize {
+        if self.deps(key).is_none() {
+            return 0;
+        }
+
+        let mut visited = HashSet::default();
+        let mut queue = vec![(key.k, key.v)];
+        while let Some((k, v)) = queue.pop() {
+            let node = self.last_n.get(&k).and_then(|versioned| {
+                versioned
+                    .range((Bound::Included(VersionNumber::new(0)), Bound::Included(v)))
+                    .next_back()
+            });
+            if let Some((_, VersionedGraphNode::Occupied(entry))) = node {
+                queue.extend(
+                    entry
+                        .metadata()
+                        .rdeps
+                        .rdeps()
+                        .iter()
+                        .filter(|(rdep, _)| visited.insert(**rdep))
+                        .map(|(rdep, v)| (*rdep, *v)),
+                );
+            }
+        }
+        visited.len()
+    }
+
+    /// gets the value of the cache entry as of the latest version at or before `key.v` at which it
+    /// was known to be valid, as long as that is at most `max_versions_behind` versions before
+    /// `key.v`.
+    /// returns 'None' if entry is missing or too stale.
+    pub(crate) fn get_allowing_stale(
+        &self,
+        key: VersionedGraphKey,
+        max_versions_behind: usize,
+    ) -> Option<DiceValidValue> {
+        let (_, node) = self
+            .last_n
+            .get(&key.k)?
+            .range((
+                Bound::Included(VersionNumber::new(0)),
+                Bound::Included(key.v),
+            ))
+            .next_back()?;
+        match node {
+            VersionedGraphNode::Occupied(entry) => {
+                let hist = &entry.metadata().hist;
+                let verified = hist.latest_verified_before(key.v)?;
+                // the value stays valid until the first version it is dirtied at.
+                let staleness = match hist.first_dirty_after(verified) {
+                    Some(dirtied) if dirtied <= key.v => key.v - dirtied + 1,
+                    _ => 0,
+                };
+                if staleness <= max_versions_behind as isize {
+                    entry.resident_val().map(|value| value.dupe())
+                } else {
+                    None
+                }
+            }
+            VersionedGraphNode::Vacant(_) => None,
+        }
+    }
+
     /// gets the cache entry corresponding to the cache entry if up to date.
     /// returns 'None' if entry is missing or versions are out of date.
     fn get_internal<'a>(
@@ -292,10 +581,14 @@ impl VersionedGraph {
         let since = latest_dep_verified.unwrap_or(v);
         let mut hist = CellHistory::verified(since);
         hist.propagate_from_deps_version(since, first_dep_dirtied);
-        let entry =
-            OccupiedGraphNode::new(key, value, VersionedDependencies::new(since, deps), hist);
+        let mut entry = OccupiedGraphNode::new(
+            key,
+            value.dupe(),
+            VersionedDependencies::new(since, deps),
+            hist,
+        );
 
-        let res = entry.computed_val();
+        let res = entry.restore_val(value);
 
         self.last_n
             .get_mut(&key)
@@ -320,12 +613,13 @@ impl VersionedGraph {
     ) -> (DiceComputedValue, bool) {
         let versioned_map = self.last_n.get_mut(&key.k).unwrap();
         let (ret, map_fixup) = match versioned_map.get_mut(&key_of_e).unwrap() {
-            VersionedGraphNode::Occupied(entry) if reusable.is_reusable(&value, entry) => {
+            VersionedGraphNode::Occupied(entry) if reusable.is_reusable(&value, entry, key.v) => {
                 debug!("marking graph entry as unchanged");
                 let since =
                     entry.mark_unchanged(key.v, latest_dep_verified, first_dep_dirtied, deps);
 
-                let ret = entry.computed_val();
+                // The value of the entry may be spilled, but the new one is equal to it.
+                let ret = entry.restore_val(value);
 
                 (ret, MapFixup::Reused { since, key_of_e })
             }
@@ -338,14 +632,14 @@ impl VersionedGraph {
 
                 hist.propagate_from_deps_version(key.v, first_dep_dirtied);
 
-                let new = OccupiedGraphNode::new(
+                let mut new = OccupiedGraphNode::new(
                     key.k,
-                    value,
+                    value.dupe(),
                     VersionedDependencies::new(since, deps),
                     hist,
                 );
 
-                let ret = new.computed_val();
+                let ret = new.restore_val(value);
 
                 (
                     ret,
@@ -360,18 +654,28 @@ impl VersionedGraph {
             }
         };
 
-        let any_invalidated = map_fixup.fixup(versioned_map);
+        let any_invalidated = map_fixup.fixup(key.k, versioned_map, self.on_evict.as_ref());
 
         (ret, any_invalidated)
     }
 
     /// Invalidates an entry and its transitive rdeps. Returning true if this caused any type of
     /// change
+    #[cfg(test)]
     pub(crate) fn invalidate(
         &mut self,
         key: VersionedGraphKey,
         invalidate: InvalidateKind,
     ) -> bool {
+        self.invalidate_with_outcome(key, invalidate) != InvalidateOutcome::Unchanged
+    }
+
+    /// Invalidates an entry and its transitive rdeps, returning what kind of change this caused.
+    pub(crate) fn invalidate_with_outcome(
+        &mut self,
+        key: VersionedGraphKey,
+        invalidate: InvalidateKind,
+    ) -> InvalidateOutcome {
         let rdeps = {
             match invalidate {
                 invalidate @ (InvalidateKind::ForceDirty | InvalidateKind::Invalidate) => {
@@ -404,10 +708,10 @@ impl VersionedGraph {
 
                                 queue
                             } else {
-                                return true;
+                                return InvalidateOutcome::Changed(0);
                             }
                         } else {
-                            return false;
+                            return InvalidateOutcome::Unchanged;
                         }
                     } else {
                         let mut entry = VersionedGraphNode::Vacant(VacantGraphNode {
@@ -419,7 +723,7 @@ impl VersionedGraph {
 
                         versioned_map.insert(key.v, entry);
 
-                        return true;
+                        return InvalidateOutcome::New;
                     }
                 }
                 InvalidateKind::Update(value, StorageType::LastN(num_to_keep)) => {
@@ -432,8 +736,12 @@ impl VersionedGraph {
                         });
 
                         match entry {
+                            // A value that isn't resident is assumed to have changed.
                             Some(VersionedGraphNode::Occupied(occ)) => {
-                                if !occ.val().equality(&value) {
+                                if !occ
+                                    .resident_val()
+                                    .map_or(false, |res| res.equality(&value))
+                                {
                                     occ.metadata()
                                         .rdeps
                                         .rdeps()
@@ -441,7 +749,7 @@ impl VersionedGraph {
                                         .map(|(r, v)| (r.dupe(), *v))
                                         .collect::<Vec<_>>()
                                 } else {
-                                    return false;
+                                    return InvalidateOutcome::Unchanged;
                                 }
                             }
                             _ => vec![],
@@ -482,28 +790,29 @@ impl VersionedGraph {
 
                         versioned_map.insert(key.v, entry);
 
-                        return true;
+                        return InvalidateOutcome::New;
                     };
 
-                    fixup.fixup(versioned_map);
+                    fixup.fixup(key.k, versioned_map, self.on_evict.as_ref());
 
                     rdeps
                 }
             }
         };
 
-        self.invalidate_rdeps(key.v, rdeps);
-        true
+        InvalidateOutcome::Changed(self.invalidate_rdeps(key.v, rdeps))
     }
 
     fn invalidate_rdeps(
         &mut self,
         version: VersionNumber,
         mut queue: Vec<(DiceKey, VersionNumber)>,
-    ) {
+    ) -> usize {
+        let mut invalidated = 0;
         while let Some((rdep, relevant_version)) = queue.pop() {
             if let Some(node) = self.get_internal(VersionedGraphKey::new(relevant_version, rdep)) {
                 if node.mark_invalidated(version) {
+                    invalidated += 1;
                     // since dirty always occurs in increasing order, it must be the case that if
                     // the history was already dirtied, it was by a version number less than the
                     // current version number.
@@ -524,6 +833,7 @@ impl VersionedGraph {
                 }
             }
         }
+        invalidated
     }
 }
 
@@ -535,9 +845,22 @@ pub(crate) enum ValueReusable {
 }
 
 impl ValueReusable {
-    fn is_reusable(&self, new_value: &DiceValidValue, value: &OccupiedGraphNode) -> bool {
+    fn is_reusable(
+        &self,
+        new_value: &DiceValidValue,
+        value: &OccupiedGraphNode,
+        v: VersionNumber,
+    ) -> bool {
         match self {
-            ValueReusable::EqualityBased => new_value.equality(value.val()),
+            ValueReusable::EqualityBased => match value.resident_val() {
+                Some(res) => new_value.equality(res),
+                // The value is spilled or couldn't be reloaded. Computations are deterministic, so
+                // a value computed at a version the entry is valid at is equal to it.
+                None => matches!(
+                    value.metadata().hist.get_history(&v),
+                    HistoryState::Verified
+                ),
+            },
             ValueReusable::VersionBased(hist) => !hist
                 .intersect(&value.metadata().hist.get_verified_ranges())
                 .is_empty(),
@@ -545,6 +868,16 @@ impl ValueReusable {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InvalidateOutcome {
+    /// The key was already invalidated, or updated to a value equal to its current one.
+    Unchanged,
+    /// The key had no previous entry.
+    New,
+    /// The key changed, which newly invalidated this many transitive rdeps.
+    Changed(usize),
+}
+
 pub(crate) enum InvalidateKind {
     ForceDirty,
     #[allow(unused)] // constructed for tests
@@ -570,7 +903,12 @@ enum MapFixup {
 }
 
 impl MapFixup {
-    fn fixup(self, versioned_map: &mut SortedVectorMap<VersionNumber, VersionedGraphNode>) -> bool {
+    fn fixup(
+        self,
+        key: DiceKey,
+        versioned_map: &mut SortedVectorMap<VersionNumber, VersionedGraphNode>,
+        on_evict: Option<&EvictionHook>,
+    ) -> bool {
         match self {
             MapFixup::Reused { since, key_of_e } => {
                 if since < key_of_e {
@@ -615,6 +953,9 @@ impl MapFixup {
                             }
 
                             versioned_map.remove(&min_version_stored);
+                            if let Some(on_evict) = on_evict {
+                                on_evict(key, min_version_stored);
+                            }
                         }
 
                         versioned_map.insert(since, VersionedGraphNode::Occupied(new));
@@ -718,6 +1059,30 @@ mod tests {
         }
     }
 
+    #[derive(Allocative, Clone, Dupe, Debug, Display, PartialEq, Eq, Hash)]
+    struct Pinned;
+
+    #[async_trait]
+    impl Key for Pinned {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            unimplemented!("test")
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn pin() -> bool {
+            true
+        }
+    }
+
     #[test]
     fn latest_only_stores_latest_only() {
         let mut cache = VersionedGraph::new();
@@ -881,6 +1246,50 @@ mod tests {
         cache.get(key5.dupe()).assert_compute()
     }
 
+    #[test]
+    fn pinned_key_retains_value_across_invalidation() {
+        let mut cache = VersionedGraph::new();
+        let res = DiceValidValue::testing_new(DiceKeyValue::<Pinned>::new(100));
+        let key = VersionedGraphKey::new(VersionNumber::new(1), DiceKey { index: 0 });
+
+        assert!(
+            cache
+                .update(
+                    key.dupe(),
+                    res.dupe(),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![]),
+                    StorageType::for_key::<Pinned>()
+                )
+                .1
+        );
+
+        let res2 = DiceValidValue::testing_new(DiceKeyValue::<Pinned>::new(200));
+        let key2 = VersionedGraphKey::new(VersionNumber::new(2), DiceKey { index: 0 });
+        assert!(cache.invalidate(key2.dupe(), InvalidateKind::Invalidate));
+        assert!(
+            cache
+                .update(
+                    key2.dupe(),
+                    res2.dupe(),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![]),
+                    StorageType::for_key::<Pinned>()
+                )
+                .1
+        );
+
+        assert!(
+            cache
+                .get(key2.dupe())
+                .assert_match()
+                .value()
+                .equality(&res2)
+        );
+        // unlike `LastN(1)`, the value computed before the invalidation is still available
+        assert!(cache.get(key.dupe()).assert_match().value().equality(&res));
+    }
+
     #[test]
     fn last_n_max_usize_stores_everything() {
         let mut cache = VersionedGraph::new();
@@ -7,17 +7,25 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+use std::time::Instant;
+
 use gazebo::prelude::SliceExt;
 
 use crate::api::storage_type::StorageType;
 use crate::arc::Arc;
 use crate::impls::cache::SharedCache;
 use crate::impls::core::graph::introspection::VersionedGraphIntrospectable;
+use crate::impls::core::graph::spill::SpillEvent;
+use crate::impls::core::graph::spill::SpillStore;
+use crate::impls::core::graph::storage::EvictionHook;
 use crate::impls::core::graph::storage::InvalidateKind;
+use crate::impls::core::graph::storage::InvalidateOutcome;
 use crate::impls::core::graph::storage::ValueReusable;
 use crate::impls::core::graph::storage::VersionedGraph;
 use crate::impls::core::graph::types::VersionedGraphKey;
 use crate::impls::core::graph::types::VersionedGraphResult;
+use crate::impls::core::state::StateRequest;
 use crate::impls::core::versions::introspection::VersionIntrospectable;
 use crate::impls::core::versions::VersionEpoch;
 use crate::impls::core::versions::VersionTracker;
@@ -27,11 +35,22 @@ use crate::impls::task::dice::TerminationObserver;
 use crate::impls::transaction::ChangeType;
 use crate::impls::value::DiceComputedValue;
 use crate::impls::value::DiceValidValue;
+use crate::impls::value::MaybeValidDiceValue;
 use crate::metrics::Metrics;
 use crate::result::CancellableResult;
 use crate::result::Cancelled;
 use crate::versions::VersionNumber;
 
+/// Summary of the invalidation performed when a set of changes was committed.
+#[derive(Debug)]
+pub(crate) struct InvalidationStats {
+    /// Number of nodes that were invalidated as transitive rdeps of the changed keys.
+    pub(crate) invalidated_count: usize,
+    pub(crate) duration: Duration,
+    /// What each of the committed changes did to its key.
+    pub(crate) outcomes: Vec<(DiceKey, InvalidateOutcome)>,
+}
+
 /// Core state of DICE, holding the actual graph and version information
 pub(super) struct CoreState {
     version_tracker: VersionTracker,
@@ -48,16 +67,53 @@ impl CoreState {
         }
     }
 
+    pub(super) fn with_eviction_hook(self, on_evict: Option<EvictionHook>) -> Self {
+        Self {
+            graph: self.graph.with_eviction_hook(on_evict),
+            ..self
+        }
+    }
+
+    pub(super) fn with_spill_store(self, spill: Option<SpillStore>) -> Self {
+        Self {
+            graph: self.graph.with_spill_store(spill),
+            ..self
+        }
+    }
+
+    /// Defers `message` until the spilled values it uses are reloaded, see
+    /// `VersionedGraph::reload_spilled`. Returns the message if it can be handled now.
+    pub(super) fn reload_spilled(&mut self, message: StateRequest) -> Option<StateRequest> {
+        let keys = match &message {
+            StateRequest::LookupKey { key, .. }
+            | StateRequest::LookupKeyAllowingStale { key, .. } => vec![key.k],
+            StateRequest::ExportValues { .. } => self.graph.last_n.keys().copied().collect(),
+            #[cfg(test)]
+            StateRequest::TestingWaitForSpills { .. } => {
+                return self.graph.wait_for_spills(message);
+            }
+            _ => return Some(message),
+        };
+        self.graph.reload_spilled(&keys, message)
+    }
+
+    pub(super) fn spill_event(&mut self, event: SpillEvent) -> Option<StateRequest> {
+        self.graph.spill_event(event)
+    }
+
     pub(super) fn update_state(
         &mut self,
         updates: impl IntoIterator<Item = (DiceKey, ChangeType)>,
-    ) -> VersionNumber {
+    ) -> (VersionNumber, InvalidationStats) {
+        let start = Instant::now();
         let version_update = self.version_tracker.write();
         let v = version_update.version();
 
         let mut changes_recorded = false;
+        let mut invalidated_count = 0;
+        let mut outcomes = Vec::new();
         for (key, change) in updates {
-            changes_recorded |= self.graph.invalidate(
+            let outcome = self.graph.invalidate_with_outcome(
                 VersionedGraphKey::new(v, key),
                 match change {
                     ChangeType::Invalidate => InvalidateKind::ForceDirty,
@@ -66,12 +122,31 @@ impl CoreState {
                     ChangeType::TestingSoftDirty => InvalidateKind::Invalidate,
                 },
             );
+            self.graph.update_spilled(key);
+            match outcome {
+                InvalidateOutcome::Unchanged => {}
+                InvalidateOutcome::New => changes_recorded = true,
+                InvalidateOutcome::Changed(count) => {
+                    changes_recorded = true;
+                    invalidated_count += count;
+                }
+            }
+            outcomes.push((key, outcome));
         }
-        if changes_recorded {
+        let v = if changes_recorded {
             version_update.commit()
         } else {
             version_update.undo()
-        }
+        };
+
+        (
+            v,
+            InvalidationStats {
+                invalidated_count,
+                duration: start.elapsed(),
+                outcomes,
+            },
+        )
     }
 
     pub(super) fn ctx_at_version(&mut self, v: VersionNumber) -> (VersionEpoch, SharedCache) {
@@ -95,6 +170,22 @@ impl CoreState {
         self.graph.get(key)
     }
 
+    pub(super) fn dep_count(&self, key: VersionedGraphKey) -> Option<usize> {
+        self.graph.deps(key).map(|deps| deps.len())
+    }
+
+    pub(super) fn invalidation_impact(&self, key: VersionedGraphKey) -> usize {
+        self.graph.invalidation_impact(key)
+    }
+
+    pub(super) fn lookup_key_allowing_stale(
+        &self,
+        key: VersionedGraphKey,
+        max_versions_behind: usize,
+    ) -> Option<DiceValidValue> {
+        self.graph.get_allowing_stale(key, max_versions_behind)
+    }
+
     pub(super) fn update_computed(
         &mut self,
         key: VersionedGraphKey,
@@ -107,7 +198,10 @@ impl CoreState {
         if self.version_tracker.is_relevant(key.v, epoch) {
             debug!(msg = "update graph entry", k = ?key.k, v = %key.v, v_epoch = %epoch);
 
-            Ok(self.graph.update(key, value, reusability, deps, storage).0)
+            let res = self.graph.update(key, value, reusability, deps, storage).0;
+            self.graph.update_spilled(key.k);
+
+            Ok(res)
         } else {
             debug!(msg = "update is rejected due to outdated epoch", k = ?key.k, v = %key.v, v_epoch = %epoch);
 
@@ -115,6 +209,47 @@ impl CoreState {
         }
     }
 
+    /// The values of all keys valid at the current version, with the deps they were computed
+    /// from.
+    pub(super) fn export_values(&mut self) -> Vec<(DiceKey, MaybeValidDiceValue, Vec<DiceKey>)> {
+        let v = self.version_tracker.current();
+        let keys = self.graph.last_n.keys().copied().collect::<Vec<_>>();
+        let mut values = keys
+            .into_iter()
+            .filter_map(|k| match self.lookup_key(VersionedGraphKey::new(v, k)) {
+                VersionedGraphResult::Match(entry) => {
+                    let deps = self
+                        .graph
+                        .deps(VersionedGraphKey::new(v, k))
+                        .map_or_else(Vec::new, |deps| deps.to_vec());
+                    Some((k, entry.value().dupe(), deps))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        values.sort_by_key(|(k, _, _)| *k);
+        values
+    }
+
+    /// Installs values as valid at version 0, with the given deps. The deps of each value must
+    /// either be in the graph already or come before it.
+    pub(super) fn load_values(
+        &mut self,
+        values: Vec<(DiceKey, DiceValidValue, Vec<DiceKey>)>,
+        storage: StorageType,
+    ) {
+        for (k, value, deps) in values {
+            self.graph.update(
+                VersionedGraphKey::new(VersionNumber::new(0), k),
+                value,
+                ValueReusable::EqualityBased,
+                Arc::new(deps),
+                storage,
+            );
+            self.graph.update_spilled(k);
+        }
+    }
+
     pub(super) fn get_tasks_pending_cancellation(&mut self) -> Vec<TerminationObserver> {
         self.pending_termination_tasks
             .retain(|task| task.is_pending());
@@ -146,6 +281,7 @@ impl CoreState {
             key_count: self.graph.last_n.len(),
             currently_active_key_count: currently_running_key_count,
             active_transaction_count: active_transaction_count as u32, // probably won't support more than u32 transactions
+            key_types: Default::default(),
         }
     }
 
@@ -192,12 +328,14 @@ mod tests {
         let mut core = CoreState::new();
 
         assert_eq!(
-            core.update_state([(DiceKey { index: 0 }, ChangeType::Invalidate)]),
+            core.update_state([(DiceKey { index: 0 }, ChangeType::Invalidate)])
+                .0,
             VersionNumber::new(1)
         );
 
         assert_eq!(
-            core.update_state([(DiceKey { index: 1 }, ChangeType::Invalidate)]),
+            core.update_state([(DiceKey { index: 1 }, ChangeType::Invalidate)])
+                .0,
             VersionNumber::new(2)
         );
     }
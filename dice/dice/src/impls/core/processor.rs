@@ -9,6 +9,9 @@
 
 use gazebo::variants::VariantName;
 
+use crate::api::spill::SpillConfig;
+use crate::impls::core::graph::spill::SpillStore;
+use crate::impls::core::graph::storage::EvictionHook;
 use crate::impls::core::graph::storage::ValueReusable;
 use crate::impls::core::internals::CoreState;
 use crate::impls::core::state::CoreStateHandle;
@@ -21,9 +24,26 @@ pub(super) struct StateProcessor {
 }
 
 impl StateProcessor {
-    pub(super) fn spawn() -> CoreStateHandle {
+    pub(super) fn spawn(
+        on_evict: Option<EvictionHook>,
+        spill: Option<SpillConfig>,
+    ) -> CoreStateHandle {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let state = CoreState::new();
+        let spill = spill.map(|config| {
+            // Weak, so that the processor terminates once all the handles are dropped.
+            let state = tx.downgrade();
+            SpillStore::new(
+                config,
+                Box::new(move |event| {
+                    if let Some(state) = state.upgrade() {
+                        let _ignored = state.send(StateRequest::Spill(event));
+                    }
+                }),
+            )
+        });
+        let state = CoreState::new()
+            .with_eviction_hook(on_evict)
+            .with_spill_store(spill);
 
         std::thread::Builder::new()
             .name("buck2-dice".to_owned())
@@ -50,6 +70,10 @@ impl StateProcessor {
 
     #[instrument(skip_all, fields(kind = %message.variant_name()))]
     fn iteration(&mut self, message: StateRequest) {
+        let Some(message) = self.state.reload_spilled(message) else {
+            return;
+        };
+
         match message {
             StateRequest::UpdateState { changes, resp } => {
                 // ignore error if the requester dropped it.
@@ -71,6 +95,15 @@ impl StateProcessor {
                 let _ = resp.send(self.state.current_version());
             }
             StateRequest::LookupKey { key, resp } => drop(resp.send(self.state.lookup_key(key))),
+            StateRequest::GetDepCount { key, resp } => drop(resp.send(self.state.dep_count(key))),
+            StateRequest::GetInvalidationImpact { key, resp } => {
+                drop(resp.send(self.state.invalidation_impact(key)))
+            }
+            StateRequest::LookupKeyAllowingStale {
+                key,
+                max_versions_behind,
+                resp,
+            } => drop(resp.send(self.state.lookup_key_allowing_stale(key, max_versions_behind))),
             StateRequest::UpdateComputed {
                 key,
                 epoch,
@@ -108,6 +141,17 @@ impl StateProcessor {
                     previous.deps_to_validate,
                 )));
             }
+            StateRequest::ExportValues { resp } => {
+                let _ignored = resp.send(self.state.export_values());
+            }
+            StateRequest::LoadValues {
+                values,
+                storage,
+                resp,
+            } => {
+                self.state.load_values(values, storage);
+                let _ignored = resp.send(());
+            }
             StateRequest::GetTasksPendingCancellation { resp } => {
                 let _ignored = resp.send(self.state.get_tasks_pending_cancellation());
             }
@@ -118,6 +162,15 @@ impl StateProcessor {
             StateRequest::Introspection { resp } => {
                 let _ignored = resp.send(self.state.introspection());
             }
+            StateRequest::Spill(event) => {
+                if let Some(then) = self.state.spill_event(event) {
+                    self.iteration(then);
+                }
+            }
+            #[cfg(test)]
+            StateRequest::TestingWaitForSpills { resp } => {
+                let _ignored = resp.send(());
+            }
         }
     }
 }
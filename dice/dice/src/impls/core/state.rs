@@ -13,12 +13,16 @@ use dupe::Dupe;
 use gazebo::variants::VariantName;
 use tokio::sync::oneshot::Sender;
 
+use crate::api::spill::SpillConfig;
 use crate::api::storage_type::StorageType;
 use crate::arc::Arc;
 use crate::impls::core::graph::introspection::VersionedGraphIntrospectable;
+use crate::impls::core::graph::spill::SpillEvent;
+use crate::impls::core::graph::storage::EvictionHook;
 use crate::impls::core::graph::types::VersionedGraphKey;
 use crate::impls::core::graph::types::VersionedGraphResult;
 use crate::impls::core::graph::types::VersionedGraphResultMismatch;
+use crate::impls::core::internals::InvalidationStats;
 use crate::impls::core::processor::StateProcessor;
 use crate::impls::core::versions::introspection::VersionIntrospectable;
 use crate::impls::core::versions::VersionEpoch;
@@ -29,6 +33,7 @@ use crate::impls::transaction::ActiveTransactionGuard;
 use crate::impls::transaction::ChangeType;
 use crate::impls::value::DiceComputedValue;
 use crate::impls::value::DiceValidValue;
+use crate::impls::value::MaybeValidDiceValue;
 use crate::metrics::Metrics;
 use crate::result::CancellableResult;
 use crate::versions::VersionNumber;
@@ -38,10 +43,11 @@ use crate::versions::VersionNumber;
 #[derivative(Debug)]
 pub(crate) enum StateRequest {
     /// Updates the core state with the given set of changes. The new VersionNumber that should be
-    /// used is sent back via the channel provided
+    /// used, along with stats about the resulting invalidation, is sent back via the channel
+    /// provided
     UpdateState {
         changes: Vec<(DiceKey, ChangeType)>,
-        resp: Sender<VersionNumber>,
+        resp: Sender<(VersionNumber, InvalidationStats)>,
     },
     /// Gets the current version number
     CurrentVersion { resp: Sender<VersionNumber> },
@@ -58,6 +64,23 @@ pub(crate) enum StateRequest {
         key: VersionedGraphKey,
         resp: Sender<VersionedGraphResult>,
     },
+    /// Get the number of deps of the value of a key, if it is valid at the given version
+    GetDepCount {
+        key: VersionedGraphKey,
+        resp: Sender<Option<usize>>,
+    },
+    /// Get the number of transitive rdeps of a key, if it is valid at the given version
+    GetInvalidationImpact {
+        key: VersionedGraphKey,
+        resp: Sender<usize>,
+    },
+    /// Lookup the value of a key, accepting a value that was last valid up to `max_versions_behind`
+    /// versions before the given version
+    LookupKeyAllowingStale {
+        key: VersionedGraphKey,
+        max_versions_behind: usize,
+        resp: Sender<Option<DiceValidValue>>,
+    },
     /// Report that a value has been computed
     UpdateComputed {
         key: VersionedGraphKey,
@@ -84,6 +107,18 @@ pub(crate) enum StateRequest {
         /// given computed value if the state already stores an instance of value that is equal.
         resp: Sender<CancellableResult<DiceComputedValue>>,
     },
+    /// Collect the values of all keys which are valid at the current version, with their deps
+    ExportValues {
+        #[derivative(Debug = "ignore")]
+        resp: Sender<Vec<(DiceKey, MaybeValidDiceValue, Vec<DiceKey>)>>,
+    },
+    /// Install previously persisted values as valid at version 0, with the deps they were
+    /// computed from
+    LoadValues {
+        values: Vec<(DiceKey, DiceValidValue, Vec<DiceKey>)>,
+        storage: StorageType,
+        resp: Sender<()>,
+    },
     /// Get all the tasks pending cancellation
     GetTasksPendingCancellation {
         #[derivative(Debug = "ignore")]
@@ -98,6 +133,11 @@ pub(crate) enum StateRequest {
         #[derivative(Debug = "ignore")]
         resp: Sender<(VersionedGraphIntrospectable, VersionIntrospectable)>,
     },
+    /// Report the work done by the spill thread
+    Spill(#[derivative(Debug = "ignore")] SpillEvent),
+    /// Wait until the spill thread is done measuring and writing values
+    #[cfg(test)]
+    TestingWaitForSpills { resp: Sender<()> },
 }
 
 /// A handle to the core state that allows sending requests
@@ -121,6 +161,9 @@ impl CoreStateHandle {
 impl Dupe for CoreStateHandle {}
 
 /// Start processing state
-pub(crate) fn init_state() -> CoreStateHandle {
-    StateProcessor::spawn()
+pub(crate) fn init_state(
+    on_evict: Option<EvictionHook>,
+    spill: Option<SpillConfig>,
+) -> CoreStateHandle {
+    StateProcessor::spawn(on_evict, spill)
 }
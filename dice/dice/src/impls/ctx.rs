@@ -8,6 +8,7 @@
  */
 
 use std::any::Any;
+use std::fmt::Display;
 use std::future::Future;
 use std::ops::Deref;
 use std::pin::Pin;
@@ -24,6 +25,7 @@ use gazebo::variants::UnpackVariants;
 use more_futures::owning_future::OwningFuture;
 use parking_lot::Mutex;
 use parking_lot::MutexGuard;
+use tokio::sync::oneshot;
 
 use crate::api::activation_tracker::ActivationData;
 use crate::api::computations::DiceComputations;
@@ -31,12 +33,17 @@ use crate::api::computations::DiceComputationsParallel;
 use crate::api::data::DiceData;
 use crate::api::error::DiceResult;
 use crate::api::key::Key;
+use crate::api::progress::KeyProgress;
 use crate::api::projection::ProjectionKey;
+use crate::api::transaction::EarlyCutoffs;
 use crate::api::user_data::UserComputationData;
 use crate::ctx::DiceComputationsImpl;
 use crate::impls::cache::DiceTaskRef;
 use crate::impls::cache::SharedCache;
+use crate::impls::core::graph::types::VersionedGraphKey;
+use crate::impls::core::graph::types::VersionedGraphResult;
 use crate::impls::core::state::CoreStateHandle;
+use crate::impls::core::state::StateRequest;
 use crate::impls::core::versions::VersionEpoch;
 use crate::impls::dep_trackers::RecordingDepsTracker;
 use crate::impls::dice::DiceModern;
@@ -112,6 +119,7 @@ impl BaseComputeCtx {
                     user_data,
                     dice,
                     KeyComputingUserCycleDetectorData::Untracked,
+                    KeyProgress::default(),
                 ),
             ))),
             live_version_guard,
@@ -168,20 +176,6 @@ pub(crate) enum ModernComputeCtx {
 }
 
 impl ModernComputeCtx {
-    /// Gets all the result of of the given computation key.
-    /// recorded as dependencies of the current computation for which this
-    /// context is for.
-    pub(crate) fn compute<'a, K>(
-        &'a self,
-        key: &K,
-    ) -> impl Future<Output = DiceResult<<K as Key>::Value>> + 'a
-    where
-        K: Key,
-    {
-        self.compute_opaque(key)
-            .map(|r| r.map(|opaque| opaque.into_value()))
-    }
-
     /// Compute "opaque" value where the value is only accessible via projections.
     /// Projections allow accessing derived results from the "opaque" value,
     /// where the dependency of reading a projection is the projection value rather
@@ -206,6 +200,63 @@ impl ModernComputeCtx {
         })
     }
 
+    /// Whether `key` has a valid value at this version, without computing it or recording a
+    /// dependency on it.
+    pub(crate) fn is_cached<K>(&self, key: &K) -> impl Future<Output = bool>
+    where
+        K: Key,
+    {
+        match self {
+            ModernComputeCtx::Regular(ctx) => ctx.is_cached(key).left_future(),
+            ModernComputeCtx::Parallel(ctx) => ctx.is_cached(key).right_future(),
+        }
+    }
+
+    /// The number of direct dependencies of the valid value of `key` at this version, without
+    /// computing it or recording a dependency on it.
+    pub(crate) fn dep_count<K>(&self, key: &K) -> impl Future<Output = Option<usize>>
+    where
+        K: Key,
+    {
+        match self {
+            ModernComputeCtx::Regular(ctx) => ctx.dep_count(key).left_future(),
+            ModernComputeCtx::Parallel(ctx) => ctx.dep_count(key).right_future(),
+        }
+    }
+
+    /// The number of distinct keys that transitively depend on the valid value of `key` at this
+    /// version, without invalidating anything.
+    pub(crate) fn invalidation_impact<K>(&self, key: &K) -> impl Future<Output = usize>
+    where
+        K: Key,
+    {
+        match self {
+            ModernComputeCtx::Regular(ctx) => ctx.invalidation_impact(key).left_future(),
+            ModernComputeCtx::Parallel(ctx) => ctx.invalidation_impact(key).right_future(),
+        }
+    }
+
+    /// The value of `key` if it was valid at most `max_versions_behind` versions ago, without
+    /// computing it. The dependency on it is recorded as transient, since the value may be
+    /// outdated.
+    pub(crate) fn get_allowing_stale<'a, K>(
+        &'a self,
+        key: &'a K,
+        max_versions_behind: usize,
+    ) -> impl Future<Output = Option<K::Value>> + 'a
+    where
+        K: Key,
+    {
+        match self {
+            ModernComputeCtx::Regular(ctx) => {
+                ctx.get_allowing_stale(key, max_versions_behind).left_future()
+            }
+            ModernComputeCtx::Parallel(ctx) => {
+                ctx.get_allowing_stale(key, max_versions_behind).right_future()
+            }
+        }
+    }
+
     /// Compute many tasks that can be ran in parallel without depending on each other
     pub(crate) fn compute_many<'a, T: 'a>(
         &'a self,
@@ -264,6 +315,18 @@ impl ModernComputeCtx {
         }
     }
 
+    /// A context for computing `key` outside of the graph. Its deps are tracked separately from
+    /// the deps of this context, and are discarded when it is dropped.
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> ModernComputeCtx
+    where
+        K: Key,
+    {
+        ModernComputeCtx::Regular(match self {
+            ModernComputeCtx::Regular(ctx) => ctx.detached_for_key(key),
+            ModernComputeCtx::Parallel(ctx) => ctx.detached_for_key(key),
+        })
+    }
+
     /// Data that is static per the entire lifetime of Dice. These data are initialized at the
     /// time that Dice is initialized via the constructor.
     pub(crate) fn global_data(&self) -> &DiceData {
@@ -314,6 +377,13 @@ impl ModernComputeCtx {
             ModernComputeCtx::Parallel(ctx) => ctx.cycle_guard(),
         }
     }
+
+    pub(crate) fn report_progress<P: Send + Sync + 'static>(&self, progress: P) {
+        match self {
+            ModernComputeCtx::Regular(ctx) => ctx.report_progress(progress),
+            ModernComputeCtx::Parallel(ctx) => ctx.report_progress(progress),
+        }
+    }
 }
 
 impl ModernComputeCtx {
@@ -325,6 +395,7 @@ impl ModernComputeCtx {
                 ctx.ctx_data.async_evaluator.user_data.dupe(),
                 ctx.ctx_data.async_evaluator.dice.dupe(),
                 KeyComputingUserCycleDetectorData::Untracked,
+                KeyProgress::default(),
             )),
             ModernComputeCtx::Parallel(_) => {
                 unreachable!("parallel context should never be held by the base ctx")
@@ -349,6 +420,9 @@ struct CoreCtx {
     // data for the entire compute of a Key, including parallel computes
     #[allocative(skip)]
     evaluation_data: Mutex<EvaluationData>,
+    // where to report the progress of the compute of a Key, including parallel computes
+    #[allocative(skip)]
+    progress: KeyProgress,
 }
 
 impl PerComputeCtx {
@@ -358,6 +432,7 @@ impl PerComputeCtx {
         user_data: Arc<UserComputationData>,
         dice: Arc<DiceModern>,
         cycles: KeyComputingUserCycleDetectorData,
+        progress: KeyProgress,
     ) -> Self {
         Self {
             dep_trackers: Mutex::new(RecordingDepsTracker::new()),
@@ -370,6 +445,7 @@ impl PerComputeCtx {
                 parent_key,
                 cycles,
                 evaluation_data: Mutex::new(EvaluationData::none()),
+                progress,
             }),
         }
     }
@@ -388,6 +464,44 @@ impl PerComputeCtx {
         self.ctx_data.compute_opaque(key)
     }
 
+    pub(crate) fn is_cached<K>(&self, key: &K) -> impl Future<Output = bool>
+    where
+        K: Key,
+    {
+        self.ctx_data.is_cached(key)
+    }
+
+    pub(crate) fn dep_count<K>(&self, key: &K) -> impl Future<Output = Option<usize>>
+    where
+        K: Key,
+    {
+        self.ctx_data.dep_count(key)
+    }
+
+    pub(crate) fn invalidation_impact<K>(&self, key: &K) -> impl Future<Output = usize>
+    where
+        K: Key,
+    {
+        self.ctx_data.invalidation_impact(key)
+    }
+
+    pub(crate) async fn get_allowing_stale<K>(
+        &self,
+        key: &K,
+        max_versions_behind: usize,
+    ) -> Option<K::Value>
+    where
+        K: Key,
+    {
+        let (dice_key, value) = self.ctx_data.get_allowing_stale(key, max_versions_behind).await?;
+        // The value may differ from the one at this version, so nothing computed from it can be
+        // reused at later versions.
+        self.dep_trackers
+            .lock()
+            .record(dice_key, DiceValidity::Transient);
+        Some(value)
+    }
+
     /// Compute many tasks that can be ran in parallel without depending on each other
     pub(crate) fn compute_many<'a, T: 'a>(
         &'a self,
@@ -485,6 +599,13 @@ impl PerComputeCtx {
             .project(key, base_key, base, &self.dep_trackers)
     }
 
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> PerComputeCtx
+    where
+        K: Key,
+    {
+        self.ctx_data.detached_for_key(key)
+    }
+
     /// Data that is static per the entire lifetime of Dice. These data are initialized at the
     /// time that Dice is initialized via the constructor.
     pub(crate) fn global_data(&self) -> &DiceData {
@@ -518,6 +639,10 @@ impl PerComputeCtx {
         self.ctx_data.store_evaluation_data(value)
     }
 
+    pub(crate) fn report_progress<P: Send + Sync + 'static>(&self, progress: P) {
+        self.ctx_data.progress.report(progress)
+    }
+
     pub(crate) fn finalize(
         self,
     ) -> (
@@ -569,6 +694,52 @@ impl PerParallelComputeCtx {
             .expect("only alive while main PerComputeCtx is alive")
     }
 
+    pub(crate) fn is_cached<K>(&self, key: &K) -> impl Future<Output = bool>
+    where
+        K: Key,
+    {
+        self.ctx_data
+            .maybe_access(|ctx| ctx.is_cached(key))
+            .expect("only alive while main PerComputeCtx is alive")
+    }
+
+    pub(crate) fn dep_count<K>(&self, key: &K) -> impl Future<Output = Option<usize>>
+    where
+        K: Key,
+    {
+        self.ctx_data
+            .maybe_access(|ctx| ctx.dep_count(key))
+            .expect("only alive while main PerComputeCtx is alive")
+    }
+
+    pub(crate) fn invalidation_impact<K>(&self, key: &K) -> impl Future<Output = usize>
+    where
+        K: Key,
+    {
+        self.ctx_data
+            .maybe_access(|ctx| ctx.invalidation_impact(key))
+            .expect("only alive while main PerComputeCtx is alive")
+    }
+
+    pub(crate) async fn get_allowing_stale<K>(
+        &self,
+        key: &K,
+        max_versions_behind: usize,
+    ) -> Option<K::Value>
+    where
+        K: Key,
+    {
+        let (dice_key, value) = self
+            .ctx_data
+            .maybe_access(|ctx| ctx.get_allowing_stale(key, max_versions_behind))
+            .expect("only alive while main PerComputeCtx is alive")
+            .await?;
+        self.dep_trackers
+            .lock()
+            .record(dice_key, DiceValidity::Transient);
+        Some(value)
+    }
+
     /// Compute many tasks that can be ran in parallel without depending on each other
     pub(crate) fn compute_many<'a: 'i, 'i, T: 'a>(
         &'a self,
@@ -622,6 +793,15 @@ impl PerParallelComputeCtx {
             .expect("only alive while main PerComputeCtx is alive")
     }
 
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> PerComputeCtx
+    where
+        K: Key,
+    {
+        self.ctx_data
+            .maybe_access(|ctx| ctx.detached_for_key(key))
+            .expect("only alive while main PerComputeCtx is alive")
+    }
+
     /// Data that is static per the entire lifetime of Dice. These data are initialized at the
     /// time that Dice is initialized via the constructor.
     pub(crate) fn global_data(&self) -> &DiceData {
@@ -669,6 +849,12 @@ impl PerParallelComputeCtx {
             .expect("only alive while main PerComputeCtx is alive")
     }
 
+    pub(crate) fn report_progress<P: Send + Sync + 'static>(&self, progress: P) {
+        self.ctx_data
+            .maybe_access(|ctx| ctx.progress.report(progress))
+            .expect("only alive while main PerComputeCtx is alive")
+    }
+
     pub(crate) fn cycle_guard<T: UserCycleDetectorGuard>(&self) -> DiceResult<Option<&T>> {
         unsafe {
             // SAFETY: lifetime of the parallel context ensures we hold it less than the main
@@ -693,12 +879,25 @@ impl CoreCtx {
     where
         K: Key,
     {
+        self.async_evaluator
+            .dice
+            .key_type_stats
+            .record_request(K::key_type_name());
+
         let dice_key = self
             .async_evaluator
             .dice
             .key_index
             .index(CowDiceKeyHashed::key_ref(key));
 
+        let parent = match self.parent_key {
+            ParentKey::None => None,
+            ParentKey::Some(parent) => {
+                Some(self.async_evaluator.dice.key_index.get(parent) as &dyn Display)
+            }
+        };
+        self.async_evaluator.user_data.record_request(parent, key);
+
         self.async_evaluator
             .per_live_version_ctx
             .compute_opaque(
@@ -711,6 +910,113 @@ impl CoreCtx {
             .map_ok(move |res| (dice_key, res))
     }
 
+    /// Whether `key` has a valid value at this version. Values that would need their deps
+    /// checked first are not considered cached.
+    pub(crate) fn is_cached<K>(&self, key: &K) -> impl Future<Output = bool>
+    where
+        K: Key,
+    {
+        let dice_key = self
+            .async_evaluator
+            .dice
+            .key_index
+            .index(CowDiceKeyHashed::key_ref(key));
+
+        let (tx, rx) = oneshot::channel();
+        self.async_evaluator
+            .dice
+            .state_handle
+            .request(StateRequest::LookupKey {
+                key: VersionedGraphKey::new(self.get_version(), dice_key),
+                resp: tx,
+            });
+
+        rx.map(|res| matches!(res.unwrap(), VersionedGraphResult::Match(_)))
+    }
+
+    /// The number of direct dependencies of the valid value of `key` at this version.
+    pub(crate) fn dep_count<K>(&self, key: &K) -> impl Future<Output = Option<usize>>
+    where
+        K: Key,
+    {
+        let dice_key = self
+            .async_evaluator
+            .dice
+            .key_index
+            .index(CowDiceKeyHashed::key_ref(key));
+
+        let (tx, rx) = oneshot::channel();
+        self.async_evaluator
+            .dice
+            .state_handle
+            .request(StateRequest::GetDepCount {
+                key: VersionedGraphKey::new(self.get_version(), dice_key),
+                resp: tx,
+            });
+
+        rx.map(|res| res.unwrap())
+    }
+
+    /// The number of transitive rdeps of the valid value of `key` at this version.
+    pub(crate) fn invalidation_impact<K>(&self, key: &K) -> impl Future<Output = usize>
+    where
+        K: Key,
+    {
+        let dice_key = self
+            .async_evaluator
+            .dice
+            .key_index
+            .index(CowDiceKeyHashed::key_ref(key));
+
+        let (tx, rx) = oneshot::channel();
+        self.async_evaluator
+            .dice
+            .state_handle
+            .request(StateRequest::GetInvalidationImpact {
+                key: VersionedGraphKey::new(self.get_version(), dice_key),
+                resp: tx,
+            });
+
+        rx.map(|res| res.unwrap())
+    }
+
+    /// The value of `key` if it was valid at most `max_versions_behind` versions ago, along with
+    /// the index of `key` for recording the dependency on it.
+    pub(crate) fn get_allowing_stale<K>(
+        &self,
+        key: &K,
+        max_versions_behind: usize,
+    ) -> impl Future<Output = Option<(DiceKey, K::Value)>>
+    where
+        K: Key,
+    {
+        let dice_key = self
+            .async_evaluator
+            .dice
+            .key_index
+            .index(CowDiceKeyHashed::key_ref(key));
+
+        let (tx, rx) = oneshot::channel();
+        self.async_evaluator
+            .dice
+            .state_handle
+            .request(StateRequest::LookupKeyAllowingStale {
+                key: VersionedGraphKey::new(self.get_version(), dice_key),
+                max_versions_behind,
+                resp: tx,
+            });
+
+        rx.map(move |res| {
+            res.unwrap().map(|value| {
+                let value = value
+                    .downcast_ref::<K::Value>()
+                    .expect("Type mismatch when computing key")
+                    .dupe();
+                (dice_key, value)
+            })
+        })
+    }
+
     /// Compute "projection" based on deriving value
     pub(crate) fn project<K>(
         &self,
@@ -759,6 +1065,28 @@ impl CoreCtx {
             .dupe())
     }
 
+    /// A context for computing `key` outside of the graph, as if it were computed as part of
+    /// this transaction, but with its own deps.
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> PerComputeCtx
+    where
+        K: Key,
+    {
+        let dice_key = self
+            .async_evaluator
+            .dice
+            .key_index
+            .index(CowDiceKeyHashed::key_ref(key));
+
+        PerComputeCtx::new(
+            ParentKey::Some(dice_key),
+            self.async_evaluator.per_live_version_ctx.dupe(),
+            self.async_evaluator.user_data.dupe(),
+            self.async_evaluator.dice.dupe(),
+            KeyComputingUserCycleDetectorData::Untracked,
+            KeyProgress::default(),
+        )
+    }
+
     /// Data that is static per the entire lifetime of Dice. These data are initialized at the
     /// time that Dice is initialized via the constructor.
     pub(crate) fn global_data(&self) -> &DiceData {
@@ -809,6 +1137,10 @@ pub(crate) struct SharedLiveTransactionCtx {
     version_epoch: VersionEpoch,
     #[derivative(Debug = "ignore")]
     cache: SharedCache,
+    /// Where to record recomputed keys that stopped at the early cutoff, if the transaction was
+    /// committed with a report.
+    #[derivative(Debug = "ignore")]
+    early_cutoff: Option<EarlyCutoffs>,
 }
 
 #[allow(clippy::manual_async_fn, unused)]
@@ -818,9 +1150,21 @@ impl SharedLiveTransactionCtx {
             version: v,
             version_epoch,
             cache,
+            early_cutoff: None,
         }
     }
 
+    pub(crate) fn with_early_cutoff(self, early_cutoff: EarlyCutoffs) -> Self {
+        Self {
+            early_cutoff: Some(early_cutoff),
+            ..self
+        }
+    }
+
+    pub(crate) fn early_cutoff(&self) -> Option<&EarlyCutoffs> {
+        self.early_cutoff.as_ref()
+    }
+
     /// Compute "opaque" value where the value is only accessible via projections.
     /// Projections allow accessing derived results from the "opaque" value,
     /// where the dependency of reading a projection is the projection value rather
@@ -832,6 +1176,7 @@ impl SharedLiveTransactionCtx {
         eval: &AsyncEvaluator,
         cycles: UserCycleDetectorData,
     ) -> impl Future<Output = CancellableResult<DiceComputedValue>> {
+        let priority = eval.user_data.priority;
         match self.cache.get(key) {
             DiceTaskRef::Computed(result) => {
                 DicePromise::ready(result).left_future()
@@ -841,6 +1186,7 @@ impl SharedLiveTransactionCtx {
                     MaybeCancelled::Ok(promise) => {
                         debug!(msg = "shared state is waiting on existing task", k = ?key, v = ?self.version, v_epoch = ?self.version_epoch);
 
+                        occupied.get().waited_on(priority);
                         promise
                     },
                     MaybeCancelled::Cancelled => {
@@ -865,6 +1211,7 @@ impl SharedLiveTransactionCtx {
                             )
                         });
 
+                        occupied.get().waited_on(priority);
                         occupied
                             .get()
                             .depended_on_by(parent_key)
@@ -890,6 +1237,7 @@ impl SharedLiveTransactionCtx {
                     None,
                 );
 
+                task.waited_on(priority);
                 let fut = task
                     .depended_on_by(parent_key)
                     .not_cancelled()
@@ -1001,6 +1349,7 @@ impl EvaluationData {
 
 #[cfg(test)]
 pub(crate) mod testing {
+    use crate::api::user_data::UserComputationData;
     use crate::impls::cache::DiceTaskRef;
     use crate::impls::core::versions::VersionEpoch;
     use crate::impls::ctx::SharedLiveTransactionCtx;
@@ -1020,7 +1369,9 @@ pub(crate) mod testing {
                 .depended_on_by(ParentKey::None)
                 .not_cancelled()
                 .expect("just created")
-                .sync_get_or_complete(|| DiceSyncResult::testing(v));
+                .sync_get_or_complete(&UserComputationData::default(), || {
+                    DiceSyncResult::testing(v)
+                });
 
             match self.cache.get(k) {
                 DiceTaskRef::Computed(_) => panic!("cannot inject already computed task"),
@@ -9,6 +9,9 @@
 
 use std::fmt::Debug;
 use std::future::Future;
+use std::io;
+use std::io::Read;
+use std::io::Write;
 use std::sync::Arc;
 
 use allocative::Allocative;
@@ -16,21 +19,41 @@ use dupe::Dupe;
 
 use crate::api::cycles::DetectCycles;
 use crate::api::data::DiceData;
+use crate::api::evict::EvictionObserver;
+use crate::api::executor::DiceExecutor;
+use crate::api::persist::PersistCacheError;
+use crate::api::persist::PersistableKey;
+use crate::api::spill::SpillConfig;
+use crate::api::storage_type::StorageType;
 use crate::api::user_data::UserComputationData;
+use crate::impls::content_key::ContentKeyCache;
+use crate::impls::core::graph::storage::EvictionHook;
 use crate::impls::core::state::init_state;
 use crate::impls::core::state::CoreStateHandle;
 use crate::impls::core::state::StateRequest;
+use crate::impls::key::DiceKey;
+use crate::impls::key::DiceKeyErased;
 use crate::impls::key_index::DiceKeyIndex;
+use crate::impls::key_type_stats::KeyTypeStats;
 use crate::impls::transaction::TransactionUpdater;
+use crate::impls::value::DiceKeyValue;
+use crate::impls::value::DiceValidValue;
+use crate::impls::value::DiceValidity;
+use crate::impls::value::MaybeValidDiceValue;
 use crate::introspection::graph::GraphIntrospectable;
 use crate::introspection::graph::ModernIntrospectable;
 use crate::metrics::Metrics;
+use crate::versions::VersionNumber;
+use crate::HashMap;
 
 #[derive(Allocative)]
 pub(crate) struct DiceModern {
-    pub(crate) key_index: DiceKeyIndex,
+    pub(crate) key_index: Arc<DiceKeyIndex>,
     pub(crate) state_handle: CoreStateHandle,
     pub(crate) global_data: DiceData,
+    pub(crate) content_keys: Arc<ContentKeyCache>,
+    #[allocative(skip)]
+    pub(crate) key_type_stats: KeyTypeStats,
 }
 
 impl Debug for DiceModern {
@@ -57,12 +80,34 @@ impl DiceModernDataBuilder {
 
 impl DiceModern {
     pub(crate) fn new(global_data: DiceData) -> Arc<Self> {
-        let state_handle = init_state();
+        let key_index: Arc<DiceKeyIndex> = Default::default();
+        let content_keys: Arc<ContentKeyCache> = Default::default();
+        let observer = global_data
+            .get::<EvictionObserver>()
+            .ok()
+            .map(|observer| observer.dupe());
+        let on_evict = {
+            let key_index = key_index.dupe();
+            let content_keys = content_keys.dupe();
+            Arc::new(move |key: DiceKey, version: VersionNumber| {
+                // Projections are not `Key`s, and are not reported.
+                if let DiceKeyErased::Key(k) = key_index.get(key) {
+                    k.evicted(&content_keys);
+                    if let Some(observer) = &observer {
+                        observer.on_evict(k.as_key_description(), version);
+                    }
+                }
+            }) as EvictionHook
+        };
+        let spill = global_data.get::<SpillConfig>().ok().cloned();
+        let state_handle = init_state(Some(on_evict), spill);
 
         Arc::new(DiceModern {
-            key_index: Default::default(),
+            key_index,
             state_handle,
             global_data,
+            content_keys,
+            key_type_stats: KeyTypeStats::default(),
         })
     }
 
@@ -75,7 +120,13 @@ impl DiceModern {
         self.updater_with_data(UserComputationData::new())
     }
 
-    pub fn updater_with_data(self: &Arc<Self>, extra: UserComputationData) -> TransactionUpdater {
+    pub fn updater_with_data(
+        self: &Arc<Self>,
+        mut extra: UserComputationData,
+    ) -> TransactionUpdater {
+        if let Ok(executor) = self.global_data.get::<DiceExecutor>() {
+            executor.apply(&mut extra);
+        }
         TransactionUpdater::new(self.dupe(), Arc::new(extra))
     }
 
@@ -87,7 +138,10 @@ impl DiceModern {
 
         // Modern dice can just run on a blocking runtime and block waiting for the channel.
         // This is safe since the processing dice thread is dedicated, and never awaits any other tasks.
-        tokio::task::block_in_place(|| rx.blocking_recv().unwrap())
+        let mut metrics = tokio::task::block_in_place(|| rx.blocking_recv().unwrap());
+        // Key type stats are recorded by the computations, not by the core state.
+        metrics.key_types = self.key_type_stats.snapshot();
+        metrics
     }
 
     pub fn to_introspectable(&self) -> GraphIntrospectable {
@@ -118,6 +172,133 @@ impl DiceModern {
         &CYCLES
     }
 
+    pub(crate) async fn persist_cache<K>(&self, mut writer: impl Write) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        self.state_handle
+            .request(StateRequest::ExportValues { resp: tx });
+
+        let mut values: HashMap<DiceKey, (Arc<K>, MaybeValidDiceValue, Vec<DiceKey>)> = rx
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|(key, value, deps)| {
+                let persisted = self.key_index.get(key).dupe().downcast::<K>()?;
+                Some((key, (persisted, value, deps)))
+            })
+            .collect();
+
+        // A value is only persisted with all of its deps, so that it's invalidated by them once
+        // loaded. Drop the values depending on anything that isn't persisted, until none do.
+        loop {
+            let incomplete = values
+                .iter()
+                .filter(|(_, (_, _, deps))| deps.iter().any(|dep| !values.contains_key(dep)))
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            if incomplete.is_empty() {
+                break;
+            }
+            for key in incomplete {
+                values.remove(&key);
+            }
+        }
+
+        // Write the deps of each value before it, so that they are referred to by their position.
+        let mut keys = values.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        let mut positions = HashMap::default();
+        for key in keys {
+            let mut stack = vec![(key, false)];
+            while let Some((key, deps_written)) = stack.pop() {
+                if positions.contains_key(&key) {
+                    continue;
+                }
+                let (persisted, value, deps) = &values[&key];
+                if !deps_written {
+                    stack.push((key, true));
+                    stack.extend(deps.iter().map(|dep| (*dep, false)));
+                    continue;
+                }
+
+                let value = value
+                    .downcast_maybe_transient::<K::Value>()
+                    .expect("Type mismatch when persisting key");
+                let bytes = persisted.to_bytes(value);
+                writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+                writer.write_all(&bytes)?;
+                writer.write_all(&(deps.len() as u32).to_le_bytes())?;
+                for dep in deps {
+                    writer.write_all(&(positions[dep] as u32).to_le_bytes())?;
+                }
+                positions.insert(key, positions.len());
+            }
+        }
+
+        Ok(positions.len())
+    }
+
+    pub(crate) async fn load_cache<K>(&self, mut reader: impl Read) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        let invalid = || PersistCacheError::InvalidEntry(K::key_type_name());
+
+        let mut values: Vec<(DiceKey, DiceValidValue, Vec<DiceKey>)> = Vec::new();
+        loop {
+            let mut len = [0; 8];
+            match reader.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            // The length is read from disk, so don't trust it for allocating, and read no more
+            // than what's there.
+            let len = u64::from_le_bytes(len);
+            let mut bytes = Vec::new();
+            (&mut reader).take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(invalid().into());
+            }
+
+            let mut dep_count = [0; 4];
+            reader.read_exact(&mut dep_count)?;
+            let mut deps = Vec::new();
+            for _ in 0..u32::from_le_bytes(dep_count) {
+                let mut position = [0; 4];
+                reader.read_exact(&mut position)?;
+                let (dep, _, _) = values
+                    .get(u32::from_le_bytes(position) as usize)
+                    .ok_or_else(invalid)?;
+                deps.push(*dep);
+            }
+
+            let (key, value) = K::from_bytes(&bytes).ok_or_else(invalid)?;
+            // Transient values are never persisted.
+            let value = MaybeValidDiceValue::new(
+                Arc::new(DiceKeyValue::<K>::new(value)),
+                DiceValidity::Valid,
+            )
+            .into_valid_value()
+            .map_err(|_| invalid())?;
+            values.push((self.key_index.index_key(key), value, deps));
+        }
+
+        let count = values.len();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.state_handle.request(StateRequest::LoadValues {
+            values,
+            storage: StorageType::for_key::<K>(),
+            resp: tx,
+        });
+        rx.await.unwrap();
+
+        Ok(count)
+    }
+
     /// Wait until all active versions have exited.
     pub fn wait_for_idle(&self) -> impl Future<Output = ()> + 'static {
         let (tx, rx) = tokio::sync::oneshot::channel();
@@ -169,5 +350,14 @@ pub(crate) mod testing {
             });
             rx.await.unwrap()
         }
+
+        /// Waits until the values to spill are written to disk.
+        pub(crate) async fn testing_wait_for_spills(&self) {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+
+            self.state_handle
+                .request(StateRequest::TestingWaitForSpills { resp: tx });
+            rx.await.unwrap()
+        }
     }
 }
@@ -8,6 +8,7 @@
  */
 
 use std::sync::Arc;
+use std::time::Instant;
 
 use allocative::Allocative;
 use dupe::Dupe;
@@ -60,6 +61,8 @@ impl AsyncEvaluator {
 
         match key_erased {
             DiceKeyErased::Key(key_dyn) => {
+                self.user_data.record_computing(key_dyn);
+
                 let mut new_ctx = DiceComputations(DiceComputationsImpl::Modern(
                     ModernComputeCtx::Regular(PerComputeCtx::new(
                         ParentKey::Some(key), // within this key's compute, this key is the parent
@@ -67,12 +70,24 @@ impl AsyncEvaluator {
                         self.user_data.dupe(),
                         self.dice.dupe(),
                         cycles,
+                        self.user_data.progress.for_key(key_dyn.as_any()),
                     )),
                 ));
 
+                let running = self.user_data.graph_stats.computation_started();
+                let start = Instant::now();
                 let value = key_dyn
-                    .compute(&mut new_ctx, &state.cancellation_ctx().into_compatible())
+                    .compute(
+                        &self.dice.content_keys,
+                        &mut new_ctx,
+                        &state.cancellation_ctx().into_compatible(),
+                    )
                     .await;
+                let elapsed = start.elapsed();
+                self.dice
+                    .key_type_stats
+                    .record_computation(key_dyn.key_type_name(), elapsed);
+                self.user_data.record_computed(key_dyn, elapsed);
                 let ((deps, dep_validity), evaluation_data, cycles) = match new_ctx.0 {
                     DiceComputationsImpl::Legacy(_) => {
                         unreachable!("modern dice created above")
@@ -82,6 +97,7 @@ impl AsyncEvaluator {
                         .expect("created regular above")
                         .finalize(),
                 };
+                running.finished(deps.len());
 
                 let activation = ActivationInfo::new(
                     &self.dice.key_index,
@@ -162,6 +178,10 @@ impl SyncEvaluator {
         }
     }
 
+    pub(crate) fn user_data(&self) -> &Arc<UserComputationData> {
+        &self.user_data
+    }
+
     pub(crate) fn evaluate(&self, key: DiceKey) -> KeyEvaluationResult {
         let key_erased = self.dice.key_index.get(key);
         match key_erased {
@@ -118,7 +118,8 @@ impl IncrementalEngine {
         eval: SyncEvaluator,
         event_dispatcher: DiceEventDispatcher,
     ) -> CancellableResult<DiceComputedValue> {
-        promise.sync_get_or_complete(|| {
+        let user_data = eval.user_data().dupe();
+        promise.sync_get_or_complete(&user_data, || {
             event_dispatcher.started(k);
 
             debug!(msg = "running projection");
@@ -266,6 +267,7 @@ impl IncrementalEngine {
         let res = {
             match eval_result.value.into_valid_value() {
                 Ok(value) => {
+                    let computed = value.dupe();
                     let (tx, rx) = oneshot::channel();
                     self.state.request(StateRequest::UpdateComputed {
                         key: VersionedGraphKey::new(v, k),
@@ -276,7 +278,17 @@ impl IncrementalEngine {
                         resp: tx,
                     });
 
-                    rx.await.unwrap()
+                    let res = rx.await.unwrap();
+                    if let (Some(early_cutoff), Ok(res)) =
+                        (eval.per_live_version_ctx.early_cutoff(), &res)
+                    {
+                        // the state hands back the instance it already stored when the computed
+                        // value is equal to it, which stops the invalidation here.
+                        if !res.value().instance_equal(&computed) {
+                            early_cutoff.record(eval.dice.key_index.get(k));
+                        }
+                    }
+                    res
                 }
                 Err(value) => Ok(DiceComputedValue::new(
                     value,
@@ -287,7 +287,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
     drop(guard);
     drop(ctx);
 
@@ -332,7 +332,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     ctx.inject(
@@ -377,7 +377,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let new_v = rx.await.unwrap();
+    let (new_v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
 
@@ -481,7 +481,7 @@ async fn when_equal_return_same_instance() -> anyhow::Result<()> {
         changes: vec![],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     let eval = AsyncEvaluator {
@@ -509,7 +509,7 @@ async fn when_equal_return_same_instance() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::Invalidate)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     let eval = AsyncEvaluator {
@@ -1103,7 +1103,7 @@ async fn soft_dirty(dice: &std::sync::Arc<DiceModern>, key: DiceKey) -> VersionN
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    rx.await.unwrap()
+    rx.await.unwrap().0
 }
 
 fn update_computed_value(
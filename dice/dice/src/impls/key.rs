@@ -24,9 +24,11 @@ use more_futures::cancellation::CancellationContext;
 
 use crate::api::computations::DiceComputations;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
 use crate::api::projection::DiceProjectionComputations;
 use crate::api::projection::ProjectionKey;
 use crate::api::storage_type::StorageType;
+use crate::impls::content_key::ContentKeyCache;
 use crate::impls::hash;
 use crate::impls::hash::key_hash;
 use crate::impls::value::DiceKeyValue;
@@ -256,10 +258,14 @@ impl<'a> Display for CowDiceKeyHashed<'a> {
 pub(crate) trait DiceKeyDyn: Allocative + Display + Send + Sync + 'static {
     async fn compute(
         &self,
+        content_keys: &ContentKeyCache,
         ctx: &mut DiceComputations,
         cancellations: &CancellationContext,
     ) -> Arc<dyn DiceValueDyn>;
 
+    /// Tells the `ContentKeyCache` that a value of this key was evicted from the graph.
+    fn evicted(&self, content_keys: &ContentKeyCache);
+
     fn cmp_any(&self) -> PartialEqAny;
 
     fn hash(&self) -> u64;
@@ -271,6 +277,10 @@ pub(crate) trait DiceKeyDyn: Allocative + Display + Send + Sync + 'static {
     fn key_type_name(&self) -> &'static str;
 
     fn storage_type(&self) -> StorageType;
+
+    fn is_injected(&self) -> bool;
+
+    fn as_key_description(&self) -> &dyn KeyDescription;
 }
 
 #[async_trait]
@@ -280,13 +290,18 @@ where
 {
     async fn compute(
         &self,
+        content_keys: &ContentKeyCache,
         ctx: &mut DiceComputations,
         cancellations: &CancellationContext,
     ) -> Arc<dyn DiceValueDyn> {
-        let value = self.compute(ctx, cancellations).await;
+        let value = content_keys.compute(self, ctx, cancellations).await;
         Arc::new(DiceKeyValue::<K>::new(value))
     }
 
+    fn evicted(&self, content_keys: &ContentKeyCache) {
+        content_keys.evicted(self)
+    }
+
     fn cmp_any(&self) -> PartialEqAny {
         PartialEqAny::new(self)
     }
@@ -308,7 +323,15 @@ where
     }
 
     fn storage_type(&self) -> StorageType {
-        K::storage_type()
+        StorageType::for_key::<K>()
+    }
+
+    fn is_injected(&self) -> bool {
+        K::is_injected()
+    }
+
+    fn as_key_description(&self) -> &dyn KeyDescription {
+        self
     }
 }
 
@@ -319,6 +342,9 @@ pub(crate) trait DiceProjectionDyn: Allocative + Display + Send + Sync + 'static
         ctx: &DiceProjectionComputations,
     ) -> Arc<dyn DiceValueDyn>;
 
+    /// Tells the `ContentKeyCache` that a value of this key was evicted from the graph.
+    fn evicted(&self, content_keys: &ContentKeyCache);
+
     fn cmp_any(&self) -> PartialEqAny;
 
     fn hash(&self) -> u64;
@@ -150,6 +150,29 @@ impl DiceKeyIndex {
         self.index(CowDiceKeyHashed::key(key))
     }
 
+    /// All keys indexed so far.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = (DiceKey, &DiceKeyErased)> {
+        self.shards
+            .iter()
+            .enumerate()
+            .flat_map(|(shard_index, shard)| {
+                shard
+                    .key_by_index
+                    .iter()
+                    .enumerate()
+                    .map(move |(index_in_shard, key)| {
+                        (
+                            DiceKeyUnpacked {
+                                shard_index: shard_index as u32,
+                                index_in_shard: index_in_shard as u32,
+                            }
+                            .pack(),
+                            key,
+                        )
+                    })
+            })
+    }
+
     pub(crate) fn get(&self, key: DiceKey) -> &DiceKeyErased {
         let unpack = DiceKeyUnpacked::unpack(key);
         self.shards[unpack.shard_index as usize]
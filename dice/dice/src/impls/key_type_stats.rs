@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::metrics::KeyTypeMetrics;
+
+#[derive(Default)]
+struct KeyTypeCounters {
+    requests: AtomicU64,
+    computations: AtomicU64,
+    compute_nanos: AtomicU64,
+}
+
+/// Aggregate metrics of the requests and computations of each key type, by
+/// `Key::key_type_name`. Projections are not recorded.
+#[derive(Default)]
+pub(crate) struct KeyTypeStats {
+    stats: DashMap<&'static str, KeyTypeCounters>,
+}
+
+impl KeyTypeStats {
+    /// Runs `f` on the counters of `key_type`. Only the first use of a key type takes a write
+    /// lock, so recording is cheap on the hot path.
+    fn with_counters(&self, key_type: &'static str, f: impl FnOnce(&KeyTypeCounters)) {
+        match self.stats.get(key_type) {
+            Some(counters) => f(&counters),
+            None => f(&self.stats.entry(key_type).or_default()),
+        }
+    }
+
+    pub(crate) fn record_request(&self, key_type: &'static str) {
+        self.with_counters(key_type, |counters| {
+            counters.requests.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    pub(crate) fn record_computation(&self, key_type: &'static str, duration: Duration) {
+        self.with_counters(key_type, |counters| {
+            counters.computations.fetch_add(1, Ordering::Relaxed);
+            counters
+                .compute_nanos
+                .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        })
+    }
+
+    pub(crate) fn snapshot(&self) -> BTreeMap<&'static str, KeyTypeMetrics> {
+        self.stats
+            .iter()
+            .map(|entry| {
+                let counters = entry.value();
+                let metrics = KeyTypeMetrics {
+                    requests: counters.requests.load(Ordering::Relaxed),
+                    computations: counters.computations.load(Ordering::Relaxed),
+                    compute_duration: Duration::from_nanos(
+                        counters.compute_nanos.load(Ordering::Relaxed),
+                    ),
+                };
+                (*entry.key(), metrics)
+            })
+            .collect()
+    }
+}
@@ -8,6 +8,7 @@
  */
 
 pub(crate) mod cache;
+pub(crate) mod content_key;
 pub(crate) mod core;
 pub(crate) mod ctx;
 mod dep_trackers;
@@ -18,6 +19,7 @@ mod hash;
 pub(crate) mod incremental;
 pub(crate) mod key;
 mod key_index;
+pub(crate) mod key_type_stats;
 pub(crate) mod opaque;
 pub(crate) mod task;
 #[cfg(test)]
@@ -58,6 +58,13 @@ where
         )
     }
 
+    /// The value, without recording a dependency on `K`.
+    pub(crate) fn value(&self) -> &K::Value {
+        self.derive_from
+            .downcast_maybe_transient::<K::Value>()
+            .expect("type mismatch")
+    }
+
     /// Get a value and record parent computation dependency on `K`.
     pub(crate) fn into_value(self) -> K::Value {
         self.parent_computation
@@ -133,4 +140,39 @@ mod tests {
             &[DiceKey { index: 0 }].into_iter().collect()
         );
     }
+
+    #[derive(Allocative, Clone, Hash, Eq, PartialEq, Debug, Display)]
+    struct NeverFinishes;
+
+    #[async_trait]
+    impl Key for NeverFinishes {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            futures::future::pending().await
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_opaque_computation_records_no_deps() {
+        let dice = DiceModern::new(DiceData::new());
+
+        let ctx = dice.updater().commit().await;
+
+        {
+            let mut fut = Box::pin(ctx.compute_opaque(&NeverFinishes));
+            // poll once so that the computation is spawned and in flight when we drop it.
+            assert!(futures::poll!(fut.as_mut()).is_pending());
+        }
+
+        assert_eq!(ctx.dep_trackers().recorded_deps(), &HashSet::default());
+    }
 }
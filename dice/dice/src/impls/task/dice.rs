@@ -27,6 +27,8 @@ use parking_lot::MutexGuard;
 use parking_lot::RwLock;
 use slab::Slab;
 
+use crate::api::priority::DicePriority;
+use crate::api::priority::PreemptionTask;
 use crate::arc::Arc;
 use crate::impls::key::DiceKey;
 use crate::impls::key::ParentKey;
@@ -67,6 +69,9 @@ pub(crate) struct DiceTask {
     /// Handle to cancel the spawned task
     #[allocative(skip)]
     pub(super) cancellations: Cancellations,
+    /// The scheduling of the spawned task, if DICE has a `DicePreemption`.
+    #[allocative(skip)]
+    pub(super) preemption: Option<PreemptionTask>,
 }
 
 pub(super) struct DiceTaskInternal {
@@ -218,6 +223,18 @@ impl DiceTask {
         self.cancellations.cancel(&lock);
     }
 
+    pub(crate) fn with_preemption(self, preemption: Option<PreemptionTask>) -> Self {
+        Self { preemption, ..self }
+    }
+
+    /// Records that a computation of a transaction with `priority` waits for this task, see
+    /// `PreemptionTask::waited_on`.
+    pub(crate) fn waited_on(&self, priority: DicePriority) {
+        if let Some(preemption) = &self.preemption {
+            preemption.waited_on(priority);
+        }
+    }
+
     pub(crate) fn await_termination(&self) -> TerminationObserver {
         let mut critical = self.internal.critical.lock();
         match &mut critical.termination_observers {
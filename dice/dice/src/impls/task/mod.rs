@@ -59,6 +59,7 @@ pub(crate) fn spawn_dice_task<S>(
     DiceTask {
         internal,
         cancellations: Cancellations::new(cancellation_handle),
+        preemption: None,
     }
 }
 
@@ -70,6 +71,7 @@ pub(crate) unsafe fn sync_dice_task(key: DiceKey) -> DiceTask {
     DiceTask {
         internal,
         cancellations: Cancellations::not_cancellable(),
+        preemption: None,
     }
 }
 
@@ -9,6 +9,7 @@
 
 //! Promise is a handle to a DiceTask that will be completed
 
+use std::any::Any;
 use std::future::Future;
 use std::mem;
 use std::pin::Pin;
@@ -18,7 +19,9 @@ use std::task::Poll;
 use dupe::Dupe;
 use futures::future::BoxFuture;
 use futures::task::AtomicWaker;
+use futures::FutureExt;
 
+use crate::api::user_data::UserComputationData;
 use crate::arc::Arc;
 use crate::impls::task::dice::Cancellations;
 use crate::impls::task::dice::DiceTaskInternal;
@@ -61,8 +64,6 @@ pub(crate) struct DiceSyncResult {
 impl DiceSyncResult {
     #[cfg(test)]
     pub(crate) fn testing(v: DiceComputedValue) -> Self {
-        use futures::FutureExt;
-
         Self {
             sync_result: v.dupe(),
             state_future: futures::future::ready(Ok(v)).boxed(),
@@ -98,9 +99,10 @@ impl DicePromise {
     }
 
     /// Get the value if already complete, or complete it. Note that `f` may run even if the result
-    /// is not used.
+    /// is not used. The rest of the completion is spawned with the spawner of `user_data`.
     pub(crate) fn sync_get_or_complete(
         self,
+        user_data: &UserComputationData,
         f: impl FnOnce() -> DiceSyncResult,
     ) -> CancellableResult<DiceComputedValue> {
         match &self.0 {
@@ -142,10 +144,10 @@ impl DicePromise {
                         result
                     };
 
-                    tokio::spawn({
-                        let future = result.state_future;
-                        let internals = task_internal.dupe();
-
+                    let future = result.state_future;
+                    let internals = task_internal.dupe();
+                    drop(user_data.spawner.spawn(
+                        user_data,
                         async move {
                             let res = future.await;
 
@@ -164,9 +166,10 @@ impl DicePromise {
                             }
 
                             // stop storing the sync value since the async one is done
-                            sync_value.take()
+                            Box::new(sync_value.take()) as Box<dyn Any + Send>
                         }
-                    });
+                        .boxed(),
+                    ));
 
                     Ok(result.sync_result)
                 }
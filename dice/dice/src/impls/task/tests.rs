@@ -29,6 +29,7 @@ use tokio::sync::Semaphore;
 
 use crate::api::computations::DiceComputations;
 use crate::api::key::Key;
+use crate::api::user_data::UserComputationData;
 use crate::arc::Arc;
 use crate::impls::core::graph::history::CellHistory;
 use crate::impls::key::DiceKey;
@@ -311,7 +312,7 @@ async fn sync_complete_task_completes_promises() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| DiceSyncResult::testing(DiceComputedValue::new(
+            .sync_get_or_complete(&UserComputationData::default(), || DiceSyncResult::testing(DiceComputedValue::new(
                 MaybeValidDiceValue::valid(DiceValidValue::testing_new(DiceKeyValue::<K>::new(2))),
                 Arc::new(CellHistory::empty())
             )))?
@@ -369,7 +370,7 @@ async fn sync_complete_task_with_future() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| DiceSyncResult {
+            .sync_get_or_complete(&UserComputationData::default(), || DiceSyncResult {
                 sync_result: v_sync,
                 state_future: rx
                     .map(|res| { res.map_err(|_| Cancelled).flatten() })
@@ -384,7 +385,7 @@ async fn sync_complete_task_with_future() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| panic!("should not run"))?
+            .sync_get_or_complete(&UserComputationData::default(), || panic!("should not run"))?
             .value()
             .equality(&DiceValidValue::testing_new(DiceKeyValue::<K>::new(2)))
     );
@@ -471,7 +472,7 @@ async fn sync_complete_task_wakes_waiters() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| DiceSyncResult::testing(DiceComputedValue::new(
+            .sync_get_or_complete(&UserComputationData::default(), || DiceSyncResult::testing(DiceComputedValue::new(
                 MaybeValidDiceValue::valid(DiceValidValue::testing_new(DiceKeyValue::<K>::new(1))),
                 Arc::new(CellHistory::empty())
             )))?
@@ -530,7 +531,7 @@ async fn sync_complete_unfinished_spawned_task() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| DiceSyncResult::testing(DiceComputedValue::new(
+            .sync_get_or_complete(&UserComputationData::default(), || DiceSyncResult::testing(DiceComputedValue::new(
                 MaybeValidDiceValue::valid(DiceValidValue::testing_new(DiceKeyValue::<K>::new(1))),
                 Arc::new(CellHistory::empty())
             )))?
@@ -598,7 +599,7 @@ async fn sync_complete_finished_spawned_task() -> anyhow::Result<()> {
         task.depended_on_by(ParentKey::None)
             .not_cancelled()
             .unwrap()
-            .sync_get_or_complete(|| DiceSyncResult::testing(DiceComputedValue::new(
+            .sync_get_or_complete(&UserComputationData::default(), || DiceSyncResult::testing(DiceComputedValue::new(
                 MaybeValidDiceValue::valid(DiceValidValue::testing_new(DiceKeyValue::<K>::new(1))),
                 Arc::new(CellHistory::empty())
             )))?
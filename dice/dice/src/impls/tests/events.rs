@@ -21,6 +21,7 @@ use crate::api::cycles::DetectCycles;
 use crate::api::dice::Dice;
 use crate::api::key::Key;
 use crate::api::user_data::UserComputationData;
+use crate::versions::VersionNumber;
 use crate::DiceDataBuilder;
 use crate::DiceEvent;
 use crate::DiceEventListener;
@@ -37,6 +38,15 @@ impl DiceEventListener for Tracker {
     }
 }
 
+impl Tracker {
+    /// Events about individual keys, ignoring events about the transaction as a whole.
+    fn key_events(&self) -> Vec<DiceEvent> {
+        let mut state = self.state.lock().unwrap();
+        state.retain(|e| !matches!(e, DiceEvent::InvalidationWave { .. }));
+        std::mem::take(&mut *state)
+    }
+}
+
 #[derive(Clone, Dupe, Debug, Display, Eq, Hash, PartialEq, Allocative)]
 #[display(fmt = "{:?}", self)]
 struct Injected;
@@ -110,7 +120,7 @@ async fn test_events_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
         transaction.compute(&Stage1).await?;
 
         assert_eq!(
-            &*tracker.state.lock().unwrap(),
+            tracker.key_events(),
             &[
                 DiceEvent::Started { key_type: "Stage1" },
                 DiceEvent::Started { key_type: "Stage0" },
@@ -136,7 +146,7 @@ async fn test_events_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
         transaction.compute(&Stage1).await?;
 
         assert_eq!(
-            &*tracker.state.lock().unwrap(),
+            tracker.key_events(),
             &[
                 DiceEvent::CheckDepsStarted { key_type: "Stage1" },
                 DiceEvent::CheckDepsStarted { key_type: "Stage0" },
@@ -151,6 +161,75 @@ async fn test_events_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Clone, Dupe, Debug, Display, PartialEq, Eq, Hash, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct Dependent(usize);
+
+#[async_trait]
+impl Key for Dependent {
+    type Value = i32;
+
+    async fn compute(
+        &self,
+        ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        ctx.compute(&Injected).await.unwrap() + self.0 as i32
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+async fn test_invalidation_wave_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
+    let dice = builder.build(DetectCycles::Enabled);
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Injected, 1)])?;
+    let transaction = updater.commit().await;
+    for i in 0..5 {
+        transaction.compute(&Dependent(i)).await?;
+    }
+    drop(transaction);
+
+    let tracker = Arc::new(Tracker::default());
+    let data = UserComputationData {
+        tracker: tracker.dupe(),
+        ..Default::default()
+    };
+
+    let mut updater = dice.updater_with_data(data);
+    updater.changed_to(vec![(Injected, 2)])?;
+    let _transaction = updater.commit().await;
+
+    match tracker.state.lock().unwrap().as_slice() {
+        [
+            DiceEvent::InvalidationWave {
+                version,
+                invalidated_count,
+                ..
+            },
+        ] => {
+            assert_eq!(*version, VersionNumber::new(2));
+            assert_eq!(*invalidated_count, 5);
+        }
+        events => panic!("expected a single invalidation wave, got {:?}", events),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalidation_wave_legacy() -> anyhow::Result<()> {
+    test_invalidation_wave_impl(Dice::builder()).await
+}
+
+#[tokio::test]
+async fn test_invalidation_wave_modern() -> anyhow::Result<()> {
+    test_invalidation_wave_impl(Dice::modern()).await
+}
+
 #[tokio::test]
 async fn test_events_legacy() -> anyhow::Result<()> {
     test_events_impl(Dice::builder()).await
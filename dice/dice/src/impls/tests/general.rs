@@ -7,11 +7,18 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Barrier;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use allocative::Allocative;
 use assert_matches::assert_matches;
@@ -20,14 +27,33 @@ use derivative::Derivative;
 use derive_more::Display;
 use dupe::Dupe;
 use futures::FutureExt;
+use futures::StreamExt;
 use more_futures::cancellation::CancellationContext;
 use tokio::sync::oneshot;
 
+use crate::api::checkpoint::CheckpointConfig;
 use crate::api::computations::DiceComputations;
 use crate::api::cycles::DetectCycles;
+use crate::api::dice::DiceDataBuilder;
 use crate::api::error::DiceErrorImpl;
+use crate::api::evict::EvictionObserver;
 use crate::api::injected::InjectedKey;
+use crate::api::key::ContentKey;
 use crate::api::key::Key;
+use crate::api::persist::PersistableKey;
+use crate::api::progress::ComputeProgress;
+use crate::api::replay::ReplayEntry;
+use crate::api::replay::ReplayLog;
+use crate::api::resource_class::DiceResourceLimits;
+use crate::api::resource_class::ResourceClass;
+use crate::api::retry::DiceRetryPolicy;
+use crate::api::span_tree::SpanTree;
+use crate::api::span_tree::SpanTreeRecorder;
+use crate::api::spill::SpillCodec;
+use crate::api::spill::SpillConfig;
+use crate::api::transaction::CommitReport;
+use crate::api::transaction::DiceTransactionUpdater;
+use crate::api::transaction::EarlyCutoffs;
 use crate::api::user_data::UserComputationData;
 use crate::impls::dice::DiceModern;
 use crate::versions::VersionNumber;
@@ -703,3 +729,2278 @@ async fn test_dice_usable_after_cancellations() {
 
     assert!(is_ran.load(Ordering::SeqCst));
 }
+
+#[tokio::test]
+async fn pinned_key_is_revalidated_not_recomputed() -> anyhow::Result<()> {
+    #[derive(Clone, Dupe, Display, Debug, Eq, PartialEq, Hash, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    struct IsOdd;
+
+    #[async_trait]
+    impl Key for IsOdd {
+        type Value = bool;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            ctx.compute(&Foo(0)).await.unwrap() % 2 == 1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Clone, Dupe, Display, Debug, Derivative, Allocative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Pinned(#[derivative(Hash = "ignore", PartialEq = "ignore")] Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Key for Pinned {
+        type Value = bool;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ctx.compute(&IsOdd).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn pin() -> bool {
+            true
+        }
+    }
+
+    let dice = DiceModern::builder().build(DetectCycles::Disabled);
+    let computed = Arc::new(AtomicUsize::new(0));
+
+    let mut ctx = dice.updater();
+    ctx.changed_to(vec![(Foo(0), 1)])?;
+    let ctx = ctx.commit().await;
+    assert!(ctx.compute(&Pinned(computed.dupe())).await?);
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+    // `Foo(0)` changes, but `IsOdd` is unchanged, so the pinned key is only re-validated.
+    let mut ctx = dice.updater();
+    ctx.changed_to(vec![(Foo(0), 3)])?;
+    let ctx = ctx.commit().await;
+    assert!(ctx.compute(&Pinned(computed.dupe())).await?);
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, Display, Derivative, Allocative)]
+#[derivative(PartialEq, Eq, Hash)]
+#[display(fmt = "{}", name)]
+#[allocative(skip)]
+struct DiamondNode {
+    name: &'static str,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    computed: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl DiamondNode {
+    /// `a -> {b, c} -> d`
+    fn deps(&self) -> &'static [&'static str] {
+        match self.name {
+            "a" => &["b", "c"],
+            "b" | "c" => &["d"],
+            _ => &[],
+        }
+    }
+
+    fn node(&self, name: &'static str) -> DiamondNode {
+        DiamondNode {
+            name,
+            computed: self.computed.dupe(),
+        }
+    }
+}
+
+#[async_trait]
+impl Key for DiamondNode {
+    type Value = usize;
+
+    async fn compute(
+        &self,
+        ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        self.computed.lock().unwrap().push(self.name);
+        let mut paths = 1;
+        for dep in self.deps() {
+            paths += ctx.compute(&self.node(dep)).await.unwrap();
+        }
+        paths
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[tokio::test]
+async fn compute_topo_computes_shared_deps_once_legacy() -> anyhow::Result<()> {
+    compute_topo_computes_shared_deps_once(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn compute_topo_computes_shared_deps_once_modern() -> anyhow::Result<()> {
+    compute_topo_computes_shared_deps_once(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn compute_topo_computes_shared_deps_once(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let root = DiamondNode {
+        name: "a",
+        computed: Arc::new(Mutex::new(Vec::new())),
+    };
+    let keys = ["a", "b", "c", "d"].map(|name| root.node(name));
+    let edges: Vec<_> = keys
+        .iter()
+        .flat_map(|k| k.deps().iter().map(|dep| (k.clone(), k.node(dep))))
+        .collect();
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute_topo(&keys, &edges).await?, vec![5, 2, 2, 1]);
+
+    let computed = root.computed.lock().unwrap().clone();
+    assert_eq!(computed.len(), 4);
+    assert_eq!(computed.first(), Some(&"d"));
+    assert_eq!(computed.last(), Some(&"a"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_topo_rejects_cycles() -> anyhow::Result<()> {
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+    let a = DiamondNode {
+        name: "a",
+        computed: Arc::new(Mutex::new(Vec::new())),
+    };
+    let b = a.node("b");
+
+    let ctx = dice.updater().commit().await;
+    assert_matches!(
+        ctx.compute_topo(&[a.clone()], &[(a.clone(), b.clone()), (b, a.clone())]).await,
+        Err(err) => assert_matches!(&*err.0, DiceErrorImpl::CyclicTopoInput(_))
+    );
+    assert!(a.computed.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_forced_recomputes_cached_key_legacy() -> anyhow::Result<()> {
+    compute_forced_recomputes_cached_key(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn compute_forced_recomputes_cached_key_modern() -> anyhow::Result<()> {
+    compute_forced_recomputes_cached_key(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn compute_forced_recomputes_cached_key(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let root = DiamondNode {
+        name: "a",
+        computed: Arc::new(Mutex::new(Vec::new())),
+    };
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&root).await?, 5);
+    root.computed.lock().unwrap().clear();
+
+    // Only `b` itself is recomputed, its dep `d` comes from the cache.
+    assert_eq!(ctx.compute_forced(&root.node("b")).await?, 2);
+    assert_eq!(*root.computed.lock().unwrap(), vec!["b"]);
+
+    // The value of `b` is unchanged, so its dependents are still cached.
+    assert_eq!(ctx.compute(&root).await?, 5);
+    assert_eq!(*root.computed.lock().unwrap(), vec!["b"]);
+
+    // The deps of the recomputation are not recorded as deps of the key forcing it.
+    let forces = ForcesNode(root.node("b"));
+    assert_eq!(ctx.compute(&forces).await?, 2);
+    assert_eq!(ctx.dep_count(&forces).await, Some(1));
+
+    // A recomputed value that differs from the cached one is returned, but not stored.
+    let counter = Counter(Arc::new(AtomicUsize::new(0)));
+    assert_eq!(ctx.compute(&counter).await?, 0);
+    assert_eq!(ctx.compute_forced(&counter).await?, 1);
+    assert_eq!(ctx.compute(&counter).await?, 0);
+
+    Ok(())
+}
+
+#[derive(Clone, Dupe, Display, Debug, Derivative, Allocative)]
+#[derivative(PartialEq, Eq, Hash)]
+#[display(fmt = "{:?}", self)]
+#[allocative(skip)]
+struct Counter(#[derivative(Hash = "ignore", PartialEq = "ignore")] Arc<AtomicUsize>);
+
+#[async_trait]
+impl Key for Counter {
+    type Value = usize;
+
+    async fn compute(
+        &self,
+        _ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        self.0.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[derive(Allocative, Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[display(fmt = "ForcesNode({})", _0)]
+struct ForcesNode(DiamondNode);
+
+#[async_trait]
+impl Key for ForcesNode {
+    type Value = usize;
+
+    async fn compute(
+        &self,
+        ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        ctx.compute_forced(&self.0).await.unwrap()
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[tokio::test]
+async fn commit_with_report_legacy() -> anyhow::Result<()> {
+    let report = commit_with_report(Dice::builder().build(DetectCycles::Disabled)).await?;
+    assert_eq!(
+        report,
+        CommitReport {
+            changed: vec!["Foo(0)".to_owned(), "Foo(2)".to_owned()],
+            unchanged: vec!["Foo(1)".to_owned()],
+            new: Vec::new(),
+            early_cutoff: EarlyCutoffs::default(),
+        }
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn commit_with_report_modern() -> anyhow::Result<()> {
+    let report = commit_with_report(Dice::modern().build(DetectCycles::Disabled)).await?;
+    assert_eq!(
+        report,
+        CommitReport {
+            changed: vec!["Foo(0)".to_owned()],
+            unchanged: vec!["Foo(1)".to_owned()],
+            new: vec!["Foo(2)".to_owned()],
+            early_cutoff: EarlyCutoffs::default(),
+        }
+    );
+    Ok(())
+}
+
+async fn commit_with_report(dice: Arc<Dice>) -> anyhow::Result<CommitReport> {
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 1), (Foo(1), 2)])?;
+    let _ctx = updater.commit().await;
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 5), (Foo(1), 2), (Foo(2), 3)])?;
+    let (_ctx, report) = updater.commit_with_report().await;
+
+    Ok(report)
+}
+
+#[derive(Clone, Dupe, Debug, Display, Eq, Hash, PartialEq, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct Parity(Foo);
+
+#[async_trait]
+impl Key for Parity {
+    type Value = i32;
+
+    async fn compute(
+        &self,
+        ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        ctx.compute(&self.0).await.unwrap() % 2
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[tokio::test]
+async fn commit_with_report_early_cutoff_legacy() -> anyhow::Result<()> {
+    commit_with_report_early_cutoff(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn commit_with_report_early_cutoff_modern() -> anyhow::Result<()> {
+    commit_with_report_early_cutoff(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn commit_with_report_early_cutoff(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 1), (Foo(1), 2)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&Parity(Foo(0))).await?, 1);
+    assert_eq!(ctx.compute(&Parity(Foo(1))).await?, 0);
+    drop(ctx);
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 3), (Foo(1), 5)])?;
+    let (ctx, report) = updater.commit_with_report().await;
+    // Nothing is recomputed by the commit itself.
+    assert!(report.early_cutoff.keys().is_empty());
+
+    assert_eq!(ctx.compute(&Parity(Foo(0))).await?, 1);
+    assert_eq!(ctx.compute(&Parity(Foo(1))).await?, 1);
+    assert_eq!(report.early_cutoff.keys(), vec!["Parity(Foo(0))".to_owned()]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn is_cached_reports_computed_keys_legacy() -> anyhow::Result<()> {
+    is_cached_reports_computed_keys(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn is_cached_reports_computed_keys_modern() -> anyhow::Result<()> {
+    is_cached_reports_computed_keys(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn is_cached_reports_computed_keys(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let root = DiamondNode {
+        name: "b",
+        computed: Arc::new(Mutex::new(Vec::new())),
+    };
+
+    let ctx = dice.updater().commit().await;
+    assert!(!ctx.is_cached(&root).await);
+
+    assert_eq!(ctx.compute(&root).await?, 2);
+    assert!(ctx.is_cached(&root).await);
+    assert!(ctx.is_cached(&root.node("d")).await);
+    assert!(!ctx.is_cached(&root.node("a")).await);
+
+    // Checking never computes anything.
+    assert_eq!(*root.computed.lock().unwrap(), vec!["b", "d"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_with_progress_streams_progress_before_value_legacy() -> anyhow::Result<()> {
+    compute_with_progress_streams_progress_before_value(
+        Dice::builder().build(DetectCycles::Disabled),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn compute_with_progress_streams_progress_before_value_modern() -> anyhow::Result<()> {
+    compute_with_progress_streams_progress_before_value(
+        Dice::modern().build(DetectCycles::Disabled),
+    )
+    .await
+}
+
+async fn compute_with_progress_streams_progress_before_value(
+    dice: Arc<Dice>,
+) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Milestones;
+
+    #[async_trait]
+    impl Key for Milestones {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            // Progress of deps isn't streamed.
+            ctx.compute(&OtherMilestones).await.unwrap();
+            for milestone in 0..3u32 {
+                ctx.report_progress(milestone);
+                // Progress of other types isn't streamed.
+                ctx.report_progress(format!("milestone {}", milestone));
+                tokio::task::yield_now().await;
+            }
+            42
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct OtherMilestones;
+
+    #[async_trait]
+    impl Key for OtherMilestones {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            ctx.report_progress(100u32);
+        }
+
+        fn equality(_: &Self::Value, _: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    let ctx = dice.updater().commit().await;
+
+    let events = ctx
+        .compute_with_progress::<_, u32>(&Milestones)
+        .collect::<Vec<_>>()
+        .await;
+    let mut events = events.into_iter();
+    for expected in 0..3 {
+        assert_matches!(events.next(), Some(ComputeProgress::Progress(p)) if *p == expected);
+    }
+    assert_matches!(events.next(), Some(ComputeProgress::Done(Ok(42))));
+    assert_matches!(events.next(), None);
+
+    // Cached values report no progress.
+    let events = ctx
+        .compute_with_progress::<_, u32>(&Milestones)
+        .collect::<Vec<_>>()
+        .await;
+    assert_matches!(&events[..], [ComputeProgress::Done(Ok(42))]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn persisted_cache_is_reused_by_new_dice() -> anyhow::Result<()> {
+    static COMPUTED: AtomicUsize = AtomicUsize::new(0);
+    static BASE: AtomicU32 = AtomicU32::new(0);
+
+    /// `Persisted(n)` is `BASE + n`, computed from `Persisted(n - 1)`.
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Persisted(u32);
+
+    #[async_trait]
+    impl Key for Persisted {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            COMPUTED.fetch_add(1, Ordering::SeqCst);
+            match self.0 {
+                0 => BASE.load(Ordering::SeqCst),
+                n => ctx.compute(&Persisted(n - 1)).await.unwrap() + 1,
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    impl PersistableKey for Persisted {
+        fn to_bytes(&self, value: &Self::Value) -> Vec<u8> {
+            [self.0.to_le_bytes(), value.to_le_bytes()].concat()
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<(Self, Self::Value)> {
+            let key = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+            let value = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+            Some((Persisted(key), value))
+        }
+    }
+
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&Persisted(2)).await?, 2);
+    drop(ctx);
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 3);
+
+    let mut persisted = Vec::new();
+    assert_eq!(dice.persist_cache::<Persisted>(&mut persisted).await?, 3);
+
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+    assert_eq!(dice.load_cache::<Persisted>(persisted.as_slice()).await?, 3);
+
+    let ctx = dice.updater().commit().await;
+    for i in 0..3 {
+        assert_eq!(ctx.compute(&Persisted(i)).await?, i);
+    }
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 3);
+    drop(ctx);
+
+    // Loaded values are invalidated through their deps.
+    BASE.store(10, Ordering::SeqCst);
+    let mut updater = dice.updater();
+    updater.changed(vec![Persisted(0)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&Persisted(2)).await?, 12);
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 6);
+
+    // Keys that weren't persisted are still computed as usual.
+    assert_eq!(ctx.compute(&Persisted(3)).await?, 13);
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 7);
+
+    // A truncated entry is rejected rather than read past the end.
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+    let truncated = [u64::MAX.to_le_bytes().as_slice(), &[0; 4]].concat();
+    assert!(dice.load_cache::<Persisted>(truncated.as_slice()).await.is_err());
+
+    let legacy = Dice::builder().build(DetectCycles::Disabled);
+    assert!(legacy.persist_cache::<Persisted>(Vec::new()).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dep_count_reports_direct_deps_legacy() -> anyhow::Result<()> {
+    dep_count_reports_direct_deps(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn dep_count_reports_direct_deps_modern() -> anyhow::Result<()> {
+    dep_count_reports_direct_deps(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn dep_count_reports_direct_deps(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct SumOfFoos;
+
+    #[async_trait]
+    impl Key for SumOfFoos {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let mut sum = 0;
+            for i in 0..3 {
+                sum += ctx.compute(&Foo(i)).await.unwrap();
+            }
+            // Requesting a dep again doesn't count twice.
+            sum + ctx.compute(&Foo(0)).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let mut updater = dice.updater();
+    updater.changed_to((0..3).map(|i| (Foo(i), i)))?;
+    let ctx = updater.commit().await;
+
+    assert_eq!(ctx.dep_count(&SumOfFoos).await, None);
+    assert_eq!(ctx.compute(&SumOfFoos).await?, 3);
+    assert_eq!(ctx.dep_count(&SumOfFoos).await, Some(3));
+    assert_eq!(ctx.dep_count(&Foo(0)).await, Some(0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalidation_impact_counts_transitive_rdeps_legacy() -> anyhow::Result<()> {
+    invalidation_impact_counts_transitive_rdeps(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn invalidation_impact_counts_transitive_rdeps_modern() -> anyhow::Result<()> {
+    invalidation_impact_counts_transitive_rdeps(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn invalidation_impact_counts_transitive_rdeps(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct FooPlus(i32);
+
+    #[async_trait]
+    impl Key for FooPlus {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            ctx.compute(&Foo(0)).await.unwrap() + self.0
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct SumOfFooPluses;
+
+    #[async_trait]
+    impl Key for SumOfFooPluses {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let mut sum = ctx.compute(&Foo(0)).await.unwrap();
+            for i in 0..3 {
+                sum += ctx.compute(&FooPlus(i)).await.unwrap();
+            }
+            sum
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let mut updater = dice.updater();
+    updater.changed_to([(Foo(0), 1), (Foo(1), 1)])?;
+    let ctx = updater.commit().await;
+
+    assert_eq!(ctx.invalidation_impact(&SumOfFooPluses).await, 0);
+    assert_eq!(ctx.compute(&SumOfFooPluses).await?, 7);
+
+    // `Foo(0)` fans out to the three `FooPlus` keys and, both directly and through them, to the
+    // sum, which is only counted once.
+    assert_eq!(ctx.invalidation_impact(&Foo(0)).await, 4);
+    assert_eq!(ctx.invalidation_impact(&FooPlus(1)).await, 1);
+    assert_eq!(ctx.invalidation_impact(&SumOfFooPluses).await, 0);
+    assert_eq!(ctx.invalidation_impact(&Foo(1)).await, 0);
+
+    // Nothing was invalidated.
+    assert!(ctx.is_cached(&SumOfFooPluses).await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalidate_matching_recomputes_only_matching_keys_legacy() -> anyhow::Result<()> {
+    invalidate_matching_recomputes_only_matching_keys(Dice::builder().build(DetectCycles::Disabled))
+        .await
+}
+
+#[tokio::test]
+async fn invalidate_matching_recomputes_only_matching_keys_modern() -> anyhow::Result<()> {
+    invalidate_matching_recomputes_only_matching_keys(Dice::modern().build(DetectCycles::Disabled))
+        .await
+}
+
+async fn invalidate_matching_recomputes_only_matching_keys(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Config(
+        u32,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>,
+    );
+
+    #[async_trait]
+    impl Key for Config {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Other(
+        u32,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>,
+    );
+
+    #[async_trait]
+    impl Key for Other {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            self.0
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let config_computes = Arc::new(AtomicUsize::new(0));
+    let other_computes = Arc::new(AtomicUsize::new(0));
+    let keys = (
+        [
+            Config(0, config_computes.dupe()),
+            Config(1, config_computes.dupe()),
+        ],
+        Other(0, other_computes.dupe()),
+    );
+
+    let ctx = dice.updater().commit().await;
+    for k in &keys.0 {
+        ctx.compute(k).await?;
+    }
+    ctx.compute(&keys.1).await?;
+    assert_eq!(config_computes.load(Ordering::SeqCst), 2);
+    assert_eq!(other_computes.load(Ordering::SeqCst), 1);
+    drop(ctx);
+
+    let mut updater = dice.updater();
+    updater.invalidate_matching(|k| k.as_any().is::<Config>());
+    let ctx = updater.commit().await;
+    for k in &keys.0 {
+        ctx.compute(k).await?;
+    }
+    ctx.compute(&keys.1).await?;
+    assert_eq!(config_computes.load(Ordering::SeqCst), 4);
+    assert_eq!(other_computes.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalidate_matching_skips_injected_keys_legacy() -> anyhow::Result<()> {
+    invalidate_matching_skips_injected_keys(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn invalidate_matching_skips_injected_keys_modern() -> anyhow::Result<()> {
+    invalidate_matching_skips_injected_keys(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn invalidate_matching_skips_injected_keys(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 1)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&Foo(0)).await?, 1);
+    drop(ctx);
+
+    // Selecting the injected key would make it dirty, and computing it would panic.
+    let mut updater = dice.updater();
+    updater.invalidate_matching(|k| k.as_any().is::<Foo>());
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&Foo(0)).await?, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn eviction_observer_reports_evicted_keys() -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct PlusOne;
+
+    #[async_trait]
+    impl Key for PlusOne {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            ctx.compute(&Foo(0)).await.unwrap() + 1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let mut builder = Dice::modern();
+    builder.set(EvictionObserver::new({
+        let evicted = evicted.dupe();
+        move |key, _version| evicted.lock().unwrap().push(key.to_string())
+    }));
+    let dice = builder.build(DetectCycles::Disabled);
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 1)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&PlusOne).await?, 2);
+    drop(ctx);
+    assert!(evicted.lock().unwrap().is_empty());
+
+    // `PlusOne` keeps only the latest value, so computing a new one evicts the old one.
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 2)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&PlusOne).await?, 3);
+    drop(ctx);
+    assert_eq!(*evicted.lock().unwrap(), vec!["PlusOne".to_owned()]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn replay_logs_of_deterministic_computations_are_identical() -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Fib(u32);
+
+    #[async_trait]
+    impl Key for Fib {
+        type Value = u64;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            if self.0 < 2 {
+                return self.0 as u64;
+            }
+            let a = ctx.compute(&Fib(self.0 - 1)).await.unwrap();
+            let b = ctx.compute(&Fib(self.0 - 2)).await.unwrap();
+            a + b
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    async fn run(root: u32) -> anyhow::Result<Arc<ReplayLog>> {
+        let log = Arc::new(ReplayLog::new());
+        let dice = Dice::modern().build(DetectCycles::Disabled);
+        let ctx = dice
+            .updater_with_data(UserComputationData {
+                replay_log: Some(log.dupe()),
+                ..Default::default()
+            })
+            .commit()
+            .await;
+        ctx.compute(&Fib(root)).await?;
+        Ok(log)
+    }
+
+    let first = run(4).await?.entries();
+    assert_eq!(
+        first,
+        vec![ReplayEntry {
+            root: "Fib(4)".to_owned(),
+            computed: ["Fib(3)", "Fib(2)", "Fib(1)", "Fib(0)"]
+                .map(str::to_owned)
+                .to_vec(),
+        }]
+    );
+
+    let second = run(4).await?;
+    assert_eq!(second.entries(), first);
+    second.verify(&first)?;
+
+    let mismatch = run(3).await?.verify(&first).unwrap_err();
+    assert_eq!(mismatch.index, 0);
+    assert_eq!(mismatch.expected.as_ref(), first.first());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn span_tree_nests_sub_computations() -> anyhow::Result<()> {
+    const SELF_TIME: Duration = Duration::from_millis(5);
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Nested(u32);
+
+    #[async_trait]
+    impl Key for Nested {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            tokio::time::sleep(SELF_TIME).await;
+            if self.0 > 0 {
+                ctx.compute(&Nested(self.0 - 1)).await.unwrap();
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    fn shape(tree: &SpanTree) -> String {
+        let children = tree.children.iter().map(shape).collect::<Vec<_>>();
+        format!("{}[{}]", tree.key, children.join(","))
+    }
+
+    fn check_times(tree: &SpanTree) {
+        assert!(tree.self_time >= SELF_TIME, "{:?}", tree);
+        let children_time = tree.children.iter().map(|c| c.total_time).sum::<Duration>();
+        assert_eq!(tree.total_time, tree.self_time + children_time);
+        tree.children.iter().for_each(check_times);
+    }
+
+    let recorder = Arc::new(SpanTreeRecorder::new());
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+    let ctx = dice
+        .updater_with_data(UserComputationData {
+            span_tree: Some(recorder.dupe()),
+            ..Default::default()
+        })
+        .commit()
+        .await;
+    ctx.compute(&Nested(2)).await?;
+    // Already computed, so it has no span of its own.
+    ctx.compute(&Nested(1)).await?;
+
+    let trees = recorder.trees();
+    assert_eq!(
+        trees.iter().map(shape).collect::<Vec<_>>(),
+        vec!["Nested(2)[Nested(1)[Nested(0)[]]]"]
+    );
+    check_times(&trees[0]);
+
+    let folded = recorder.folded_stacks();
+    assert_eq!(
+        folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>(),
+        vec![
+            "Nested(2)",
+            "Nested(2);Nested(1)",
+            "Nested(2);Nested(1);Nested(0)"
+        ]
+    );
+    assert_eq!(folded, trees[0].folded_stacks());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn spillable_values_are_spilled_and_reloaded() -> anyhow::Result<()> {
+    static COMPUTED: AtomicUsize = AtomicUsize::new(0);
+    static RELOADED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Large(u8);
+
+    #[async_trait]
+    impl Key for Large {
+        type Value = Arc<Vec<u8>>;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            COMPUTED.fetch_add(1, Ordering::SeqCst);
+            Arc::new(vec![self.0; 1024])
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn spill_codec() -> Option<SpillCodec<Self::Value>> {
+            Some(SpillCodec {
+                serialize: |value| value.to_vec(),
+                deserialize: |bytes| {
+                    RELOADED.fetch_add(1, Ordering::SeqCst);
+                    Some(Arc::new(bytes.to_vec()))
+                },
+            })
+        }
+    }
+
+    let spill_dir = tempfile::tempdir()?;
+    let spilled_files = || spilled_files(spill_dir.path());
+
+    // Room for one value, but not two.
+    let mut builder = DiceModern::builder();
+    builder.set(SpillConfig::new(spill_dir.path(), 1500));
+    let dice = builder.build(DetectCycles::Disabled);
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(*ctx.compute(&Large(0)).await?, vec![0; 1024]);
+    dice.testing_wait_for_spills().await;
+    assert_eq!(spilled_files(), 0);
+
+    // The least recently used value is spilled.
+    assert_eq!(*ctx.compute(&Large(1)).await?, vec![1; 1024]);
+    dice.testing_wait_for_spills().await;
+    assert_eq!(spilled_files(), 1);
+    drop(ctx);
+
+    // Reloading a value spills the other one, and removes the file it was reloaded from.
+    let ctx = dice.updater().commit().await;
+    assert_eq!(*ctx.compute(&Large(0)).await?, vec![0; 1024]);
+    dice.testing_wait_for_spills().await;
+    assert_eq!(spilled_files(), 1);
+    assert_eq!(*ctx.compute(&Large(1)).await?, vec![1; 1024]);
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 2);
+    assert_eq!(RELOADED.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn spilled_values_that_cannot_be_reloaded_are_recomputed() -> anyhow::Result<()> {
+    static COMPUTED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Corrupted(u8);
+
+    #[async_trait]
+    impl Key for Corrupted {
+        type Value = Arc<Vec<u8>>;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            COMPUTED.fetch_add(1, Ordering::SeqCst);
+            Arc::new(vec![self.0; 1024])
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn spill_codec() -> Option<SpillCodec<Self::Value>> {
+            Some(SpillCodec {
+                serialize: |value| value.to_vec(),
+                deserialize: |_| None,
+            })
+        }
+    }
+
+    let spill_dir = tempfile::tempdir()?;
+
+    let mut builder = DiceModern::builder();
+    builder.set(SpillConfig::new(spill_dir.path(), 1500));
+    let dice = builder.build(DetectCycles::Disabled);
+
+    let ctx = dice.updater().commit().await;
+    ctx.compute(&Corrupted(0)).await?;
+    ctx.compute(&Corrupted(1)).await?;
+    dice.testing_wait_for_spills().await;
+    assert_eq!(spilled_files(spill_dir.path()), 1);
+    drop(ctx);
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(*ctx.compute(&Corrupted(0)).await?, vec![0; 1024]);
+    assert_eq!(COMPUTED.load(Ordering::SeqCst), 3);
+
+    Ok(())
+}
+
+/// The number of files spilled by the DICE instances spilling to `dir`.
+fn spilled_files(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|store| std::fs::read_dir(store.unwrap().path()).unwrap().count())
+        .sum()
+}
+
+#[tokio::test]
+async fn compute_allowing_stale_reuses_recent_values() -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    struct PlusOne(
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        #[allocative(skip)]
+        Arc<AtomicUsize>,
+    );
+
+    #[async_trait]
+    impl Key for PlusOne {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ctx.compute(&Foo(0)).await.unwrap() + 1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct StaleReader(
+        #[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")] PlusOne,
+    );
+
+    #[async_trait]
+    impl Key for StaleReader {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ctx.compute_allowing_stale(&self.1, 1).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let computes = Arc::new(AtomicUsize::new(0));
+    let key = PlusOne(computes.dupe());
+    let reads = Arc::new(AtomicUsize::new(0));
+    let reader = StaleReader(reads.dupe(), key.clone());
+    let dice = Dice::modern().build(DetectCycles::Disabled);
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 1)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&key).await?, 2);
+    drop(ctx);
+
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(0), 2)])?;
+    let ctx = updater.commit().await;
+
+    // The value computed one version ago is still acceptable.
+    assert_eq!(ctx.compute_allowing_stale(&key, 1).await?, 2);
+    assert_eq!(ctx.compute(&reader).await?, 2);
+    assert_eq!(computes.load(Ordering::SeqCst), 1);
+
+    // But not if no staleness is tolerated.
+    assert_eq!(ctx.compute_allowing_stale(&key, 0).await?, 3);
+    assert_eq!(computes.load(Ordering::SeqCst), 2);
+    drop(ctx);
+
+    // A value computed from a stale value is not reused at later versions.
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Foo(1), 0)])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&reader).await?, 3);
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_all_or_first_error_cancels_remaining_keys() -> anyhow::Result<()> {
+    #[derive(Debug)]
+    struct DropSignal(Option<oneshot::Sender<()>>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            self.0.take().unwrap().send(()).unwrap();
+        }
+    }
+
+    #[derive(Clone, Dupe, Debug, Derivative, Allocative, Display)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Validate {
+        id: u32,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        started: Arc<tokio::sync::Semaphore>,
+        /// Keys without a drop signal fail, once the other keys have started.
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        drop_signal: Arc<Mutex<Option<DropSignal>>>,
+    }
+
+    #[async_trait]
+    impl Key for Validate {
+        type Value = Result<(), u32>;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let drop_signal = self.drop_signal.lock().unwrap().take();
+            if drop_signal.is_none() {
+                let _permits = self.started.acquire_many(2).await.unwrap();
+                return Err(self.id);
+            }
+
+            self.started.add_permits(1);
+            futures::future::pending().await
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let started = Arc::new(tokio::sync::Semaphore::new(0));
+    let (tx1, rx1) = oneshot::channel();
+    let (tx2, rx2) = oneshot::channel();
+    let key = |id, drop_signal| Validate {
+        id,
+        started: started.dupe(),
+        drop_signal: Arc::new(Mutex::new(drop_signal)),
+    };
+    let keys = [
+        key(0, Some(DropSignal(Some(tx1)))),
+        key(1, None),
+        key(2, Some(DropSignal(Some(tx2)))),
+    ];
+
+    let dice = DiceModern::builder().build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+
+    assert_eq!(ctx.compute_all_or_first_error(&keys).await?, Err(1));
+
+    // The keys still computing are cancelled once the error is returned.
+    rx1.await?;
+    rx2.await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_until_aborts_computation_when_cancelled() {
+    #[derive(Debug)]
+    struct DropSignal(Option<oneshot::Sender<()>>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            self.0.take().unwrap().send(()).unwrap();
+        }
+    }
+
+    #[derive(Clone, Dupe, Debug, Derivative, Allocative, Display)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct KeyThatWaits {
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        started: Arc<tokio::sync::Semaphore>,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        proceed: Arc<tokio::sync::Semaphore>,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        is_ran: Arc<AtomicBool>,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        drop_signal: Arc<Mutex<Option<DropSignal>>>,
+    }
+
+    #[async_trait]
+    impl Key for KeyThatWaits {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let _drop_signal = self.drop_signal.lock().unwrap().take();
+
+            self.started.add_permits(1);
+            let _guard = self.proceed.acquire().await.unwrap();
+            self.is_ran.store(true, Ordering::SeqCst);
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let key = KeyThatWaits {
+        started: Arc::new(tokio::sync::Semaphore::new(0)),
+        proceed: Arc::new(tokio::sync::Semaphore::new(0)),
+        is_ran: Arc::new(AtomicBool::new(false)),
+        drop_signal: Arc::new(Mutex::new(Some(DropSignal(Some(tx))))),
+    };
+
+    let dice = DiceModern::builder().build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+
+    // Cancel as soon as the key starts computing.
+    let res = ctx
+        .compute_until(&key, key.started.acquire())
+        .await
+        .unwrap();
+    assert_eq!(res, None);
+
+    // Once the task is gone, letting it proceed must have no effect.
+    key.proceed.add_permits(1);
+    rx.await.unwrap();
+    assert!(!key.is_ran.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn content_key_shares_computations_legacy() -> anyhow::Result<()> {
+    content_key_shares_computations(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn content_key_shares_computations_modern() -> anyhow::Result<()> {
+    content_key_shares_computations(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn content_key_shares_computations(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Blob {
+        name: &'static str,
+        digest: u32,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        computes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Key for Blob {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.computes.fetch_add(1, Ordering::SeqCst);
+            self.digest * 10
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn content_key(&self) -> Option<ContentKey> {
+            Some(ContentKey::new(self.digest.to_le_bytes()))
+        }
+    }
+
+    let computes = Arc::new(AtomicUsize::new(0));
+    let blob = |name, digest| Blob {
+        name,
+        digest,
+        computes: computes.dupe(),
+    };
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&blob("a", 1)).await?, 10);
+    assert_eq!(ctx.compute(&blob("b", 1)).await?, 10);
+    assert_eq!(computes.load(Ordering::SeqCst), 1);
+    // The key that reused the value depends on the key that computed it.
+    assert_eq!(ctx.dep_count(&blob("b", 1)).await, Some(1));
+
+    assert_eq!(ctx.compute(&blob("c", 2)).await?, 20);
+    assert_eq!(computes.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn content_key_values_persist_across_transactions_legacy() -> anyhow::Result<()> {
+    content_key_values_persist_across_transactions(Dice::builder().build(DetectCycles::Disabled))
+        .await
+}
+
+#[tokio::test]
+async fn content_key_values_persist_across_transactions_modern() -> anyhow::Result<()> {
+    content_key_values_persist_across_transactions(Dice::modern().build(DetectCycles::Disabled))
+        .await
+}
+
+async fn content_key_values_persist_across_transactions(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Derivative)]
+    #[derivative(PartialEq, Eq, Hash)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Toolchain {
+        digest: u32,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        computes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Key for Toolchain {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.computes.fetch_add(1, Ordering::SeqCst) + 1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn content_key(&self) -> Option<ContentKey> {
+            Some(ContentKey::new(self.digest.to_le_bytes()))
+        }
+    }
+
+    let computes = Arc::new(AtomicUsize::new(0));
+    let toolchain = Toolchain {
+        digest: 1,
+        computes: computes.dupe(),
+    };
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&toolchain).await?, 1);
+    drop(ctx);
+
+    // The key is recomputed by the graph, but its value is reused regardless of the version.
+    let mut updater = dice.updater();
+    updater.changed(vec![toolchain.clone()])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&toolchain).await?, 1);
+    assert_eq!(computes.load(Ordering::SeqCst), 1);
+    drop(ctx);
+
+    // Invalidations only apply when committed.
+    let mut updater = dice.updater();
+    updater.invalidate_content_key::<Toolchain>(ContentKey::new(1u32.to_le_bytes()));
+    drop(updater);
+    let mut updater = dice.updater();
+    updater.changed(vec![toolchain.clone()])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&toolchain).await?, 1);
+    drop(ctx);
+
+    let mut updater = dice.updater();
+    updater.invalidate_content_key::<Toolchain>(ContentKey::new(1u32.to_le_bytes()));
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&toolchain).await?, 2);
+    assert_eq!(computes.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compute_stream_computes_keys_as_they_arrive_legacy() -> anyhow::Result<()> {
+    compute_stream_computes_keys_as_they_arrive(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn compute_stream_computes_keys_as_they_arrive_modern() -> anyhow::Result<()> {
+    compute_stream_computes_keys_as_they_arrive(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn compute_stream_computes_keys_as_they_arrive(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Double(i32);
+
+    #[async_trait]
+    impl Key for Double {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.0 * 2
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let ctx = dice.updater().commit().await;
+    let keys = futures::stream::iter(0..10).map(Double);
+    let mut values = ctx
+        .compute_stream(keys, 3)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    values.sort_unstable();
+    assert_eq!(values, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+
+    // With no concurrency, the stream would never make progress, so it is rejected upfront.
+    let keys = futures::stream::iter(0..10).map(Double);
+    assert!(std::panic::catch_unwind(AssertUnwindSafe(|| ctx.compute_stream(keys, 0))).is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prometheus_metrics_per_key_type_legacy() -> anyhow::Result<()> {
+    prometheus_metrics_per_key_type(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prometheus_metrics_per_key_type_modern() -> anyhow::Result<()> {
+    prometheus_metrics_per_key_type(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn prometheus_metrics_per_key_type(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    struct Measured;
+
+    #[async_trait]
+    impl Key for Measured {
+        type Value = i32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn key_type_name() -> &'static str {
+            "Measured"
+        }
+    }
+
+    let ctx = dice.updater().commit().await;
+    ctx.compute(&Measured).await?;
+    ctx.compute(&Measured).await?;
+
+    let text = dice.prometheus_metrics();
+
+    // Check the exposition format: every sample belongs to a metric declared with a type.
+    let mut declared = HashMap::new();
+    for line in text.lines() {
+        if let Some(help) = line.strip_prefix("# HELP ") {
+            assert_matches!(help.split_once(' '), Some((name, _)) if !name.is_empty());
+        } else if let Some(decl) = line.strip_prefix("# TYPE ") {
+            let (name, metric_type) = decl.split_once(' ').unwrap();
+            assert_matches!(metric_type, "counter" | "gauge");
+            declared.insert(name.to_owned(), metric_type.to_owned());
+        } else {
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            value.parse::<f64>()?;
+            let name = match series.split_once('{') {
+                Some((name, labels)) => {
+                    assert!(labels.ends_with('}'), "bad labels in `{}`", line);
+                    name
+                }
+                None => series,
+            };
+            assert!(
+                name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "bad metric name in `{}`",
+                line
+            );
+            assert!(
+                declared.contains_key(name),
+                "undeclared metric in `{}`",
+                line
+            );
+        }
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines.contains(&"dice_key_requests_total{key_type=\"Measured\"} 2"));
+    assert!(lines.contains(&"dice_key_computations_total{key_type=\"Measured\"} 1"));
+    assert!(lines.contains(&"dice_key_cache_hit_ratio{key_type=\"Measured\"} 0.5"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transient_errors_are_retried_legacy() -> anyhow::Result<()> {
+    let mut builder = Dice::builder();
+    builder.set(DiceRetryPolicy::new(3, Duration::from_millis(1)));
+    transient_errors_are_retried(builder.build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn transient_errors_are_retried_modern() -> anyhow::Result<()> {
+    let mut builder = Dice::modern();
+    builder.set(DiceRetryPolicy::new(3, Duration::from_millis(1)));
+    transient_errors_are_retried(builder.build(DetectCycles::Disabled)).await
+}
+
+async fn transient_errors_are_retried(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Dupe, Debug, Display, Derivative)]
+    #[derivative(Hash, PartialEq, Eq)]
+    #[display(fmt = "{:?}", self)]
+    struct Flaky(#[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Key for Flaky {
+        type Value = Result<usize, Arc<String>>;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let attempt = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            if attempt <= 2 {
+                Err(Arc::new(format!("attempt {} failed", attempt)))
+            } else {
+                Ok(attempt)
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn is_transient_error(x: &Self::Value) -> bool {
+            x.is_err()
+        }
+    }
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&Flaky(attempts.dupe())).await?, Ok(3));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // The successful result is cached.
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&Flaky(attempts.dupe())).await?, Ok(3));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    drop(ctx);
+
+    #[derive(Allocative, Clone, Dupe, Debug, Display, Derivative)]
+    #[derivative(Hash, PartialEq, Eq)]
+    #[display(fmt = "{:?}", self)]
+    struct FlakyBlob(#[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Key for FlakyBlob {
+        type Value = Result<usize, Arc<String>>;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            // Fails every attempt of the first computation.
+            let attempt = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            if attempt <= 4 {
+                Err(Arc::new(format!("attempt {} failed", attempt)))
+            } else {
+                Ok(attempt)
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn is_transient_error(x: &Self::Value) -> bool {
+            x.is_err()
+        }
+
+        fn content_key(&self) -> Option<ContentKey> {
+            Some(ContentKey::new(*b"flaky"))
+        }
+    }
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let blob = FlakyBlob(attempts.dupe());
+    let ctx = dice.updater().commit().await;
+    assert!(ctx.compute(&blob).await?.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    drop(ctx);
+
+    // The error that outlived the retries isn't reused when the key is recomputed.
+    let mut updater = dice.updater();
+    updater.changed(vec![blob.dupe()])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&blob).await?, Ok(5));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_results_fail_requests_legacy() -> anyhow::Result<()> {
+    invalid_results_fail_requests(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn invalid_results_fail_requests_modern() -> anyhow::Result<()> {
+    invalid_results_fail_requests(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn invalid_results_fail_requests(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Even(u32);
+
+    #[async_trait]
+    impl Key for Even {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            self.0
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn validate_result(x: &Self::Value) -> Result<(), String> {
+            if x % 2 == 0 {
+                Ok(())
+            } else {
+                Err(format!("{} is odd", x))
+            }
+        }
+    }
+
+    // Tests are debug builds, so results are validated without `ValidateResults`.
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&Even(2)).await?, 2);
+    let err = ctx.compute(&Even(3)).await.unwrap_err();
+    assert_matches!(&*err.0, DiceErrorImpl::InvalidResult { message, .. } if message == "3 is odd");
+    assert_eq!(
+        err.to_string(),
+        "Value of key `Even(3)` is invalid: 3 is odd"
+    );
+
+    // Values reused from the cache are validated too.
+    let ctx = dice.updater().commit().await;
+    assert_matches!(ctx.compute(&Even(3)).await, Err(..));
+    // And so are values only read through projections.
+    assert_matches!(ctx.compute_mapped(&Even(3), |x: &u32| x + 1).await, Err(..));
+    assert_matches!(ctx.compute_opaque(&Even(3)).await, Err(..));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resource_classes_limit_concurrency_legacy() -> anyhow::Result<()> {
+    let mut builder = Dice::builder();
+    builder.set(DiceResourceLimits::new().with_limit(ResourceClass("network"), 2));
+    resource_classes_limit_concurrency(builder.build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn resource_classes_limit_concurrency_modern() -> anyhow::Result<()> {
+    let mut builder = Dice::modern();
+    builder.set(DiceResourceLimits::new().with_limit(ResourceClass("network"), 2));
+    resource_classes_limit_concurrency(builder.build(DetectCycles::Disabled)).await
+}
+
+async fn resource_classes_limit_concurrency(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Clone, Dupe, Debug, Display, Derivative, Allocative)]
+    #[derivative(Hash, PartialEq, Eq)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct Contending {
+        class: ResourceClass,
+        index: usize,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        running: Arc<AtomicUsize>,
+        #[derivative(Hash = "ignore", PartialEq = "ignore")]
+        max_running: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Key for Contending {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+
+        fn resource_class(&self) -> Option<ResourceClass> {
+            Some(self.class)
+        }
+    }
+
+    let network = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let disk = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let keys = (0..6).flat_map(|index| {
+        [
+            Contending {
+                class: ResourceClass("network"),
+                index,
+                running: network.0.dupe(),
+                max_running: network.1.dupe(),
+            },
+            Contending {
+                class: ResourceClass("disk"),
+                index,
+                running: disk.0.dupe(),
+                max_running: disk.1.dupe(),
+            },
+        ]
+    });
+
+    let ctx = dice.updater().commit().await;
+    let ctx = &ctx;
+    futures::future::try_join_all(keys.map(|key| async move { ctx.compute(&key).await })).await?;
+
+    assert_eq!(network.1.load(Ordering::SeqCst), 2);
+    // The limit of one class does not hold back computations of another.
+    assert!(disk.1.load(Ordering::SeqCst) > 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn resource_classes_are_released_while_waiting_for_deps_legacy() -> anyhow::Result<()> {
+    let mut builder = Dice::builder();
+    builder.set(DiceResourceLimits::new().with_limit(ResourceClass("network"), 1));
+    resource_classes_are_released_while_waiting_for_deps(builder.build(DetectCycles::Disabled))
+        .await
+}
+
+#[tokio::test]
+async fn resource_classes_are_released_while_waiting_for_deps_modern() -> anyhow::Result<()> {
+    let mut builder = Dice::modern();
+    builder.set(DiceResourceLimits::new().with_limit(ResourceClass("network"), 1));
+    resource_classes_are_released_while_waiting_for_deps(builder.build(DetectCycles::Disabled))
+        .await
+}
+
+async fn resource_classes_are_released_while_waiting_for_deps(
+    dice: Arc<Dice>,
+) -> anyhow::Result<()> {
+    /// Downloads `Fetch(n - 1)` before itself.
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Fetch(u32);
+
+    #[async_trait]
+    impl Key for Fetch {
+        type Value = u32;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            match self.0 {
+                0 => 0,
+                n => ctx.compute(&Fetch(n - 1)).await.unwrap() + 1,
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn resource_class(&self) -> Option<ResourceClass> {
+            Some(ResourceClass("network"))
+        }
+    }
+
+    // Only one computation of the class runs at a time, so this deadlocks if `Fetch(3)` holds its
+    // permit while it waits for `Fetch(2)`.
+    let ctx = dice.updater().commit().await;
+    let value = tokio::time::timeout(Duration::from_secs(60), ctx.compute(&Fetch(3))).await??;
+    assert_eq!(value, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_dependencies_are_invalidated_legacy() -> anyhow::Result<()> {
+    file_dependencies_are_invalidated(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn file_dependencies_are_invalidated_modern() -> anyhow::Result<()> {
+    file_dependencies_are_invalidated(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn file_dependencies_are_invalidated(dice: Arc<Dice>) -> anyhow::Result<()> {
+    /// Collects the files touched since the last transaction, like a file watcher would.
+    #[derive(Default)]
+    struct MockFileWatcher {
+        touched: Mutex<Vec<PathBuf>>,
+    }
+
+    impl MockFileWatcher {
+        fn touch(&self, path: &str) {
+            self.touched.lock().unwrap().push(PathBuf::from(path));
+        }
+
+        fn sync(&self, updater: &mut DiceTransactionUpdater) {
+            updater.files_changed(std::mem::take(&mut *self.touched.lock().unwrap()))
+        }
+    }
+
+    #[derive(Clone, Dupe, Debug, Display, Derivative, Allocative)]
+    #[derivative(Hash, PartialEq, Eq)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct ReadsFile(
+        &'static str,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")] Arc<AtomicUsize>,
+    );
+
+    #[async_trait]
+    impl Key for ReadsFile {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            ctx.record_file_dependency(self.0).await.unwrap();
+            self.1.fetch_add(1, Ordering::SeqCst) + 1
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let watcher = MockFileWatcher::default();
+    let computed = Arc::new(AtomicUsize::new(0));
+    let key = ReadsFile("config.toml", computed.dupe());
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&key).await?, 1);
+
+    // Touching another file does not invalidate the computation, nor add the file to the graph.
+    let key_count = dice.metrics().key_count;
+    watcher.touch("other.toml");
+    let mut updater = dice.updater();
+    watcher.sync(&mut updater);
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&key).await?, 1);
+    assert_eq!(dice.metrics().key_count, key_count);
+
+    watcher.touch("config.toml");
+    let mut updater = dice.updater();
+    watcher.sync(&mut updater);
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&key).await?, 2);
+
+    // Paths are the same file with or without `.` components.
+    watcher.touch("./config.toml");
+    let mut updater = dice.updater();
+    watcher.sync(&mut updater);
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&key).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_resource_report_legacy() -> anyhow::Result<()> {
+    request_resource_report(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn request_resource_report_modern() -> anyhow::Result<()> {
+    request_resource_report(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn request_resource_report(dice: Arc<Dice>) -> anyhow::Result<()> {
+    #[derive(Clone, Dupe, Debug, Display, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    struct Chain(usize);
+
+    #[async_trait]
+    impl Key for Chain {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            match self.0 {
+                0 => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    0
+                }
+                n => ctx.compute(&Chain(n - 1)).await.unwrap() + 1,
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let ctx0 = dice
+        .updater_with_data(UserComputationData::new())
+        .commit()
+        .await;
+    assert_eq!(ctx0.compute(&Chain(2)).await?, 2);
+    assert_eq!(ctx0.request_resource_report().computations, 3);
+    // Only `Chain(0)` takes time: the others spend it waiting for their dependency.
+    let compute_time = ctx0.request_resource_report().compute_time;
+    assert!(compute_time >= Duration::from_millis(100), "{:?}", compute_time);
+    assert!(compute_time < Duration::from_millis(200), "{:?}", compute_time);
+
+    let ctx1 = dice
+        .updater_with_data(UserComputationData::new())
+        .commit()
+        .await;
+    // `Chain(2)` and its dependencies are reused rather than computed for this request.
+    assert_eq!(ctx1.compute(&Chain(4)).await?, 4);
+    assert_eq!(ctx1.request_resource_report().computations, 2);
+    assert_eq!(ctx0.request_resource_report().computations, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transaction_graph_stats_legacy() -> anyhow::Result<()> {
+    transaction_graph_stats(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test]
+async fn transaction_graph_stats_modern() -> anyhow::Result<()> {
+    transaction_graph_stats(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn transaction_graph_stats(dice: Arc<Dice>) -> anyhow::Result<()> {
+    /// A diamond: `Top` depends on `Left` and `Right`, which both depend on `Bottom`.
+    #[derive(Clone, Copy, Dupe, Debug, Display, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    enum Diamond {
+        Top,
+        Left,
+        Right,
+        Bottom,
+    }
+
+    #[async_trait]
+    impl Key for Diamond {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            match self {
+                Diamond::Top => {
+                    ctx.compute(&Diamond::Left).await.unwrap()
+                        + ctx.compute(&Diamond::Right).await.unwrap()
+                }
+                Diamond::Left | Diamond::Right => ctx.compute(&Diamond::Bottom).await.unwrap() + 1,
+                Diamond::Bottom => 1,
+            }
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let ctx = dice
+        .updater_with_data(UserComputationData::new())
+        .commit()
+        .await;
+    assert_eq!(ctx.compute(&Diamond::Top).await?, 4);
+
+    let stats = ctx.transaction_graph_stats();
+    assert_eq!(stats.nodes, 4);
+    assert_eq!(stats.edges, 4);
+    // `Top`, `Left` and `Bottom` are computed at the same time, then `Bottom` is reused for
+    // `Right`.
+    assert_eq!(stats.peak_concurrency, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn checkpointed_computation_resumes_legacy() -> anyhow::Result<()> {
+    checkpointed_computation_resumes(Dice::builder).await
+}
+
+#[tokio::test]
+async fn checkpointed_computation_resumes_modern() -> anyhow::Result<()> {
+    checkpointed_computation_resumes(Dice::modern).await
+}
+
+async fn checkpointed_computation_resumes(builder: fn() -> DiceDataBuilder) -> anyhow::Result<()> {
+    const STEPS: usize = 4;
+
+    /// Sums the numbers below `STEPS` one step at a time, checkpointing after each step. With
+    /// `interrupt_at`, it stops making progress at that step, like a killed daemon. `inputs`
+    /// stands for the fingerprint of the values the sum is computed from.
+    #[derive(Clone, Debug, Display, Derivative, Allocative)]
+    #[derivative(Hash, PartialEq, Eq)]
+    #[display(fmt = "{:?}", self)]
+    #[allocative(skip)]
+    struct LongSum {
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        inputs: u64,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        interrupt_at: Option<usize>,
+        #[derivative(PartialEq = "ignore", Hash = "ignore")]
+        steps: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Key for LongSum {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let (mut step, mut sum) = ctx
+                .resume_checkpoint::<_, (usize, usize)>(self, self.inputs)
+                .await
+                .unwrap()
+                .unwrap_or((0, 0));
+            while step < STEPS {
+                if self.interrupt_at == Some(step) {
+                    futures::future::pending::<()>().await;
+                }
+                sum += step;
+                step += 1;
+                self.steps.fetch_add(1, Ordering::SeqCst);
+                ctx.checkpoint(self, self.inputs, &(step, sum))
+                    .await
+                    .unwrap();
+            }
+            sum
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn checkpoint_id(&self) -> Option<String> {
+            Some("long_sum".to_owned())
+        }
+    }
+
+    let checkpoint_dir = tempfile::tempdir()?;
+    let checkpoints = || std::fs::read_dir(checkpoint_dir.path()).map_or(0, |dir| dir.count());
+    let steps = Arc::new(AtomicUsize::new(0));
+
+    let mut interrupted = builder();
+    interrupted.set(CheckpointConfig::new(checkpoint_dir.path()));
+    let dice = interrupted.build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+    let key = LongSum {
+        inputs: 0,
+        interrupt_at: Some(2),
+        steps: steps.dupe(),
+    };
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), ctx.compute(&key))
+            .await
+            .is_err()
+    );
+    assert_eq!(steps.load(Ordering::SeqCst), 2);
+    assert_eq!(checkpoints(), 1);
+    drop(ctx);
+    drop(dice);
+
+    // A new DICE whose inputs changed discards the checkpoint and starts over.
+    let mut changed = builder();
+    changed.set(CheckpointConfig::new(checkpoint_dir.path()));
+    let dice = changed.build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+    let key = LongSum {
+        inputs: 1,
+        interrupt_at: Some(3),
+        steps: steps.dupe(),
+    };
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), ctx.compute(&key))
+            .await
+            .is_err()
+    );
+    assert_eq!(steps.load(Ordering::SeqCst), 2 + 3);
+    assert_eq!(checkpoints(), 1);
+    drop(ctx);
+    drop(dice);
+
+    // A new DICE with the same inputs, like after a daemon restart, only does the steps after
+    // the checkpoint.
+    let mut resumed = builder();
+    resumed.set(CheckpointConfig::new(checkpoint_dir.path()));
+    let dice = resumed.build(DetectCycles::Disabled);
+    let ctx = dice.updater().commit().await;
+    let key = LongSum {
+        inputs: 1,
+        interrupt_at: None,
+        steps: steps.dupe(),
+    };
+    assert_eq!(ctx.compute(&key).await?, 1 + 2 + 3);
+    assert_eq!(steps.load(Ordering::SeqCst), 2 + STEPS);
+    // The checkpoint is removed once the computation completes.
+    assert_eq!(checkpoints(), 0);
+
+    Ok(())
+}
@@ -8,9 +8,11 @@
  */
 
 use std::any::Any;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
 use async_trait::async_trait;
@@ -23,7 +25,11 @@ use tokio::task::JoinHandle;
 
 use crate::api::computations::DiceComputations;
 use crate::api::cycles::DetectCycles;
+use crate::api::dice::Dice;
+use crate::api::executor::DiceExecutor;
 use crate::api::key::Key;
+use crate::api::priority::DicePreemption;
+use crate::api::priority::DicePriority;
 use crate::api::user_data::UserComputationData;
 use crate::impls::dice::DiceModern;
 
@@ -75,3 +81,259 @@ async fn uses_custom_spawner() {
 
     assert_eq!(spawner.0.load(Ordering::SeqCst), 1);
 }
+
+#[tokio::test]
+async fn uses_executor_set_at_construction() {
+    for mut builder in [Dice::builder(), Dice::modern()] {
+        let spawner = Arc::new(MySpawner(AtomicUsize::new(0)));
+        builder.set(DiceExecutor::new(spawner.dupe()));
+        let dice = builder.build(DetectCycles::Disabled);
+
+        // The executor replaces the default spawner of the transaction.
+        let ctx = dice.updater().commit().await;
+
+        ctx.compute(&K).await.unwrap();
+
+        assert_eq!(spawner.0.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[tokio::test]
+async fn executor_does_not_replace_spawner_of_transaction() {
+    for mut builder in [Dice::builder(), Dice::modern()] {
+        let executor = Arc::new(MySpawner(AtomicUsize::new(0)));
+        builder.set(DiceExecutor::new(executor.dupe()));
+        let dice = builder.build(DetectCycles::Disabled);
+
+        let spawner = Arc::new(MySpawner(AtomicUsize::new(0)));
+        let mut data = UserComputationData::new();
+        data.spawner = spawner.dupe();
+        let ctx = dice.updater_with_data(data).commit().await;
+
+        ctx.compute(&K).await.unwrap();
+
+        assert_eq!(spawner.0.load(Ordering::SeqCst), 1);
+        assert_eq!(executor.0.load(Ordering::SeqCst), 0);
+    }
+}
+
+#[tokio::test]
+async fn projections_use_custom_spawner() {
+    let dice = DiceModern::builder().build(DetectCycles::Disabled);
+    let spawner = Arc::new(MySpawner(AtomicUsize::new(0)));
+
+    let mut data = UserComputationData::new();
+    data.spawner = spawner.dupe();
+    let ctx = dice.updater_with_data(data).commit().await;
+
+    ctx.compute(&K).await.unwrap();
+    assert_eq!(spawner.0.load(Ordering::SeqCst), 1);
+
+    // Storing the projection in the graph finishes on the spawner too.
+    assert!(ctx.compute_mapped(&K, |_: &()| true).await.unwrap());
+    assert_eq!(spawner.0.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn interactive_computations_preempt_background_ones() -> anyhow::Result<()> {
+    const BACKGROUND_KEYS: usize = 20;
+    // Long enough that background computations are never resumed by the timer.
+    const MAX_PAUSE: Duration = Duration::from_secs(3600);
+
+    /// Iterations done by all the background computations.
+    static PROGRESS: AtomicUsize = AtomicUsize::new(0);
+    static PROGRESS_BEFORE_URGENT: AtomicUsize = AtomicUsize::new(0);
+    static PROGRESS_AFTER_URGENT: AtomicUsize = AtomicUsize::new(0);
+    static URGENT_DONE: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Warm(usize);
+
+    #[async_trait]
+    impl Key for Warm {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            while !URGENT_DONE.load(Ordering::SeqCst) {
+                PROGRESS.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+            }
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Urgent;
+
+    #[async_trait]
+    impl Key for Urgent {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            PROGRESS_BEFORE_URGENT.store(PROGRESS.load(Ordering::SeqCst), Ordering::SeqCst);
+            for _ in 0..100 {
+                tokio::task::yield_now().await;
+            }
+            PROGRESS_AFTER_URGENT.store(PROGRESS.load(Ordering::SeqCst), Ordering::SeqCst);
+            URGENT_DONE.store(true, Ordering::SeqCst);
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    for mut builder in [Dice::builder(), Dice::modern()] {
+        PROGRESS.store(0, Ordering::SeqCst);
+        URGENT_DONE.store(false, Ordering::SeqCst);
+
+        builder.set(DicePreemption::new(MAX_PAUSE));
+        let dice = builder.build(DetectCycles::Disabled);
+
+        let background = dice
+            .updater_with_data(UserComputationData {
+                priority: DicePriority::Background,
+                ..Default::default()
+            })
+            .commit()
+            .await;
+        let warming = tokio::spawn(async move {
+            let keys = (0..BACKGROUND_KEYS).map(Warm).collect::<Vec<_>>();
+            futures::future::join_all(keys.iter().map(|k| background.compute(k))).await
+        });
+        while PROGRESS.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let interactive = dice.updater().commit().await;
+        interactive.compute(&Urgent).await?;
+
+        let before = PROGRESS_BEFORE_URGENT.load(Ordering::SeqCst);
+        let after = PROGRESS_AFTER_URGENT.load(Ordering::SeqCst);
+        // Each background computation may finish the iteration it was in when it was preempted.
+        assert!(
+            after - before <= BACKGROUND_KEYS,
+            "{} iterations",
+            after - before
+        );
+
+        // The background computations resume once the interactive one is done.
+        for res in warming.await? {
+            res?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn background_computations_waited_on_by_interactive_ones_are_not_paused()
+-> anyhow::Result<()> {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    static WAITING: AtomicBool = AtomicBool::new(false);
+    static RELEASE: AtomicBool = AtomicBool::new(false);
+
+    /// Computes the chain below it, the last one runs until released.
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Chain(usize);
+
+    #[async_trait]
+    impl Key for Chain {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            if self.0 > 0 {
+                ctx.compute(&Chain(self.0 - 1)).await.unwrap();
+            } else {
+                STARTED.store(true, Ordering::SeqCst);
+                while !RELEASE.load(Ordering::SeqCst) {
+                    tokio::task::yield_now().await;
+                }
+            }
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    #[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+    #[display(fmt = "{:?}", self)]
+    struct Outer;
+
+    #[async_trait]
+    impl Key for Outer {
+        type Value = ();
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            WAITING.store(true, Ordering::SeqCst);
+            ctx.compute(&Chain(2)).await.unwrap();
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            true
+        }
+    }
+
+    for mut builder in [Dice::builder(), Dice::modern()] {
+        STARTED.store(false, Ordering::SeqCst);
+        WAITING.store(false, Ordering::SeqCst);
+        RELEASE.store(false, Ordering::SeqCst);
+
+        // Paused computations are never resumed by the timer during the test.
+        builder.set(DicePreemption::new(Duration::from_secs(3600)));
+        let dice = builder.build(DetectCycles::Disabled);
+
+        let background = dice
+            .updater_with_data(UserComputationData {
+                priority: DicePriority::Background,
+                ..Default::default()
+            })
+            .commit()
+            .await;
+        let warming = tokio::spawn(async move { background.compute(&Chain(2)).await });
+        while !STARTED.load(Ordering::SeqCst) {
+            tokio::task::yield_now().await;
+        }
+
+        // Both transactions are at the same version, so they share the running computations.
+        let interactive = dice.updater().commit().await;
+        let urgent = tokio::spawn(async move { interactive.compute(&Outer).await });
+        while !WAITING.load(Ordering::SeqCst) {
+            tokio::task::yield_now().await;
+        }
+        // Unless it inherited the priority, the chain stays paused now that it is released.
+        RELEASE.store(true, Ordering::SeqCst);
+
+        tokio::time::timeout(Duration::from_secs(60), async {
+            urgent.await??;
+            warming.await??;
+            anyhow::Ok(())
+        })
+        .await??;
+    }
+
+    Ok(())
+}
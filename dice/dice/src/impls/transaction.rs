@@ -16,14 +16,20 @@ use tokio::sync::oneshot;
 
 use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
+use crate::api::events::DiceEvent;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
 use crate::api::storage_type::StorageType;
+use crate::api::transaction::CommitReport;
 use crate::api::user_data::UserComputationData;
+use crate::impls::content_key::ContentKeyId;
+use crate::impls::core::graph::storage::InvalidateOutcome;
 use crate::impls::core::state::CoreStateHandle;
 use crate::impls::core::state::StateRequest;
 use crate::impls::ctx::BaseComputeCtx;
 use crate::impls::ctx::SharedLiveTransactionCtx;
 use crate::impls::key::DiceKey;
+use crate::impls::key::DiceKeyErased;
 use crate::impls::value::DiceKeyValue;
 use crate::impls::value::DiceValidValue;
 use crate::impls::value::DiceValidity;
@@ -37,6 +43,11 @@ use crate::HashMap;
 pub(crate) struct TransactionUpdater {
     dice: Arc<DiceModern>,
     scheduled_changes: Changes,
+    /// Predicates selecting keys to invalidate on commit.
+    #[allocative(skip)]
+    invalidate_matching: Vec<Box<dyn Fn(&dyn KeyDescription) -> bool + Send + Sync>>,
+    /// Content keys whose values are dropped on commit.
+    invalidate_content_keys: Vec<ContentKeyId>,
     user_data: Arc<UserComputationData>,
 }
 
@@ -45,6 +56,8 @@ impl TransactionUpdater {
         Self {
             dice: dice.dupe(),
             scheduled_changes: Changes::new(dice),
+            invalidate_matching: Vec::new(),
+            invalidate_content_keys: Vec::new(),
             user_data,
         }
     }
@@ -82,31 +95,71 @@ impl TransactionUpdater {
             {
                 Ok(validated_value) => self.scheduled_changes.change(
                     k,
-                    ChangeType::UpdateValue(validated_value, K::storage_type()),
+                    ChangeType::UpdateValue(validated_value, StorageType::for_key::<K>()),
                 ),
                 Err(_) => Err(DiceError::invalid_change(Arc::new(k))),
             }
         })
     }
 
+    /// Records all currently known `Key`s matching `pred` as changed. The keys are selected
+    /// when committing, and keys recorded via 'changed' or 'changed_to' take precedence.
+    pub(crate) fn invalidate_matching<F>(&mut self, pred: F)
+    where
+        F: Fn(&dyn KeyDescription) -> bool + Send + Sync + 'static,
+    {
+        self.invalidate_matching.push(Box::new(pred))
+    }
+
+    /// Drops the value computed for `id` on commit, see `ContentKeyCache::invalidate`.
+    pub(crate) fn invalidate_content_key(&mut self, id: ContentKeyId) {
+        self.invalidate_content_keys.push(id)
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version.
     pub(crate) async fn commit(self) -> BaseComputeCtx {
         let user_data = self.user_data.dupe();
         let dice = self.dice.dupe();
 
-        let (transaction, guard) = self.commit_to_state().await;
+        let (transaction, guard, _) = self.commit_to_state(&user_data).await;
 
         BaseComputeCtx::new(transaction, user_data, dice, guard)
     }
 
+    /// Same as `commit`, but also reports what the committed changes did to their keys.
+    pub(crate) async fn commit_with_report(self) -> (BaseComputeCtx, CommitReport) {
+        let user_data = self.user_data.dupe();
+        let dice = self.dice.dupe();
+
+        let (transaction, guard, outcomes) = self.commit_to_state(&user_data).await;
+
+        let mut report = CommitReport::default();
+        for (key, outcome) in outcomes {
+            let key = dice.key_index.get(key).to_string();
+            match outcome {
+                InvalidateOutcome::Unchanged => report.unchanged.push(key),
+                InvalidateOutcome::New => report.new.push(key),
+                InvalidateOutcome::Changed(_) => report.changed.push(key),
+            }
+        }
+        report.sort();
+
+        let transaction = transaction.with_early_cutoff(report.early_cutoff.dupe());
+        (
+            BaseComputeCtx::new(transaction, user_data, dice, guard),
+            report,
+        )
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version,
     /// replacing the user data with the given set
     pub(crate) async fn commit_with_data(self, extra: UserComputationData) -> BaseComputeCtx {
         let dice = self.dice.dupe();
+        let extra = Arc::new(extra);
 
-        let (transaction, guard) = self.commit_to_state().await;
+        let (transaction, guard, _) = self.commit_to_state(&extra).await;
 
-        BaseComputeCtx::new(transaction, Arc::new(extra), dice, guard)
+        BaseComputeCtx::new(transaction, extra, dice, guard)
     }
 
     pub(crate) async fn existing_state(&self) -> BaseComputeCtx {
@@ -134,14 +187,53 @@ impl TransactionUpdater {
             .request(StateRequest::UnstableDropEverything)
     }
 
-    async fn commit_to_state(self) -> (SharedLiveTransactionCtx, ActiveTransactionGuard) {
+    /// Commits the scheduled changes, reporting the resulting invalidation to the tracker of
+    /// `user_data`. Also returns what each change did to its key.
+    async fn commit_to_state(
+        mut self,
+        user_data: &UserComputationData,
+    ) -> (
+        SharedLiveTransactionCtx,
+        ActiveTransactionGuard,
+        Vec<(DiceKey, InvalidateOutcome)>,
+    ) {
+        if !self.invalidate_matching.is_empty() {
+            for (key, erased) in self.dice.key_index.keys() {
+                let k = match erased {
+                    // Injected keys can't be recomputed, so only `changed_to` changes them.
+                    DiceKeyErased::Key(k) if !k.is_injected() => k,
+                    DiceKeyErased::Key(_) | DiceKeyErased::Projection(_) => continue,
+                };
+                if self
+                    .invalidate_matching
+                    .iter()
+                    .any(|pred| pred(k.as_key_description()))
+                {
+                    self.scheduled_changes
+                        .changes
+                        .entry(key)
+                        .or_insert(ChangeType::Invalidate);
+                }
+            }
+        }
+
         let (tx, rx) = oneshot::channel();
         self.dice.state_handle.request(StateRequest::UpdateState {
             changes: self.scheduled_changes.changes.into_iter().collect(),
             resp: tx,
         });
 
-        let v = rx.await.unwrap();
+        let (v, stats) = rx.await.unwrap();
+        // Before any transaction at `v` can reuse the values.
+        for id in &self.invalidate_content_keys {
+            self.dice.content_keys.invalidate(id, v);
+        }
+        user_data.tracker.event(DiceEvent::InvalidationWave {
+            version: v,
+            invalidated_count: stats.invalidated_count,
+            duration: stats.duration,
+        });
+
         let guard = ActiveTransactionGuard::new(v, self.dice.state_handle.dupe());
         let (tx, rx) = oneshot::channel();
         self.dice.state_handle.request(StateRequest::CtxAtVersion {
@@ -150,7 +242,8 @@ impl TransactionUpdater {
             resp: tx,
         });
 
-        rx.await.unwrap()
+        let (transaction, guard) = rx.await.unwrap();
+        (transaction, guard, stats.outcomes)
     }
 }
 
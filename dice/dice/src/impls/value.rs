@@ -12,6 +12,7 @@ use std::fmt::Debug;
 use std::fmt::Formatter;
 
 use allocative::Allocative;
+use allocative::FlameGraphBuilder;
 use dupe::Dupe;
 
 use crate::arc::Arc;
@@ -40,6 +41,37 @@ impl DiceValidValue {
     pub(crate) fn equality(&self, other: &DiceValidValue) -> bool {
         self.0.equality(&*other.0)
     }
+
+    pub(crate) fn instance_equal(&self, other: &DiceValidValue) -> bool {
+        #[allow(clippy::vtable_address_comparisons)]
+        // we literally just want to compare the exact pointer
+        std::sync::Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Whether the key of the value provides a `Key::spill_codec`.
+    pub(crate) fn is_spillable(&self) -> bool {
+        self.0.is_spillable()
+    }
+
+    /// Memory held by the value, if its key provides a `Key::spill_codec`. This walks the whole
+    /// value.
+    pub(crate) fn spillable_size(&self) -> Option<usize> {
+        self.0.spillable_size()
+    }
+
+    /// Serializes the value with the `Key::spill_codec` of its key.
+    pub(crate) fn spill(&self) -> Option<SpilledValue> {
+        self.0.spill()
+    }
+}
+
+/// Reads back a value serialized by `DiceValidValue::spill`. Returns `None` if the bytes can't be
+/// read.
+pub(crate) type ReloadSpilledValue = fn(&[u8]) -> Option<DiceValidValue>;
+
+pub(crate) struct SpilledValue {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) reload: ReloadSpilledValue,
 }
 
 /// Type erased value that may be transient, or whose dependencies are transient
@@ -92,7 +124,6 @@ impl MaybeValidDiceValue {
         self.value.equality(&*other.0)
     }
 
-    #[cfg(test)]
     pub(crate) fn instance_equal(&self, other: &DiceValidValue) -> bool {
         #[allow(clippy::vtable_address_comparisons)]
         // we literally just want to compare the exact pointer
@@ -150,6 +181,18 @@ pub(crate) trait DiceValueDyn: Allocative + Any + Send + Sync + 'static {
     /// Panics if called with incompatible values.
     fn equality(&self, other: &dyn DiceValueDyn) -> bool;
     fn validity(&self) -> bool;
+
+    fn is_spillable(&self) -> bool {
+        false
+    }
+
+    fn spillable_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn spill(&self) -> Option<SpilledValue> {
+        None
+    }
 }
 
 impl dyn DiceValueDyn {
@@ -187,6 +230,29 @@ where
     fn validity(&self) -> bool {
         K::validity(&self.value)
     }
+
+    fn is_spillable(&self) -> bool {
+        K::spill_codec().is_some()
+    }
+
+    fn spillable_size(&self) -> Option<usize> {
+        K::spill_codec()?;
+        let mut builder = FlameGraphBuilder::default();
+        builder.visit_root(&self.value);
+        Some(builder.finish().flamegraph().total_size())
+    }
+
+    fn spill(&self) -> Option<SpilledValue> {
+        let codec = K::spill_codec()?;
+        Some(SpilledValue {
+            bytes: (codec.serialize)(&self.value),
+            reload: |bytes| {
+                let value = (K::spill_codec()?.deserialize)(bytes)?;
+                let value = DiceKeyValue::<K>::new(value);
+                Some(DiceValidValue(std::sync::Arc::new(value)))
+            },
+        })
+    }
 }
 
 #[derive(Allocative)]
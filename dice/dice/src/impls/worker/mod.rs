@@ -16,6 +16,7 @@ use futures::FutureExt;
 use itertools::Either;
 use tracing::Instrument;
 
+use crate::api::priority::DicePreemption;
 use crate::impls::evaluator::AsyncEvaluator;
 use crate::impls::events::DiceEventDispatcher;
 use crate::impls::incremental::IncrementalEngine;
@@ -53,6 +54,13 @@ impl DiceTaskWorker {
 
         let spawner = eval.user_data.spawner.dupe();
         let spawner_ctx = eval.user_data.dupe();
+        let preemption = eval
+            .dice
+            .global_data
+            .get::<DicePreemption>()
+            .ok()
+            .map(|preemption| preemption.task(eval.user_data.priority));
+        let scheduled = preemption.dupe();
 
         let worker = DiceTaskWorker::new(k, eval, events_dispatcher, incremental);
 
@@ -67,7 +75,7 @@ impl DiceTaskWorker {
             // we hold onto the handle and drop it last after consuming the `worker`. This
             // ensures any data being held for the actual evaluation is dropped before we
             // notify the future as done.
-            async move {
+            let work = async move {
                 match worker
                     .await_previous(previously_cancelled_task, state)
                     .await
@@ -82,9 +90,14 @@ impl DiceTaskWorker {
 
                 Box::new(()) as Box<dyn Any + Send + 'static>
             }
-            .instrument(span)
-            .boxed()
+            .instrument(span);
+
+            match scheduled {
+                Some(task) => task.schedule(work).boxed(),
+                None => work.boxed(),
+            }
         })
+        .with_preemption(preemption)
     }
 
     fn new(
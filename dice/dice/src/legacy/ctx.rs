@@ -8,6 +8,7 @@
  */
 
 use std::any::Any;
+use std::fmt::Display;
 use std::future::Future;
 use std::sync::Arc;
 
@@ -21,10 +22,17 @@ use crate::api::cycles::DetectCycles;
 use crate::api::data::DiceData;
 use crate::api::error::DiceErrorImpl;
 use crate::api::error::DiceResult;
+use crate::api::history::ValueChange;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
+use crate::api::priority::DicePreemption;
+use crate::api::progress::KeyProgress;
 use crate::api::projection::ProjectionKey;
+use crate::api::transaction::CommitReport;
+use crate::api::transaction::EarlyCutoffs;
 use crate::api::user_data::UserComputationData;
 use crate::api::user_data::UserCycleDetectorGuard;
+use crate::impls::content_key::ContentKeyId;
 use crate::legacy::cycles::CycleDetector;
 use crate::legacy::incremental::dep_trackers::BothDepTrackers;
 use crate::legacy::incremental::dep_trackers::BothDeps;
@@ -57,6 +65,18 @@ pub(crate) struct ComputationData {
     /// user_data's ActivationTracker when the key evaluation finishes.
     #[allocative(skip)]
     pub(crate) evaluation_data: Mutex<Option<Box<dyn Any + Send + Sync + 'static>>>,
+    /// Where to record recomputed keys that stopped at the early cutoff, if the transaction was
+    /// committed with a report.
+    pub(crate) early_cutoff: Option<EarlyCutoffs>,
+    /// Where to report the progress of the key's evaluation.
+    #[allocative(skip)]
+    pub(crate) progress: KeyProgress,
+    /// The key being evaluated, if the transaction records its requests, see
+    /// `UserComputationData::records_requests`.
+    pub(crate) recorded_key: Option<String>,
+    /// Schedules the spawned computations, if DICE was built with a `DicePreemption`.
+    #[allocative(skip)]
+    pub(crate) preemption: Option<DicePreemption>,
 }
 
 impl ComputationData {
@@ -69,6 +89,10 @@ impl ComputationData {
             },
             user_cycle_detector_guard: None,
             evaluation_data: Mutex::new(None),
+            early_cutoff: None,
+            progress: KeyProgress::default(),
+            recorded_key: None,
+            preemption: None,
         }
     }
 
@@ -90,6 +114,10 @@ impl ComputationData {
                 .transpose()?,
             user_cycle_detector_guard: None,
             evaluation_data: Mutex::new(None),
+            early_cutoff: self.early_cutoff.dupe(),
+            progress: KeyProgress::default(),
+            recorded_key: None,
+            preemption: self.preemption.dupe(),
         })
     }
 
@@ -195,6 +223,14 @@ impl DiceComputationsImplLegacy {
     {
         // This would be simpler with an `async fn/async move {}`, but we create these for every edge in the computation
         // and many of those may be live at a time, and so we need to take more care and ensure this is fairly small.
+        self.dice.key_type_stats.record_request(K::key_type_name());
+        self.extra.user_data.record_request(
+            self.extra
+                .recorded_key
+                .as_ref()
+                .map(|parent| parent as &dyn Display),
+            key,
+        );
         let cache = self.dice.find_cache::<K>();
         let extra = self.extra.subrequest::<StoragePropertiesForKey<K>>(key);
         match extra {
@@ -206,6 +242,53 @@ impl DiceComputationsImplLegacy {
         }
     }
 
+    /// A context for computing `key` outside of the cache, whose deps are tracked separately
+    /// from the deps of this context.
+    pub(crate) fn detached_for_key<K>(&self, key: &K) -> DiceResult<Arc<Self>>
+    where
+        K: Key,
+    {
+        Ok(Self::new_for_key_evaluation(
+            self.dice.dupe(),
+            self.transaction_ctx.dupe(),
+            self.extra.subrequest::<StoragePropertiesForKey<K>>(key)?,
+        ))
+    }
+
+    pub(crate) fn is_cached<K>(&self, key: &K) -> bool
+    where
+        K: Key,
+    {
+        self.dice
+            .find_cache::<K>()
+            .is_cached(key, &self.transaction_ctx)
+    }
+
+    pub(crate) fn dep_count<K>(&self, key: &K) -> Option<usize>
+    where
+        K: Key,
+    {
+        self.dice
+            .find_cache::<K>()
+            .dep_count(key, &self.transaction_ctx)
+    }
+
+    pub(crate) fn invalidation_impact<K>(&self, key: &K) -> usize
+    where
+        K: Key,
+    {
+        self.dice
+            .find_cache::<K>()
+            .invalidation_impact(key, &self.transaction_ctx)
+    }
+
+    pub(crate) fn value_history<K>(&self, key: &K) -> Vec<(VersionNumber, ValueChange)>
+    where
+        K: Key,
+    {
+        self.dice.find_cache::<K>().value_history(key)
+    }
+
     pub(crate) fn compute_projection_sync<P>(
         self: &Arc<Self>,
         derive_from: &OpaqueValueImplLegacy<P::DeriveFromKey>,
@@ -246,10 +329,10 @@ impl DiceComputationsImplLegacy {
             let dice = self.dice.dupe();
             changes.change(
                 k.clone(),
-                Box::new(move |version| {
+                Box::new(move |version, invalidated_count| {
                     debug!(msg = "marking value as changed", version = %version, key = %k);
                     let cache = dice.find_cache::<K>();
-                    cache.dirty(k, version, true);
+                    *invalidated_count += cache.dirty(k, version, true);
 
                     true
                 }),
@@ -271,20 +354,58 @@ impl DiceComputationsImplLegacy {
             let dice = self.dice.dupe();
             changes.change(
                 k.clone(),
-                Box::new(move |version| {
+                Box::new(move |version, invalidated_count| {
                     let cache = dice.find_cache::<K>();
                     debug!(msg = "marking value as updated", version = %version, key = %k);
-                    cache.update_injected_value(k, version, v)
+                    let (changed, count) =
+                        cache.update_injected_value_counting_rdeps(k, version, v);
+                    *invalidated_count += count;
+                    changed
                 }),
             )
         })
     }
 
+    pub(crate) fn invalidate_matching<F>(&self, pred: F)
+    where
+        F: Fn(&dyn KeyDescription) -> bool + Send + Sync + 'static,
+    {
+        let dice = self.dice.dupe();
+        self.transaction_ctx
+            .changes()
+            .change_matching(Box::new(move |version, invalidated_count, mut report| {
+                debug!(msg = "marking matching values as changed", version = %version);
+                let engines = dice.map.read().engines().to_vec();
+                let mut any_matched = false;
+                for engine in engines {
+                    any_matched |= engine.dirty_matching(
+                        &pred,
+                        version,
+                        invalidated_count,
+                        report.as_deref_mut().map(|report| &mut report.changed),
+                    );
+                }
+                any_matched
+            }))
+    }
+
+    /// Drops the value computed for `id` on commit, see `ContentKeyCache::invalidate`.
+    pub(crate) fn invalidate_content_key(&self, id: ContentKeyId) {
+        let dice = self.dice.dupe();
+        self.transaction_ctx
+            .changes()
+            .change_matching(Box::new(move |version, _invalidated_count, _report| {
+                dice.content_keys.invalidate(&id, version);
+                // Only the keys with the content key change the graph, see `invalidate_matching`.
+                false
+            }))
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version.
     /// This can only be called when the this is the only node remaining in the computation graph
     pub(crate) fn commit(self: Arc<Self>) -> Arc<DiceComputationsImplLegacy> {
         // TODO need to clean up these ctxs so we have less runtime errors from Arc references
-        let this = Arc::try_unwrap(self)
+        let mut this = Arc::try_unwrap(self)
             .map_err(|_| "Error: tried to commit when there are more references")
             .unwrap();
         let eval = Arc::try_unwrap(this.transaction_ctx)
@@ -293,11 +414,32 @@ impl DiceComputationsImplLegacy {
 
         // hold onto the prev version until we get the new one below so we don't increment minor
         // version needlessly.
-        let _prev_v = eval.commit();
+        let _prev_v = eval.commit(&this.extra.user_data);
 
+        this.extra.early_cutoff = None;
         this.dice.make_ctx(this.extra)
     }
 
+    /// Same as `commit`, but also reports what the committed changes did to their keys.
+    pub(crate) fn commit_with_report(
+        self: Arc<Self>,
+    ) -> (Arc<DiceComputationsImplLegacy>, CommitReport) {
+        // TODO need to clean up these ctxs so we have less runtime errors from Arc references
+        let mut this = Arc::try_unwrap(self)
+            .map_err(|_| "Error: tried to commit when there are more references")
+            .unwrap();
+        let eval = Arc::try_unwrap(this.transaction_ctx)
+            .map_err(|_| "Error: tried to commit when there are more references")
+            .unwrap();
+
+        // hold onto the prev version until we get the new one below so we don't increment minor
+        // version needlessly.
+        let (_prev_v, report) = eval.commit_with_report(&this.extra.user_data);
+
+        this.extra.early_cutoff = Some(report.early_cutoff.dupe());
+        (this.dice.make_ctx(this.extra), report)
+    }
+
     /// Same as `commit`, but replacing the user data with the given
     pub(crate) fn commit_with_data(
         self: Arc<Self>,
@@ -313,13 +455,18 @@ impl DiceComputationsImplLegacy {
 
         // hold onto the prev version until we get the new one below so we don't increment minor
         // version needlessly.
-        let _prev_v = eval.commit();
+        let extra = Arc::new(extra);
+        let _prev_v = eval.commit(&extra);
 
         this.dice.make_ctx(ComputationData {
-            user_data: Arc::new(extra),
+            user_data: extra,
             cycle_detector: this.extra.cycle_detector.take(),
             user_cycle_detector_guard: None,
             evaluation_data: Mutex::new(None),
+            early_cutoff: None,
+            progress: KeyProgress::default(),
+            recorded_key: None,
+            preemption: this.extra.preemption.take(),
         })
     }
 
@@ -362,6 +509,10 @@ impl DiceComputationsImplLegacy {
         *evaluation_data = Some(Box::new(value) as _);
         Ok(())
     }
+
+    pub(crate) fn report_progress<P: Send + Sync + 'static>(&self, progress: P) {
+        self.extra.progress.report(progress)
+    }
 }
 
 #[cfg(test)]
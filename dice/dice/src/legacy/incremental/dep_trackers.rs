@@ -309,7 +309,7 @@ mod internals {
         }
 
         fn dirty(&self, v: VersionNumber) {
-            self.engine().dirty(self.k.clone(), v, false)
+            self.engine().dirty(self.k.clone(), v, false);
         }
 
         fn get_key_equality(&self) -> PartialEqAny {
@@ -22,6 +22,7 @@ pub(crate) mod dependencies;
 pub(crate) mod storage_properties;
 
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::collections::Bound;
 use std::fmt::Debug;
 use std::ops::Bound::Included;
@@ -46,6 +47,7 @@ use parking_lot::RwLockReadGuard;
 use parking_lot::RwLockWriteGuard;
 use sorted_vector_map::SortedVectorMap;
 
+use crate::api::history::ValueChange;
 use crate::api::storage_type::StorageType;
 use crate::impls::core::graph::history::HistoryState;
 use crate::introspection::graph::AnyKey;
@@ -699,6 +701,49 @@ where
         }
     }
 
+    /// The value stored for `key` nearest before its version, which a recomputation of the key at
+    /// that version reuses if it computes an equal value.
+    pub(crate) fn previous_value(&self, key: VersionedGraphKeyRef<K::Key>) -> Option<K::Value> {
+        let versioned = self.last_n.get(key.k)?;
+        let previous = versioned
+            .range((Included(VersionNumber::new(0)), Included(key.v)))
+            .next_back()
+            .and_then(|(_, e)| match e {
+                VersionedGraphNodeInternal::Occupied(e) => Some(e.res.dupe()),
+                VersionedGraphNodeInternal::Vacant(_) => None,
+                VersionedGraphNodeInternal::Transient(_) => None,
+            });
+        previous
+    }
+
+    /// The versions at which a new value was stored for `key`, and the versions at which a stored
+    /// value was verified again after being invalidated, in version order. Only the values that
+    /// are still stored are reported.
+    pub(crate) fn value_history(&self, key: &K::Key) -> Vec<(VersionNumber, ValueChange)> {
+        let Some(versioned) = self.last_n.get(key) else {
+            return Vec::new();
+        };
+
+        let mut history = BTreeMap::new();
+        let mut seen = HashSet::default();
+        for (v, entry) in versioned.iter() {
+            let VersionedGraphNodeInternal::Occupied(entry) = entry else {
+                continue;
+            };
+            // the same node can be stored at several versions to keep newer history reachable,
+            // but only the first of those is where its value became valid.
+            if !seen.insert(Arc::as_ptr(entry)) {
+                continue;
+            }
+            for verified in entry.read_meta().hist.verified_versions() {
+                history.entry(verified).or_insert(ValueChange::Unchanged);
+            }
+            history.insert(*v, ValueChange::Changed);
+        }
+
+        history.into_iter().collect()
+    }
+
     /// Marks an existing entry as reusable at the given key version.
     pub(crate) fn mark_unchanged(
         &self,
@@ -49,9 +49,14 @@ use tracing::Span;
 
 use crate::api::error::DiceResult;
 use crate::api::events::DiceEvent;
+use crate::api::history::ValueChange;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
+use crate::api::priority::DicePriority;
+use crate::api::priority::PreemptionTask;
 use crate::api::projection::DiceProjectionComputations;
 use crate::api::projection::ProjectionKey;
+use crate::api::transaction::EarlyCutoffs;
 use crate::api::user_data::UserComputationData;
 use crate::impls::core::graph::history::CellHistory;
 use crate::introspection::graph::EngineForIntrospection;
@@ -72,6 +77,7 @@ use crate::legacy::incremental::graph::VersionedGraphKeyRef;
 use crate::legacy::incremental::graph::VersionedGraphResult;
 use crate::legacy::incremental::graph::VersionedGraphResultMismatch;
 use crate::legacy::incremental::transaction_ctx::TransactionCtx;
+use crate::legacy::incremental::versions::MinorVersion;
 use crate::legacy::opaque::OpaqueValueImplLegacy;
 use crate::legacy::projection::ProjectionKeyAsKey;
 use crate::legacy::projection::ProjectionKeyProperties;
@@ -96,12 +102,31 @@ pub(crate) trait IncrementalComputeProperties: StorageProperties {
         transaction_ctx: &Arc<TransactionCtx>,
         extra: &ComputationData,
     ) -> DiceResult<GraphNode<Self>>;
+
+    /// Describes `key` if it is a user `Key` which can be recomputed, so it can be selected by
+    /// predicates over keys.
+    fn key_description(_key: &Self::Key) -> Option<&dyn KeyDescription> {
+        None
+    }
 }
 
 #[derive(Allocative)]
 struct RunningEntry<K: IncrementalComputeProperties> {
     task: <K as IncrementalComputeProperties>::DiceTask,
     epoch: Epoch,
+    /// The scheduling of the task, if DICE has a `DicePreemption`.
+    #[allocative(skip)]
+    preemption: Option<PreemptionTask>,
+}
+
+impl<K: IncrementalComputeProperties> RunningEntry<K> {
+    /// Records that a computation of a transaction with `priority` waits for this task, see
+    /// `PreemptionTask::waited_on`.
+    fn waited_on(&self, priority: DicePriority) {
+        if let Some(preemption) = &self.preemption {
+            preemption.waited_on(priority);
+        }
+    }
 }
 
 #[derive(Allocative, Copy, Clone, Dupe, Eq, PartialEq, derive_more::Display)]
@@ -135,6 +160,17 @@ pub(crate) trait ErasedEngine: Allocative {
     fn introspect(&self) -> &dyn EngineForIntrospection;
 
     fn gc_version(&self, v: VersionNumber);
+
+    /// Marks the keys matching `pred` as changed at `version`, returning whether any key matched.
+    /// The number of transitive rdeps this invalidated is added to `invalidated_count`, and the
+    /// matched keys are added to `changed` if given.
+    fn dirty_matching(
+        &self,
+        pred: &dyn Fn(&dyn KeyDescription) -> bool,
+        version: VersionNumber,
+        invalidated_count: &mut usize,
+        changed: Option<&mut Vec<String>>,
+    ) -> bool;
 }
 
 impl<K> ErasedEngine for IncrementalEngine<K>
@@ -150,6 +186,31 @@ where
         running_map.remove(&v);
         running_map.shrink_to_fit();
     }
+
+    fn dirty_matching(
+        &self,
+        pred: &dyn Fn(&dyn KeyDescription) -> bool,
+        version: VersionNumber,
+        invalidated_count: &mut usize,
+        mut changed: Option<&mut Vec<String>>,
+    ) -> bool {
+        // Collect first, since dirtying writes to the graph we are iterating.
+        let matching: Vec<K::Key> = self
+            .versioned_cache
+            .iter()
+            .filter(|e| K::key_description(e.key()).map_or(false, pred))
+            .map(|e| e.key().clone())
+            .collect();
+
+        let any_matched = !matching.is_empty();
+        for k in matching {
+            if let Some(changed) = changed.as_deref_mut() {
+                changed.push(k.to_string());
+            }
+            *invalidated_count += self.dirty(k, version, true);
+        }
+        any_matched
+    }
 }
 
 pub trait Computable:
@@ -178,9 +239,37 @@ where
         Epoch(self.epoch.fetch_add(1, Ordering::Relaxed))
     }
 
-    /// Dirties the value at K
+    /// Stores the newly computed value of a key. If the transaction was committed with a report,
+    /// also records the key there when the value is equal to the one it replaces, since that
+    /// stops the invalidation at the key.
+    fn store_computed_value(
+        &self,
+        key: VersionedGraphKey<K::Key>,
+        m_v: MinorVersion,
+        early_cutoff: Option<&EarlyCutoffs>,
+        value: K::Value,
+        both_deps: BothDeps,
+    ) -> GraphNode<K> {
+        let previous = early_cutoff.and_then(|_| self.versioned_cache.previous_value(key.as_ref()));
+
+        let (entry, _old) = self.versioned_cache.update_computed_value(key, m_v, value, both_deps);
+
+        if let (Some(early_cutoff), Some(previous)) = (early_cutoff, previous) {
+            if self
+                .versioned_cache
+                .storage_properties
+                .equality(&previous, entry.val())
+            {
+                early_cutoff.record(entry.key());
+            }
+        }
+
+        entry
+    }
+
+    /// Dirties the value at K. Returns the number of transitive rdeps that were invalidated.
     #[instrument(level = "info", skip(self), fields(k = %k, version = %version))]
-    pub(crate) fn dirty(&self, k: K::Key, version: VersionNumber, force_dirty: bool) {
+    pub(crate) fn dirty(&self, k: K::Key, version: VersionNumber, force_dirty: bool) -> usize {
         // It is crucial that we dirty first before updating the rdeps.
         // This is related to the race condition where we invalidate while nodes are being inserted
         // into the graph at the same time:
@@ -205,12 +294,15 @@ where
             // if we actually did something, invalidate the rdeps of occupied entries
             if let Some(node) = node.unpack_occupied() {
                 debug!("dirtying rdeps");
-                Self::invalidate_rdeps(version, GraphNode::occupied(node.dupe()))
+                return Self::invalidate_rdeps(version, GraphNode::occupied(node.dupe()));
             }
         }
+        0
     }
 
-    fn invalidate_rdeps(version: VersionNumber, invalidated: GraphNode<K>) {
+    /// Invalidates the transitive rdeps of `invalidated`, returning how many were newly
+    /// invalidated.
+    fn invalidate_rdeps(version: VersionNumber, invalidated: GraphNode<K>) -> usize {
         let mut queue = {
             let metadata = invalidated.read_meta();
             let rdeps = metadata.rdeps.rdeps();
@@ -222,6 +314,7 @@ where
                 .collect::<Vec<_>>()
         };
 
+        let mut invalidated_count = 0;
         while let Some((rdep, relevant_version)) = queue.pop() {
             if let Some(node) = rdep.0.upgrade() {
                 let mut metadata = node.writable();
@@ -238,6 +331,7 @@ where
                     // the version it was dirtied at, it may no longer depend on the current node
                     // so we skip marking it as dirty, and rely on delayed propagation of dirty
                     if metadata.hist.mark_invalidated(version) {
+                        invalidated_count += 1;
                         queue.extend({
                             let rdeps = metadata.rdeps.rdeps();
 
@@ -251,6 +345,7 @@ where
                 }
             }
         }
+        invalidated_count
     }
 }
 
@@ -268,14 +363,84 @@ where
         self.eval_entry_versioned(k, transaction_ctx, extra)
     }
 
+    /// Whether the cache has an entry for `k` that is valid at the transaction's version.
+    pub(crate) fn is_cached(&self, k: &K::Key, transaction_ctx: &TransactionCtx) -> bool {
+        matches!(
+            self.versioned_cache.get(
+                VersionedGraphKeyRef::new(transaction_ctx.get_version(), k),
+                transaction_ctx.get_minor_version(),
+            ),
+            VersionedGraphResult::Match(_)
+        )
+    }
+
+    /// The number of deps of the entry for `k` that is valid at the transaction's version.
+    pub(crate) fn dep_count(&self, k: &K::Key, transaction_ctx: &TransactionCtx) -> Option<usize> {
+        match self.versioned_cache.get(
+            VersionedGraphKeyRef::new(transaction_ctx.get_version(), k),
+            transaction_ctx.get_minor_version(),
+        ) {
+            VersionedGraphResult::Match(entry) => {
+                Some(entry.read_meta().deps.deps().map_or(0, |deps| deps.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The number of distinct nodes that transitively depend on the valid value of `k` at this
+    /// version, i.e. how many nodes invalidating `k` would dirty. Nothing is invalidated.
+    pub(crate) fn invalidation_impact(
+        &self,
+        k: &K::Key,
+        transaction_ctx: &TransactionCtx,
+    ) -> usize {
+        let rdeps = match self.versioned_cache.get(
+            VersionedGraphKeyRef::new(transaction_ctx.get_version(), k),
+            transaction_ctx.get_minor_version(),
+        ) {
+            VersionedGraphResult::Match(entry) => entry.read_meta().rdeps.dupe(),
+            _ => return 0,
+        };
+
+        let mut visited = HashSet::default();
+        let mut queue = vec![rdeps];
+        while let Some(rdeps) = queue.pop() {
+            for rdep in rdeps.rdeps().rdeps.keys() {
+                if let Some(node) = rdep.0.upgrade() {
+                    if visited.insert(rdep.dupe()) {
+                        queue.push(node.read_rdeps());
+                    }
+                }
+            }
+        }
+        visited.len()
+    }
+
+    /// The history of the values stored for `k`. See `VersionedGraph::value_history`.
+    pub(crate) fn value_history(&self, k: &K::Key) -> Vec<(VersionNumber, ValueChange)> {
+        self.versioned_cache.value_history(k)
+    }
+
     /// Updates the value at K. Returns whether this injected value actually causes a change
-    #[instrument(level = "info", skip(self, res, ), fields(k = %k, version = %version))]
+    #[cfg(test)]
     pub(crate) fn update_injected_value(
         self: &Arc<Self>,
         k: K::Key,
         version: VersionNumber,
         res: K::Value,
     ) -> bool {
+        self.update_injected_value_counting_rdeps(k, version, res).0
+    }
+
+    /// Updates the value at K. Returns whether this injected value actually causes a change, and
+    /// the number of transitive rdeps that were invalidated.
+    #[instrument(level = "info", skip(self, res, ), fields(k = %k, version = %version))]
+    pub(crate) fn update_injected_value_counting_rdeps(
+        self: &Arc<Self>,
+        k: K::Key,
+        version: VersionNumber,
+        res: K::Value,
+    ) -> (bool, usize) {
         // It is crucial that we `dirty` first before updating the `rdeps`.
         // See `IncrementalEngine::dirty` below for details.
         let node = self
@@ -288,13 +453,15 @@ where
             .versioned_cache
             .update_injected_value(VersionedGraphKey::new(version, k), res);
 
-        if let Some(invalidated) = invalidated {
+        let invalidated_count = if let Some(invalidated) = invalidated {
             debug!("dirtying rdeps");
             Self::invalidate_rdeps(version, invalidated)
-        }
+        } else {
+            0
+        };
 
         let is_changed = new.get_history().latest_verified_before(version) == Some(version);
-        is_changed
+        (is_changed, invalidated_count)
     }
 
     // NOTE: Avoid making this an `async fn`. This function uses a bit of stack space, and it's
@@ -314,6 +481,7 @@ where
             DiceFuture::Ready(Some(entry))
         } else {
             let this = self.dupe();
+            let priority = extra.user_data.priority;
 
             let running_map = self.get_running_map(transaction_ctx);
 
@@ -321,6 +489,7 @@ where
                 Entry::Occupied(mut occupied) => {
                     if let Some(existing) = occupied.get().task.pollable() {
                         debug!(k=%k, msg = "found a task that is currently running. polling on existing task");
+                        occupied.get().waited_on(priority);
                         existing
                     } else {
                         let mut fut = None;
@@ -338,6 +507,7 @@ where
 
                         debug!(k=%k, epoch=%occupied.get().epoch, msg = "new task inserted into running map");
 
+                        occupied.get().waited_on(priority);
                         fut.unwrap()
                     }
                 }
@@ -347,6 +517,7 @@ where
 
                     debug!(k=%k, epoch=%entry.epoch, msg = "new task inserted into running map");
 
+                    entry.waited_on(priority);
                     fut
                 }
             };
@@ -377,6 +548,10 @@ where
         let epoch = self.next_epoch();
 
         let user_data = extra.user_data.dupe();
+        let preemption = extra
+            .preemption
+            .as_ref()
+            .map(|preemption| preemption.task(user_data.priority));
 
         struct Evaluation<K: IncrementalComputeProperties> {
             engine: Arc<IncrementalEngine<K>>,
@@ -512,21 +687,40 @@ where
                     debug!(msg = "awaiting cancelled future", epoch = %instance_epoch);
                     instance.await
                 },
+                preemption.dupe(),
+                &user_data,
+                span,
+            ),
+            None => Self::spawn_task(
+                future,
+                futures::future::ready(()),
+                preemption.dupe(),
                 &user_data,
                 span,
             ),
-            None => Self::spawn_task(future, futures::future::ready(()), &user_data, span),
         };
 
-        (RunningEntry { task, epoch }, handle)
+        (
+            RunningEntry {
+                task,
+                epoch,
+                preemption,
+            },
+            handle,
+        )
     }
 
     fn spawn_task(
         future: impl Future<Output = CancellableResult<GraphNode<K>>> + Send + 'static,
         preamble: impl Future<Output = ()> + Send + 'static,
+        preemption: Option<PreemptionTask>,
         spawner_ctx: &UserComputationData,
         span: Span,
     ) -> (WeakDiceFutureHandle<K>, DiceFuture<K>) {
+        let future = match preemption {
+            Some(preemption) => preemption.schedule(future).boxed(),
+            None => future.boxed(),
+        };
         let (task, fut) = spawn_dropcancel_with_preamble(
             future,
             preamble,
@@ -587,11 +781,13 @@ where
         };
 
         debug!(msg = "evaluation finished. updating caches");
+        let early_cutoff = extra.early_cutoff.dupe();
         extra.finished_computing_key::<K>(k, &both_deps, false);
 
-        let (entry, _old) = self.versioned_cache.update_computed_value(
+        let entry = self.store_computed_value(
             VersionedGraphKey::new(v, k.clone()),
             m_v,
+            early_cutoff.as_ref(),
             value,
             both_deps,
         );
@@ -794,14 +990,13 @@ impl<P: ProjectionKey> IncrementalEngine<ProjectionKeyProperties<P>> {
 
         let value = key.k.compute(derive_from, &ctx);
 
-        let (entry, _old) = self.versioned_cache.update_computed_value(
+        self.store_computed_value(
             VersionedGraphKey::new(transaction_ctx.get_version(), key.clone()),
             transaction_ctx.get_minor_version(),
+            extra.early_cutoff.as_ref(),
             value,
             derive_from_as_deps,
-        );
-
-        entry
+        )
     }
 
     /// Asynchronously recompute projection key.
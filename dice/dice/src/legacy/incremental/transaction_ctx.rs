@@ -9,6 +9,7 @@
 
 use std::sync::Arc;
 use std::sync::Weak;
+use std::time::Instant;
 
 use allocative::Allocative;
 use anymap::any::Any;
@@ -18,7 +19,10 @@ use parking_lot::MutexGuard;
 
 use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
+use crate::api::events::DiceEvent;
 use crate::api::key::Key;
+use crate::api::transaction::CommitReport;
+use crate::api::user_data::UserComputationData;
 use crate::legacy::incremental::versions::MinorVersion;
 use crate::legacy::incremental::versions::VersionForWrites;
 use crate::legacy::incremental::versions::VersionGuard;
@@ -114,11 +118,37 @@ impl TransactionCtx {
         }
     }
 
-    pub(crate) fn commit(self) -> VersionGuard {
-        let is_changed = {
+    /// Commits the changes, reporting the resulting invalidation to the tracker of `user_data`.
+    pub(crate) fn commit(self, user_data: &UserComputationData) -> VersionGuard {
+        self.commit_reporting(user_data, None)
+    }
+
+    /// Commits the changes, reporting which keys had their value changed. The legacy engine can't
+    /// tell a new key apart from a changed one, so new keys are reported as changed.
+    pub(crate) fn commit_with_report(
+        self,
+        user_data: &UserComputationData,
+    ) -> (VersionGuard, CommitReport) {
+        let mut report = CommitReport::default();
+        let version_guard = self.commit_reporting(user_data, Some(&mut report));
+        report.sort();
+
+        (version_guard, report)
+    }
+
+    /// Commits the changes. The changed keys are only formatted when `report` is given.
+    fn commit_reporting(
+        self,
+        user_data: &UserComputationData,
+        mut report: Option<&mut CommitReport>,
+    ) -> VersionGuard {
+        let start = Instant::now();
+        let mut any_changed = false;
+        let mut invalidated_count = 0;
+        {
             let mut changed = self.changes();
             let version_for_writes = self.get_version_for_writes();
-            let num_changes = changed.ops().len();
+            let num_changes = changed.ops().len() + changed.matching.len();
             debug!(
                 old_version = ?self.version_guard.version,
                 version_for_writes = ?version_for_writes,
@@ -126,32 +156,62 @@ impl TransactionCtx {
                 num_changes = num_changes
             );
 
-            changed.ops().drain(..).fold(false, |has_change, change| {
-                change(version_for_writes) || has_change
-            })
-        };
+            // Apply the changes of matching keys first, so that explicit changes of the same keys
+            // take precedence.
+            for change in changed.matching.drain(..) {
+                any_changed |= change(
+                    version_for_writes,
+                    &mut invalidated_count,
+                    report.as_deref_mut(),
+                );
+            }
+            for change in changed.ops().drain(..) {
+                any_changed |= change(
+                    version_for_writes,
+                    &mut invalidated_count,
+                    report.as_deref_mut(),
+                );
+            }
+        }
 
-        if is_changed {
+        let version = if any_changed {
             debug!(
                 old_version = %self.version_guard.version,
                 version_for_writes = %self.get_version_for_writes(),
                 msg = "committed new changes",
             );
+            self.get_version_for_writes()
         } else {
             debug!(version = %self.version_guard.version, msg = "no changes to commit");
-            self.version_for_writes.rollback()
-        }
+            self.version_for_writes.rollback();
+            self.version_guard.version
+        };
+        user_data.tracker.event(DiceEvent::InvalidationWave {
+            version,
+            invalidated_count,
+            duration: start.elapsed(),
+        });
 
         self.version_guard
     }
 }
 
+/// A change recorded on a transaction, applied on commit. It is given the version to commit to,
+/// adds the number of rdeps it invalidated to the given count, and returns whether any value
+/// changed. The changed keys are added to the report if one is being collected.
+pub(crate) type Change =
+    Box<dyn FnOnce(VersionNumber, &mut usize, Option<&mut CommitReport>) -> bool + Send>;
+
 #[derive(Allocative)]
 pub(crate) struct Changes {
     #[allocative(skip)] // TODO(nga): measure.
     keys: Map<dyn Any + Sync + Send>,
+    /// Changes of individual keys.
     #[allocative(skip)] // TODO(nga): measure.
-    changes: Vec<Box<dyn FnOnce(VersionNumber) -> bool + Send>>,
+    changes: Vec<Change>,
+    /// Changes of all keys matching a predicate.
+    #[allocative(skip)]
+    matching: Vec<Change>,
 }
 
 impl Changes {
@@ -159,13 +219,15 @@ impl Changes {
         Self {
             keys: Map::new(),
             changes: vec![],
+            matching: vec![],
         }
     }
 
+    /// Records a change of `key`, which returns whether the key's value changed.
     pub(crate) fn change<K: Key>(
         &mut self,
         key: K,
-        change: Box<dyn FnOnce(VersionNumber) -> bool + Send>,
+        change: Box<dyn FnOnce(VersionNumber, &mut usize) -> bool + Send>,
     ) -> DiceResult<()> {
         let map = self
             .keys
@@ -174,12 +236,26 @@ impl Changes {
         if !map.insert(key.clone()) {
             Err(DiceError::duplicate(Arc::new(key)))
         } else {
-            self.changes.push(change);
+            self.changes.push(Box::new(move |version, invalidated_count, report| {
+                let changed = change(version, invalidated_count);
+                if let Some(report) = report {
+                    if changed {
+                        report.changed.push(key.to_string());
+                    } else {
+                        report.unchanged.push(key.to_string());
+                    }
+                }
+                changed
+            }));
             Ok(())
         }
     }
 
-    pub fn ops(&mut self) -> &mut Vec<Box<dyn FnOnce(VersionNumber) -> bool + Send>> {
+    pub(crate) fn change_matching(&mut self, change: Change) {
+        self.matching.push(change);
+    }
+
+    pub fn ops(&mut self) -> &mut Vec<Change> {
         &mut self.changes
     }
 }
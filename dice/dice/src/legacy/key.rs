@@ -42,7 +42,7 @@ where
     type Value = K::Value;
 
     fn storage_type(&self) -> StorageType {
-        K::storage_type()
+        StorageType::for_key::<K>()
     }
 
     fn equality(&self, x: &Self::Value, y: &Self::Value) -> bool {
@@ -11,6 +11,7 @@ use std::fmt::Debug;
 use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 use std::sync::Weak;
+use std::time::Instant;
 
 use allocative::Allocative;
 use async_trait::async_trait;
@@ -36,11 +37,16 @@ use crate::api::computations::DiceComputations;
 use crate::api::cycles::DetectCycles;
 use crate::api::data::DiceData;
 use crate::api::error::DiceResult;
+use crate::api::executor::DiceExecutor;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
+use crate::api::priority::DicePreemption;
 use crate::api::projection::ProjectionKey;
 use crate::api::transaction::DiceTransactionUpdater;
 use crate::api::user_data::UserComputationData;
 use crate::ctx::DiceComputationsImpl;
+use crate::impls::content_key::ContentKeyCache;
+use crate::impls::key_type_stats::KeyTypeStats;
 use crate::legacy::ctx::ComputationData;
 use crate::legacy::ctx::DiceComputationsImplLegacy;
 use crate::legacy::incremental::dep_trackers::BothDeps;
@@ -72,6 +78,9 @@ pub(crate) struct DiceLegacy {
     pub(crate) active_transaction_count: AtomicU32,
     #[allocative(skip)]
     active_versions_observer: watch::Receiver<usize>,
+    pub(crate) content_keys: ContentKeyCache,
+    #[allocative(skip)]
+    pub(crate) key_type_stats: KeyTypeStats,
 }
 
 impl Debug for DiceLegacy {
@@ -130,6 +139,8 @@ impl DiceLegacy {
             detect_cycles,
             active_transaction_count: AtomicU32::new(0),
             active_versions_observer,
+            content_keys: ContentKeyCache::default(),
+            key_type_stats: KeyTypeStats::default(),
         })
     }
 
@@ -139,9 +150,14 @@ impl DiceLegacy {
 
     pub fn updater_with_data(
         self: &Arc<DiceLegacy>,
-        extra: UserComputationData,
+        mut extra: UserComputationData,
     ) -> DiceTransactionUpdater {
-        let ctx = self.make_ctx(ComputationData::new(extra, self.detect_cycles));
+        if let Ok(executor) = self.data.get::<DiceExecutor>() {
+            executor.apply(&mut extra);
+        }
+        let mut data = ComputationData::new(extra, self.detect_cycles);
+        data.preemption = self.data.get::<DicePreemption>().ok().cloned();
+        let ctx = self.make_ctx(data);
         DiceTransactionUpdater(DiceTransactionUpdaterImpl::Legacy(ctx))
     }
 
@@ -214,6 +230,7 @@ impl DiceLegacy {
             active_transaction_count: self
                 .active_transaction_count
                 .load(std::sync::atomic::Ordering::SeqCst),
+            key_types: self.key_type_stats.snapshot(),
         }
     }
 
@@ -253,6 +270,11 @@ impl<K: Key> IncrementalComputeProperties for StoragePropertiesForKey<K> {
             .eval_entry_versioned(key, transaction_ctx, extra.subrequest::<Self>(key)?)
             .await)
     }
+
+    fn key_description(key: &K) -> Option<&dyn KeyDescription> {
+        // Injected keys can't be recomputed, so they are never selected.
+        if K::is_injected() { None } else { Some(key) }
+    }
 }
 
 #[async_trait]
@@ -262,24 +284,38 @@ impl<K: Key> Evaluator for StoragePropertiesForKey<K> {
         k: &K,
         transaction_ctx: Arc<TransactionCtx>,
         cancellations: &CancellationContext,
-        extra: ComputationData,
+        mut extra: ComputationData,
     ) -> EvaluationResult<K::Value> {
-        let ctx = DiceComputationsImplLegacy::new_for_key_evaluation(
-            self.dice
-                .upgrade()
-                .expect("Dice holds DiceMap so it should still be alive here"),
-            transaction_ctx,
-            extra,
-        );
+        let dice = self
+            .dice
+            .upgrade()
+            .expect("Dice holds DiceMap so it should still be alive here");
+        let user_data = extra.user_data.dupe();
+        extra.progress = user_data.progress.for_key(k);
+        user_data.record_computing(k);
+        if user_data.records_requests() {
+            extra.recorded_key = Some(k.to_string());
+        }
+        let ctx =
+            DiceComputationsImplLegacy::new_for_key_evaluation(dice.dupe(), transaction_ctx, extra);
 
-        let value = k
+        let running = user_data.graph_stats.computation_started();
+        let start = Instant::now();
+        let value = dice
+            .content_keys
             .compute(
+                k,
                 &mut DiceComputations(DiceComputationsImpl::Legacy(ctx.dupe())),
                 cancellations,
             )
             .await;
+        let elapsed = start.elapsed();
+        dice.key_type_stats
+            .record_computation(K::key_type_name(), elapsed);
+        user_data.record_computed(k, elapsed);
 
         let (both_deps, extra) = ctx.finalize();
+        running.finished(both_deps.deps.len());
 
         EvaluationResult {
             value,
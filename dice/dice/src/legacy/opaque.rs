@@ -67,6 +67,11 @@ impl<'a, K: Key> OpaqueValueImplLegacy<'a, K> {
         self.value.key()
     }
 
+    /// The value, without recording a dependency on `K`.
+    pub(crate) fn value(&self) -> &K::Value {
+        self.value.val()
+    }
+
     pub(crate) fn as_both_deps(&self) -> BothDeps {
         BothDeps::only_one_dep(
             self.parent_computations.transaction_ctx.get_version(),
@@ -28,8 +28,13 @@ use super::*;
 use crate::api::computations::DiceComputations;
 use crate::api::cycles::DetectCycles;
 use crate::api::error::DiceErrorImpl;
+use crate::api::history::ValueChange;
 use crate::api::injected::InjectedKey;
 use crate::api::key::Key;
+use crate::api::replay::ReplayEntry;
+use crate::api::replay::ReplayLog;
+use crate::api::span_tree::SpanTree;
+use crate::api::span_tree::SpanTreeRecorder;
 use crate::api::user_data::UserComputationData;
 use crate::legacy::ctx::testing::DiceCtxExt;
 use crate::legacy::incremental::evaluator::testing::EvaluatorUnreachable;
@@ -106,6 +111,33 @@ async fn set_injected_with_no_change_no_new_ctx() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn value_history_records_changed_and_unchanged_versions() -> anyhow::Result<()> {
+    let dice = DiceLegacy::builder().build(DetectCycles::Enabled);
+
+    // (value of Foo(0), value of Foo(1)) at each version. Foo(1) changes at every version, so a
+    // new version is created even when Foo(0) is set to its previous value.
+    for (foo0, foo1) in [(0, 0), (1, 1), (1, 2), (2, 3)] {
+        let mut ctx = dice.updater();
+        ctx.changed_to(vec![(Foo(0), foo0), (Foo(1), foo1)])?;
+        ctx.commit().await;
+    }
+
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&Foo(0)).await?, 2);
+    assert_eq!(
+        ctx.value_history(&Foo(0)),
+        vec![
+            (VersionNumber::new(1), ValueChange::Changed),
+            (VersionNumber::new(2), ValueChange::Changed),
+            (VersionNumber::new(3), ValueChange::Unchanged),
+            (VersionNumber::new(4), ValueChange::Changed),
+        ]
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn updates_caches_only_on_ctx_finalize_in_order() -> anyhow::Result<()> {
     let dice = DiceLegacy::builder().build(DetectCycles::Enabled);
@@ -464,6 +496,73 @@ async fn different_data_per_compute_ctx() {
     assert_eq!(request1.await.unwrap(), 1);
 }
 
+#[tokio::test]
+async fn request_recorders_record_computed_keys() -> anyhow::Result<()> {
+    #[derive(Clone, Copy, Dupe, Debug, Display, PartialEq, Eq, Hash, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    struct Fib(u32);
+
+    #[async_trait]
+    impl Key for Fib {
+        type Value = u64;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            if self.0 < 2 {
+                return self.0 as u64;
+            }
+            let a = ctx.compute(&Fib(self.0 - 1)).await.unwrap();
+            let b = ctx.compute(&Fib(self.0 - 2)).await.unwrap();
+            a + b
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    let log = Arc::new(ReplayLog::new());
+    let span_tree = Arc::new(SpanTreeRecorder::new());
+    let dice = DiceLegacy::builder().build(DetectCycles::Enabled);
+    let ctx = dice
+        .updater_with_data(UserComputationData {
+            replay_log: Some(log.dupe()),
+            span_tree: Some(span_tree.dupe()),
+            ..Default::default()
+        })
+        .commit()
+        .await;
+    assert_eq!(ctx.compute(&Fib(4)).await?, 3);
+
+    assert_eq!(
+        log.entries(),
+        vec![ReplayEntry {
+            root: "Fib(4)".to_owned(),
+            computed: ["Fib(3)", "Fib(2)", "Fib(1)", "Fib(0)"]
+                .map(str::to_owned)
+                .to_vec(),
+        }]
+    );
+
+    fn keys(tree: &SpanTree) -> Vec<String> {
+        std::iter::once(tree.key.clone())
+            .chain(tree.children.iter().flat_map(keys))
+            .collect()
+    }
+    let trees = span_tree.trees();
+    assert_eq!(trees.len(), 1);
+    assert_eq!(
+        keys(&trees[0]),
+        ["Fib(4)", "Fib(3)", "Fib(2)", "Fib(1)", "Fib(0)"].map(str::to_owned)
+    );
+    assert_eq!(trees[0].children[0].children[0].children.len(), 2);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn invalid_results_are_not_cached() -> anyhow::Result<()> {
     #[derive(Clone, Dupe, Debug, Display, Derivative, Allocative)]
@@ -220,6 +220,7 @@ mod transaction_update;
 mod versions;
 
 use std::fmt::Debug;
+use std::io::Read;
 use std::io::Write;
 use std::sync::Arc;
 
@@ -242,6 +243,7 @@ use serde::Serializer;
 
 pub use crate::api::activation_tracker::ActivationData;
 pub use crate::api::activation_tracker::ActivationTracker;
+pub use crate::api::checkpoint::CheckpointConfig;
 pub use crate::api::computations::DiceComputations;
 pub use crate::api::computations::DiceComputationsParallel;
 pub use crate::api::cycles::DetectCycles;
@@ -252,17 +254,45 @@ pub use crate::api::error::DiceError;
 pub use crate::api::error::DiceResult;
 pub use crate::api::events::DiceEvent;
 pub use crate::api::events::DiceEventListener;
+pub use crate::api::evict::EvictionObserver;
+pub use crate::api::executor::DiceExecutor;
+pub use crate::api::graph_stats::TransactionGraphCounters;
+pub use crate::api::graph_stats::TransactionGraphStats;
+pub use crate::api::history::ValueChange;
 pub use crate::api::injected::InjectedKey;
+pub use crate::api::key::ContentKey;
 pub use crate::api::key::Key;
+pub use crate::api::key::KeyDescription;
 pub use crate::api::opaque::OpaqueValue;
+use crate::api::persist::PersistCacheError;
+pub use crate::api::persist::PersistableKey;
+pub use crate::api::priority::DicePreemption;
+pub use crate::api::priority::DicePriority;
+pub use crate::api::progress::ComputeProgress;
+pub use crate::api::progress::DiceProgress;
 pub use crate::api::projection::DiceProjectionComputations;
 pub use crate::api::projection::ProjectionKey;
+pub use crate::api::replay::ReplayEntry;
+pub use crate::api::replay::ReplayLog;
+pub use crate::api::replay::ReplayMismatch;
+pub use crate::api::resource_class::DiceResourceLimits;
+pub use crate::api::resource_class::ResourceClass;
+pub use crate::api::resource_usage::RequestResourceReport;
+pub use crate::api::resource_usage::RequestResourceUsage;
+pub use crate::api::retry::DiceRetryPolicy;
+pub use crate::api::span_tree::SpanTree;
+pub use crate::api::span_tree::SpanTreeRecorder;
+pub use crate::api::spill::SpillCodec;
+pub use crate::api::spill::SpillConfig;
+pub use crate::api::transaction::CommitReport;
 pub use crate::api::transaction::DiceEquality;
 pub use crate::api::transaction::DiceTransaction;
 pub use crate::api::transaction::DiceTransactionUpdater;
+pub use crate::api::transaction::EarlyCutoffs;
 pub use crate::api::user_data::UserComputationData;
 pub use crate::api::user_data::UserCycleDetector;
 pub use crate::api::user_data::UserCycleDetectorGuard;
+pub use crate::api::validation::ValidateResults;
 pub use crate::api::which::WhichDice;
 use crate::impls::dice::DiceModern;
 use crate::impls::dice::DiceModernDataBuilder;
@@ -341,6 +371,26 @@ impl DiceImplementation {
         }
     }
 
+    pub async fn persist_cache<K>(&self, writer: impl Write) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        match self {
+            DiceImplementation::Legacy(_) => Err(PersistCacheError::Unsupported.into()),
+            DiceImplementation::Modern(dice) => dice.persist_cache::<K>(writer).await,
+        }
+    }
+
+    pub async fn load_cache<K>(&self, reader: impl Read) -> anyhow::Result<usize>
+    where
+        K: PersistableKey,
+    {
+        match self {
+            DiceImplementation::Legacy(_) => Err(PersistCacheError::Unsupported.into()),
+            DiceImplementation::Modern(dice) => dice.load_cache::<K>(reader).await,
+        }
+    }
+
     /// Wait until all active versions have exited.
     pub fn wait_for_idle(&self) -> impl Future<Output = ()> + 'static {
         match self {
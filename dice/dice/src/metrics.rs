@@ -7,6 +7,11 @@
  * of this source tree.
  */
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::time::Duration;
+
 /// Dice metrics.
 #[derive(Debug)]
 pub struct Metrics {
@@ -14,4 +19,115 @@ pub struct Metrics {
     /// The number of keys currently active in the per transaction cache
     pub currently_active_key_count: usize,
     pub active_transaction_count: u32,
+    /// Aggregate metrics per key type, by `Key::key_type_name`.
+    pub key_types: BTreeMap<&'static str, KeyTypeMetrics>,
+}
+
+/// Aggregate metrics of the keys of one type, since the Dice was created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTypeMetrics {
+    /// The number of times keys of this type were requested.
+    pub requests: u64,
+    /// The number of times keys of this type were computed rather than served from the cache.
+    pub computations: u64,
+    /// Total wall time of the computations, including the time spent waiting on their deps.
+    pub compute_duration: Duration,
+}
+
+impl KeyTypeMetrics {
+    /// The ratio of requests served from the cache, or 0 if there were no requests.
+    pub fn hit_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.requests.saturating_sub(self.computations) as f64 / self.requests as f64
+    }
+}
+
+impl Metrics {
+    /// Renders the metrics in the Prometheus text exposition format. Metric names are stable, and
+    /// per key type metrics are labeled with `key_type`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        self.write_prometheus(&mut out)
+            .expect("writing to a String can't fail");
+        out
+    }
+
+    fn write_prometheus(&self, out: &mut String) -> fmt::Result {
+        let gauges = [
+            (
+                "dice_key_count",
+                "Number of keys in the graph.",
+                self.key_count as u64,
+            ),
+            (
+                "dice_currently_active_key_count",
+                "Number of keys active in the per transaction cache.",
+                self.currently_active_key_count as u64,
+            ),
+            (
+                "dice_active_transaction_count",
+                "Number of active transactions.",
+                self.active_transaction_count as u64,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            write_header(out, name, "gauge", help)?;
+            writeln!(out, "{} {}", name, value)?;
+        }
+
+        let per_key_type: [(&str, &str, &str, fn(&KeyTypeMetrics) -> f64); 4] = [
+            (
+                "dice_key_requests_total",
+                "counter",
+                "Number of requests of keys.",
+                |m| m.requests as f64,
+            ),
+            (
+                "dice_key_computations_total",
+                "counter",
+                "Number of computations of keys, as opposed to cache hits.",
+                |m| m.computations as f64,
+            ),
+            (
+                "dice_key_compute_seconds_total",
+                "counter",
+                "Wall time spent computing keys.",
+                |m| m.compute_duration.as_secs_f64(),
+            ),
+            (
+                "dice_key_cache_hit_ratio",
+                "gauge",
+                "Ratio of requests of keys served from the cache.",
+                KeyTypeMetrics::hit_rate,
+            ),
+        ];
+        for (name, metric_type, help, value) in per_key_type {
+            write_header(out, name, metric_type, help)?;
+            for (key_type, metrics) in &self.key_types {
+                writeln!(
+                    out,
+                    "{}{{key_type=\"{}\"}} {}",
+                    name,
+                    escape_label_value(key_type),
+                    value(metrics)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_header(out: &mut String, name: &str, metric_type: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, metric_type)
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
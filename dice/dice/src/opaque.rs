@@ -23,6 +23,10 @@ use crate::legacy::opaque::OpaqueValueImplLegacy;
 /// so projection result is recorded as a dependency
 /// of a computation which requested the opaqued value,
 /// but the opaque value key is not.
+///
+/// Dependencies are recorded all-or-nothing: nothing is recorded until the opaque value
+/// is resolved and then either projected or converted into the value. Dropping the
+/// `compute_opaque` future before it completes (i.e. cancelling it) records no dependency.
 pub(crate) enum OpaqueValueImpl<'a, K: Key> {
     Legacy(OpaqueValueImplLegacy<'a, K>),
     Modern(OpaqueValueModern<'a, K>),
@@ -42,6 +46,22 @@ where
 }
 
 impl<'a, K: Key> OpaqueValueImpl<'a, K> {
+    /// The value, without recording a dependency on `K`.
+    pub(crate) fn value(&self) -> &K::Value {
+        match self {
+            OpaqueValueImpl::Legacy(delegate) => delegate.value(),
+            OpaqueValueImpl::Modern(delegate) => delegate.value(),
+        }
+    }
+
+    /// Get a value and record parent computation dependency on `K`.
+    pub(crate) fn into_value(self) -> K::Value {
+        match self {
+            OpaqueValueImpl::Legacy(delegate) => delegate.into_value(),
+            OpaqueValueImpl::Modern(delegate) => delegate.into_value(),
+        }
+    }
+
     pub(crate) fn projection<P>(&self, projection_key: &P) -> DiceResult<P::Value>
     where
         P: ProjectionKey<DeriveFromKey = K>,
@@ -17,9 +17,13 @@ use futures::FutureExt;
 
 use crate::api::computations::DiceComputations;
 use crate::api::error::DiceResult;
+use crate::api::key::ContentKey;
 use crate::api::key::Key;
+use crate::api::key::KeyDescription;
+use crate::api::transaction::CommitReport;
 use crate::api::user_data::UserComputationData;
 use crate::ctx::DiceComputationsImpl;
+use crate::impls::content_key::ContentKeyId;
 use crate::impls::transaction::TransactionUpdater;
 use crate::legacy::ctx::DiceComputationsImplLegacy;
 use crate::transaction::DiceTransactionImpl;
@@ -81,6 +85,34 @@ impl DiceTransactionUpdaterImpl {
         }
     }
 
+    /// Records all currently known `Key`s matching `pred` as changed.
+    pub(crate) fn invalidate_matching<F>(&mut self, pred: F)
+    where
+        F: Fn(&dyn KeyDescription) -> bool + Send + Sync + 'static,
+    {
+        match self {
+            DiceTransactionUpdaterImpl::Legacy(ctx) => ctx.invalidate_matching(pred),
+            DiceTransactionUpdaterImpl::Modern(delegate) => delegate.invalidate_matching(pred),
+        }
+    }
+
+    /// Records the value shared by the `K` keys with `content_key` to be dropped on commit, and
+    /// records those keys as changed.
+    pub(crate) fn invalidate_content_key<K: Key>(&mut self, content_key: ContentKey) {
+        let id = ContentKeyId::new::<K>(content_key.dupe());
+        match self {
+            DiceTransactionUpdaterImpl::Legacy(ctx) => ctx.invalidate_content_key(id),
+            DiceTransactionUpdaterImpl::Modern(delegate) => delegate.invalidate_content_key(id),
+        }
+        self.invalidate_matching(move |key| {
+            key.as_any()
+                .downcast_ref::<K>()
+                .and_then(|key| key.content_key())
+                .as_ref()
+                == Some(&content_key)
+        })
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version.
     pub(crate) fn commit(self) -> impl Future<Output = DiceTransaction> {
         match self {
@@ -97,6 +129,28 @@ impl DiceTransactionUpdaterImpl {
         }
     }
 
+    /// Same as `commit`, but also reports what the committed changes did to their keys.
+    pub(crate) fn commit_with_report(
+        self,
+    ) -> impl Future<Output = (DiceTransaction, CommitReport)> {
+        match self {
+            DiceTransactionUpdaterImpl::Legacy(ctx) => {
+                let (ctx, report) = ctx.commit_with_report();
+                futures::future::ready((
+                    DiceTransaction(DiceTransactionImpl::Legacy(DiceComputations(
+                        DiceComputationsImpl::Legacy(ctx),
+                    ))),
+                    report,
+                ))
+                .left_future()
+            }
+            DiceTransactionUpdaterImpl::Modern(delegate) => delegate
+                .commit_with_report()
+                .map(|(x, report)| (DiceTransaction(DiceTransactionImpl::Modern(x)), report))
+                .right_future(),
+        }
+    }
+
     /// Commit the changes registered via 'changed' and 'changed_to' to the current newest version,
     /// replacing the user data with the given set
     pub(crate) fn commit_with_data(
@@ -0,0 +1,99 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use derive_more::Display;
+use dice::DetectCycles;
+use dice::Dice;
+use dice::DiceComputations;
+use dice::InjectedKey;
+use dice::Key;
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+
+#[derive(Allocative, Clone, Copy, Debug, Display, Dupe, Eq, Hash, PartialEq)]
+#[display(fmt = "{:?}", self)]
+struct PairKey;
+
+impl InjectedKey for PairKey {
+    type Value = Arc<(u32, u32)>;
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+/// Only uses the first element of the pair.
+#[derive(Allocative, Clone, Debug, Display, Dupe)]
+#[display(fmt = "{:?}", self)]
+struct FirstKey(Arc<AtomicUsize>);
+
+impl PartialEq for FirstKey {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for FirstKey {}
+
+impl std::hash::Hash for FirstKey {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+#[async_trait]
+impl Key for FirstKey {
+    type Value = u32;
+
+    async fn compute(
+        &self,
+        ctx: &mut DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        ctx.compute_mapped(&PairKey, |pair: &Arc<(u32, u32)>| pair.0)
+            .await
+            .unwrap()
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[tokio::test]
+async fn compute_mapped_depends_on_mapped_value_only() -> anyhow::Result<()> {
+    let dice = Dice::modern().build(DetectCycles::Enabled);
+    let computed = Arc::new(AtomicUsize::new(0));
+
+    let mut updater = dice.updater();
+    updater.changed_to([(PairKey, Arc::new((1, 2)))])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&FirstKey(computed.dupe())).await?, 1);
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+    // The mapped value is unchanged, so `FirstKey` is not recomputed.
+    let mut updater = ctx.into_updater();
+    updater.changed_to([(PairKey, Arc::new((1, 3)))])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&FirstKey(computed.dupe())).await?, 1);
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+    let mut updater = ctx.into_updater();
+    updater.changed_to([(PairKey, Arc::new((4, 3)))])?;
+    let ctx = updater.commit().await;
+    assert_eq!(ctx.compute(&FirstKey(computed.dupe())).await?, 4);
+    assert_eq!(computed.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
@@ -8,4 +8,5 @@
  */
 
 mod concurrent;
+mod mapped;
 mod smoke;
@@ -198,14 +198,18 @@ impl<'v> Compiler<'v, '_, '_> {
     }
 
     fn mk_module_var_types(&self) -> ModuleVarTypes {
-        let types = self
-            .eval
-            .module_env
-            .values_by_slot_id()
+        let values = self.eval.module_env.values_by_slot_id();
+        let eval_types = values
+            .iter()
+            .filter_map(|(module_slot_id, value)| {
+                Some((*module_slot_id, value.get_ref().eval_type()?))
+            })
+            .collect();
+        let types = values
             .into_iter()
             .map(|(module_slot_id, value)| (module_slot_id, Ty::of_value(value)))
             .collect();
-        ModuleVarTypes { types }
+        ModuleVarTypes { types, eval_types }
     }
 
     pub(crate) fn eval_module(
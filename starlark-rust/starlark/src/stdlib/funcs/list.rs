@@ -47,6 +47,10 @@ impl TyCustomFunctionImpl for ListType {
         true
     }
 
+    fn as_type(&self) -> Option<Ty> {
+        Some(Ty::any_list())
+    }
+
     fn validate_call(
         &self,
         span: Span,
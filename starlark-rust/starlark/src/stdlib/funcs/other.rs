@@ -22,12 +22,23 @@ use std::char;
 use std::cmp::Ordering;
 use std::num::NonZeroI32;
 
+use allocative::Allocative;
 use either::Either;
+use once_cell::sync::Lazy;
 use starlark_derive::starlark_module;
 
 use crate as starlark;
+use crate::codemap::Span;
+use crate::codemap::Spanned;
 use crate::environment::GlobalsBuilder;
 use crate::eval::Evaluator;
+use crate::typing::error::TypingOrInternalError;
+use crate::typing::function::TyCustomFunctionImpl;
+use crate::typing::Arg;
+use crate::typing::Param;
+use crate::typing::Ty;
+use crate::typing::TyFunction;
+use crate::typing::TypingOracleCtx;
 use crate::values::bool::StarlarkBool;
 use crate::values::float::StarlarkFloat;
 use crate::values::function::SpecialBuiltinFunction;
@@ -56,6 +67,48 @@ use crate::values::ValueError;
 use crate::values::ValueLike;
 use crate::values::ValueOf;
 
+#[derive(Allocative, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+struct StrType;
+
+impl TyCustomFunctionImpl for StrType {
+    fn has_type_attr(&self) -> bool {
+        true
+    }
+
+    fn as_type(&self) -> Option<Ty> {
+        Some(Ty::string())
+    }
+
+    fn validate_call(
+        &self,
+        span: Span,
+        args: &[Spanned<Arg>],
+        oracle: TypingOracleCtx,
+    ) -> Result<Ty, TypingOrInternalError> {
+        static STR: Lazy<TyFunction> = Lazy::new(|| {
+            TyFunction::new_with_type_attr(
+                vec![Param::pos_only(Ty::any())],
+                Ty::string(),
+                Ty::string(),
+            )
+        });
+
+        oracle.validate_fn_call(span, &STR, args)?;
+
+        if let Some(arg) = args.get(0) {
+            // This is infallible after the check above.
+            if let Arg::Pos(arg_ty) = &arg.node {
+                oracle.validate_stringable(Spanned {
+                    span: arg.span,
+                    node: arg_ty,
+                })?;
+            }
+        }
+
+        Ok(Ty::string())
+    }
+}
+
 #[starlark_module]
 pub(crate) fn register_other(builder: &mut GlobalsBuilder) {
     /// The `None` value, used to represent nothing.
@@ -800,7 +853,7 @@ pub(crate) fn register_other(builder: &mut GlobalsBuilder) {
     /// str([1, "x"])                   == "[1, \"x\"]"
     /// # "#);
     /// ```
-    #[starlark(as_type = StarlarkStr, speculative_exec_safe)]
+    #[starlark(as_type = StarlarkStr, speculative_exec_safe, ty_custom_function = StrType)]
     fn str<'v>(
         #[starlark(require = pos)] a: Value<'v>,
         eval: &mut Evaluator<'v, '_>,
@@ -140,6 +140,14 @@ impl TyBasic {
         }
     }
 
+    /// If values of this type can be used as a type, like the `int` function, return that type.
+    pub(crate) fn as_type(&self) -> Option<Ty> {
+        match self {
+            TyBasic::Custom(c) => c.0.as_type_dyn(),
+            _ => None,
+        }
+    }
+
     /// Type is a tuple, with specified or unspecified member types.
     pub(crate) fn is_tuple(&self) -> bool {
         matches!(self, TyBasic::Tuple(_))
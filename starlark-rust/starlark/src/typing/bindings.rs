@@ -18,9 +18,12 @@
 use std::collections::HashMap;
 
 use starlark_map::small_map::SmallMap;
+use starlark_syntax::syntax::ast::ArgumentP;
 use starlark_syntax::syntax::ast::AssignOp;
 use starlark_syntax::syntax::ast::AssignP;
 use starlark_syntax::syntax::ast::AssignTargetP;
+use starlark_syntax::syntax::ast::AstLiteral;
+use starlark_syntax::syntax::ast::BinOp;
 use starlark_syntax::syntax::ast::ClauseP;
 use starlark_syntax::syntax::ast::DefP;
 use starlark_syntax::syntax::ast::ExprP;
@@ -94,6 +97,47 @@ pub(crate) struct Bindings<'a> {
     /// ```
     pub(crate) check: Vec<&'a CstExpr>,
     pub(crate) check_type: Vec<(Span, Option<&'a CstExpr>, Ty)>,
+    /// Spans of `x.attr` expressions which are guarded by `hasattr(x, "other")` checks,
+    /// mapped to the attributes of `x` known to be present there.
+    ///
+    /// Like the rest of the typechecker, this is not flow-sensitive, so branches which
+    /// reassign `x` are not guarded at all.
+    pub(crate) present_attrs: HashMap<Span, Vec<String>>,
+    /// Spans of `x.attr` expressions which are guarded by `x.field == "value"` checks,
+    /// mapped to the `(field, value)` pairs known there. Bodies which assign to `x` are not
+    /// guarded.
+    pub(crate) discriminant_guards: HashMap<Span, Vec<(String, String)>>,
+    /// Spans of `x` expressions which are guarded by `x == v` checks, mapped to `v`.
+    /// Bodies which assign to `x` are not guarded.
+    pub(crate) eq_guards: HashMap<Span, &'a CstExpr>,
+    /// Spans of `x` expressions which are guarded by `isinstance(x, t)` checks, mapped to `t`
+    /// and whether the check passed there, i.e. `true` in the `if` branch and `false` in the
+    /// `else` branch. Branches which assign to `x` are not guarded.
+    pub(crate) isinstance_guards: HashMap<Span, (&'a CstExpr, bool)>,
+}
+
+/// Whether `body` assigns to `binding`, like `x = ...`, `x += ...` or `for x in ...`.
+/// Guards on `x` don't hold for the reads after such an assignment, and since the typechecker
+/// is not flow-sensitive, they are not collected for a body which contains one.
+fn assigns_to(body: &CstStmt, binding: BindingId) -> bool {
+    fn visit(x: Visit<CstPayload>, binding: BindingId, found: &mut bool) {
+        if let Visit::Stmt(stmt) = &x {
+            match &**stmt {
+                StmtP::Assign(AssignP { lhs: target, .. })
+                | StmtP::AssignModify(target, _, _)
+                | StmtP::For(ForP { var: target, .. }) => {
+                    target.visit_lvalue(|ident| *found |= ident.payload == Some(binding));
+                }
+                StmtP::Def(DefP { name, .. }) => *found |= name.payload == Some(binding),
+                _ => {}
+            }
+        }
+        x.visit_children(|x| visit(x, binding, found));
+    }
+
+    let mut found = false;
+    visit(Visit::Stmt(body), binding, &mut found);
+    found
 }
 
 pub(crate) struct BindingsCollect<'a, 'b> {
@@ -261,6 +305,236 @@ impl<'a, 'b> BindingsCollect<'a, 'b> {
         Ok(())
     }
 
+    /// If `cond` is `hasattr(x, "attr")`, record that `attr` is present
+    /// for the `x.<any>` expressions in `body`, unless `body` assigns to `x`.
+    fn collect_present_attrs(&mut self, cond: &CstExpr, body: &CstStmt) {
+        fn hasattr_check(cond: &CstExpr) -> Option<(BindingId, &str)> {
+            let (fun, args) = match &**cond {
+                ExprP::Call(fun, args) if args.len() == 2 => (fun, args),
+                _ => return None,
+            };
+            match &fun.node {
+                ExprP::Identifier(fun)
+                    if fun.node.ident == "hasattr"
+                        && matches!(fun.node.payload, Some(ResolvedIdent::Global(_))) => {}
+                _ => return None,
+            }
+            let (object, attr) = match (&args[0].node, &args[1].node) {
+                (ArgumentP::Positional(object), ArgumentP::Positional(attr)) => (object, attr),
+                _ => return None,
+            };
+            let binding = match &object.node {
+                ExprP::Identifier(object) => match &object.node.payload {
+                    Some(ResolvedIdent::Slot(_, binding)) => *binding,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            match &attr.node {
+                ExprP::Literal(AstLiteral::String(attr)) => Some((binding, attr.as_str())),
+                _ => None,
+            }
+        }
+
+        fn collect(
+            x: Visit<CstPayload>,
+            binding: BindingId,
+            attr: &str,
+            present_attrs: &mut HashMap<Span, Vec<String>>,
+        ) {
+            if let Visit::Expr(e) = x {
+                if let ExprP::Dot(object, _) = &**e {
+                    if let ExprP::Identifier(object) = &object.node {
+                        if let Some(ResolvedIdent::Slot(_, b)) = &object.node.payload {
+                            if *b == binding {
+                                present_attrs
+                                    .entry(e.span)
+                                    .or_default()
+                                    .push(attr.to_owned());
+                            }
+                        }
+                    }
+                }
+            }
+            x.visit_children(|x| collect(x, binding, attr, present_attrs));
+        }
+
+        if let Some((binding, attr)) = hasattr_check(cond) {
+            if assigns_to(body, binding) {
+                return;
+            }
+            collect(
+                Visit::Stmt(body),
+                binding,
+                attr,
+                &mut self.bindings.present_attrs,
+            );
+        }
+    }
+
+    /// If `cond` is `x.field == "value"`, record that `field` is `value`
+    /// for the `x.<any>` expressions in `body`, unless `body` assigns to `x`.
+    fn collect_discriminant_guards(&mut self, cond: &CstExpr, body: &CstStmt) {
+        fn discriminant_check(cond: &CstExpr) -> Option<(BindingId, &str, &str)> {
+            let (lhs, value) = match &**cond {
+                ExprP::Op(lhs, BinOp::Equal, value) => (lhs, value),
+                _ => return None,
+            };
+            let (object, field) = match &lhs.node {
+                ExprP::Dot(object, field) => (object, field),
+                _ => return None,
+            };
+            let binding = match &object.node {
+                ExprP::Identifier(object) => match &object.node.payload {
+                    Some(ResolvedIdent::Slot(_, binding)) => *binding,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            match &value.node {
+                ExprP::Literal(AstLiteral::String(value)) => {
+                    Some((binding, field.as_str(), value.as_str()))
+                }
+                _ => None,
+            }
+        }
+
+        fn collect(
+            x: Visit<CstPayload>,
+            binding: BindingId,
+            guard: (&str, &str),
+            discriminant_guards: &mut HashMap<Span, Vec<(String, String)>>,
+        ) {
+            if let Visit::Expr(e) = x {
+                if let ExprP::Dot(object, _) = &**e {
+                    if let ExprP::Identifier(object) = &object.node {
+                        if let Some(ResolvedIdent::Slot(_, b)) = &object.node.payload {
+                            if *b == binding {
+                                discriminant_guards
+                                    .entry(e.span)
+                                    .or_default()
+                                    .push((guard.0.to_owned(), guard.1.to_owned()));
+                            }
+                        }
+                    }
+                }
+            }
+            x.visit_children(|x| collect(x, binding, guard, discriminant_guards));
+        }
+
+        if let Some((binding, field, value)) = discriminant_check(cond) {
+            if assigns_to(body, binding) {
+                return;
+            }
+            collect(
+                Visit::Stmt(body),
+                binding,
+                (field, value),
+                &mut self.bindings.discriminant_guards,
+            );
+        }
+    }
+
+    /// If `cond` is `x == v`, record `v` for the `x` expressions in `body`,
+    /// so that they can be narrowed to the type of `v`, unless `body` assigns to `x`.
+    fn collect_eq_guards(&mut self, cond: &'a CstExpr, body: &CstStmt) {
+        fn collect<'a>(
+            x: Visit<CstPayload>,
+            binding: BindingId,
+            value: &'a CstExpr,
+            eq_guards: &mut HashMap<Span, &'a CstExpr>,
+        ) {
+            if let Visit::Expr(e) = x {
+                if let ExprP::Identifier(ident) = &**e {
+                    if let Some(ResolvedIdent::Slot(_, b)) = &ident.node.payload {
+                        if *b == binding {
+                            eq_guards.insert(e.span, value);
+                        }
+                    }
+                }
+            }
+            x.visit_children(|x| collect(x, binding, value, eq_guards));
+        }
+
+        if let ExprP::Op(lhs, BinOp::Equal, value) = &**cond {
+            if let ExprP::Identifier(ident) = &lhs.node {
+                match &ident.node.payload {
+                    Some(ResolvedIdent::Slot(_, binding)) if !assigns_to(body, *binding) => {
+                        collect(
+                            Visit::Stmt(body),
+                            *binding,
+                            value,
+                            &mut self.bindings.eq_guards,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// If `cond` is `isinstance(x, t)`, record `t` for the `x` expressions in `body`, and in
+    /// `orelse` if there is one, so that they can be narrowed according to `t`. Branches which
+    /// assign to `x` are skipped.
+    fn collect_isinstance_guards(
+        &mut self,
+        cond: &'a CstExpr,
+        body: &CstStmt,
+        orelse: Option<&CstStmt>,
+    ) {
+        fn isinstance_check(cond: &CstExpr) -> Option<(BindingId, &CstExpr)> {
+            let (fun, args) = match &**cond {
+                ExprP::Call(fun, args) if args.len() == 2 => (fun, args),
+                _ => return None,
+            };
+            match &fun.node {
+                ExprP::Identifier(fun)
+                    if fun.node.ident == "isinstance"
+                        && matches!(fun.node.payload, Some(ResolvedIdent::Global(_))) => {}
+                _ => return None,
+            }
+            let (object, ty) = match (&args[0].node, &args[1].node) {
+                (ArgumentP::Positional(object), ArgumentP::Positional(ty)) => (object, ty),
+                _ => return None,
+            };
+            match &object.node {
+                ExprP::Identifier(object) => match &object.node.payload {
+                    Some(ResolvedIdent::Slot(_, binding)) => Some((*binding, ty)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+
+        fn collect<'a>(
+            x: Visit<CstPayload>,
+            binding: BindingId,
+            guard: (&'a CstExpr, bool),
+            isinstance_guards: &mut HashMap<Span, (&'a CstExpr, bool)>,
+        ) {
+            if let Visit::Expr(e) = x {
+                if let ExprP::Identifier(ident) = &**e {
+                    if let Some(ResolvedIdent::Slot(_, b)) = &ident.node.payload {
+                        if *b == binding {
+                            isinstance_guards.insert(e.span, guard);
+                        }
+                    }
+                }
+            }
+            x.visit_children(|x| collect(x, binding, guard, isinstance_guards));
+        }
+
+        if let Some((binding, ty)) = isinstance_check(cond) {
+            let guards = &mut self.bindings.isinstance_guards;
+            if !assigns_to(body, binding) {
+                collect(Visit::Stmt(body), binding, (ty, true), guards);
+            }
+            if let Some(orelse) = orelse.filter(|orelse| !assigns_to(orelse, binding)) {
+                collect(Visit::Stmt(orelse), binding, (ty, false), guards);
+            }
+        }
+    }
+
     fn visit(
         &mut self,
         x: Visit<'a, CstPayload>,
@@ -334,8 +608,20 @@ impl<'a, 'b> BindingsCollect<'a, 'b> {
 
                     self.bindings.check.push(x)
                 }
-                StmtP::If(x, _) => self.bindings.check.push(x),
-                StmtP::IfElse(x, _) => self.bindings.check.push(x),
+                StmtP::If(x, body) => {
+                    self.bindings.check.push(x);
+                    self.collect_present_attrs(x, body);
+                    self.collect_discriminant_guards(x, body);
+                    self.collect_eq_guards(x, body);
+                    self.collect_isinstance_guards(x, body, None);
+                }
+                StmtP::IfElse(x, bodies) => {
+                    self.bindings.check.push(x);
+                    self.collect_present_attrs(x, &bodies.0);
+                    self.collect_discriminant_guards(x, &bodies.0);
+                    self.collect_eq_guards(x, &bodies.0);
+                    self.collect_isinstance_guards(x, &bodies.0, Some(&bodies.1));
+                }
                 _ => {}
             },
             Visit::Expr(x) => match &**x {
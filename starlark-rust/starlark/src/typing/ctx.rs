@@ -16,6 +16,7 @@
  */
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use starlark_map::unordered_map::UnorderedMap;
@@ -60,6 +61,17 @@ pub(crate) struct TypingContext<'a> {
     pub(crate) approximoations: RefCell<Vec<Approximation>>,
     pub(crate) types: UnorderedMap<BindingId, Ty>,
     pub(crate) module_var_types: &'a ModuleVarTypes,
+    /// Spans of `x.attr` expressions guarded by `hasattr(x, "other")`,
+    /// mapped to the attributes known to be present.
+    pub(crate) present_attrs: HashMap<Span, Vec<String>>,
+    /// Spans of `x.attr` expressions guarded by `x.field == "value"`,
+    /// mapped to the `(field, value)` pairs known there.
+    pub(crate) discriminant_guards: HashMap<Span, Vec<(String, String)>>,
+    /// Spans of `x` expressions guarded by `x == v`, mapped to `v`.
+    pub(crate) eq_guards: HashMap<Span, &'a CstExpr>,
+    /// Spans of `x` expressions guarded by `isinstance(x, t)`, mapped to `t` and whether the
+    /// check passed there.
+    pub(crate) isinstance_guards: HashMap<Span, (&'a CstExpr, bool)>,
 }
 
 impl TypingContext<'_> {
@@ -113,10 +125,33 @@ impl TypingContext<'_> {
         }
     }
 
+    fn validate_hashable(&self, key: Spanned<&Ty>) {
+        if let Err(e) = self.oracle.validate_hashable(key) {
+            self.errors.borrow_mut().push(e);
+        }
+    }
+
+    fn validate_stringable(&self, ty: Spanned<&Ty>) {
+        if let Err(e) = self.oracle.validate_stringable(ty) {
+            self.errors.borrow_mut().push(e);
+        }
+    }
+
     fn expr_dot(&self, ty: &Ty, attr: &str, span: Span) -> Ty {
         self.result_to_ty(self.oracle.expr_dot(span, ty, attr))
     }
 
+    fn expr_dot_present(
+        &self,
+        ty: &Ty,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+        span: Span,
+    ) -> Ty {
+        self.result_to_ty(self.oracle.expr_dot_present(span, ty, attr, present, discriminants))
+    }
+
     fn expr_index(
         &self,
         span: Span,
@@ -363,19 +398,56 @@ impl TypingContext<'_> {
         }
     }
 
+    /// The type denoted by an expression used as a type at runtime, like `t` in
+    /// `isinstance(x, t)`, if it is a variable whose value is known.
+    fn expr_eval_type(&self, x: &CstExpr) -> Option<Ty> {
+        match &x.node {
+            ExprP::Identifier(x) => match &x.node.payload {
+                Some(ResolvedIdent::Slot(Slot::Module(module_slot_id), _)) => self
+                    .module_var_types
+                    .eval_types
+                    .get(module_slot_id)
+                    .cloned(),
+                Some(ResolvedIdent::Global(g)) => g.to_value().get_ref().eval_type(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub(crate) fn expression_type(&self, x: &CstExpr) -> Result<Ty, InternalError> {
         let span = x.span;
         match &**x {
             ExprP::Tuple(xs) => Ok(Ty::tuple(xs.try_map(|x| self.expression_type(x))?)),
-            ExprP::Dot(a, b) => Ok(self.expr_dot(&self.expression_type(a)?, b, b.span)),
+            ExprP::Dot(a, b) => {
+                let ty = self.expression_type(a)?;
+                let present = self.present_attrs.get(&span);
+                let discriminants = self.discriminant_guards.get(&span);
+                if present.is_some() || discriminants.is_some() {
+                    Ok(self.expr_dot_present(
+                        &ty,
+                        b,
+                        present.map_or(&[][..], Vec::as_slice),
+                        discriminants.map_or(&[][..], Vec::as_slice),
+                        b.span,
+                    ))
+                } else {
+                    Ok(self.expr_dot(&ty, b, b.span))
+                }
+            }
             ExprP::Call(f, args) => self.expr_call(span, f, args),
             ExprP::Index(a_b) => self.expr_index(span, &a_b.0, &a_b.1),
             ExprP::Index2(a_i0_i1) => {
                 let (a, i0, i1) = &**a_i0_i1;
-                self.expression_type(a)?;
-                self.expression_type(i0)?;
-                self.expression_type(i1)?;
-                Ok(Ty::any())
+                let array = self.expression_type(a)?;
+                let index = Ty::tuple(vec![self.expression_type(i0)?, self.expression_type(i1)?]);
+                // Only typed when `a` accepts the pair as an index, like `Pair[int, str]`
+                // instantiating a generic type with two type parameters.
+                match self.oracle.expr_index(span, array, Spanned { span, node: index }) {
+                    Ok(ty) => Ok(ty),
+                    Err(TypingOrInternalError::Typing(_)) => Ok(Ty::any()),
+                    Err(TypingOrInternalError::Internal(e)) => Err(e),
+                }
             }
             ExprP::Slice(x, start, stop, stride) => self.expr_slice(
                 span,
@@ -384,7 +456,19 @@ impl TypingContext<'_> {
                 stop.as_deref(),
                 stride.as_deref(),
             ),
-            ExprP::Identifier(x) => Ok(self.expr_ident(x)),
+            ExprP::Identifier(x) => {
+                let mut ty = self.expr_ident(x);
+                if let Some(value) = self.eq_guards.get(&span) {
+                    let value = self.expression_type(value)?;
+                    ty = self.oracle.narrow_eq(&ty, &value);
+                }
+                if let Some((guard, matches)) = self.isinstance_guards.get(&span) {
+                    if let Some(guard) = self.expr_eval_type(guard) {
+                        ty = self.oracle.narrow_isinstance(&ty, &guard, *matches);
+                    }
+                }
+                Ok(ty)
+            }
             ExprP::Lambda(_) => {
                 self.approximation("We don't type check lambdas", ());
                 Ok(Ty::any_function())
@@ -422,7 +506,11 @@ impl TypingContext<'_> {
             }
             ExprP::Dict(xs) => {
                 let (ks, vs) = xs
-                    .try_map(|(k, v)| Ok((self.expression_type(k)?, self.expression_type(v)?)))?
+                    .try_map(|(k, v)| {
+                        let k = self.expression_type_spanned(k)?;
+                        self.validate_hashable(k.as_ref());
+                        Ok((k.node, self.expression_type(v)?))
+                    })?
                     .into_iter()
                     .unzip();
                 Ok(Ty::dict(Ty::unions(ks), Ty::unions(vs)))
@@ -433,12 +521,17 @@ impl TypingContext<'_> {
             }
             ExprP::DictComprehension(k_v, b, c) => {
                 self.check_comprehension(b, c)?;
-                Ok(Ty::dict(
-                    self.expression_type(&k_v.0)?,
-                    self.expression_type(&k_v.1)?,
-                ))
+                let k = self.expression_type_spanned(&k_v.0)?;
+                self.validate_hashable(k.as_ref());
+                Ok(Ty::dict(k.node, self.expression_type(&k_v.1)?))
+            }
+            ExprP::FString(fstring) => {
+                for x in &fstring.expressions {
+                    let ty = self.expression_type_spanned(x)?;
+                    self.validate_stringable(ty.as_ref());
+                }
+                Ok(Ty::string())
             }
-            ExprP::FString(_) => Ok(Ty::string()),
         }
     }
 }
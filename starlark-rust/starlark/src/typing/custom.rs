@@ -65,6 +65,24 @@ pub trait TyCustomImpl: Debug + Display + Hash + Ord + Allocative + Send + Sync
     fn as_function(&self) -> Option<&TyFunction> {
         None
     }
+    /// The type values of this type stand for when used as a type, e.g. `int` for the type of
+    /// the `int` function.
+    fn as_type(&self) -> Option<Ty> {
+        None
+    }
+    /// Whether values of this type can be used as dict keys.
+    fn is_hashable(&self) -> bool {
+        true
+    }
+    /// Whether values of this type can be converted to string with `str()` or interpolation.
+    fn is_stringable(&self) -> bool {
+        true
+    }
+    /// Whether values of this type are only equal to themselves, so that `x == v` with `v` of
+    /// this type implies `x` is of this type too, which lets the typechecker narrow `x`.
+    fn is_identity_eq(&self) -> bool {
+        false
+    }
     fn bin_op(&self, bin_op: TypingBinOp, rhs: &TyBasic, ctx: &TypingOracleCtx) -> Result<Ty, ()> {
         let _unused = (bin_op, rhs, ctx);
         Err(())
@@ -72,22 +90,53 @@ pub trait TyCustomImpl: Debug + Display + Hash + Ord + Allocative + Send + Sync
     fn iter_item(&self) -> Result<Ty, ()> {
         Err(())
     }
+    /// Type of the `i`-th element when a value of this type is destructured in assignment,
+    /// like `a, b = x`.
+    fn indexed(&self, i: usize) -> Ty {
+        let _unused = i;
+        Ty::any()
+    }
     fn index(&self, item: &TyBasic, ctx: &TypingOracleCtx) -> Result<Ty, ()> {
         let _unused = (item, ctx);
         Err(())
     }
     fn attribute(&self, attr: &str) -> Result<Ty, ()>;
+    /// Type of the attribute when the attributes in `present` are known to be present,
+    /// e.g. after `hasattr(x, "attr")`, and the fields of `discriminants` are known to have
+    /// the given string values, e.g. after `x.kind == "a"`.
+    fn present_attribute(
+        &self,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+    ) -> Result<Ty, ()> {
+        let _ignore = (present, discriminants);
+        self.attribute(attr)
+    }
     fn union2(x: Arc<Self>, other: Arc<Self>) -> Result<Arc<Self>, (Arc<Self>, Arc<Self>)> {
         if x == other { Ok(x) } else { Err((x, other)) }
     }
-    fn intersects(x: &Self, y: &Self) -> bool {
-        let _ignore = (x, y);
+    fn intersects(x: &Self, y: &Self, ctx: &TypingOracleCtx) -> bool {
+        let _ignore = (x, y, ctx);
         true
     }
+    /// Whether every value of type `x` is a value of type `y`. Defaults to `intersects`.
+    fn is_subtype(x: &Self, y: &Self, ctx: &TypingOracleCtx) -> bool {
+        Self::intersects(x, y, ctx)
+    }
     /// Additional types that this type intersects with.
     fn intersects_with(&self, _other: &TyBasic) -> bool {
         false
     }
+    /// Message added to the runtime error when a value does not match this type.
+    fn match_error_message(&self) -> Option<&str> {
+        None
+    }
+    /// If set, this type is deprecated, and using it in a type annotation produces a warning
+    /// with this message.
+    fn deprecated(&self) -> Option<&str> {
+        None
+    }
 
     /// Create runtime type matcher for values.
     fn matcher<T: TypeMatcherAlloc>(&self, factory: T) -> T::Result;
@@ -108,11 +157,24 @@ pub(crate) trait TyCustomDyn: Debug + Display + Allocative + Send + Sync + 'stat
         oracle: TypingOracleCtx,
     ) -> Result<Ty, TypingOrInternalError>;
     fn is_callable_dyn(&self) -> bool;
+    fn is_hashable_dyn(&self) -> bool;
+    fn is_stringable_dyn(&self) -> bool;
+    fn is_identity_eq_dyn(&self) -> bool;
     fn is_intersects_with_dyn(&self, other: &TyBasic) -> bool;
     fn as_function_dyn(&self) -> Option<&TyFunction>;
+    fn as_type_dyn(&self) -> Option<Ty>;
+    fn match_error_message_dyn(&self) -> Option<&str>;
+    fn deprecated_dyn(&self) -> Option<&str>;
     fn iter_item_dyn(&self) -> Result<Ty, ()>;
+    fn indexed_dyn(&self, i: usize) -> Ty;
     fn index_dyn(&self, index: &TyBasic, ctx: &TypingOracleCtx) -> Result<Ty, ()>;
     fn attribute_dyn(&self, attr: &str) -> Result<Ty, ()>;
+    fn present_attribute_dyn(
+        &self,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+    ) -> Result<Ty, ()>;
     fn bin_op_dyn(
         &self,
         bin_op: TypingBinOp,
@@ -123,7 +185,8 @@ pub(crate) trait TyCustomDyn: Debug + Display + Allocative + Send + Sync + 'stat
         self: Arc<Self>,
         other: Arc<dyn TyCustomDyn>,
     ) -> Result<Arc<dyn TyCustomDyn>, (Arc<dyn TyCustomDyn>, Arc<dyn TyCustomDyn>)>;
-    fn intersects_dyn(&self, other: &dyn TyCustomDyn) -> bool;
+    fn intersects_dyn(&self, other: &dyn TyCustomDyn, ctx: &TypingOracleCtx) -> bool;
+    fn is_subtype_dyn(&self, other: &dyn TyCustomDyn, ctx: &TypingOracleCtx) -> bool;
 
     fn matcher_with_type_compiled_factory_dyn<'v>(
         &self,
@@ -173,6 +236,18 @@ impl<T: TyCustomImpl> TyCustomDyn for T {
         self.is_callable()
     }
 
+    fn is_hashable_dyn(&self) -> bool {
+        self.is_hashable()
+    }
+
+    fn is_stringable_dyn(&self) -> bool {
+        self.is_stringable()
+    }
+
+    fn is_identity_eq_dyn(&self) -> bool {
+        self.is_identity_eq()
+    }
+
     fn is_intersects_with_dyn(&self, other: &TyBasic) -> bool {
         self.intersects_with(other)
     }
@@ -181,14 +256,39 @@ impl<T: TyCustomImpl> TyCustomDyn for T {
         self.as_function()
     }
 
+    fn as_type_dyn(&self) -> Option<Ty> {
+        self.as_type()
+    }
+
+    fn match_error_message_dyn(&self) -> Option<&str> {
+        self.match_error_message()
+    }
+
+    fn deprecated_dyn(&self) -> Option<&str> {
+        self.deprecated()
+    }
+
     fn attribute_dyn(&self, attr: &str) -> Result<Ty, ()> {
         self.attribute(attr)
     }
 
+    fn present_attribute_dyn(
+        &self,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+    ) -> Result<Ty, ()> {
+        self.present_attribute(attr, present, discriminants)
+    }
+
     fn iter_item_dyn(&self) -> Result<Ty, ()> {
         self.iter_item()
     }
 
+    fn indexed_dyn(&self, i: usize) -> Ty {
+        self.indexed(i)
+    }
+
     fn index_dyn(&self, index: &TyBasic, ctx: &TypingOracleCtx) -> Result<Ty, ()> {
         self.index(index, ctx)
     }
@@ -216,9 +316,17 @@ impl<T: TyCustomImpl> TyCustomDyn for T {
         }
     }
 
-    fn intersects_dyn(&self, other: &dyn TyCustomDyn) -> bool {
+    fn intersects_dyn(&self, other: &dyn TyCustomDyn, ctx: &TypingOracleCtx) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            T::intersects(self, other, ctx)
+        } else {
+            false
+        }
+    }
+
+    fn is_subtype_dyn(&self, other: &dyn TyCustomDyn, ctx: &TypingOracleCtx) -> bool {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            T::intersects(self, other)
+            T::is_subtype(self, other, ctx)
         } else {
             false
         }
@@ -248,22 +356,62 @@ impl TyCustom {
         self.0.as_name_dyn()
     }
 
+    pub(crate) fn is_hashable(&self) -> bool {
+        self.0.is_hashable_dyn()
+    }
+
+    pub(crate) fn is_stringable(&self) -> bool {
+        self.0.is_stringable_dyn()
+    }
+
+    pub(crate) fn is_identity_eq(&self) -> bool {
+        self.0.is_identity_eq_dyn()
+    }
+
+    pub(crate) fn match_error_message(&self) -> Option<&str> {
+        self.0.match_error_message_dyn()
+    }
+
+    pub(crate) fn deprecated(&self) -> Option<&str> {
+        self.0.deprecated_dyn()
+    }
+
     pub(crate) fn union2(x: TyCustom, y: TyCustom) -> Result<TyCustom, (TyCustom, TyCustom)> {
         x.0.union2_dyn(y.0)
             .map(TyCustom)
             .map_err(|(x, y)| (TyCustom(x), TyCustom(y)))
     }
 
-    pub(crate) fn intersects(x: &TyCustom, y: &TyCustom) -> bool {
-        x.0.intersects_dyn(&*y.0)
+    pub(crate) fn intersects(x: &TyCustom, y: &TyCustom, ctx: &TypingOracleCtx) -> bool {
+        x.0.intersects_dyn(&*y.0, ctx)
+    }
+
+    /// Whether `x` and `y` intersect, `x` being the type of a value and `y` the type it is
+    /// required to have. `TyCustomImpl::intersects` is only called in this order, since it may not
+    /// be symmetric, e.g. for variant type arguments.
+    pub(crate) fn intersects_ordered(x: &TyCustom, y: &TyCustom, ctx: &TypingOracleCtx) -> bool {
+        x.0.is_intersects_with_dyn(&TyBasic::Custom(y.dupe()))
+            || y.0.is_intersects_with_dyn(&TyBasic::Custom(x.dupe()))
+            || Self::intersects(x, y, ctx)
+    }
+
+    /// Whether every value of this type is a value of type `other`.
+    pub(crate) fn is_subtype_of(&self, other: &TyBasic, ctx: &TypingOracleCtx) -> bool {
+        if self.0.is_intersects_with_dyn(other) {
+            return true;
+        }
+        match other {
+            TyBasic::Custom(other) => self.0.is_subtype_dyn(&*other.0, ctx),
+            _ => false,
+        }
     }
 
-    pub(crate) fn intersects_with(&self, other: &TyBasic) -> bool {
+    pub(crate) fn intersects_with(&self, other: &TyBasic, ctx: &TypingOracleCtx) -> bool {
         if self.0.is_intersects_with_dyn(other) {
             return true;
         }
         match other {
-            TyBasic::Custom(other) => Self::intersects(self, other),
+            TyBasic::Custom(other) => Self::intersects(self, other, ctx),
             TyBasic::Name(name) => self.as_name() == Some(name.as_str()),
             TyBasic::Callable => self.0.is_callable_dyn(),
             _ => false,
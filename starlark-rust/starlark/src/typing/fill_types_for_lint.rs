@@ -56,6 +56,7 @@ use crate::typing::error::TypingError;
 use crate::typing::Approximation;
 use crate::typing::Param;
 use crate::typing::Ty;
+use crate::typing::TyBasic;
 use crate::typing::TypingOracleCtx;
 use crate::values::tuple::AllocTuple;
 use crate::values::types::ellipsis::Ellipsis;
@@ -112,6 +113,7 @@ impl<'v> GlobalValue<'v> {
 
 struct GlobalTypesBuilder<'a, 'v> {
     approximations: &'a mut Vec<Approximation>,
+    warnings: &'a mut Vec<TypingError>,
     heap: &'v Heap,
     values: UnorderedMap<ModuleSlotId, GlobalValue<'v>>,
     errors: Vec<TypingError>,
@@ -682,12 +684,28 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
         }
     }
 
+    fn check_deprecated(&mut self, span: Span, ty: &Ty) {
+        for x in ty.iter_union() {
+            if let TyBasic::Custom(custom) = x {
+                if let Some(message) = custom.deprecated() {
+                    self.warnings.push(TypingError::msg(
+                        format!("Type `{}` is deprecated: {}", custom, message),
+                        span,
+                        self.ctx.codemap,
+                    ));
+                }
+            }
+        }
+    }
+
     fn fill_types(&mut self, stmt: &mut CstStmt) -> Result<(), InternalError> {
         stmt.visit_type_expr_err_mut(&mut |type_expr| {
             if type_expr.payload.typechecker_ty.is_some() {
                 return Err(self.internal_error(type_expr.span, "type already set"));
             }
-            type_expr.payload.typechecker_ty = Some(self.ty_expr(type_expr)?);
+            let ty = self.ty_expr(type_expr)?;
+            self.check_deprecated(type_expr.span, &ty);
+            type_expr.payload.typechecker_ty = Some(ty);
             Ok(())
         })
     }
@@ -704,6 +722,9 @@ impl<'a, 'v> GlobalTypesBuilder<'a, 'v> {
 #[derive(Default)]
 pub(crate) struct ModuleVarTypes {
     pub(crate) types: UnorderedMap<ModuleSlotId, Ty>,
+    /// Types denoted by module-level variables which are types, like `Fruit` in
+    /// `isinstance(x, Fruit)`.
+    pub(crate) eval_types: UnorderedMap<ModuleSlotId, Ty>,
 }
 
 /// Populate `TypeExprP` type payload when running lint typechecker.
@@ -713,6 +734,7 @@ pub(crate) fn fill_types_for_lint_typechecker(
     ctx: TypingOracleCtx,
     module_scope_data: &ModuleScopeData,
     approximations: &mut Vec<Approximation>,
+    warnings: &mut Vec<TypingError>,
     allow_string_literals_in_type_expr: bool,
 ) -> Result<(Vec<TypingError>, ModuleVarTypes), InternalError> {
     let heap = Heap::new();
@@ -723,12 +745,18 @@ pub(crate) fn fill_types_for_lint_typechecker(
         errors: Vec::new(),
         module_scope_data,
         approximations,
+        warnings,
         allow_string_literals_in_type_expr,
     };
     for stmt in module.iter_mut() {
         builder.top_level_stmt(stmt)?;
     }
     let GlobalTypesBuilder { errors, values, .. } = builder;
+    let eval_types = values
+        .entries_sorted()
+        .into_iter()
+        .filter_map(|(id, v)| Some((*id, v.value?.get_ref().eval_type()?)))
+        .collect();
     let types = values.map_values(|v| v.ty);
-    Ok((errors, ModuleVarTypes { types }))
+    Ok((errors, ModuleVarTypes { types, eval_types }))
 }
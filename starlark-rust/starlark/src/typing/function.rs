@@ -31,6 +31,7 @@ use crate::typing::error::TypingOrInternalError;
 use crate::typing::small_arc_vec_or_static::SmallArcVec1OrStatic;
 use crate::typing::Ty;
 use crate::typing::TyBasic;
+use crate::typing::TyUserFields;
 use crate::typing::TypingBinOp;
 use crate::typing::TypingOracleCtx;
 use crate::values::layout::heap::profile::arc_str::ArcStr;
@@ -177,6 +178,11 @@ pub trait TyCustomFunctionImpl:
     fn as_function(&self) -> Option<&TyFunction> {
         None
     }
+
+    /// The type this function stands for when used as a type, e.g. `int` for the `int` function.
+    fn as_type(&self) -> Option<Ty> {
+        None
+    }
 }
 
 #[derive(
@@ -214,6 +220,10 @@ impl<F: TyCustomFunctionImpl> TyCustomImpl for TyCustomFunction<F> {
         self.0.as_function()
     }
 
+    fn as_type(&self) -> Option<Ty> {
+        self.0.as_type()
+    }
+
     fn bin_op(
         &self,
         bin_op: TypingBinOp,
@@ -280,6 +290,28 @@ impl TyFunction {
         }
     }
 
+    /// Signature of a constructor of a record-like type: one named-only parameter
+    /// per known field, typed as the field, which has a default if the field is optional.
+    /// If the fields are not fully known, additional `**kwargs` are accepted.
+    pub fn constructor_from_fields(fields: &TyUserFields, result: Ty) -> Self {
+        let mut params: Vec<Param> = fields
+            .known
+            .iter()
+            .map(|(name, field)| {
+                let param = Param::name_only(name, field.ty.dupe());
+                if field.required {
+                    param
+                } else {
+                    param.optional()
+                }
+            })
+            .collect();
+        if fields.unknown {
+            params.push(Param::kwargs(Ty::any()));
+        }
+        TyFunction::new(params, result)
+    }
+
     fn maybe_intern_params(params: Vec<Param>) -> SmallArcVec1OrStatic<Param> {
         if params.as_slice() == Self::any_params() {
             SmallArcVec1OrStatic::new_static(Self::any_params())
@@ -344,4 +376,8 @@ impl TyCustomFunctionImpl for TyFunction {
     fn as_function(&self) -> Option<&TyFunction> {
         Some(self)
     }
+
+    fn as_type(&self) -> Option<Ty> {
+        self.type_attr.clone()
+    }
 }
@@ -60,7 +60,20 @@ pub use ty::Approximation;
 pub use ty::Ty;
 pub use ty::TyName;
 pub use typecheck::AstModuleTypecheck;
+pub use typecheck::TypecheckOutput;
 pub use typecheck::TypeMap;
+pub use user::MatcherCompose;
 pub use user::TyUser;
+pub use user::TyUserAttributeResolver;
+pub use user::TyUserCacheKey;
+pub use user::TyUserDiscriminant;
+pub use user::TyUserEquality;
+pub use user::TyUserField;
 pub use user::TyUserFields;
 pub use user::TyUserIndex;
+pub use user::TyUserLazyField;
+pub use user::TyUserParams;
+pub use user::TyUserSameAs;
+pub use user::TyUserTypeArgCallable;
+pub use user::TyUserTypeParam;
+pub use user::TyUserVariance;
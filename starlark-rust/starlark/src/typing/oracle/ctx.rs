@@ -24,6 +24,7 @@ use crate::codemap::CodeMap;
 use crate::codemap::Span;
 use crate::codemap::Spanned;
 use crate::typing::basic::TyBasic;
+use crate::typing::custom::TyCustom;
 use crate::typing::error::InternalError;
 use crate::typing::error::TypingError;
 use crate::typing::error::TypingOrInternalError;
@@ -63,6 +64,10 @@ enum TypingOracleCtxError {
     AttributeNotAvailable { ty: Ty, attr: String },
     #[error("Type `{ty}` is not iterable")]
     NotIterable { ty: Ty },
+    #[error("Type `{ty}` is not hashable")]
+    NotHashable { ty: Ty },
+    #[error("Type `{ty}` cannot be converted to string")]
+    NotStringable { ty: Ty },
     #[error("Unary operator `{un_op}` is not available on the type `{ty}`")]
     UnaryOperatorNotAvailable { ty: Ty, un_op: TypingUnOp },
     #[error("Binary operator `{bin_op}` is not available on the types `{left}` and `{right}`")]
@@ -104,6 +109,7 @@ impl<'a> TypingOracleCtx<'a> {
             TyBasic::Any => Ty::any(),
             TyBasic::List(x) => x.to_ty(),
             TyBasic::Tuple(xs) => xs.get(i).cloned().unwrap_or(Ty::never()),
+            TyBasic::Custom(c) => c.0.indexed_dyn(i),
             // Not exactly sure what we should do here
             _ => Ty::any(),
         }
@@ -367,6 +373,51 @@ impl<'a> TypingOracleCtx<'a> {
         }
     }
 
+    /// Check values of the type can be used as dict keys.
+    pub(crate) fn validate_hashable(&self, key: Spanned<&Ty>) -> Result<(), TypingError> {
+        match key.typecheck_union_simple(|basic| match basic {
+            TyBasic::Custom(custom) if !custom.is_hashable() => Err(()),
+            basic => Ok(Ty::basic(basic.dupe())),
+        }) {
+            Ok(_) => Ok(()),
+            Err(()) => Err(self.mk_error(
+                key.span,
+                TypingOracleCtxError::NotHashable {
+                    ty: key.node.clone(),
+                },
+            )),
+        }
+    }
+
+    /// Check that a value of type `ty` can be converted to string.
+    pub(crate) fn validate_stringable(&self, ty: Spanned<&Ty>) -> Result<(), TypingError> {
+        match ty.typecheck_union_simple(|basic| match basic {
+            TyBasic::Custom(custom) if !custom.is_stringable() => Err(()),
+            basic => Ok(Ty::basic(basic.dupe())),
+        }) {
+            Ok(_) => Ok(()),
+            Err(()) => Err(self.mk_error(
+                ty.span,
+                TypingOracleCtxError::NotStringable {
+                    ty: ty.node.clone(),
+                },
+            )),
+        }
+    }
+
+    /// `"..." % args` converts `args` to string, or each element of `args` if it is a tuple.
+    fn validate_interpolated(&self, args: Spanned<&Ty>) -> Result<(), TypingError> {
+        match args.iter_union() {
+            [TyBasic::Tuple(TyTuple::Elems(elems))] => elems.iter().try_for_each(|elem| {
+                self.validate_stringable(Spanned {
+                    span: args.span,
+                    node: elem,
+                })
+            }),
+            _ => self.validate_stringable(args),
+        }
+    }
+
     fn expr_index_ty(
         &self,
         array: &TyBasic,
@@ -533,6 +584,87 @@ impl<'a> TypingOracleCtx<'a> {
         }
     }
 
+    /// Like `expr_dot`, but the attributes in `present` are known to be present,
+    /// e.g. after a `hasattr(x, "attr")` check, and the fields of `discriminants` are known
+    /// to have the given values, e.g. after a `x.kind == "a"` check.
+    pub(crate) fn expr_dot_present(
+        &self,
+        span: Span,
+        array: &Ty,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+    ) -> Result<Ty, TypingError> {
+        match array.typecheck_union_simple(|basic| match basic {
+            TyBasic::Custom(custom) => custom.0.present_attribute_dyn(attr, present, discriminants),
+            basic => self.expr_dot_basic(basic, attr),
+        }) {
+            Ok(x) => Ok(x),
+            Err(()) => Err(self.mk_error(
+                span,
+                TypingOracleCtxError::AttributeNotAvailable {
+                    ty: array.clone(),
+                    attr: attr.to_owned(),
+                },
+            )),
+        }
+    }
+
+    /// Type of a variable of type `ty` where it is known to be equal to a value of type `value`,
+    /// e.g. in the body of `if x == v:`. The variable can only be narrowed to `value` if values
+    /// of that type are only equal to themselves.
+    pub(crate) fn narrow_eq(&self, ty: &Ty, value: &Ty) -> Ty {
+        match value.iter_union() {
+            [TyBasic::Custom(custom)] if custom.is_identity_eq() && self.intersects(ty, value) => {
+                value.dupe()
+            }
+            _ => ty.dupe(),
+        }
+    }
+
+    /// Type of a variable of type `ty` where `isinstance(x, t)` is known to be `matches`,
+    /// e.g. in the branches of `if isinstance(x, t):`.
+    ///
+    /// If it matches, the alternatives of `ty` which are subtypes of `t` are kept, and the others
+    /// which intersect `t` are narrowed to `t`. If it doesn't, the alternatives of `ty` which are
+    /// exactly `t` are dropped.
+    pub(crate) fn narrow_isinstance(&self, ty: &Ty, t: &Ty, matches: bool) -> Ty {
+        if matches {
+            let mut narrowed = Vec::new();
+            for x in ty.iter_union() {
+                // Keep what is already known about `x`, e.g. an `Apple` checked to be a `Plant`.
+                if *x != TyBasic::Any && self.is_subtype(&Ty::basic(x.dupe()), t) {
+                    narrowed.push(Ty::basic(x.dupe()));
+                    continue;
+                }
+                for y in t.iter_union() {
+                    if self.intersects_basic(x, y) {
+                        narrowed.push(Ty::basic(y.dupe()));
+                    }
+                }
+            }
+            if narrowed.is_empty() {
+                // The check never passes, so anything would do.
+                t.dupe()
+            } else {
+                Ty::unions(narrowed)
+            }
+        } else {
+            let rest = ty
+                .iter_union()
+                .iter()
+                .filter(|x| !t.iter_union().contains(x))
+                .map(|x| Ty::basic(x.dupe()))
+                .collect::<Vec<_>>();
+            if rest.is_empty() {
+                // The check always passes, keep the type rather than making it never.
+                ty.dupe()
+            } else {
+                Ty::unions(rest)
+            }
+        }
+    }
+
     fn expr_un_op_basic(&self, ty: &TyBasic, un_op: TypingUnOp) -> Result<Ty, ()> {
         match ty {
             TyBasic::StarlarkValue(ty) => match ty.un_op(un_op) {
@@ -782,7 +914,12 @@ impl<'a> TypingOracleCtx<'a> {
             BinOp::Subtract => self.expr_bin_op_ty(span, lhs, TypingBinOp::Sub, rhs),
             BinOp::Add => self.expr_bin_op_ty(span, lhs, TypingBinOp::Add, rhs),
             BinOp::Multiply => self.expr_bin_op_ty(span, lhs, TypingBinOp::Mul, rhs),
-            BinOp::Percent => self.expr_bin_op_ty(span, lhs, TypingBinOp::Percent, rhs),
+            BinOp::Percent => {
+                if lhs.node == Ty::string() {
+                    self.validate_interpolated(rhs.as_ref())?;
+                }
+                self.expr_bin_op_ty(span, lhs, TypingBinOp::Percent, rhs)
+            }
             BinOp::Divide => self.expr_bin_op_ty(span, lhs, TypingBinOp::Div, rhs),
             BinOp::FloorDivide => self.expr_bin_op_ty(span, lhs, TypingBinOp::FloorDiv, rhs),
             BinOp::BitAnd => self.expr_bin_op_ty(span, lhs, TypingBinOp::BitAnd, rhs),
@@ -818,7 +955,41 @@ impl<'a> TypingOracleCtx<'a> {
     }
 
     pub(crate) fn intersects_basic(&self, x: &TyBasic, y: &TyBasic) -> bool {
-        x == y || self.intersects_one_side(x, y) || self.intersects_one_side(y, x)
+        match (x, y) {
+            // Custom types may intersect in one direction only, e.g. for variant type arguments,
+            // so `x` is taken to be the type of a value and `y` the type it is required to have.
+            (TyBasic::Custom(x_custom), TyBasic::Custom(y_custom)) => {
+                x == y || TyCustom::intersects_ordered(x_custom, y_custom, self)
+            }
+            _ => x == y || self.intersects_one_side(x, y) || self.intersects_one_side(y, x),
+        }
+    }
+
+    /// Whether every value of type `xs` is a value of type `ys`, as far as the typechecker knows.
+    /// Like for intersection, `Any` is compatible with every type both ways.
+    pub(crate) fn is_subtype(&self, xs: &Ty, ys: &Ty) -> bool {
+        if xs.is_any() || xs.is_never() || ys.is_any() {
+            return true;
+        }
+
+        xs.iter_union()
+            .iter()
+            .all(|x| ys.iter_union().iter().any(|y| self.is_subtype_basic(x, y)))
+    }
+
+    fn is_subtype_basic(&self, x: &TyBasic, y: &TyBasic) -> bool {
+        match (x, y) {
+            _ if x == y => true,
+            (TyBasic::Any, _) | (_, TyBasic::Any) => true,
+            (TyBasic::List(x), TyBasic::List(y)) | (TyBasic::Iter(x), TyBasic::Iter(y)) => {
+                self.is_subtype(x, y)
+            }
+            (TyBasic::Dict(x_k, x_v), TyBasic::Dict(y_k, y_v)) => {
+                self.is_subtype(x_k, y_k) && self.is_subtype(x_v, y_v)
+            }
+            (TyBasic::Custom(x), y) => x.is_subtype_of(y, self),
+            _ => false,
+        }
     }
 
     /// We consider two type intersecting if either side knows if they intersect.
@@ -843,7 +1014,7 @@ impl<'a> TypingOracleCtx<'a> {
                 Ok(yy) => self.intersects(x, &yy),
                 Err(()) => false,
             },
-            (TyBasic::Custom(x), y) => x.intersects_with(y),
+            (TyBasic::Custom(x), y) => x.intersects_with(y, self),
             (TyBasic::Type, TyBasic::StarlarkValue(y)) => y.is_type(),
             (TyBasic::Type, _) => {
                 // TODO(nga): more precise.
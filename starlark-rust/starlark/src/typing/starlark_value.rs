@@ -233,6 +233,16 @@ impl TyStarlarkValue {
         self.vtable.vtable.HAS_at
     }
 
+    /// Values of this type implement hashing.
+    pub(crate) fn is_hashable(self) -> bool {
+        self.vtable.vtable.HAS_write_hash || self.vtable.vtable.HAS_get_hash
+    }
+
+    /// Values of this type implement `compare`.
+    pub(crate) fn is_comparable(self) -> bool {
+        self.vtable.vtable.HAS_compare
+    }
+
     pub(crate) fn attr_from_methods(self, name: &str) -> Result<Ty, ()> {
         if let Some(methods) = (self.vtable.vtable.get_methods)() {
             if let Some(method) = methods.get(name) {
@@ -33,6 +33,7 @@ use crate::syntax::AstModule;
 use crate::syntax::Dialect;
 use crate::typing::interface::Interface;
 use crate::typing::AstModuleTypecheck;
+use crate::typing::TypecheckOutput;
 use crate::values::none::NoneType;
 use crate::values::typing::StarlarkIter;
 use crate::values::Value;
@@ -85,7 +86,13 @@ impl TypeCheck {
         // `AstModule` is not `Clone`. Parse twice.
         let ast0 = AstModule::parse("filename", code.to_owned(), &Dialect::Extended).unwrap();
         let ast1 = AstModule::parse("filename", code.to_owned(), &Dialect::Extended).unwrap();
-        let (errors, typemap, interface, approximations) = ast0.typecheck(
+        let TypecheckOutput {
+            errors,
+            typemap,
+            interface,
+            approximations,
+            warnings,
+        } = ast0.typecheck_with_warnings(
             &globals,
             &self
                 .loads
@@ -111,6 +118,12 @@ impl TypeCheck {
             }
         }
 
+        for warning in &warnings {
+            writeln!(output).unwrap();
+            writeln!(output, "Warning:").unwrap();
+            writeln!(output, "{}", format!("{:#}", warning).trim_end()).unwrap();
+        }
+
         if !approximations.is_empty() {
             writeln!(output).unwrap();
             writeln!(output, "Approximations:").unwrap();
@@ -203,6 +203,14 @@ impl Ty {
         }
     }
 
+    /// Custom message for runtime match failures, if this type is a custom type providing one.
+    pub(crate) fn match_error_message(&self) -> Option<&str> {
+        match self.iter_union() {
+            [TyBasic::Custom(x)] => x.match_error_message(),
+            _ => None,
+        }
+    }
+
     /// This type is `TyStarlarkValue`.
     pub(crate) fn is_starlark_value(&self) -> Option<TyStarlarkValue> {
         match self.iter_union() {
@@ -55,10 +55,10 @@ use crate::typing::ty::Ty;
 use crate::values::FrozenHeap;
 
 // Things which are None in the map have type void - they are never constructed
-pub(crate) fn solve_bindings(
-    bindings: Bindings,
-    oracle: TypingOracleCtx,
-    module_var_types: &ModuleVarTypes,
+pub(crate) fn solve_bindings<'a>(
+    bindings: Bindings<'a>,
+    oracle: TypingOracleCtx<'a>,
+    module_var_types: &'a ModuleVarTypes,
 ) -> Result<(Vec<TypingError>, HashMap<BindingId, Ty>, Vec<Approximation>), InternalError> {
     let mut types = bindings
         .expressions
@@ -76,6 +76,10 @@ pub(crate) fn solve_bindings(
         approximoations: RefCell::new(Vec::new()),
         types,
         module_var_types,
+        present_attrs: bindings.present_attrs,
+        discriminant_guards: bindings.discriminant_guards,
+        eq_guards: bindings.eq_guards,
+        isinstance_guards: bindings.isinstance_guards,
     };
     const ITERATIONS: usize = 100;
     for _iteration in 0..ITERATIONS {
@@ -167,6 +171,36 @@ impl TypeMap {
     }
 }
 
+/// Everything typechecking a module produces, see
+/// [`AstModuleTypecheck::typecheck_with_warnings`].
+pub struct TypecheckOutput {
+    /// Type errors.
+    pub errors: Vec<anyhow::Error>,
+    /// Types of the bindings.
+    pub typemap: TypeMap,
+    /// Interface of the module, for typechecking the modules loading it.
+    pub interface: Interface,
+    /// Approximations made by the typechecker.
+    pub approximations: Vec<Approximation>,
+    /// Problems which do not fail typechecking, like uses of deprecated types.
+    pub warnings: Vec<anyhow::Error>,
+}
+
+impl TypecheckOutput {
+    fn internal_error(error: anyhow::Error, codemap: CodeMap) -> TypecheckOutput {
+        TypecheckOutput {
+            errors: vec![error],
+            typemap: TypeMap {
+                codemap,
+                bindings: UnorderedMap::new(),
+            },
+            interface: Interface::default(),
+            approximations: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
 /// Typecheck a module.
 pub trait AstModuleTypecheck {
     /// Typecheck a module.
@@ -175,6 +209,13 @@ pub trait AstModuleTypecheck {
         globals: &Globals,
         loads: &HashMap<String, Interface>,
     ) -> (Vec<anyhow::Error>, TypeMap, Interface, Vec<Approximation>);
+
+    /// Typecheck a module, also reporting warnings, which `typecheck` drops.
+    fn typecheck_with_warnings(
+        self,
+        globals: &Globals,
+        loads: &HashMap<String, Interface>,
+    ) -> TypecheckOutput;
 }
 
 impl AstModuleTypecheck for AstModule {
@@ -183,6 +224,21 @@ impl AstModuleTypecheck for AstModule {
         globals: &Globals,
         loads: &HashMap<String, Interface>,
     ) -> (Vec<anyhow::Error>, TypeMap, Interface, Vec<Approximation>) {
+        let TypecheckOutput {
+            errors,
+            typemap,
+            interface,
+            approximations,
+            warnings: _,
+        } = self.typecheck_with_warnings(globals, loads);
+        (errors, typemap, interface, approximations)
+    }
+
+    fn typecheck_with_warnings(
+        self,
+        globals: &Globals,
+        loads: &HashMap<String, Interface>,
+    ) -> TypecheckOutput {
         let codemap = self.codemap.dupe();
         let names = MutableNames::new();
         let frozen_heap = FrozenHeap::new();
@@ -211,24 +267,18 @@ impl AstModuleTypecheck for AstModule {
         let oracle = TypingOracleCtx { codemap: &codemap };
 
         let mut approximations = Vec::new();
+        let mut warnings = Vec::new();
         let (fill_types_errors, module_var_types) = match fill_types_for_lint_typechecker(
             &mut cst,
             oracle,
             &scope_data,
             &mut approximations,
+            &mut warnings,
             self.allow_string_literals_in_type_expr,
         ) {
             Ok(fill_types_errors) => fill_types_errors,
             Err(e) => {
-                return (
-                    vec![InternalError::into_anyhow(e)],
-                    TypeMap {
-                        codemap,
-                        bindings: UnorderedMap::new(),
-                    },
-                    Interface::default(),
-                    Vec::new(),
-                );
+                return TypecheckOutput::internal_error(InternalError::into_anyhow(e), codemap);
             }
         };
 
@@ -245,14 +295,9 @@ impl AstModuleTypecheck for AstModule {
                 ) {
                     Ok(bindings) => bindings,
                     Err(e) => {
-                        return (
-                            vec![InternalError::into_anyhow(e)],
-                            TypeMap {
-                                codemap,
-                                bindings: UnorderedMap::new(),
-                            },
-                            Interface::default(),
-                            Vec::new(),
+                        return TypecheckOutput::internal_error(
+                            InternalError::into_anyhow(e),
+                            codemap,
                         );
                     }
                 };
@@ -260,15 +305,7 @@ impl AstModuleTypecheck for AstModule {
                     match solve_bindings(bindings.bindings, oracle, &module_var_types) {
                         Ok(x) => x,
                         Err(e) => {
-                            return (
-                                vec![e.into_anyhow()],
-                                TypeMap {
-                                    codemap,
-                                    bindings: UnorderedMap::new(),
-                                },
-                                Interface::default(),
-                                Vec::new(),
-                            );
+                            return TypecheckOutput::internal_error(e.into_anyhow(), codemap);
                         }
                     };
 
@@ -311,6 +348,14 @@ impl AstModuleTypecheck for AstModule {
         }
         let interface = Interface::new(res);
 
-        (errors, typemap, interface, approximations)
+        let warnings = warnings.into_map(TypingError::into_anyhow);
+
+        TypecheckOutput {
+            errors,
+            typemap,
+            interface,
+            approximations,
+            warnings,
+        }
     }
 }
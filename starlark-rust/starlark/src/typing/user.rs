@@ -16,26 +16,39 @@
  */
 
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::Arc;
+use std::sync::Weak;
 
 use allocative::Allocative;
 use dupe::Dupe;
+use once_cell::sync::OnceCell;
 use starlark_map::sorted_map::SortedMap;
+use starlark_syntax::codemap::CodeMap;
 use starlark_syntax::codemap::Span;
 use starlark_syntax::codemap::Spanned;
 
+use crate::typing::arc_ty::ArcTy;
 use crate::typing::custom::TyCustomImpl;
 use crate::typing::error::TypingOrInternalError;
+use crate::typing::function::ParamMode;
 use crate::typing::function::TyCustomFunctionImpl;
 use crate::typing::starlark_value::TyStarlarkValue;
+use crate::typing::tuple::TyTuple;
 use crate::typing::Arg;
+use crate::typing::Param;
 use crate::typing::Ty;
 use crate::typing::TyBasic;
 use crate::typing::TyFunction;
+use crate::typing::TypingBinOp;
 use crate::typing::TypingOracleCtx;
 use crate::values::types::type_instance_id::TypeInstanceId;
 use crate::values::typing::type_compiled::alloc::TypeMatcherAlloc;
+use crate::values::typing::type_compiled::matcher::TypeMatcherBoxAlloc;
 use crate::values::typing::type_compiled::type_matcher_factory::TypeMatcherFactory;
 
 #[derive(Debug, thiserror::Error)]
@@ -52,10 +65,54 @@ enum TyUserError {
         "Type `{0}` specifies custom iterable, but underlying `StarlarkValue` is not iterable"
     )]
     IterableNotIterable(String),
+    #[error(
+        "Type `{0}` specifies tuple shape, but underlying `StarlarkValue` is not iterable"
+    )]
+    TupleShapeNotIterable(String),
+    #[error(
+        "Type `{0}` specifies it is hashable, but underlying `StarlarkValue` is not hashable"
+    )]
+    HashableNotHashable(String),
+    #[error(
+        "Type `{0}` specifies it is comparable, but underlying `StarlarkValue` is not comparable"
+    )]
+    ComparableNotComparable(String),
+    #[error(
+        "Type `{0}` specifies it is not stringable, but underlying `StarlarkValue` is a string"
+    )]
+    StringNotStringable(String),
+    #[error("Type `{0}` is a record with {1} fields, but its callable takes {2} parameters")]
+    ConstructorArityMismatch(String, usize, usize),
+    #[error("Type `{0}` cannot declare `{1}` as a supertype, because it is not a primitive type")]
+    NotPrimitiveSupertype(String, String),
+    #[error(
+        "Type `{0}` declares `{1}` as an exclusive field, but it is not a known optional field"
+    )]
+    ExclusiveFieldNotOptional(String, String),
+    #[error("Type `{0}` is a sequence, but its index result `{1}` is not its iter item `{2}`")]
+    SequenceIndexIterItemMismatch(String, Ty, Ty),
+    #[error("Type `{0}` takes {1} type arguments, but {2} were given")]
+    TypeArgCountMismatch(String, usize, usize),
+    #[error("Type `{0}` is opaque, values of it cannot be called")]
+    OpaqueNotCallable(String),
+    #[error("Type `{0}` declares `{1}` as a self-returning method, but it is not a method of it")]
+    SelfReturningNotMethod(String, String),
+    #[error("Type `{0}` declares field `{1}` the same as `{2}`, but `{2}` is not a known field")]
+    SameAsNotKnown(String, String, String),
+    #[error("Type `{0}` declares field `{1}` the same as another field, but it is a known field")]
+    SameAsShadowsField(String, String),
+    #[error(
+        "Type `{0}` declares `{1}` as a discriminant or variant field, but it is not a known field"
+    )]
+    DiscriminantFieldNotKnown(String, String),
+    #[error(
+        "Cannot merge field `{0}` of type `{1}` with field of type `{2}`, the types are disjoint"
+    )]
+    MergeDisjointField(String, Ty, Ty),
 }
 
 /// Types of `[]` operator.
-#[derive(Allocative, Debug)]
+#[derive(Allocative, Debug, Clone)]
 pub struct TyUserIndex {
     /// Type of index argument.
     pub(crate) index: Ty,
@@ -63,14 +120,168 @@ pub struct TyUserIndex {
     pub(crate) result: Ty,
 }
 
+/// Known field of the struct.
+#[derive(Allocative, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TyUserField {
+    /// Type of the field when it is present.
+    pub ty: Ty,
+    /// Is the field always present? Optional fields may be absent at runtime,
+    /// so accessing them produces `ty | None`.
+    pub required: bool,
+}
+
+impl TyUserField {
+    /// Field which is always present.
+    pub fn required(ty: Ty) -> TyUserField {
+        TyUserField { ty, required: true }
+    }
+
+    /// Field which may be absent.
+    pub fn optional(ty: Ty) -> TyUserField {
+        TyUserField {
+            ty,
+            required: false,
+        }
+    }
+
+    /// Type of accessing this field.
+    pub(crate) fn attribute_ty(&self) -> Ty {
+        if self.required {
+            self.ty.dupe()
+        } else {
+            Ty::union2(self.ty.dupe(), Ty::none())
+        }
+    }
+}
+
+/// Field whose type is defined after the type having the field, so that types whose fields refer
+/// to each other can be defined: the first type is created with a lazy field naming the second
+/// type, and the second type is stored in the slot of the field once it is created.
+///
+/// The field only holds a weak reference to its slot, which is owned by whoever defines the
+/// types, so types referring to each other don't keep each other alive. Lazy fields are compared,
+/// hashed and ordered by the name of their type, since comparing their types could recurse
+/// forever through the types referring to each other.
+#[derive(Clone, Dupe, Allocative)]
+pub struct TyUserLazyField(#[allocative(skip)] Arc<TyUserLazyFieldData>);
+
+struct TyUserLazyFieldData {
+    /// Name of the type of the field, which identifies it.
+    name: String,
+    ty: Weak<OnceCell<Ty>>,
+    required: bool,
+}
+
+impl TyUserLazyField {
+    /// Field which is always present.
+    pub fn required(name: impl Into<String>, ty: &Arc<OnceCell<Ty>>) -> TyUserLazyField {
+        TyUserLazyField::new(name.into(), ty, true)
+    }
+
+    /// Field which may be absent.
+    pub fn optional(name: impl Into<String>, ty: &Arc<OnceCell<Ty>>) -> TyUserLazyField {
+        TyUserLazyField::new(name.into(), ty, false)
+    }
+
+    fn new(name: String, ty: &Arc<OnceCell<Ty>>, required: bool) -> TyUserLazyField {
+        TyUserLazyField(Arc::new(TyUserLazyFieldData {
+            name,
+            ty: Arc::downgrade(ty),
+            required,
+        }))
+    }
+
+    /// The field. Its type is `Any` if it is not defined yet, or no longer is.
+    pub fn resolve(&self) -> TyUserField {
+        let ty = self
+            .0
+            .ty
+            .upgrade()
+            .and_then(|ty| ty.get().map(|ty| ty.dupe()))
+            .unwrap_or_else(Ty::any);
+        TyUserField {
+            ty,
+            required: self.0.required,
+        }
+    }
+
+    fn id(&self) -> (&str, bool) {
+        (&self.0.name, self.0.required)
+    }
+}
+
+impl Debug for TyUserLazyField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TyUserLazyField({})", self.0.name)
+    }
+}
+
+impl PartialEq for TyUserLazyField {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for TyUserLazyField {}
+
+impl PartialOrd for TyUserLazyField {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TyUserLazyField {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
+impl Hash for TyUserLazyField {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+/// Field declared to have the same type as the field with this name, see
+/// [`TyUserFields::same_as`].
+#[derive(Allocative, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TyUserSameAs(pub String);
+
+/// Field whose string value selects which fields are available, like the tag of a
+/// tagged union, see [`TyUserFields::discriminant`].
+#[derive(Allocative, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TyUserDiscriminant {
+    /// Name of the discriminant field, a known field.
+    pub field: String,
+    /// Known fields available for each value of the discriminant field.
+    /// Fields listed here are only accessible after checking the discriminant,
+    /// e.g. `if x.kind == "a":`. Fields not listed for any value are always accessible.
+    pub variants: SortedMap<String, Vec<String>>,
+}
+
 /// Fields of the struct.
-#[derive(Allocative, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[derive(Allocative, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct TyUserFields {
     /// Known fields.
-    pub known: SortedMap<String, Ty>,
+    pub known: SortedMap<String, TyUserField>,
+    /// Fields whose types are defined after this type, for types referring to each other.
+    /// Unlike known fields, they are not parameters of record constructors.
+    pub lazy: SortedMap<String, TyUserLazyField>,
     /// Are there unknown fields?
     /// Unknown fields are possible if this type represents an abstract type like a provider.
     pub unknown: bool,
+    /// Groups of optional fields of which at most one is present, like the variants of a
+    /// tagged union. After `hasattr(x, "a")`, other fields of the group of `a` are `None`.
+    pub exclusive: Vec<Vec<String>>,
+    /// Type of attributes which are neither fields nor methods, for types whose attributes may
+    /// or may not exist, like some providers. Unlike `unknown`, accessing such an attribute has
+    /// this type rather than any type. If `None`, accessing them is an error.
+    pub fallback: Option<Ty>,
+    /// Fields whose type is the type of a known or lazy field, for types like generated providers
+    /// whose fields come in pairs of the same type. They are present when that field is.
+    pub same_as: SortedMap<String, TyUserSameAs>,
+    /// Field selecting which other fields are available.
+    pub discriminant: Option<TyUserDiscriminant>,
 }
 
 impl TyUserFields {
@@ -79,6 +290,11 @@ impl TyUserFields {
         TyUserFields {
             known: SortedMap::new(),
             unknown: false,
+            lazy: SortedMap::new(),
+            exclusive: Vec::new(),
+            fallback: None,
+            same_as: SortedMap::new(),
+            discriminant: None,
         }
     }
 
@@ -87,28 +303,455 @@ impl TyUserFields {
         TyUserFields {
             known: SortedMap::new(),
             unknown: true,
+            lazy: SortedMap::new(),
+            exclusive: Vec::new(),
+            fallback: None,
+            same_as: SortedMap::new(),
+            discriminant: None,
+        }
+    }
+
+    /// Fields of a type extending types with the fields `self` and `other`, like a provider
+    /// extending another. Known fields of both have the most specific of their types, and are
+    /// required if either requires them. It is an error if their types are disjoint. Other
+    /// fields declared by both are taken from `other`. The fields are `unknown` if either is.
+    pub fn merge(&self, other: &TyUserFields) -> anyhow::Result<TyUserFields> {
+        let codemap = CodeMap::default();
+        let oracle = TypingOracleCtx { codemap: &codemap };
+        let mut known = Vec::new();
+        for (name, field) in self.known.iter() {
+            if !other.known.contains_key(name) {
+                known.push((name.clone(), field.clone()));
+            }
+        }
+        for (name, field) in other.known.iter() {
+            let field = match self.known.get(name) {
+                Some(prev) => TyUserField {
+                    ty: match TyUserFields::intersect(&prev.ty, &field.ty, &oracle) {
+                        Some(ty) => ty,
+                        None => {
+                            return Err(TyUserError::MergeDisjointField(
+                                name.clone(),
+                                prev.ty.dupe(),
+                                field.ty.dupe(),
+                            )
+                            .into());
+                        }
+                    },
+                    required: prev.required || field.required,
+                },
+                None => field.clone(),
+            };
+            known.push((name.clone(), field));
+        }
+        Ok(TyUserFields {
+            known: known.into_iter().collect(),
+            lazy: self
+                .lazy
+                .iter()
+                .filter(|(name, _)| !other.lazy.contains_key(*name))
+                .chain(other.lazy.iter())
+                .map(|(name, field)| (name.clone(), field.clone()))
+                .collect(),
+            unknown: self.unknown || other.unknown,
+            exclusive: self
+                .exclusive
+                .iter()
+                .chain(other.exclusive.iter())
+                .cloned()
+                .collect(),
+            fallback: other.fallback.clone().or_else(|| self.fallback.clone()),
+            same_as: self
+                .same_as
+                .iter()
+                .filter(|(name, _)| !other.same_as.contains_key(*name))
+                .chain(other.same_as.iter())
+                .map(|(name, field)| (name.clone(), field.clone()))
+                .collect(),
+            discriminant: other
+                .discriminant
+                .clone()
+                .or_else(|| self.discriminant.clone()),
+        })
+    }
+
+    /// The most specific of the types `a` and `b`: the one which is a subtype of the other, or
+    /// else the alternatives of one which intersect the other, preferring `b` if both have as
+    /// many. `None` if they are disjoint.
+    fn intersect(a: &Ty, b: &Ty, oracle: &TypingOracleCtx) -> Option<Ty> {
+        if a.is_any() {
+            return Some(b.dupe());
+        }
+        if b.is_any() {
+            return Some(a.dupe());
+        }
+        match (oracle.is_subtype(a, b), oracle.is_subtype(b, a)) {
+            (true, false) => return Some(a.dupe()),
+            (false, true) => return Some(b.dupe()),
+            _ => {}
+        }
+        let filter = |xs: &Ty, ys: &Ty| -> Vec<Ty> {
+            xs.iter_union()
+                .iter()
+                .filter(|x| ys.iter_union().iter().any(|y| oracle.intersects_basic(x, y)))
+                .map(|x| Ty::basic(x.dupe()))
+                .collect()
+        };
+        let from_a = filter(a, b);
+        let from_b = filter(b, a);
+        if from_b.is_empty() {
+            None
+        } else if from_a.len() < from_b.len() {
+            Some(Ty::unions(from_a))
+        } else {
+            Some(Ty::unions(from_b))
+        }
+    }
+
+    /// The known, lazy or same-as field `name`.
+    fn get(&self, name: &str) -> Option<TyUserField> {
+        self.get_declared(self.resolve_same_as(name))
+    }
+
+    /// The field that the same-as field `name` has the type of, or `name` itself, so that
+    /// same-as fields behave like that field in discriminant, exclusive and presence checks.
+    fn resolve_same_as<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.same_as.get(name) {
+            Some(TyUserSameAs(field)) => field,
+            None => name,
+        }
+    }
+
+    /// The known or lazy field `name`.
+    fn get_declared(&self, name: &str) -> Option<TyUserField> {
+        match self.known.get(name) {
+            Some(field) => Some(field.clone()),
+            None => self.lazy.get(name).map(|field| field.resolve()),
+        }
+    }
+
+    /// Whether the field `name` is only available for some values of the discriminant.
+    fn is_variant_field(&self, name: &str) -> bool {
+        let name = self.resolve_same_as(name);
+        match &self.discriminant {
+            Some(discriminant) => discriminant
+                .variants
+                .values()
+                .any(|fields| fields.iter().any(|f| f == name)),
+            None => false,
+        }
+    }
+
+    /// Whether the field `name` is available when the fields of `discriminants`
+    /// have the given values.
+    fn is_variant_field_available(&self, name: &str, discriminants: &[(String, String)]) -> bool {
+        let name = self.resolve_same_as(name);
+        let discriminant = match &self.discriminant {
+            Some(discriminant) => discriminant,
+            None => return true,
+        };
+        discriminants.iter().any(|(field, value)| {
+            *field == discriminant.field
+                && matches!(
+                    discriminant.variants.get(value),
+                    Some(fields) if fields.iter().any(|f| f == name)
+                )
+        })
+    }
+
+    /// Whether `a` and `b` are different fields which cannot be present at the same time.
+    /// Same-as fields are the same field as the field they have the type of.
+    fn are_exclusive(&self, a: &str, b: &str) -> bool {
+        let (a, b) = (self.resolve_same_as(a), self.resolve_same_as(b));
+        a != b
+            && self
+                .exclusive
+                .iter()
+                .any(|group| group.iter().any(|f| f == a) && group.iter().any(|f| f == b))
+    }
+}
+
+/// Resolves types of attributes which are not [known fields](TyUserFields::known),
+/// for types whose attributes follow a rule rather than a fixed list,
+/// e.g. any attribute ending with `_path` is a `str`.
+///
+/// The function must be pure and deterministic: it may be called any number of times
+/// for the same attribute. It does not participate in equality or hashing of [`TyUser`],
+/// which are defined by the type id.
+#[derive(Clone, Copy, Allocative)]
+pub struct TyUserAttributeResolver(#[allocative(skip)] pub fn(&str) -> Option<Ty>);
+
+impl Debug for TyUserAttributeResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TyUserAttributeResolver(..)")
+    }
+}
+
+/// Callable signature of the instantiations of a generic type, given their type argument.
+/// For example, `LazyValue[T]` can be called to force the value, so its signature is
+/// `def() -> T`.
+///
+/// Like [`TyUserAttributeResolver`], the function must be pure and deterministic, and it does
+/// not participate in equality or hashing of [`TyUser`].
+#[derive(Clone, Copy, Allocative)]
+pub struct TyUserTypeArgCallable(#[allocative(skip)] pub fn(&Ty) -> TyFunction);
+
+impl Debug for TyUserTypeArgCallable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TyUserTypeArgCallable(..)")
+    }
+}
+
+/// How instantiations of a generic type relate, depending on how their type arguments relate.
+#[derive(Allocative, Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash)]
+pub enum TyUserVariance {
+    /// `Box[Apple]` can be used where `Box[Fruit]` is expected.
+    Covariant,
+    /// `Sink[Fruit]` can be used where `Sink[Apple]` is expected.
+    Contravariant,
+    /// `Cell[Apple]` can only be used where `Cell[Apple]` is expected.
+    Invariant,
+}
+
+/// Type parameter of a generic [`TyUser`].
+#[derive(Allocative, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TyUserTypeParam {
+    /// Name of the parameter, like `T`.
+    pub name: String,
+    pub variance: TyUserVariance,
+}
+
+impl TyUserTypeParam {
+    /// The type standing for this parameter in the field, index, iter item, tuple shape and
+    /// callable types of a generic type. Instantiations replace it with their type argument.
+    pub fn ty(&self) -> Ty {
+        Ty::name(&self.name)
+    }
+}
+
+/// Replace the type parameters `params` in `ty` with the corresponding `args`.
+///
+/// Types nested in function signatures are left as they are.
+fn substitute_type_params(ty: &Ty, params: &[TyUserTypeParam], args: &[Ty]) -> Ty {
+    let substitute = |ty: &Ty| substitute_type_params(ty, params, args);
+    Ty::unions(
+        ty.iter_union()
+            .iter()
+            .map(|basic| match basic {
+                TyBasic::Name(name) => match params.iter().position(|p| name == p.name.as_str()) {
+                    Some(i) => args[i].dupe(),
+                    None => Ty::basic(basic.dupe()),
+                },
+                TyBasic::Iter(item) => Ty::iter(substitute(&**item)),
+                TyBasic::List(item) => Ty::list(substitute(&**item)),
+                TyBasic::Dict(k, v) => Ty::dict(substitute(&**k), substitute(&**v)),
+                TyBasic::Tuple(TyTuple::Elems(elems)) => {
+                    Ty::tuple(elems.iter().map(substitute).collect())
+                }
+                TyBasic::Tuple(TyTuple::Of(item)) => {
+                    Ty::basic(TyBasic::Tuple(TyTuple::Of(ArcTy::new(substitute(&**item)))))
+                }
+                TyBasic::Custom(custom) => match custom.0.as_any().downcast_ref::<TyUser>() {
+                    // Instantiations like `Box[T]` in the signature of the constructor of `Box`.
+                    Some(user) if !user.type_args.is_empty() => Ty::custom(TyUser {
+                        type_args: user.type_args.iter().map(substitute).collect(),
+                        ..user.clone()
+                    }),
+                    _ => Ty::basic(basic.dupe()),
+                },
+                _ => Ty::basic(basic.dupe()),
+            })
+            .collect(),
+    )
+}
+
+/// What `==` means for values of a [`TyUser`], which decides whether the typechecker can narrow
+/// a variable compared with `==` to a value of the type.
+#[derive(Allocative, Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash, Default)]
+pub enum TyUserEquality {
+    /// Values are compared by content, and may be equal to values of other types,
+    /// so `x == v` tells nothing about the type of `x`.
+    #[default]
+    Structural,
+    /// Values are only equal to themselves, like the variants of an enum, so after
+    /// `x == v`, `x` has the type of `v`.
+    Identity,
+}
+
+/// How the custom matcher of a [`TyUser`] composes with the matcher of its base type.
+#[derive(Allocative, Debug, Clone, Copy, Dupe, Eq, PartialEq, Hash, Default)]
+pub enum MatcherCompose {
+    /// The custom matcher replaces the matcher of the base type.
+    #[default]
+    Replace,
+    /// The matcher of the base type runs first, and the custom matcher only on the values it
+    /// accepts. Useful when the base type cheaply rejects most values and the custom matcher
+    /// only checks the fields.
+    BaseThenCustom,
+}
+
+/// Parameters for [`TyUser`] creation.
+pub struct TyUserParams {
+    /// Other names this type is exported under, e.g. by re-exports from other modules, see
+    /// `TyUserAsType::set_with_aliases`.
+    /// Diagnostics always use the canonical name given to [`TyUser::new`], whichever name
+    /// was referenced, and aliases do not affect the identity of the type.
+    pub aliases: Vec<String>,
+    /// Module this type is defined in, like `//foo:rules.bzl`, to tell apart types with the
+    /// same name in diagnostics, see [`TyUser::qualified_name`]. Like aliases, it does not
+    /// affect the identity of the type.
+    pub module: Option<String>,
+    /// Super types for this type (`base` is included in this list implicitly).
+    pub supertypes: Vec<TyBasic>,
+    /// Ids of the interface types this type implements. Values of this type are accepted
+    /// where any of these interface types is expected.
+    pub interfaces: Vec<TypeInstanceId>,
+    /// Custom matcher for this type. If `None`, the matcher of `base` is used.
+    pub matcher: Option<TypeMatcherFactory>,
+    /// How `matcher` composes with the matcher of `base`.
+    pub matcher_compose: MatcherCompose,
+    /// Appended to the runtime error when a value does not match this type,
+    /// e.g. "expected a record with a name".
+    pub match_error_message: Option<String>,
+    /// Marks the whole type as deprecated. Using it in a type annotation produces a typechecker
+    /// warning with this message.
+    pub deprecated: Option<String>,
+    /// Fields of this type.
+    pub fields: TyUserFields,
+    /// Consulted for attributes which are not known fields, before falling back to
+    /// [`TyUserFields::unknown`].
+    pub attribute_resolver: Option<TyUserAttributeResolver>,
+    /// Type wrapped by this type, whose attributes are attributes of this type too.
+    /// Consulted for attributes which are not known fields, before `attribute_resolver`.
+    pub decorates: Option<Ty>,
+    /// Set if more precise callable signature is known than `base` provides.
+    pub callable: Option<TyFunction>,
+    /// Set for generic types like `LazyValue`, whose instantiation with a type argument,
+    /// like `LazyValue[int]`, is callable with the signature produced for that argument.
+    pub type_arg_callable: Option<TyUserTypeArgCallable>,
+    /// Type parameters of a generic type. Instantiations are created with
+    /// [`TyUser::instantiate`].
+    pub type_params: Vec<TyUserTypeParam>,
+    /// Set if more precise index signature is known than `base` provides.
+    pub index: Option<TyUserIndex>,
+    /// Set if more precise iter item is known than `base` provides.
+    pub iter_item: Option<Ty>,
+    /// Set if values of this type are tuple-like with this fixed shape.
+    /// Used to type destructuring assignments like `a, b = x`.
+    pub tuple_shape: Option<Vec<Ty>>,
+    /// Whether values of this type can be used as dict keys. `None` means not checked.
+    pub hashable: Option<bool>,
+    /// Whether values of this type can be compared with `<`. `None` means `base` decides.
+    pub comparable: Option<bool>,
+    /// Whether values of this type can be passed to `str()` or string interpolation.
+    /// `None` means they can.
+    pub stringable: Option<bool>,
+    /// Branded types don't intersect with `base`, so they are not interchangeable with it
+    /// or with other types branded over the same `base`.
+    pub brand: bool,
+    /// Set for record types, whose `callable` is the constructor and must take exactly
+    /// one parameter per known field.
+    pub record: bool,
+    /// Set for sequence-like types, whose `int` keyed `index` must produce `iter_item`.
+    pub sequence: bool,
+    /// Opaque types hide everything about their values from the typechecker, including the
+    /// methods of `base`: attribute access, indexing, iteration and calls are all type errors.
+    pub opaque: bool,
+    /// Equality semantics of values of this type.
+    pub equality: TyUserEquality,
+    /// Methods of `base` which return the value they are called on, like the methods of a
+    /// builder. They are typed as returning this type, whatever `base` declares they return.
+    pub self_returning_methods: Vec<String>,
+}
+
+impl Default for TyUserParams {
+    fn default() -> Self {
+        TyUserParams {
+            aliases: Vec::new(),
+            module: None,
+            supertypes: Vec::new(),
+            interfaces: Vec::new(),
+            matcher: None,
+            matcher_compose: MatcherCompose::default(),
+            match_error_message: None,
+            deprecated: None,
+            fields: TyUserFields::no_fields(),
+            attribute_resolver: None,
+            decorates: None,
+            callable: None,
+            type_arg_callable: None,
+            type_params: Vec::new(),
+            index: None,
+            iter_item: None,
+            tuple_shape: None,
+            hashable: None,
+            comparable: None,
+            stringable: None,
+            brand: false,
+            record: false,
+            sequence: false,
+            opaque: false,
+            equality: TyUserEquality::Structural,
+            self_returning_methods: Vec::new(),
         }
     }
 }
 
 /// Type description for arbitrary type.
-#[derive(Allocative, Debug, derive_more::Display)]
-#[display(fmt = "{}", name)]
+#[derive(Allocative, Debug, Clone)]
 pub struct TyUser {
     name: String,
+    /// Other names this type is exported under.
+    aliases: Vec<String>,
+    /// Module this type is defined in.
+    module: Option<String>,
     /// Base type for this custom type, e.g. generic record for record with known fields.
     base: TyStarlarkValue,
     /// Super types for this type (`base` is included in this list implicitly).
     supertypes: Vec<TyBasic>,
+    /// Ids of the interface types this type implements.
+    interfaces: Vec<TypeInstanceId>,
     matcher: Option<TypeMatcherFactory>,
+    /// How `matcher` composes with the matcher of `base`.
+    matcher_compose: MatcherCompose,
+    /// Appended to the runtime error when a value does not match this type.
+    match_error_message: Option<String>,
+    /// If set, this type is deprecated with this message.
+    deprecated: Option<String>,
     id: TypeInstanceId,
     fields: TyUserFields,
+    /// Resolves attributes which are not known fields.
+    attribute_resolver: Option<TyUserAttributeResolver>,
+    /// Type whose attributes are attributes of this type too.
+    decorates: Option<Ty>,
     /// Set if more precise callable signature is known than `base` provides.
     callable: Option<TyFunction>,
+    /// Callable signature of instantiations of this type, given the type argument.
+    type_arg_callable: Option<TyUserTypeArgCallable>,
+    /// Type parameters, if this type is generic.
+    type_params: Vec<TyUserTypeParam>,
+    /// Type arguments, one per type parameter, if this type is an instantiation.
+    type_args: Vec<Ty>,
     /// Set if more precise index signature is known than `base` provides.
     index: Option<TyUserIndex>,
     /// Set if more precise iter item is known than `base` provides.
     iter_item: Option<Ty>,
+    /// Set if values of this type are tuple-like with this fixed shape.
+    tuple_shape: Option<Vec<Ty>>,
+    /// Set if it is known whether values of this type are hashable.
+    hashable: Option<bool>,
+    /// Set if it is known whether values of this type are comparable.
+    comparable: Option<bool>,
+    /// Set if it is known whether values of this type can be converted to string.
+    stringable: Option<bool>,
+    /// If set, this type does not intersect with `base`.
+    brand: bool,
+    /// If set, nothing about values of this type is visible to the typechecker.
+    opaque: bool,
+    /// Equality semantics of values of this type.
+    equality: TyUserEquality,
+    /// Methods of `base` which return the value they are called on.
+    self_returning_methods: Vec<String>,
 }
 
 impl TyUser {
@@ -116,20 +759,53 @@ impl TyUser {
     pub fn new(
         name: String,
         base: TyStarlarkValue,
-        supertypes: Vec<TyBasic>,
-        matcher: Option<TypeMatcherFactory>,
         id: TypeInstanceId,
-        fields: TyUserFields,
-        callable: Option<TyFunction>,
-        index: Option<TyUserIndex>,
-        iter_item: Option<Ty>,
+        params: TyUserParams,
     ) -> anyhow::Result<TyUser> {
+        let TyUserParams {
+            aliases,
+            module,
+            supertypes,
+            interfaces,
+            matcher,
+            matcher_compose,
+            match_error_message,
+            deprecated,
+            fields,
+            attribute_resolver,
+            decorates,
+            callable,
+            type_arg_callable,
+            type_params,
+            index,
+            iter_item,
+            tuple_shape,
+            hashable,
+            comparable,
+            stringable,
+            brand,
+            record,
+            sequence,
+            opaque,
+            equality,
+            self_returning_methods,
+        } = params;
         if callable.is_some() {
             if !base.is_callable() {
                 return Err(TyUserError::CallableNotCallable(name).into());
             }
         }
-        if index.is_some() {
+        if let Some(callable) = &callable {
+            if record && callable.params.len() != fields.known.len() {
+                return Err(TyUserError::ConstructorArityMismatch(
+                    name,
+                    fields.known.len(),
+                    callable.params.len(),
+                )
+                .into());
+            }
+        }
+        if index.is_some() || type_arg_callable.is_some() {
             if !base.is_indexable() {
                 return Err(TyUserError::IndexableNotIndexable(name).into());
             }
@@ -139,23 +815,399 @@ impl TyUser {
                 return Err(TyUserError::IterableNotIterable(name).into());
             }
         }
+        if let (true, Some(index), Some(iter_item)) = (sequence, &index, &iter_item) {
+            if index.index == Ty::int() && index.result != *iter_item {
+                return Err(TyUserError::SequenceIndexIterItemMismatch(
+                    name,
+                    index.result.dupe(),
+                    iter_item.dupe(),
+                )
+                .into());
+            }
+        }
+        if tuple_shape.is_some() {
+            if base.iter_item().is_err() {
+                return Err(TyUserError::TupleShapeNotIterable(name).into());
+            }
+        }
+        if hashable == Some(true) {
+            if !base.is_hashable() {
+                return Err(TyUserError::HashableNotHashable(name).into());
+            }
+        }
+        if comparable == Some(true) {
+            if !base.is_comparable() {
+                return Err(TyUserError::ComparableNotComparable(name).into());
+            }
+        }
+        if stringable == Some(false) {
+            if base.is_str() {
+                return Err(TyUserError::StringNotStringable(name).into());
+            }
+        }
+        for field in fields.exclusive.iter().flatten() {
+            if !matches!(fields.known.get(field), Some(f) if !f.required) {
+                return Err(TyUserError::ExclusiveFieldNotOptional(name, field.clone()).into());
+            }
+        }
+        for (field, TyUserSameAs(same_as)) in fields.same_as.iter() {
+            if fields.known.contains_key(field) || fields.lazy.contains_key(field) {
+                return Err(TyUserError::SameAsShadowsField(name, field.clone()).into());
+            }
+            if !fields.known.contains_key(same_as) && !fields.lazy.contains_key(same_as) {
+                return Err(
+                    TyUserError::SameAsNotKnown(name, field.clone(), same_as.clone()).into(),
+                );
+            }
+        }
+        if let Some(discriminant) = &fields.discriminant {
+            for field in
+                std::iter::once(&discriminant.field).chain(discriminant.variants.values().flatten())
+            {
+                if !fields.known.contains_key(field) {
+                    return Err(
+                        TyUserError::DiscriminantFieldNotKnown(name, field.clone()).into(),
+                    );
+                }
+            }
+        }
+        for method in &self_returning_methods {
+            if !matches!(base.attr_from_methods(method), Ok(ty) if ty.as_function().is_some()) {
+                return Err(TyUserError::SelfReturningNotMethod(name, method.clone()).into());
+            }
+        }
         Ok(TyUser {
             name,
+            aliases,
+            module,
             base,
             supertypes,
+            interfaces,
             matcher,
+            matcher_compose,
+            match_error_message,
+            deprecated,
             id,
             fields,
+            attribute_resolver,
+            decorates,
             callable,
+            type_arg_callable,
+            type_params,
+            type_args: Vec::new(),
             index,
             iter_item,
+            tuple_shape,
+            hashable,
+            comparable,
+            stringable,
+            brand,
+            opaque,
+            equality,
+            self_returning_methods,
+        })
+    }
+
+    /// This type and all its supertypes, including `base`.
+    fn supertypes_with_base(&self) -> impl Iterator<Item = TyBasic> + '_ {
+        std::iter::once(TyBasic::StarlarkValue(self.base))
+            .chain(self.supertypes.iter().map(|t| t.dupe()))
+    }
+
+    /// Find a supertype shared by both types.
+    ///
+    /// This is used to produce diagnostics like
+    /// "`Fruit` and `Vegetable` are both `Plant` but are not interchangeable".
+    /// Declared `supertypes` are preferred, `base` is only considered when the types share
+    /// no declared supertype, since any two types over the same `base` trivially share it.
+    /// If there are several candidates, the smallest one (in `TyBasic` order) is returned.
+    pub fn common_supertype(a: &TyUser, b: &TyUser) -> Option<TyBasic> {
+        fn smallest_shared(
+            a: impl Iterator<Item = TyBasic>,
+            b: impl Iterator<Item = TyBasic>,
+        ) -> Option<TyBasic> {
+            let a: BTreeSet<TyBasic> = a.collect();
+            let b: BTreeSet<TyBasic> = b.collect();
+            a.intersection(&b).next().map(|t| t.dupe())
+        }
+
+        smallest_shared(
+            a.supertypes.iter().map(|t| t.dupe()),
+            b.supertypes.iter().map(|t| t.dupe()),
+        )
+        .or_else(|| smallest_shared(a.supertypes_with_base(), b.supertypes_with_base()))
+    }
+
+    /// Declare this type a subtype of the primitive type `ty` (like `int` or `str`),
+    /// so it can be used wherever `ty` is expected.
+    ///
+    /// `ty` must be a single primitive type, e.g. `Ty::string()`, not a union or a container.
+    pub fn with_primitive_supertype(mut self, ty: Ty) -> anyhow::Result<TyUser> {
+        match ty.iter_union() {
+            [basic @ TyBasic::StarlarkValue(_)] => {
+                if !self.supertypes.contains(basic) {
+                    self.supertypes.push(basic.dupe());
+                }
+                Ok(self)
+            }
+            _ => Err(TyUserError::NotPrimitiveSupertype(self.name, ty.to_string()).into()),
+        }
+    }
+
+    /// Canonical name of this type, used in diagnostics.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Other names this type is exported under, see [`TyUserParams::aliases`].
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Module this type is defined in, see [`TyUserParams::module`].
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    /// Name of this type prefixed with its module if known, like `//foo:rules.bzl:Info`.
+    /// `Display` does not include the module.
+    pub fn qualified_name(&self) -> String {
+        match &self.module {
+            Some(module) => format!("{}:{}", module, self),
+            None => self.to_string(),
+        }
+    }
+
+    /// Whether `name` is the canonical name of this type or one of its aliases.
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    /// Id of this type, which other types list in [`TyUserParams::interfaces`] to implement it.
+    pub fn id(&self) -> TypeInstanceId {
+        self.id
+    }
+
+    /// Instantiate this generic type with one type argument per type parameter, e.g. `Box[Apple]`.
+    /// Instantiations share the id of this type, and intersect when their type arguments are
+    /// related according to the variance of the type parameters. Their field, index, iter item,
+    /// tuple shape and callable types have the [type parameters](TyUserTypeParam::ty) replaced
+    /// with the type arguments.
+    pub fn instantiate(&self, type_args: Vec<Ty>) -> anyhow::Result<TyUser> {
+        if type_args.len() != self.type_params.len() {
+            return Err(TyUserError::TypeArgCountMismatch(
+                self.name.clone(),
+                self.type_params.len(),
+                type_args.len(),
+            )
+            .into());
+        }
+        Ok(TyUser {
+            type_args,
+            ..self.clone()
+        })
+    }
+
+    /// `ty` with the type parameters replaced with the type arguments of this instantiation,
+    /// or with `Any` when this is a generic type used without type arguments, like `b: Box`.
+    fn substitute_type_args(&self, ty: Ty) -> Ty {
+        if self.type_params.is_empty() {
+            ty
+        } else if self.type_args.is_empty() {
+            let any = vec![Ty::any(); self.type_params.len()];
+            substitute_type_params(&ty, &self.type_params, &any)
+        } else {
+            substitute_type_params(&ty, &self.type_params, &self.type_args)
+        }
+    }
+
+    /// The callable signature of this instantiation. When this is a generic type called without
+    /// type arguments, like `Box(item = 1)`, the type arguments are inferred from the types of
+    /// the arguments passed to parameters typed as a type parameter, or are `Any`.
+    fn instantiated_callable(&self, callable: &TyFunction, args: &[Spanned<Arg>]) -> TyFunction {
+        let type_args = if !self.type_args.is_empty() {
+            self.type_args.clone()
+        } else if !self.type_params.is_empty() {
+            self.infer_type_args(callable, args)
+        } else {
+            return callable.clone();
+        };
+        let substitute = |ty: &Ty| substitute_type_params(ty, &self.type_params, &type_args);
+        TyFunction {
+            type_attr: callable.type_attr.clone(),
+            ..TyFunction::new(
+                callable
+                    .params
+                    .iter()
+                    .map(|param| Param {
+                        ty: substitute(&param.ty),
+                        ..param.clone()
+                    })
+                    .collect(),
+                substitute(&callable.result),
+            )
+        }
+    }
+
+    /// Type arguments of a call of `callable` with `args`, see `instantiated_callable`.
+    fn infer_type_args(&self, callable: &TyFunction, args: &[Spanned<Arg>]) -> Vec<Ty> {
+        let mut inferred: Vec<Option<Ty>> = vec![None; self.type_params.len()];
+        let mut pos = callable
+            .params
+            .iter()
+            .filter(|p| matches!(p.mode, ParamMode::PosOnly | ParamMode::PosOrName(_)));
+        for arg in args {
+            let (param, ty) = match &arg.node {
+                Arg::Pos(ty) => (pos.next(), ty),
+                Arg::Name(name, ty) => (
+                    callable.params.iter().find(|p| match &p.mode {
+                        ParamMode::PosOrName(n) | ParamMode::NameOnly(n) => n.as_str() == *name,
+                        _ => false,
+                    }),
+                    ty,
+                ),
+                Arg::Args(_) | Arg::Kwargs(_) => continue,
+            };
+            if let Some(param) = param {
+                if let Some(i) = self.type_params.iter().position(|p| p.ty() == param.ty) {
+                    inferred[i] = Some(match inferred[i].take() {
+                        Some(prev) => Ty::union2(prev, ty.dupe()),
+                        None => ty.dupe(),
+                    });
+                }
+            }
+        }
+        inferred
+            .into_iter()
+            .map(|ty| ty.unwrap_or_else(Ty::any))
+            .collect()
+    }
+
+    /// Whether a value of the instantiation `x` can be used where the instantiation `y` of the
+    /// same generic type is required.
+    fn type_args_intersect(x: &TyUser, y: &TyUser, ctx: &TypingOracleCtx) -> bool {
+        if x.type_args.is_empty() || y.type_args.is_empty() {
+            // The generic type itself stands for any of its instantiations.
+            return true;
+        }
+        x.type_params
+            .iter()
+            .zip(x.type_args.iter().zip(y.type_args.iter()))
+            .all(|(param, (x, y))| match param.variance {
+                TyUserVariance::Covariant => ctx.is_subtype(x, y),
+                TyUserVariance::Contravariant => ctx.is_subtype(y, x),
+                TyUserVariance::Invariant => x == y || x.is_any() || y.is_any(),
+            })
+    }
+
+    /// Attribute `attr` of the type decorated by this type.
+    fn decorated_attribute(decorates: &Ty, attr: &str) -> Result<Ty, ()> {
+        decorates.typecheck_union_simple(|basic| match basic {
+            TyBasic::StarlarkValue(s) => s.attr(attr),
+            TyBasic::Custom(custom) => custom.0.attribute_dyn(attr),
+            _ => Err(()),
         })
     }
+
+    /// Whether this type declares it implements the interface `other`.
+    fn implements(&self, other: &TyUser) -> bool {
+        self.interfaces.contains(&other.id)
+    }
+
+    /// Custom matcher for this type, if any.
+    ///
+    /// Can be cloned into `TyUserParams` of related types to share matcher construction.
+    pub fn matcher_factory(&self) -> Option<&TypeMatcherFactory> {
+        self.matcher.as_ref()
+    }
+
+    /// Key for caching typechecking results which involve this type.
+    ///
+    /// `Eq` and `Hash` of `TyUser` are based on its id, so equal types created separately are
+    /// distinct. The cache key instead includes everything the typechecker observes about this
+    /// type, like its supertypes and callable signature, and not its id. The attribute resolver
+    /// and the type argument callable are compared by function pointer. The matcher is not
+    /// included, as it only affects runtime checks.
+    pub fn cache_key(&self) -> TyUserCacheKey {
+        TyUserCacheKey {
+            name: self.name.clone(),
+            aliases: self.aliases.clone(),
+            module: self.module.clone(),
+            base: self.base,
+            supertypes: self.supertypes.clone(),
+            interfaces: self.interfaces.clone(),
+            fields: self.fields.clone(),
+            attribute_resolver: self.attribute_resolver.map(|resolver| resolver.0 as usize),
+            decorates: self.decorates.clone(),
+            callable: self.callable.clone(),
+            type_arg_callable: self.type_arg_callable.map(|callable| callable.0 as usize),
+            type_params: self.type_params.clone(),
+            type_args: self.type_args.clone(),
+            index: self
+                .index
+                .as_ref()
+                .map(|index| (index.index.dupe(), index.result.dupe())),
+            iter_item: self.iter_item.clone(),
+            tuple_shape: self.tuple_shape.clone(),
+            hashable: self.hashable,
+            comparable: self.comparable,
+            stringable: self.stringable,
+            brand: self.brand,
+            opaque: self.opaque,
+            equality: self.equality,
+            self_returning_methods: self.self_returning_methods.clone(),
+        }
+    }
+}
+
+/// Structural description of a [`TyUser`], see [`TyUser::cache_key`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TyUserCacheKey {
+    name: String,
+    aliases: Vec<String>,
+    module: Option<String>,
+    base: TyStarlarkValue,
+    supertypes: Vec<TyBasic>,
+    interfaces: Vec<TypeInstanceId>,
+    fields: TyUserFields,
+    attribute_resolver: Option<usize>,
+    decorates: Option<Ty>,
+    callable: Option<TyFunction>,
+    type_arg_callable: Option<usize>,
+    type_params: Vec<TyUserTypeParam>,
+    type_args: Vec<Ty>,
+    index: Option<(Ty, Ty)>,
+    iter_item: Option<Ty>,
+    tuple_shape: Option<Vec<Ty>>,
+    hashable: Option<bool>,
+    comparable: Option<bool>,
+    stringable: Option<bool>,
+    brand: bool,
+    opaque: bool,
+    equality: TyUserEquality,
+    self_returning_methods: Vec<String>,
+}
+
+impl fmt::Display for TyUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.type_args.is_empty() {
+            write!(f, "[")?;
+            for (i, arg) in self.type_args.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", arg)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
 }
 
 impl PartialEq for TyUser {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.id == other.id && self.type_args == other.type_args
     }
 }
 
@@ -169,7 +1221,12 @@ impl PartialOrd for TyUser {
 
 impl Ord for TyUser {
     fn cmp(&self, other: &Self) -> Ordering {
-        (&self.name, &self.fields, self.id).cmp(&(&other.name, &self.fields, other.id))
+        (&self.name, &self.fields, self.id, &self.type_args).cmp(&(
+            &other.name,
+            &other.fields,
+            other.id,
+            &other.type_args,
+        ))
     }
 }
 
@@ -177,6 +1234,7 @@ impl Hash for TyUser {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.name.hash(state);
         self.fields.hash(state);
+        self.type_args.hash(state);
     }
 }
 
@@ -186,13 +1244,40 @@ impl TyCustomImpl for TyUser {
     }
 
     fn attribute(&self, attr: &str) -> Result<Ty, ()> {
+        if self.opaque {
+            return Err(());
+        }
+        // Types of methods never include `this`, so they are already the types of bound methods.
         if let Ok(ty) = self.base.attr_from_methods(attr) {
-            Ok(ty)
+            match ty.as_function() {
+                Some(f) if self.self_returning_methods.iter().any(|m| m == attr) => {
+                    Ok(Ty::ty_function(TyFunction {
+                        result: Ty::custom(self.clone()),
+                        ..f.clone()
+                    }))
+                }
+                _ => Ok(ty),
+            }
         } else {
-            match self.fields.known.get(attr) {
-                Some(ty) => Ok(ty.dupe()),
+            match self.fields.get(attr) {
+                // Variant fields are only available after checking the discriminant.
+                Some(_) if self.fields.is_variant_field(attr) => Err(()),
+                Some(field) => Ok(self.substitute_type_args(field.attribute_ty())),
                 None => {
-                    if self.fields.unknown {
+                    if let Some(ty) = self
+                        .decorates
+                        .as_ref()
+                        .and_then(|decorates| TyUser::decorated_attribute(decorates, attr).ok())
+                    {
+                        Ok(ty)
+                    } else if let Some(ty) = self
+                        .attribute_resolver
+                        .and_then(|resolver| (resolver.0)(attr))
+                    {
+                        Ok(ty)
+                    } else if let Some(fallback) = &self.fields.fallback {
+                        Ok(fallback.dupe())
+                    } else if self.fields.unknown {
                         Ok(Ty::any())
                     } else {
                         Err(())
@@ -202,56 +1287,180 @@ impl TyCustomImpl for TyUser {
         }
     }
 
+    fn present_attribute(
+        &self,
+        attr: &str,
+        present: &[String],
+        discriminants: &[(String, String)],
+    ) -> Result<Ty, ()> {
+        match self.fields.get(attr) {
+            Some(field) if !self.opaque && self.base.attr_from_methods(attr).is_err() => {
+                if self.fields.is_variant_field(attr)
+                    && !self.fields.is_variant_field_available(attr, discriminants)
+                {
+                    Err(())
+                } else if present
+                    .iter()
+                    .any(|p| self.fields.resolve_same_as(p) == self.fields.resolve_same_as(attr))
+                {
+                    // Optional field is not `None` when it is present, or its same-as field is.
+                    Ok(self.substitute_type_args(field.ty.dupe()))
+                } else if present.iter().any(|p| self.fields.are_exclusive(p, attr)) {
+                    // Another field of its exclusive group is present, so this one is absent.
+                    Ok(Ty::none())
+                } else {
+                    Ok(self.substitute_type_args(field.attribute_ty()))
+                }
+            }
+            _ => self.attribute(attr),
+        }
+    }
+
     fn index(&self, item: &TyBasic, ctx: &TypingOracleCtx) -> Result<Ty, ()> {
+        if self.opaque {
+            return Err(());
+        }
+        if let Some(type_arg_callable) = self.type_arg_callable {
+            if let Some(type_arg) = item.as_type() {
+                // Instantiation like `LazyValue[int]`: callable with the signature for `int`.
+                return Ok(Ty::ty_function((type_arg_callable.0)(&type_arg)));
+            }
+        }
+        if !self.type_params.is_empty() && self.type_args.is_empty() {
+            // Instantiation like `Box[int]` or `Pair[int, str]` of a generic constructor.
+            let type_args = match item {
+                TyBasic::Tuple(TyTuple::Elems(elems)) if self.type_params.len() != 1 => elems
+                    .iter()
+                    .map(|elem| match elem.iter_union() {
+                        [elem] => elem.as_type(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>(),
+                _ => item.as_type().map(|type_arg| vec![type_arg]),
+            };
+            if let Some(type_args) = type_args {
+                return self.instantiate(type_args).map(Ty::custom).map_err(|_| ());
+            }
+        }
         if let Some(index) = &self.index {
-            if !ctx.intersects(&Ty::basic(item.dupe()), &index.index) {
+            let index_ty = self.substitute_type_args(index.index.dupe());
+            if !ctx.intersects(&Ty::basic(item.dupe()), &index_ty) {
                 return Err(());
             }
-            Ok(index.result.dupe())
+            Ok(self.substitute_type_args(index.result.dupe()))
         } else {
             self.base.index(item)
         }
     }
 
     fn iter_item(&self) -> Result<Ty, ()> {
+        if self.opaque {
+            return Err(());
+        }
         if let Some(iter_item) = &self.iter_item {
-            Ok(iter_item.dupe())
+            Ok(self.substitute_type_args(iter_item.dupe()))
         } else {
             self.base.iter_item()
         }
     }
 
-    fn is_callable(&self) -> bool {
-        self.base.is_callable()
-    }
-
-    fn validate_call(
-        &self,
-        span: Span,
-        args: &[Spanned<Arg>],
-        oracle: TypingOracleCtx,
-    ) -> Result<Ty, TypingOrInternalError> {
-        if let Some(callable) = &self.callable {
-            callable.validate_call(span, args, oracle)
-        } else {
-            Ok(self.base.validate_call(span, oracle)?)
+    fn indexed(&self, i: usize) -> Ty {
+        match &self.tuple_shape {
+            Some(shape) => match shape.get(i) {
+                Some(ty) => self.substitute_type_args(ty.dupe()),
+                None => Ty::never(),
+            },
+            None => Ty::any(),
+        }
+    }
+
+    fn is_callable(&self) -> bool {
+        !self.opaque && self.base.is_callable()
+    }
+
+    fn is_hashable(&self) -> bool {
+        self.hashable.unwrap_or(true)
+    }
+
+    fn is_stringable(&self) -> bool {
+        self.stringable.unwrap_or(true)
+    }
+
+    fn is_identity_eq(&self) -> bool {
+        self.equality == TyUserEquality::Identity
+    }
+
+    fn match_error_message(&self) -> Option<&str> {
+        self.match_error_message.as_deref()
+    }
+
+    fn deprecated(&self) -> Option<&str> {
+        self.deprecated.as_deref()
+    }
+
+    fn bin_op(&self, bin_op: TypingBinOp, rhs: &TyBasic, _ctx: &TypingOracleCtx) -> Result<Ty, ()> {
+        match (bin_op, self.comparable) {
+            (TypingBinOp::Less, Some(false)) => Err(()),
+            (TypingBinOp::Less, Some(true)) => {
+                let same_type = match rhs {
+                    TyBasic::Any => true,
+                    TyBasic::Custom(rhs) => rhs.0.as_any().downcast_ref::<TyUser>() == Some(self),
+                    rhs => self.intersects_with(rhs),
+                };
+                if same_type { Ok(Ty::bool()) } else { Err(()) }
+            }
+            _ => self.base.bin_op(bin_op, rhs),
+        }
+    }
+
+    fn validate_call(
+        &self,
+        span: Span,
+        args: &[Spanned<Arg>],
+        oracle: TypingOracleCtx,
+    ) -> Result<Ty, TypingOrInternalError> {
+        if self.opaque {
+            return Err(oracle.mk_error_as_maybe_internal(
+                span,
+                TyUserError::OpaqueNotCallable(self.name.clone()),
+            ));
+        }
+        if let Some(callable) = &self.callable {
+            self.instantiated_callable(callable, args)
+                .validate_call(span, args, oracle)
+        } else {
+            Ok(self.base.validate_call(span, oracle)?)
         }
     }
 
     fn matcher<T: TypeMatcherAlloc>(&self, factory: T) -> T::Result {
-        match &self.matcher {
-            Some(matcher) => factory.from_type_matcher_factory(matcher),
-            None => self.base.matcher(factory),
+        match (&self.matcher, self.matcher_compose) {
+            (Some(matcher), MatcherCompose::BaseThenCustom) => factory.all_of_two_matcher(
+                self.base.matcher(TypeMatcherBoxAlloc),
+                TypeMatcherBoxAlloc.from_type_matcher_factory(matcher),
+            ),
+            (Some(matcher), MatcherCompose::Replace) => factory.from_type_matcher_factory(matcher),
+            (None, _) => self.base.matcher(factory),
+        }
+    }
+
+    fn intersects(x: &Self, y: &Self, ctx: &TypingOracleCtx) -> bool {
+        if x.id == y.id {
+            return TyUser::type_args_intersect(x, y, ctx);
         }
+        x.implements(y) || y.implements(x)
     }
 
-    fn intersects(x: &Self, y: &Self) -> bool {
-        x == y
+    fn is_subtype(x: &Self, y: &Self, ctx: &TypingOracleCtx) -> bool {
+        if x.id == y.id {
+            return TyUser::type_args_intersect(x, y, ctx);
+        }
+        x.implements(y)
     }
 
     fn intersects_with(&self, other: &TyBasic) -> bool {
         if let TyBasic::StarlarkValue(other) = other {
-            if self.base == *other {
+            if !self.brand && self.base == *other {
                 return true;
             }
         }
@@ -261,29 +1470,67 @@ impl TyCustomImpl for TyUser {
 
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
     use allocative::Allocative;
     use dupe::Dupe;
+    use once_cell::sync::OnceCell;
     use starlark_derive::starlark_module;
     use starlark_derive::starlark_value;
     use starlark_derive::NoSerialize;
     use starlark_derive::ProvidesStaticType;
+    use starlark_map::sorted_map::SortedMap;
 
     use crate as starlark;
     use crate::assert::Assert;
+    use crate::codemap::CodeMap;
     use crate::environment::GlobalsBuilder;
+    use crate::environment::Methods;
+    use crate::environment::MethodsBuilder;
+    use crate::environment::MethodsStatic;
     use crate::eval::Arguments;
     use crate::eval::Evaluator;
+    use crate::syntax::AstModule;
+    use crate::syntax::Dialect;
+    use crate::typing::custom::TyCustomImpl;
+    use crate::typing::AstModuleTypecheck;
+    use crate::typing::MatcherCompose;
+    use crate::typing::Param;
     use crate::typing::Ty;
+    use crate::typing::TyBasic;
     use crate::typing::TyFunction;
     use crate::typing::TyStarlarkValue;
     use crate::typing::TyUser;
+    use crate::typing::TyUserAttributeResolver;
+    use crate::typing::TyUserDiscriminant;
+    use crate::typing::TyUserEquality;
+    use crate::typing::TyUserField;
     use crate::typing::TyUserFields;
+    use crate::typing::TyUserIndex;
+    use crate::typing::TyUserLazyField;
+    use crate::typing::TyUserParams;
+    use crate::typing::TyUserSameAs;
+    use crate::typing::TyUserTypeArgCallable;
+    use crate::typing::TyUserTypeParam;
+    use crate::typing::TyUserVariance;
+    use crate::typing::TypingOracleCtx;
+    use crate::values::list::value::FrozenList;
     use crate::values::starlark_value_as_type::StarlarkValueAsType;
+    use crate::values::typing::type_compiled::matchers::IsAny;
+    use crate::values::typing::type_compiled::matchers::IsNever;
+    use crate::values::typing::TypeCompiled;
     use crate::values::typing::TypeInstanceId;
+    use crate::values::typing::TypeMatcherFactory;
+    use crate::values::AllocFrozenValue;
     use crate::values::AllocValue;
+    use crate::values::FrozenHeap;
+    use crate::values::FrozenValue;
     use crate::values::Heap;
     use crate::values::StarlarkValue;
     use crate::values::Value;
+    use crate::values::ValueError;
 
     #[derive(
         Debug,
@@ -323,6 +1570,12 @@ mod tests {
         }
     }
 
+    impl AllocFrozenValue for FruitCallable {
+        fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+            heap.alloc_simple(self)
+        }
+    }
+
     #[starlark_value(type = "fruit_callable")]
     impl<'v> StarlarkValue<'v> for FruitCallable {
         fn get_type_starlark_repr() -> Ty {
@@ -369,39 +1622,251 @@ mod tests {
         fn get_type_starlark_repr() -> Ty {
             Ty::starlark_value::<Fruit>()
         }
+
+        fn get_methods() -> Option<&'static Methods> {
+            static RES: MethodsStatic = MethodsStatic::new();
+            RES.methods(fruit_methods)
+        }
+    }
+
+    #[starlark_module]
+    fn fruit_methods(builder: &mut MethodsBuilder) {
+        fn ripen(this: Value, days: i32) -> anyhow::Result<bool> {
+            let _unused = (this, days);
+            unreachable!("not needed in test")
+        }
+
+        fn peel<'v>(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+            Ok(this)
+        }
+
+        fn slice<'v>(this: Value<'v>, pieces: i32) -> anyhow::Result<Value<'v>> {
+            let _unused = pieces;
+            Ok(this)
+        }
+    }
+
+    #[derive(
+        Debug,
+        derive_more::Display,
+        ProvidesStaticType,
+        Allocative,
+        NoSerialize
+    )]
+    #[display(fmt = "vegetable")]
+    #[allocative(skip)]
+    enum Vegetable {}
+
+    #[starlark_value(type = "vegetable")]
+    impl<'v> StarlarkValue<'v> for Vegetable {
+        fn get_type_starlark_repr() -> Ty {
+            Ty::starlark_value::<Self>()
+        }
+    }
+
+    #[derive(
+        Debug,
+        derive_more::Display,
+        ProvidesStaticType,
+        Allocative,
+        NoSerialize
+    )]
+    #[display(fmt = "pair")]
+    #[allocative(skip)]
+    enum Pair {}
+
+    #[starlark_value(type = "pair")]
+    impl<'v> StarlarkValue<'v> for Pair {
+        fn get_type_starlark_repr() -> Ty {
+            Ty::starlark_value::<Self>()
+        }
+
+        fn iterate_collect(&self, _heap: &'v Heap) -> anyhow::Result<Vec<Value<'v>>> {
+            match *self {}
+        }
+    }
+
+    #[derive(
+        Debug,
+        derive_more::Display,
+        ProvidesStaticType,
+        Allocative,
+        NoSerialize
+    )]
+    #[display(fmt = "rank")]
+    #[allocative(skip)]
+    enum Rank {}
+
+    #[starlark_value(type = "rank")]
+    impl<'v> StarlarkValue<'v> for Rank {
+        fn get_type_starlark_repr() -> Ty {
+            Ty::starlark_value::<Self>()
+        }
+
+        fn compare(&self, _other: Value<'v>) -> anyhow::Result<std::cmp::Ordering> {
+            match *self {}
+        }
+    }
+
+    #[derive(
+        Debug,
+        derive_more::Display,
+        ProvidesStaticType,
+        Allocative,
+        NoSerialize
+    )]
+    #[display(fmt = "lazy_value")]
+    struct LazyValueType {
+        ty_lazy_value: Ty,
+    }
+
+    impl<'v> AllocValue<'v> for LazyValueType {
+        fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+            heap.alloc_simple(self)
+        }
+    }
+
+    #[starlark_value(type = "lazy_value")]
+    impl<'v> StarlarkValue<'v> for LazyValueType {
+        fn get_type_starlark_repr() -> Ty {
+            Ty::starlark_value::<Self>()
+        }
+
+        fn typechecker_ty(&self) -> Option<Ty> {
+            Some(self.ty_lazy_value.dupe())
+        }
+
+        fn at(&self, _index: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            // The tests only typecheck indexing, they never run it.
+            ValueError::unsupported(self, "[]")
+        }
+    }
+
+    /// Constructor of the type `name` of `Fruit` values created with `params`. It takes the known
+    /// fields of the type as parameters, and infers the type arguments of generic types.
+    fn fruit_with(name: &str, params: TyUserParams) -> anyhow::Result<FruitCallable> {
+        let fields = params.fields.clone();
+        let type_params = params.type_params.clone();
+        let ty_fruit = TyUser::new(
+            name.to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            params,
+        )?;
+        let result = if type_params.is_empty() {
+            Ty::custom(ty_fruit.clone())
+        } else {
+            Ty::custom(ty_fruit.instantiate(type_params.iter().map(|p| p.ty()).collect())?)
+        };
+        let ty_fruit_callable = Ty::custom(TyUser::new(
+            format!("fruit[{}]", name),
+            TyStarlarkValue::new::<FruitCallable>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                callable: Some(TyFunction::constructor_from_fields(&fields, result)),
+                type_params,
+                ..TyUserParams::default()
+            },
+        )?);
+        Ok(FruitCallable {
+            name: name.to_owned(),
+            ty_fruit: Ty::custom(ty_fruit),
+            ty_fruit_callable,
+        })
+    }
+
+    /// `Assert` with `globals` and the `constructors` in scope.
+    fn assert_with(constructors: Vec<(&str, FruitCallable)>) -> Assert<'static> {
+        let mut a = Assert::new();
+        a.globals_add(|builder| {
+            globals(builder);
+            for (name, constructor) in constructors {
+                builder.set(name, constructor);
+            }
+        });
+        a
+    }
+
+    /// A required `weight` field and an optional `color` field.
+    fn weight_and_color() -> TyUserFields {
+        TyUserFields {
+            known: [
+                ("weight".to_owned(), TyUserField::required(Ty::int())),
+                ("color".to_owned(), TyUserField::optional(Ty::string())),
+            ]
+            .into_iter()
+            .collect(),
+            ..TyUserFields::no_fields()
+        }
     }
 
     #[starlark_module]
     fn globals(globals: &mut GlobalsBuilder) {
+        fn lazy_value() -> anyhow::Result<LazyValueType> {
+            let ty_lazy_value = Ty::custom(TyUser::new(
+                "LazyValue".to_owned(),
+                TyStarlarkValue::new::<LazyValueType>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    type_arg_callable: Some(TyUserTypeArgCallable(|t| {
+                        TyFunction::new(vec![], t.dupe())
+                    })),
+                    ..TyUserParams::default()
+                },
+            )?);
+            Ok(LazyValueType { ty_lazy_value })
+        }
+
         fn fruit(name: String) -> anyhow::Result<FruitCallable> {
-            let ty_fruit = Ty::custom(TyUser::new(
+            fruit_with(
+                &name,
+                TyUserParams {
+                    supertypes: AbstractPlant::get_type_starlark_repr()
+                        .iter_union()
+                        .to_vec(),
+                    ..TyUserParams::default()
+                },
+            )
+        }
+
+        fn fruit_variant(
+            fruit: Value,
+            name: String,
+            identity: bool,
+        ) -> anyhow::Result<FruitCallable> {
+            let fruit = fruit
+                .downcast_ref::<FruitCallable>()
+                .ok_or_else(|| anyhow::anyhow!("expected a fruit"))?;
+            let ty_variant = Ty::custom(TyUser::new(
                 name.clone(),
                 TyStarlarkValue::new::<Fruit>(),
-                AbstractPlant::get_type_starlark_repr()
-                    .iter_union()
-                    .to_vec(),
-                None,
-                TypeInstanceId::gen(),
-                TyUserFields::no_fields(),
-                None,
-                None,
-                None,
-            )?);
-            let ty_fruit_callable = Ty::custom(TyUser::new(
-                format!("fruit[{}]", name),
-                TyStarlarkValue::new::<FruitCallable>(),
-                Vec::new(),
-                None,
                 TypeInstanceId::gen(),
-                TyUserFields::no_fields(),
-                Some(TyFunction::new(vec![], ty_fruit.clone())),
-                None,
-                None,
+                TyUserParams {
+                    supertypes: fruit.ty_fruit.iter_union().to_vec(),
+                    fields: TyUserFields {
+                        known: [("grams".to_owned(), TyUserField::required(Ty::int()))]
+                            .into_iter()
+                            .collect(),
+                        unknown: false,
+                        lazy: SortedMap::new(),
+                        exclusive: Vec::new(),
+                        fallback: None,
+                        same_as: SortedMap::new(),
+                        discriminant: None,
+                    },
+                    equality: if identity {
+                        TyUserEquality::Identity
+                    } else {
+                        TyUserEquality::Structural
+                    },
+                    ..TyUserParams::default()
+                },
             )?);
+            // The variant is a value of the variant type rather than a constructor of it.
             Ok(FruitCallable {
                 name,
-                ty_fruit,
-                ty_fruit_callable,
+                ty_fruit: ty_variant.dupe(),
+                ty_fruit_callable: ty_variant,
             })
         }
 
@@ -448,4 +1913,1920 @@ def test():
 "#,
         );
     }
+
+    #[test]
+    fn test_constructor_from_fields() {
+        let a = assert_with(vec![(
+            "Melon",
+            fruit_with(
+                "melon",
+                TyUserParams {
+                    fields: weight_and_color(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def make_melon() -> Melon:
+    return Melon(weight=3)
+"#,
+        );
+        a.fail(
+            r#"
+def make_melon() -> Melon:
+    return Melon(weight="heavy")
+"#,
+            "Expected type `int` but got `str`",
+        );
+        a.fail(
+            r#"
+def make_melon() -> Melon:
+    return Melon()
+"#,
+            "Missing required parameter `weight`",
+        );
+    }
+
+    #[test]
+    fn test_optional_field() {
+        let a = assert_with(vec![(
+            "Melon",
+            fruit_with(
+                "melon",
+                TyUserParams {
+                    fields: weight_and_color(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def color(melon: Melon) -> str | None:
+    return melon.color
+
+def make_melon() -> Melon:
+    return Melon(weight=3, color="green")
+"#,
+        );
+        a.fail(
+            r#"
+def color(melon: Melon) -> str:
+    return melon.color
+"#,
+            "Expected type `str` but got",
+        );
+    }
+
+    #[test]
+    fn test_optional_field_hasattr_narrowing() {
+        let a = assert_with(vec![(
+            "Melon",
+            fruit_with(
+                "melon",
+                TyUserParams {
+                    fields: weight_and_color(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def color(melon: Melon) -> str:
+    if hasattr(melon, "color"):
+        return melon.color
+    return "green"
+"#,
+        );
+        a.fail(
+            r#"
+def color(melon: Melon) -> str:
+    if hasattr(melon, "weight"):
+        return melon.color
+    return "green"
+"#,
+            "Expected type `str` but got",
+        );
+        // `melon` may no longer have a color once it is reassigned.
+        a.fail(
+            r#"
+def color(melon: Melon, other: Melon) -> str:
+    if hasattr(melon, "color"):
+        melon = other
+        return melon.color
+    return "green"
+"#,
+            "Expected type `str` but got",
+        );
+    }
+
+    #[test]
+    fn test_exclusive_fields() {
+        let a = assert_with(vec![(
+            "Plum",
+            fruit_with(
+                "plum",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [
+                            ("seeds".to_owned(), TyUserField::optional(Ty::int())),
+                            ("pit".to_owned(), TyUserField::optional(Ty::int())),
+                        ]
+                        .into_iter()
+                        .collect(),
+                        exclusive: vec![vec!["seeds".to_owned(), "pit".to_owned()]],
+                        same_as: [("stone".to_owned(), TyUserSameAs("pit".to_owned()))]
+                            .into_iter()
+                            .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def pit(plum: Plum) -> None:
+    if hasattr(plum, "seeds"):
+        return plum.pit
+    return None
+
+def seeds(plum: Plum) -> int | None:
+    return plum.seeds
+"#,
+        );
+        // `stone` is present and exclusive with `seeds` like `pit`.
+        a.pass(
+            r#"
+def stone(plum: Plum) -> None:
+    if hasattr(plum, "seeds"):
+        return plum.stone
+    return None
+
+def pit(plum: Plum) -> int:
+    if hasattr(plum, "stone"):
+        return plum.pit
+    return 0
+"#,
+        );
+        a.fail(
+            r#"
+def pit(plum: Plum) -> int:
+    if hasattr(plum, "seeds"):
+        return plum.pit
+    return 0
+"#,
+            "Expected type `int` but got",
+        );
+    }
+
+    #[test]
+    fn test_exclusive_field_must_be_optional() {
+        let err = TyUser::new(
+            "plum".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    known: [
+                        ("seeds".to_owned(), TyUserField::required(Ty::int())),
+                        ("pit".to_owned(), TyUserField::optional(Ty::int())),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    unknown: false,
+                    lazy: SortedMap::new(),
+                    exclusive: vec![vec!["seeds".to_owned(), "pit".to_owned()]],
+                    fallback: None,
+                    same_as: SortedMap::new(),
+                    discriminant: None,
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "Type `plum` declares `seeds` as an exclusive field, but it is not a known optional field",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_fallback_attribute_type() {
+        let a = assert_with(vec![(
+            "Kiwi",
+            fruit_with(
+                "kiwi",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [("weight".to_owned(), TyUserField::required(Ty::int()))]
+                            .into_iter()
+                            .collect(),
+                        fallback: Some(Ty::union2(Ty::string(), Ty::none())),
+                        ..TyUserFields::no_fields()
+                    },
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def origin(kiwi: Kiwi) -> str | None:
+    return kiwi.origin
+
+def weight(kiwi: Kiwi) -> int:
+    return kiwi.weight
+"#,
+        );
+        a.fail(
+            r#"
+def origin(kiwi: Kiwi) -> int:
+    return kiwi.origin
+"#,
+            "Expected type `int` but got",
+        );
+    }
+
+    #[test]
+    fn test_same_as_field() {
+        let a = assert_with(vec![(
+            "Melon",
+            fruit_with(
+                "melon",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [("weight".to_owned(), TyUserField::required(Ty::int()))]
+                            .into_iter()
+                            .collect(),
+                        same_as: [("net_weight".to_owned(), TyUserSameAs("weight".to_owned()))]
+                            .into_iter()
+                            .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def net_weight(melon: Melon) -> int:
+    return melon.net_weight
+"#,
+        );
+        a.fail(
+            r#"
+def net_weight(melon: Melon) -> str:
+    return melon.net_weight
+"#,
+            "Expected type `str` but got `int`",
+        );
+    }
+
+    #[test]
+    fn test_same_as_unknown_field() {
+        let err = TyUser::new(
+            "melon".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    same_as: [("net_weight".to_owned(), TyUserSameAs("weight".to_owned()))]
+                        .into_iter()
+                        .collect(),
+                    ..TyUserFields::no_fields()
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "Type `melon` declares field `net_weight` the same as `weight`, but `weight` is not a known field",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_same_as_declared_field() {
+        let err = TyUser::new(
+            "melon".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    known: [
+                        ("weight".to_owned(), TyUserField::required(Ty::int())),
+                        ("net_weight".to_owned(), TyUserField::required(Ty::string())),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    same_as: [("net_weight".to_owned(), TyUserSameAs("weight".to_owned()))]
+                        .into_iter()
+                        .collect(),
+                    ..TyUserFields::no_fields()
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "Type `melon` declares field `net_weight` the same as another field, but it is a known field",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_generic_type_params() {
+        let type_param = TyUserTypeParam {
+            name: "T".to_owned(),
+            variance: TyUserVariance::Covariant,
+        };
+        let a = assert_with(vec![(
+            "Box",
+            fruit_with(
+                "box",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [("item".to_owned(), TyUserField::required(type_param.ty()))]
+                            .into_iter()
+                            .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    type_params: vec![type_param],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def explicit() -> int:
+    return Box[int](item = 1).item
+
+def inferred() -> str:
+    return Box(item = "apple").item
+
+def unparameterized(b: Box):
+    return b.item + 1
+"#,
+        );
+        a.fail(
+            r#"
+def test() -> str:
+    return Box[int](item = 1).item
+"#,
+            "Expected type `str` but got `int`",
+        );
+        a.fail(
+            r#"
+def test() -> int:
+    return Box(item = "apple").item
+"#,
+            "Expected type `int` but got `str`",
+        );
+    }
+
+    #[test]
+    fn test_generic_two_type_params() {
+        let first = TyUserTypeParam {
+            name: "A".to_owned(),
+            variance: TyUserVariance::Covariant,
+        };
+        let second = TyUserTypeParam {
+            name: "B".to_owned(),
+            variance: TyUserVariance::Covariant,
+        };
+        let a = assert_with(vec![(
+            "Pair",
+            fruit_with(
+                "pair",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [
+                            ("first".to_owned(), TyUserField::required(first.ty())),
+                            ("second".to_owned(), TyUserField::required(second.ty())),
+                        ]
+                        .into_iter()
+                        .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    type_params: vec![first, second],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def pair() -> str:
+    return Pair[int, str](first = 1, second = "apple").second
+
+def parenthesized() -> int:
+    return Pair[(int, str)](first = 1, second = "apple").first
+"#,
+        );
+        a.fail(
+            r#"
+def test() -> int:
+    return Pair[int, str](first = 1, second = "apple").second
+"#,
+            "Expected type `int` but got `str`",
+        );
+        a.fail(
+            r#"
+def test():
+    return Pair[(int, str, bool)]
+"#,
+            "Type `fruit[pair]` does not have [] operator or [] cannot accept",
+        );
+    }
+
+    #[test]
+    fn test_aliases_use_canonical_name() {
+        let a = assert_with(vec![(
+            "Lime",
+            fruit_with(
+                "lime",
+                TyUserParams {
+                    aliases: vec!["fruits.lime".to_owned()],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+KeyLime = Lime
+
+def test(lime: KeyLime) -> Lime:
+    return lime
+"#,
+        );
+        a.fail(
+            r#"
+KeyLime = Lime
+
+def test(lime: KeyLime) -> int:
+    return lime
+"#,
+            "Expected type `int` but got `lime`",
+        );
+
+        let lime = TyUser::new(
+            "lime".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                aliases: vec!["fruits.lime".to_owned()],
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(Some("lime"), lime.as_name());
+        assert!(lime.has_name("fruits.lime"));
+        assert!(!lime.has_name("lemon"));
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        let info = |module: Option<&str>| {
+            TyUser::new(
+                "Info".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    module: module.map(str::to_owned),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let rules_info = info(Some("//foo:rules.bzl"));
+        assert_eq!("//foo:rules.bzl:Info", rules_info.qualified_name());
+        assert_eq!("Info", rules_info.to_string());
+        assert_eq!(Some("Info"), rules_info.as_name());
+
+        assert_eq!("Info", info(None).qualified_name());
+    }
+
+    #[test]
+    fn test_discriminant_narrowing() {
+        let a = assert_with(vec![(
+            "Plum",
+            fruit_with(
+                "plum",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [
+                            ("kind".to_owned(), TyUserField::required(Ty::string())),
+                            ("seeds".to_owned(), TyUserField::required(Ty::int())),
+                            ("stone".to_owned(), TyUserField::required(Ty::string())),
+                        ]
+                        .into_iter()
+                        .collect(),
+                        same_as: [("pit".to_owned(), TyUserSameAs("stone".to_owned()))]
+                            .into_iter()
+                            .collect(),
+                        discriminant: Some(TyUserDiscriminant {
+                            field: "kind".to_owned(),
+                            variants: [
+                                ("berry".to_owned(), vec!["seeds".to_owned()]),
+                                ("drupe".to_owned(), vec!["stone".to_owned()]),
+                            ]
+                            .into_iter()
+                            .collect(),
+                        }),
+                        ..TyUserFields::no_fields()
+                    },
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def describe(plum: Plum) -> str:
+    if plum.kind == "berry":
+        return str(plum.seeds)
+    if plum.kind == "drupe":
+        return plum.stone
+    return plum.kind
+"#,
+        );
+        a.fail(
+            r#"
+def test(plum: Plum):
+    plum.stone
+"#,
+            "The attribute `stone` is not available on the type `plum`",
+        );
+        a.fail(
+            r#"
+def test(plum: Plum):
+    if plum.kind == "berry":
+        plum.stone
+"#,
+            "The attribute `stone` is not available on the type `plum`",
+        );
+        // `plum` may no longer be a drupe once it is reassigned, so it isn't narrowed.
+        a.fail(
+            r#"
+def test(plum: Plum, other: Plum):
+    if plum.kind == "drupe":
+        plum = other
+        plum.stone
+"#,
+            "The attribute `stone` is not available on the type `plum`",
+        );
+        // `pit` is a variant field like `stone`.
+        a.pass(
+            r#"
+def pit(plum: Plum) -> str:
+    if plum.kind == "drupe":
+        return plum.pit
+    return ""
+"#,
+        );
+        a.fail(
+            r#"
+def test(plum: Plum):
+    plum.pit
+"#,
+            "The attribute `pit` is not available on the type `plum`",
+        );
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        let base = TyUserFields {
+            known: [
+                (
+                    "name".to_owned(),
+                    TyUserField::optional(Ty::union2(Ty::int(), Ty::string())),
+                ),
+                ("weight".to_owned(), TyUserField::required(Ty::int())),
+            ]
+            .into_iter()
+            .collect(),
+            ..TyUserFields::no_fields()
+        };
+        let extension = TyUserFields {
+            known: [
+                ("name".to_owned(), TyUserField::required(Ty::string())),
+                ("seeds".to_owned(), TyUserField::optional(Ty::int())),
+            ]
+            .into_iter()
+            .collect(),
+            ..TyUserFields::unknown()
+        };
+
+        let merged = base.merge(&extension).unwrap();
+        assert_eq!(
+            Some(&TyUserField::required(Ty::string())),
+            merged.known.get("name")
+        );
+        assert_eq!(
+            Some(&TyUserField::required(Ty::int())),
+            merged.known.get("weight")
+        );
+        assert_eq!(
+            Some(&TyUserField::optional(Ty::int())),
+            merged.known.get("seeds")
+        );
+        assert!(merged.unknown);
+
+        let disjoint = TyUserFields {
+            known: [("weight".to_owned(), TyUserField::required(Ty::string()))]
+                .into_iter()
+                .collect(),
+            ..TyUserFields::no_fields()
+        };
+        let err = base.merge(&disjoint).unwrap_err();
+        assert_eq!(
+            "Cannot merge field `weight` of type `int` with field of type `str`, the types are disjoint",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_ord_compares_fields() {
+        let melon = |fields: TyUserFields| {
+            TyUser::new(
+                "melon".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    fields,
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+        // Created first so that it has the lower id: types are ordered by fields before ids.
+        let heavy = melon(weight_and_color());
+        let light = melon(TyUserFields::no_fields());
+        assert_eq!(Ordering::Greater, heavy.cmp(&light));
+        assert_eq!(Ordering::Less, light.cmp(&heavy));
+    }
+
+    #[test]
+    fn test_merge_fields_subtype() {
+        let plant = AbstractPlant::get_type_starlark_repr();
+        let apple = Ty::custom(
+            TyUser::new(
+                "Apple".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    supertypes: plant.iter_union().to_vec(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        let fields = |ty: &Ty| TyUserFields {
+            known: [("fruit".to_owned(), TyUserField::required(ty.dupe()))]
+                .into_iter()
+                .collect(),
+            ..TyUserFields::no_fields()
+        };
+
+        // The subtype is picked whichever of the two declares it.
+        for (a, b) in [(&plant, &apple), (&apple, &plant)] {
+            let merged = fields(a).merge(&fields(b)).unwrap();
+            assert_eq!(
+                Some(&TyUserField::required(apple.dupe())),
+                merged.known.get("fruit")
+            );
+        }
+    }
+
+    #[test]
+    fn test_discriminant_unknown_field() {
+        let err = TyUser::new(
+            "plum".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    discriminant: Some(TyUserDiscriminant {
+                        field: "kind".to_owned(),
+                        variants: SortedMap::new(),
+                    }),
+                    ..TyUserFields::no_fields()
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "Type `plum` declares `kind` as a discriminant or variant field, but it is not a known field",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_decorated_attributes() {
+        let ty_inner = Ty::custom(
+            TyUser::new(
+                "lime_inner".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [("weight".to_owned(), TyUserField::required(Ty::int()))]
+                            .into_iter()
+                            .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        let a = assert_with(vec![(
+            "Lime",
+            fruit_with(
+                "lime",
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [("label".to_owned(), TyUserField::required(Ty::string()))]
+                            .into_iter()
+                            .collect(),
+                        ..TyUserFields::no_fields()
+                    },
+                    decorates: Some(ty_inner),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def label(lime: Lime) -> str:
+    return lime.label
+
+def weight(lime: Lime) -> int:
+    return lime.weight
+"#,
+        );
+        a.fail(
+            r#"
+def test(lime: Lime):
+    lime.seeds
+"#,
+            "The attribute `seeds` is not available on the type `lime`",
+        );
+    }
+
+    #[test]
+    fn test_attribute_resolver() {
+        let a = assert_with(vec![(
+            "Fig",
+            fruit_with(
+                "fig",
+                TyUserParams {
+                    attribute_resolver: Some(TyUserAttributeResolver(|attr| {
+                        attr.ends_with("_path").then(Ty::string)
+                    })),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def seed_path(fig: Fig) -> str:
+    return fig.seed_path
+"#,
+        );
+        a.fail(
+            r#"
+def seed_path(fig: Fig) -> int:
+    return fig.seed_path
+"#,
+            "Expected type `int` but got `str`",
+        );
+        a.fail(
+            r#"
+def seed(fig: Fig):
+    return fig.seed
+"#,
+            "The attribute `seed` is not available on the type `fig`",
+        );
+    }
+
+    #[test]
+    fn test_unhashable_dict_key() {
+        let a = assert_with(vec![(
+            "Banana",
+            fruit_with(
+                "banana",
+                TyUserParams {
+                    hashable: Some(false),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+Apple = fruit("apple")
+
+def test():
+    return {Apple(): 1}
+"#,
+        );
+        a.fail(
+            r#"
+def test():
+    return {Banana(): 1}
+"#,
+            "Type `banana` is not hashable",
+        );
+        a.fail(
+            r#"
+def test():
+    return {b: 1 for b in [Banana()]}
+"#,
+            "Type `banana` is not hashable",
+        );
+    }
+
+    #[test]
+    fn test_not_stringable() {
+        let mut a = assert_with(vec![(
+            "Unprintable",
+            fruit_with(
+                "unprintable",
+                TyUserParams {
+                    stringable: Some(false),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+Apple = fruit("apple")
+
+def test() -> str:
+    return str(Apple()) + "%s" % Apple()
+"#,
+        );
+        a.fail(
+            r#"
+def test() -> str:
+    return str(Unprintable())
+"#,
+            "Type `unprintable` cannot be converted to string",
+        );
+        a.fail(
+            r#"
+def test() -> str:
+    return "%s: %s" % ("x", Unprintable())
+"#,
+            "Type `unprintable` cannot be converted to string",
+        );
+        a.dialect_set(|d| d.enable_f_strings = true);
+        a.fail(
+            r#"
+def test() -> str:
+    x = Unprintable()
+    return f"x: {x}"
+"#,
+            "Type `unprintable` cannot be converted to string",
+        );
+    }
+
+    #[test]
+    fn test_hashable_comparable_validated_against_base() {
+        // `Fruit` implements neither hashing nor comparison.
+        for params in [
+            TyUserParams {
+                hashable: Some(true),
+                ..TyUserParams::default()
+            },
+            TyUserParams {
+                comparable: Some(true),
+                ..TyUserParams::default()
+            },
+        ] {
+            assert!(
+                TyUser::new(
+                    "Apple".to_owned(),
+                    TyStarlarkValue::new::<Fruit>(),
+                    TypeInstanceId::gen(),
+                    params,
+                )
+                .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_comparable() {
+        // `Rank` rather than `Fruit`, because comparable types require a comparable base.
+        let rank = |name: &str, comparable: Option<bool>| {
+            let ty_rank = Ty::custom(
+                TyUser::new(
+                    name.to_owned(),
+                    TyStarlarkValue::new::<Rank>(),
+                    TypeInstanceId::gen(),
+                    TyUserParams {
+                        comparable,
+                        ..TyUserParams::default()
+                    },
+                )
+                .unwrap(),
+            );
+            FruitCallable {
+                name: name.to_owned(),
+                ty_fruit: ty_rank.dupe(),
+                ty_fruit_callable: Ty::function(vec![], ty_rank),
+            }
+        };
+        let a = assert_with(vec![
+            ("Ordered", rank("ordered", Some(true))),
+            ("Unordered", rank("unordered", Some(false))),
+        ]);
+        a.pass(
+            r#"
+def test(x: Ordered, y: Ordered) -> bool:
+    return x < y and x >= y
+"#,
+        );
+        a.fail(
+            r#"
+def test(x: Ordered) -> bool:
+    return x < 1
+"#,
+            "Binary operator `<` is not available on the types `ordered` and `int`",
+        );
+        a.fail(
+            r#"
+def test(x: Unordered, y: Unordered) -> bool:
+    return x < y
+"#,
+            "Binary operator `<` is not available on the types `unordered` and `unordered`",
+        );
+    }
+
+    #[test]
+    fn test_tuple_shape_destructuring() {
+        let ty_point = Ty::custom(
+            TyUser::new(
+                "point".to_owned(),
+                TyStarlarkValue::new::<Pair>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    tuple_shape: Some(vec![Ty::int(), Ty::string()]),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        // `Pair` rather than `Fruit`, because tuple shapes require an iterable base.
+        let point = FruitCallable {
+            name: "point".to_owned(),
+            ty_fruit: ty_point.dupe(),
+            ty_fruit_callable: Ty::function(vec![], ty_point),
+        };
+        let a = assert_with(vec![("Point", point)]);
+        a.pass(
+            r#"
+def takes_int(x: int):
+    pass
+
+def takes_str(x: str):
+    pass
+
+def test(p: Point):
+    a, b = p
+    takes_int(a)
+    takes_str(b)
+"#,
+        );
+        a.fail(
+            r#"
+def takes_int(x: int):
+    pass
+
+def test(p: Point):
+    a, b = p
+    takes_int(b)
+"#,
+            "Expected type `int` but got `str`",
+        );
+    }
+
+    #[test]
+    fn test_tuple_shape_requires_iterable_base() {
+        assert!(
+            TyUser::new(
+                "Apple".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    tuple_shape: Some(vec![Ty::int(), Ty::int()]),
+                    ..TyUserParams::default()
+                },
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let fruit = |callable: TyFunction| {
+            TyUser::new(
+                "fruit".to_owned(),
+                TyStarlarkValue::new::<FruitCallable>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    callable: Some(callable),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let a = fruit(TyFunction::new(vec![], Ty::int()));
+        let b = fruit(TyFunction::new(vec![], Ty::int()));
+        let c = fruit(TyFunction::new(vec![], Ty::string()));
+
+        // Types are distinct by id, but structurally equal types share a cache key.
+        assert_ne!(a, b);
+        assert_eq!(a.cache_key(), b.cache_key());
+        assert_ne!(a.cache_key(), c.cache_key());
+
+        let resolved = |attribute_resolver: fn(&str) -> Option<Ty>| {
+            TyUser::new(
+                "fruit".to_owned(),
+                TyStarlarkValue::new::<FruitCallable>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    attribute_resolver: Some(TyUserAttributeResolver(attribute_resolver)),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+        fn int_attrs(_attr: &str) -> Option<Ty> {
+            Some(Ty::int())
+        }
+        fn str_attrs(_attr: &str) -> Option<Ty> {
+            Some(Ty::string())
+        }
+
+        // Attribute resolvers are compared by function.
+        assert_eq!(resolved(int_attrs).cache_key(), resolved(int_attrs).cache_key());
+        assert_ne!(resolved(int_attrs).cache_key(), resolved(str_attrs).cache_key());
+    }
+
+    #[test]
+    fn test_common_supertype() {
+        let plant = AbstractPlant::get_type_starlark_repr().iter_union().to_vec();
+        let apple = TyUser::new(
+            "Apple".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                supertypes: plant.clone(),
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        let carrot = TyUser::new(
+            "Carrot".to_owned(),
+            TyStarlarkValue::new::<Vegetable>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                supertypes: plant.clone(),
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        let pear = TyUser::new(
+            "Pear".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(plant[0].dupe()),
+            TyUser::common_supertype(&apple, &carrot)
+        );
+        // Shared base is a common supertype too.
+        assert_eq!(
+            Some(TyBasic::StarlarkValue(TyStarlarkValue::new::<Fruit>())),
+            TyUser::common_supertype(&apple, &pear)
+        );
+        assert_eq!(None, TyUser::common_supertype(&carrot, &pear));
+    }
+
+    #[test]
+    fn test_common_supertype_prefers_declared_supertypes() {
+        let plant = AbstractPlant::get_type_starlark_repr().iter_union().to_vec();
+        let fruit = |name: &str| {
+            TyUser::new(
+                name.to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    supertypes: plant.clone(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+
+        // Both types are over the `Fruit` base, but the shared `Plant` supertype wins.
+        assert_eq!(
+            Some(plant[0].dupe()),
+            TyUser::common_supertype(&fruit("Apple"), &fruit("Pear"))
+        );
+    }
+
+    #[test]
+    fn test_brand_intersection() {
+        let brand = |name: &str| {
+            Ty::custom(
+                TyUser::new(
+                    name.to_owned(),
+                    TyStarlarkValue::int(),
+                    TypeInstanceId::gen(),
+                    TyUserParams {
+                        brand: true,
+                        ..TyUserParams::default()
+                    },
+                )
+                .unwrap(),
+            )
+        };
+        let meters = brand("Meters");
+        let feet = brand("Feet");
+
+        let codemap = CodeMap::default();
+        let oracle = TypingOracleCtx { codemap: &codemap };
+
+        assert!(oracle.intersects(&meters, &meters));
+        assert!(oracle.intersects(&feet, &feet));
+        assert!(!oracle.intersects(&meters, &feet));
+        assert!(!oracle.intersects(&feet, &meters));
+        assert!(!oracle.intersects(&meters, &Ty::int()));
+    }
+
+    #[test]
+    fn test_matcher_factory_shared() {
+        let apple = TyUser::new(
+            "Apple".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                matcher: Some(TypeMatcherFactory::new(IsAny)),
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        let pear = TyUser::new(
+            "Pear".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                matcher: apple.matcher_factory().cloned(),
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        let plain = TyUser::new(
+            "Plain".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams::default(),
+        )
+        .unwrap();
+
+        assert!(Arc::ptr_eq(
+            &apple.matcher_factory().unwrap().factory,
+            &pear.matcher_factory().unwrap().factory,
+        ));
+        assert!(plain.matcher_factory().is_none());
+    }
+
+    #[test]
+    fn test_record_constructor_arity() {
+        let record = |params: Vec<Param>| {
+            TyUser::new(
+                "Apple".to_owned(),
+                TyStarlarkValue::new::<FruitCallable>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    fields: TyUserFields {
+                        known: [
+                            ("weight".to_owned(), TyUserField::required(Ty::int())),
+                            ("color".to_owned(), TyUserField::required(Ty::string())),
+                        ]
+                        .into_iter()
+                        .collect(),
+                        unknown: false,
+                        lazy: SortedMap::new(),
+                        exclusive: Vec::new(),
+                        fallback: None,
+                        same_as: SortedMap::new(),
+                        discriminant: None,
+                    },
+                    callable: Some(TyFunction::new(params, Ty::any())),
+                    record: true,
+                    ..TyUserParams::default()
+                },
+            )
+        };
+
+        assert!(
+            record(vec![
+                Param::name_only("weight", Ty::int()),
+                Param::name_only("color", Ty::string()),
+            ])
+            .is_ok()
+        );
+        let err = record(vec![Param::name_only("weight", Ty::int())]).unwrap_err();
+        assert_eq!(
+            "Type `Apple` is a record with 2 fields, but its callable takes 1 parameters",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_type_arg_callable() {
+        let mut a = Assert::new();
+        a.globals_add(globals);
+        a.pass(
+            r#"
+LazyValue = lazy_value()
+
+def force_int() -> int:
+    return LazyValue[int]()
+
+def force_str() -> str:
+    return LazyValue[str]()
+"#,
+        );
+        a.fail(
+            r#"
+LazyValue = lazy_value()
+
+def force() -> str:
+    return LazyValue[int]()
+"#,
+            "Expected type `str` but got `int`",
+        );
+    }
+
+    #[test]
+    fn test_type_param_variance() {
+        let fruit = TyUser::new(
+            "Fruit".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams::default(),
+        )
+        .unwrap();
+        let apple = Ty::custom(
+            TyUser::new(
+                "Apple".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    supertypes: vec![TyBasic::custom(fruit.clone())],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        let fruit = Ty::custom(fruit);
+
+        let generic = |name: &str, variance| {
+            TyUser::new(
+                name.to_owned(),
+                TyStarlarkValue::new::<FrozenList>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    type_params: vec![TyUserTypeParam {
+                        name: "T".to_owned(),
+                        variance,
+                    }],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let codemap = CodeMap::default();
+        let oracle = TypingOracleCtx { codemap: &codemap };
+        // Whether a value of `generic[x]` can be used where `generic[y]` is required.
+        let intersects = |generic: &TyUser, x: &Ty, y: &Ty| {
+            oracle.intersects(
+                &Ty::custom(generic.instantiate(vec![x.dupe()]).unwrap()),
+                &Ty::custom(generic.instantiate(vec![y.dupe()]).unwrap()),
+            )
+        };
+
+        let boxed = generic("Box", TyUserVariance::Covariant);
+        assert!(intersects(&boxed, &apple, &fruit));
+        assert!(!intersects(&boxed, &fruit, &apple));
+        assert!(!intersects(&boxed, &apple, &Ty::int()));
+
+        let sink = generic("Sink", TyUserVariance::Contravariant);
+        assert!(intersects(&sink, &fruit, &apple));
+        assert!(!intersects(&sink, &apple, &fruit));
+
+        let cell = generic("Cell", TyUserVariance::Invariant);
+        assert!(intersects(&cell, &apple, &apple));
+        assert!(!intersects(&cell, &apple, &fruit));
+
+        assert_eq!(
+            "Box[Apple]",
+            boxed.instantiate(vec![apple.dupe()]).unwrap().to_string()
+        );
+        let err = boxed.instantiate(Vec::new()).unwrap_err();
+        assert_eq!(
+            "Type `Box` takes 1 type arguments, but 0 were given",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_sequence_index_iter_item_mismatch() {
+        let sequence = |result: Ty| {
+            TyUser::new(
+                "Fruits".to_owned(),
+                TyStarlarkValue::new::<FrozenList>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    index: Some(TyUserIndex {
+                        index: Ty::int(),
+                        result,
+                    }),
+                    iter_item: Some(Ty::string()),
+                    sequence: true,
+                    ..TyUserParams::default()
+                },
+            )
+        };
+
+        assert!(sequence(Ty::string()).is_ok());
+        let err = sequence(Ty::int()).unwrap_err();
+        assert_eq!(
+            "Type `Fruits` is a sequence, but its index result `int` is not its iter item `str`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_matcher_replaces_base() {
+        let heap = Heap::new();
+        let fruit_with = |matcher: TypeMatcherFactory| {
+            Ty::custom(
+                TyUser::new(
+                    "CustomFruit".to_owned(),
+                    TyStarlarkValue::new::<Fruit>(),
+                    TypeInstanceId::gen(),
+                    TyUserParams {
+                        matcher: Some(matcher),
+                        ..TyUserParams::default()
+                    },
+                )
+                .unwrap(),
+            )
+        };
+        let apple = heap.alloc_simple(Fruit {
+            name: "apple".to_owned(),
+        });
+
+        // By default the custom matcher replaces the base matcher, so a value which is not
+        // a `Fruit` is accepted...
+        let any_fruit = fruit_with(TypeMatcherFactory::new(IsAny));
+        assert!(TypeCompiled::from_ty(&any_fruit, &heap).matches(heap.alloc(1)));
+
+        // ... and a `Fruit` is rejected if the custom matcher rejects it.
+        let no_fruit = fruit_with(TypeMatcherFactory::new(IsNever));
+        assert!(!TypeCompiled::from_ty(&no_fruit, &heap).matches(apple));
+    }
+
+    #[test]
+    fn test_matcher_base_then_custom() {
+        let heap = Heap::new();
+        let fruit_with = |matcher: TypeMatcherFactory| {
+            Ty::custom(
+                TyUser::new(
+                    "CheckedFruit".to_owned(),
+                    TyStarlarkValue::new::<Fruit>(),
+                    TypeInstanceId::gen(),
+                    TyUserParams {
+                        matcher: Some(matcher),
+                        matcher_compose: MatcherCompose::BaseThenCustom,
+                        ..TyUserParams::default()
+                    },
+                )
+                .unwrap(),
+            )
+        };
+        let apple = heap.alloc_simple(Fruit {
+            name: "apple".to_owned(),
+        });
+
+        // The base matcher rejects values which are not a `Fruit`.
+        let any_fruit = fruit_with(TypeMatcherFactory::new(IsAny));
+        let any_fruit = TypeCompiled::from_ty(&any_fruit, &heap);
+        assert!(any_fruit.matches(apple));
+        assert!(!any_fruit.matches(heap.alloc(1)));
+
+        // The custom matcher runs on values accepted by the base matcher.
+        let no_fruit = fruit_with(TypeMatcherFactory::new(IsNever));
+        let no_fruit = TypeCompiled::from_ty(&no_fruit, &heap);
+        assert!(!no_fruit.matches(apple));
+    }
+
+    #[test]
+    fn test_primitive_supertype() {
+        let new_type = |name: &str| {
+            TyUser::new(
+                name.to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams::default(),
+            )
+            .unwrap()
+        };
+        let validated_str = Ty::custom(
+            new_type("ValidatedStr")
+                .with_primitive_supertype(Ty::string())
+                .unwrap(),
+        );
+
+        let codemap = CodeMap::default();
+        let oracle = TypingOracleCtx { codemap: &codemap };
+
+        assert!(oracle.intersects(&validated_str, &Ty::string()));
+        assert!(oracle.intersects(&Ty::string(), &validated_str));
+        assert!(!oracle.intersects(&validated_str, &Ty::int()));
+
+        let err = new_type("Strings")
+            .with_primitive_supertype(Ty::list(Ty::string()))
+            .unwrap_err();
+        assert_eq!(
+            "Type `Strings` cannot declare `list[str]` as a supertype, because it is not a primitive type",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_match_error_message() {
+        let heap = Heap::new();
+        let fruit = Ty::custom(
+            TyUser::new(
+                "Fruit".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    match_error_message: Some(
+                        "value is not a valid Fruit; expected a record with a name".to_owned(),
+                    ),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+
+        let err = TypeCompiled::from_ty(&fruit, &heap)
+            .check_type(heap.alloc(1), Some("x"))
+            .unwrap_err();
+        assert_eq!(
+            "Value `1` of type `int` does not match the type annotation `Fruit` for argument `x`: \
+            value is not a valid Fruit; expected a record with a name",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let new_type = |name: &str, interfaces: Vec<TypeInstanceId>| {
+            TyUser::new(
+                name.to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    interfaces,
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap()
+        };
+        let edible = new_type("Edible", Vec::new());
+        let apple = Ty::custom(new_type("Apple", vec![edible.id()]));
+        let stone = Ty::custom(new_type("Stone", Vec::new()));
+        let edible = Ty::custom(edible);
+
+        let codemap = CodeMap::default();
+        let oracle = TypingOracleCtx { codemap: &codemap };
+
+        assert!(oracle.intersects(&apple, &edible));
+        assert!(oracle.intersects(&edible, &apple));
+        assert!(!oracle.intersects(&stone, &edible));
+        assert!(!oracle.intersects(&stone, &apple));
+    }
+
+    #[test]
+    fn test_method_attribute_is_bound() {
+        let apple = TyUser::new(
+            "Apple".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(Ty::function(
+                vec![Param::pos_or_name("days", Ty::int())],
+                Ty::bool()
+            )),
+            apple.attribute("ripen")
+        );
+
+        let mut a = Assert::new();
+        a.globals_add(globals);
+        a.pass(
+            r#"
+Apple = fruit("apple")
+
+def test(apple: Apple) -> bool:
+    return apple.ripen(3)
+"#,
+        );
+        a.fail(
+            r#"
+Apple = fruit("apple")
+
+def test(apple: Apple) -> bool:
+    return apple.ripen(apple, 3)
+"#,
+            "Too many positional arguments",
+        );
+    }
+
+    #[test]
+    fn test_eq_narrowing() {
+        let mut a = Assert::new();
+        a.globals_add(globals);
+        a.pass(
+            r#"
+Apple = fruit("apple")
+Gala = fruit_variant(Apple, "gala", True)
+
+def grams(apple: Apple) -> int:
+    if apple == Gala:
+        return apple.grams
+    return 0
+"#,
+        );
+        a.fail(
+            r#"
+Apple = fruit("apple")
+Gala = fruit_variant(Apple, "gala", True)
+
+def grams(apple: Apple) -> int:
+    return apple.grams
+"#,
+            "The attribute `grams` is not available on the type `apple`",
+        );
+        // Values of structural types may equal values of other types, so there is no narrowing.
+        a.fail(
+            r#"
+Apple = fruit("apple")
+Fuji = fruit_variant(Apple, "fuji", False)
+
+def grams(apple: Apple) -> int:
+    if apple == Fuji:
+        return apple.grams
+    return 0
+"#,
+            "The attribute `grams` is not available on the type `apple`",
+        );
+        // `apple` may no longer be `Gala` once it is reassigned, so it isn't narrowed.
+        a.fail(
+            r#"
+Apple = fruit("apple")
+Gala = fruit_variant(Apple, "gala", True)
+
+def grams(apple: Apple, other: Apple) -> int:
+    if apple == Gala:
+        apple = other
+        return apple.grams
+    return 0
+"#,
+            "The attribute `grams` is not available on the type `apple`",
+        );
+    }
+
+    #[test]
+    fn test_isinstance_narrowing() {
+        let a = assert_with(vec![(
+            "Pear",
+            fruit_with(
+                "pear",
+                TyUserParams {
+                    supertypes: AbstractPlant::get_type_starlark_repr()
+                        .iter_union()
+                        .to_vec(),
+                    fields: weight_and_color(),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+Apple = fruit("apple")
+
+def weigh(ripe: bool) -> int:
+    fruit = Apple() if ripe else Pear(weight = 3)
+    if isinstance(fruit, Pear):
+        return fruit.weight
+    return 0
+"#,
+        );
+        // `pear` is already a `Plant`, so it isn't widened to one.
+        a.pass(
+            r#"
+def weigh(pear: Pear) -> int:
+    if isinstance(pear, Plant):
+        return pear.weight
+    return 0
+"#,
+        );
+        // Attributes of unions are available if any alternative has them, so these only fail
+        // because `fruit` is narrowed to `Apple`.
+        a.fail(
+            r#"
+Apple = fruit("apple")
+
+def weigh(ripe: bool) -> int:
+    fruit = Apple() if ripe else Pear(weight = 3)
+    if isinstance(fruit, Apple):
+        return fruit.weight
+    return 0
+"#,
+            "The attribute `weight` is not available on the type `apple`",
+        );
+        a.fail(
+            r#"
+Apple = fruit("apple")
+
+def weigh(ripe: bool) -> int:
+    fruit = Apple() if ripe else Pear(weight = 3)
+    if isinstance(fruit, Pear):
+        return 0
+    else:
+        return fruit.weight
+"#,
+            "The attribute `weight` is not available on the type `apple`",
+        );
+        // `fruit` may no longer be an `Apple` once it is reassigned, so it isn't narrowed.
+        a.pass(
+            r#"
+Apple = fruit("apple")
+
+def weigh(ripe: bool) -> int:
+    fruit = Apple() if ripe else Pear(weight = 3)
+    if isinstance(fruit, Apple):
+        fruit = Pear(weight = 3)
+        return fruit.weight
+    return 0
+"#,
+        );
+    }
+
+    #[test]
+    fn test_self_returning_methods() {
+        let a = assert_with(vec![(
+            "Apple",
+            fruit_with(
+                "apple",
+                TyUserParams {
+                    self_returning_methods: vec!["peel".to_owned(), "slice".to_owned()],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def prepare(apple: Apple) -> Apple:
+    return apple.peel().slice(4).peel()
+"#,
+        );
+        a.fail(
+            r#"
+def prepare(apple: Apple) -> int:
+    return apple.peel().slice(4)
+"#,
+            "Expected type `int` but got `apple`",
+        );
+        a.fail(
+            r#"
+def prepare(apple: Apple) -> int:
+    return apple.peel().slice(4).weight
+"#,
+            "The attribute `weight` is not available on the type `apple`",
+        );
+
+        let err = TyUser::new(
+            "apple".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                self_returning_methods: vec!["juice".to_owned()],
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`juice` as a self-returning method"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_lazy_field_types() {
+        // `Parent` has a `child` field of type `Child`, which has a `parent` field of type
+        // `Parent`, so `Parent` must be created before `Child` exists.
+        let ty_child = Arc::new(OnceCell::<Ty>::new());
+        let parent = TyUser::new(
+            "Parent".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    lazy: [(
+                        "child".to_owned(),
+                        TyUserLazyField::required("Child", &ty_child),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    ..TyUserFields::no_fields()
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(Ok(Ty::any()), parent.attribute("child"));
+
+        let child = TyUser::new(
+            "Child".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields {
+                    known: [(
+                        "parent".to_owned(),
+                        TyUserField::required(Ty::custom(parent.clone())),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    ..TyUserFields::no_fields()
+                },
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        ty_child.set(Ty::custom(child.clone())).unwrap();
+
+        assert_eq!(Ok(Ty::custom(child)), parent.attribute("child"));
+        assert_eq!(Err(()), parent.attribute("sibling"));
+
+        // Lazy fields are identified by the name of their type.
+        assert_eq!(
+            TyUserLazyField::required("Child", &ty_child),
+            TyUserLazyField::required("Child", &Arc::new(OnceCell::new())),
+        );
+        assert_ne!(
+            TyUserLazyField::required("Child", &ty_child),
+            TyUserLazyField::optional("Child", &ty_child),
+        );
+
+        // The types don't keep each other alive.
+        assert_eq!(1, Arc::strong_count(&ty_child));
+        drop(ty_child);
+        assert_eq!(Ok(Ty::any()), parent.attribute("child"));
+    }
+
+    #[test]
+    fn test_opaque() {
+        let apple = TyUser::new(
+            "Apple".to_owned(),
+            TyStarlarkValue::new::<Fruit>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                fields: TyUserFields::unknown(),
+                opaque: true,
+                ..TyUserParams::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(Err(()), apple.attribute("ripen"));
+        assert_eq!(Err(()), apple.attribute("color"));
+        assert_eq!(Err(()), apple.iter_item());
+        assert!(!apple.is_callable());
+
+        let a = assert_with(vec![(
+            "Apple",
+            fruit_with(
+                "apple",
+                TyUserParams {
+                    fields: TyUserFields::unknown(),
+                    opaque: true,
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        a.pass(
+            r#"
+def test(apple: Apple) -> Apple:
+    return apple
+"#,
+        );
+        // Methods of the base are hidden too.
+        a.fail(
+            r#"
+def test(apple: Apple) -> bool:
+    return apple.ripen(3)
+"#,
+            "The attribute `ripen` is not available on the type `apple`",
+        );
+        a.fail(
+            r#"
+def test(apple: Apple):
+    return apple.color
+"#,
+            "The attribute `color` is not available on the type `apple`",
+        );
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let ty_fruit = Ty::custom(
+            TyUser::new(
+                "OldFruit".to_owned(),
+                TyStarlarkValue::new::<Fruit>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    deprecated: Some("use NewFruit instead".to_owned()),
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap(),
+        );
+        let mut globals = GlobalsBuilder::standard();
+        globals.set(
+            "OldFruit",
+            FruitCallable {
+                name: "OldFruit".to_owned(),
+                ty_fruit_callable: Ty::any(),
+                ty_fruit,
+            },
+        );
+        let globals = globals.build();
+
+        let ast = AstModule::parse(
+            "test.star",
+            r#"
+def eat(fruit: OldFruit) -> None:
+    pass
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let output = ast.typecheck_with_warnings(&globals, &HashMap::new());
+        assert!(output.errors.is_empty(), "{:?}", output.errors);
+        assert_eq!(1, output.warnings.len());
+        let warning = output.warnings[0].to_string();
+        assert!(
+            warning.contains("Type `OldFruit` is deprecated: use NewFruit instead"),
+            "{}",
+            warning
+        );
+    }
 }
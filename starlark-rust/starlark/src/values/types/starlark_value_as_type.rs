@@ -21,15 +21,19 @@ use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::iter;
 use std::marker::PhantomData;
 
 use allocative::Allocative;
+use dupe::Dupe;
 use starlark_derive::starlark_value;
 use starlark_derive::NoSerialize;
 
 use crate as starlark;
 use crate::any::ProvidesStaticType;
+use crate::environment::GlobalsBuilder;
 use crate::typing::Ty;
+use crate::typing::TyUser;
 use crate::values::layout::avalue::alloc_static;
 use crate::values::layout::avalue::AValueImpl;
 use crate::values::layout::avalue::Basic;
@@ -37,9 +41,12 @@ use crate::values::layout::heap::repr::AValueRepr;
 use crate::values::type_repr::StarlarkTypeRepr;
 use crate::values::typing::ty::AbstractType;
 use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
 use crate::values::FrozenHeap;
 use crate::values::FrozenValue;
+use crate::values::Heap;
 use crate::values::StarlarkValue;
+use crate::values::Value;
 
 #[derive(Debug, NoSerialize, Allocative, ProvidesStaticType)]
 struct StarlarkValueAsTypeStarlarkValue(fn() -> Ty);
@@ -131,6 +138,73 @@ impl<T: StarlarkTypeRepr> AllocFrozenValue for StarlarkValueAsType<T> {
     }
 }
 
+/// Like [`StarlarkValueAsType`], but for a [`TyUser`] created at runtime rather than for a Rust
+/// type, so that types defined by libraries can be named in type expressions.
+///
+/// ```ignore
+/// globals.set("Fruit", TyUserAsType::new(ty_fruit));
+/// // This can now be used like:
+/// // ```
+/// // def f(x: Fruit): pass
+/// // ```
+/// ```
+#[derive(Debug, NoSerialize, Allocative, ProvidesStaticType)]
+pub struct TyUserAsType(Ty);
+
+impl TyUserAsType {
+    /// Constructor.
+    pub fn new(ty: TyUser) -> Self {
+        TyUserAsType(Ty::custom(ty))
+    }
+
+    /// Set the type in `globals` under its name and each of its
+    /// [aliases](crate::typing::TyUserParams::aliases). Diagnostics use its name whichever
+    /// is referenced.
+    pub fn set_with_aliases(globals: &mut GlobalsBuilder, ty: TyUser) {
+        let names: Vec<String> = iter::once(ty.name())
+            .chain(ty.aliases().iter().map(|alias| alias.as_str()))
+            .map(|name| name.to_owned())
+            .collect();
+        let ty = Ty::custom(ty);
+        for name in names {
+            globals.set(&name, TyUserAsType(ty.dupe()));
+        }
+    }
+}
+
+#[starlark_value(type = "type")]
+impl<'v> StarlarkValue<'v> for TyUserAsType {
+    type Canonical = AbstractType;
+
+    fn eval_type(&self) -> Option<Ty> {
+        Some(self.0.dupe())
+    }
+}
+
+impl Display for TyUserAsType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl StarlarkTypeRepr for TyUserAsType {
+    fn starlark_type_repr() -> Ty {
+        AbstractType::starlark_type_repr()
+    }
+}
+
+impl<'v> AllocValue<'v> for TyUserAsType {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_simple(self)
+    }
+}
+
+impl AllocFrozenValue for TyUserAsType {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use allocative::Allocative;
@@ -142,8 +216,13 @@ mod tests {
     use crate as starlark;
     use crate::assert::Assert;
     use crate::environment::GlobalsBuilder;
+    use crate::typing::TyStarlarkValue;
+    use crate::typing::TyUser;
+    use crate::typing::TyUserParams;
     use crate::values::types::starlark_value_as_type::tests;
     use crate::values::types::starlark_value_as_type::StarlarkValueAsType;
+    use crate::values::types::starlark_value_as_type::TyUserAsType;
+    use crate::values::typing::TypeInstanceId;
     use crate::values::AllocValue;
     use crate::values::Heap;
     use crate::values::StarlarkValue;
@@ -217,4 +296,72 @@ noop(h)(1)
             r#"Value `1` of type `int` does not match the type annotation"#,
         );
     }
+
+    #[test]
+    fn test_ty_user_as_type() {
+        let mut a = Assert::new();
+        a.globals_add(|globals| {
+            compiler_args_globals(globals);
+            let ty = TyUser::new(
+                "CppArgs".to_owned(),
+                TyStarlarkValue::new::<CompilerArgs>(),
+                TypeInstanceId::gen(),
+                TyUserParams::default(),
+            )
+            .unwrap();
+            globals.set("CppArgs", TyUserAsType::new(ty));
+        });
+        a.pass(
+            r#"
+def f(x: CppArgs): pass
+
+f(compiler_args("hello"))
+"#,
+        );
+        a.fail(
+            r#"
+def g(x: CppArgs): pass
+
+def h():
+    g([])
+"#,
+            r#"Expected type `CppArgs` but got"#,
+        );
+    }
+
+    #[test]
+    fn test_ty_user_as_type_aliases() {
+        let mut a = Assert::new();
+        a.globals_add(|globals| {
+            compiler_args_globals(globals);
+            let ty = TyUser::new(
+                "CppArgs".to_owned(),
+                TyStarlarkValue::new::<CompilerArgs>(),
+                TypeInstanceId::gen(),
+                TyUserParams {
+                    aliases: vec!["CxxArgs".to_owned()],
+                    ..TyUserParams::default()
+                },
+            )
+            .unwrap();
+            TyUserAsType::set_with_aliases(globals, ty);
+        });
+        a.pass(
+            r#"
+def f(x: CxxArgs) -> CppArgs:
+    return x
+
+f(compiler_args("hello"))
+"#,
+        );
+        a.fail(
+            r#"
+def g(x: CxxArgs): pass
+
+def h():
+    g([])
+"#,
+            r#"Expected type `CppArgs` but got"#,
+        );
+    }
 }
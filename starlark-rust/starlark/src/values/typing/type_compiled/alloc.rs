@@ -24,6 +24,7 @@ use crate::typing::TyBasic;
 use crate::typing::TyName;
 use crate::values::typing::type_compiled::matcher::TypeMatcher;
 use crate::values::typing::type_compiled::matcher::TypeMatcherBoxAlloc;
+use crate::values::typing::type_compiled::matchers::IsAllOfTwo;
 use crate::values::typing::type_compiled::matchers::IsAny;
 use crate::values::typing::type_compiled::matchers::IsAnyOf;
 use crate::values::typing::type_compiled::matchers::IsAnyOfTwo;
@@ -117,6 +118,17 @@ pub trait TypeMatcherAlloc: Sized {
         }
     }
 
+    /// Values matching both `A` and `B`. `B` is only run on values which match `A`.
+    fn all_of_two_matcher(self, m0: impl TypeMatcher, m1: impl TypeMatcher) -> Self::Result {
+        if m0.is_wildcard() {
+            self.alloc(m1)
+        } else if m1.is_wildcard() {
+            self.alloc(m0)
+        } else {
+            self.alloc(IsAllOfTwo(m0, m1))
+        }
+    }
+
     /// `A | B`.
     fn any_of_two_basic(self, ty0: &TyBasic, ty1: &TyBasic) -> Self::Result {
         if ty0 == &TyBasic::Any {
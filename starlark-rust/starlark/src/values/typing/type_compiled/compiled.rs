@@ -69,6 +69,8 @@ enum TypingError {
     /// The value does not have the specified type
     #[error("Value `{0}` of type `{1}` does not match the type annotation `{2}` for {3}")]
     TypeAnnotationMismatch(String, String, String, String),
+    #[error("Value `{0}` of type `{1}` does not match the type annotation `{2}` for {3}: {4}")]
+    TypeAnnotationMismatchWithMessage(String, String, String, String, String),
     /// The given type annotation does not represent a type
     #[error("Type `{0}` is not a valid type annotation")]
     InvalidTypeAnnotation(String),
@@ -296,16 +298,27 @@ impl<'v, V: ValueLike<'v>> TypeCompiled<V> {
     #[cold]
     #[inline(never)]
     fn check_type_error(self, value: Value<'v>, arg_name: Option<&str>) -> anyhow::Result<()> {
-        Err(TypingError::TypeAnnotationMismatch(
-            value.to_str(),
-            value.get_type().to_owned(),
-            self.to_string(),
-            match arg_name {
-                None => "return type".to_owned(),
-                Some(x) => format!("argument `{}`", x),
-            },
-        )
-        .into())
+        let value_str = value.to_str();
+        let value_type = value.get_type().to_owned();
+        let expected = self.to_string();
+        let context = match arg_name {
+            None => "return type".to_owned(),
+            Some(x) => format!("argument `{}`", x),
+        };
+        match self.as_ty().match_error_message() {
+            Some(message) => Err(TypingError::TypeAnnotationMismatchWithMessage(
+                value_str,
+                value_type,
+                expected,
+                context,
+                message.to_owned(),
+            )
+            .into()),
+            None => Err(TypingError::TypeAnnotationMismatch(
+                value_str, value_type, expected, context,
+            )
+            .into()),
+        }
     }
 
     pub(crate) fn check_type(self, value: Value<'v>, arg_name: Option<&str>) -> anyhow::Result<()> {
@@ -181,6 +181,15 @@ impl<A: TypeMatcher, B: TypeMatcher> TypeMatcher for IsAnyOfTwo<A, B> {
     }
 }
 
+#[derive(Clone, Allocative, Debug)]
+pub(crate) struct IsAllOfTwo<A: TypeMatcher, B: TypeMatcher>(pub(crate) A, pub(crate) B);
+
+impl<A: TypeMatcher, B: TypeMatcher> TypeMatcher for IsAllOfTwo<A, B> {
+    fn matches(&self, value: Value) -> bool {
+        self.0.matches(value) && self.1.matches(value)
+    }
+}
+
 #[derive(Clone, Allocative, Debug)]
 pub(crate) struct IsAnyOf(pub(crate) Vec<TypeMatcherBox>);
 